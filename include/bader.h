@@ -0,0 +1,114 @@
+/* Generated by cbindgen from src/capi.rs, do not edit. */
+
+#ifndef BADER_H
+#define BADER_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/*
+ The methods to partition the density with, the values of
+ [`BaderOptions::method`].
+ */
+typedef enum BaderMethod {
+  /*
+   Flux weighted partitioning of the boundary voxels.
+   */
+  BADER_WEIGHT = 0,
+  /*
+   Steepest ascent on the grid.
+   */
+  BADER_ONGRID = 1,
+  /*
+   Gradient ascent with a correction for the grid bias.
+   */
+  BADER_NEARGRID = 2,
+} BaderMethod;
+
+/*
+ The settings of a partition.
+ */
+typedef struct BaderOptions {
+  /*
+   The method to partition the density with, one of [`BaderMethod`].
+   */
+  int method;
+  /*
+   Tolerance to disregard weights at.
+   */
+  double weight_tolerance;
+  /*
+   Density below which a voxel is vacuum, a negative value for no vacuum.
+   */
+  double vacuum_tolerance;
+  /*
+   The number of threads to use, 0 for a thread for each cpu up to 12.
+   */
+  size_t threads;
+} BaderOptions;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ The default settings: the weight method with a tolerance of 1E-8, no
+ vacuum and a thread for each cpu.
+ */
+struct BaderOptions bader_default_options(void);
+
+/*
+ The version of the library as a null terminated string.
+ */
+const char *bader_version(void);
+
+/*
+ Partitions a density into the Bader atoms, writing the charge and volume
+ of each atom into the caller's arrays.
+
+ The density holds `grid[0] * grid[1] * grid[2]` values in electrons per unit
+ volume, with the last axis running fastest so that voxel (i, j, k) is
+ `density[(i * grid[1] + j) * grid[2] + k]`. The lattice holds the vector
+ along each axis of the grid, one after another, and the positions hold the
+ cartesian coordinates of each atom, in the same units. A Fortran array
+ `rho(nx, ny, nz)` is laid out this way with `grid = {nz, ny, nx}`, the
+ lattice vectors given in the order c, b, a and the components of every
+ vector and position reversed. The options may be null to use the defaults.
+
+ Returns 0 on success or the exit code of bca for the error, with 1 for any
+ other failure, whose message can be read with bader_last_error.
+
+ # Safety
+
+ The density has to hold the number of voxels in the grid, the lattice 9
+ values, the positions 3 for each atom and the charges and volumes space for
+ each atom.
+ */
+int bader_partition(const double *density,
+                    const size_t *grid,
+                    const double *lattice,
+                    const double *positions,
+                    size_t atoms_len,
+                    const struct BaderOptions *options,
+                    double *charges,
+                    double *volumes);
+
+/*
+ Copies the message of the last error on this thread into the buffer, as a
+ null terminated string truncated to fit, and returns the length of the
+ whole message. The message is empty if the last call succeeded.
+
+ # Safety
+
+ The buffer has to hold length bytes, or be null if length is 0.
+ */
+size_t bader_last_error(char *buffer, size_t length);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* BADER_H */
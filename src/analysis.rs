@@ -1,11 +1,11 @@
 use crate::atoms::Atoms;
+use crate::error::{Error, Result};
 use crate::grid::Grid;
 use crate::progress::Bar;
 use crate::utils;
 use crate::voxel_map::NonBlockingVoxelMap as VoxelMap;
-use anyhow::{Context, Result};
 use crossbeam_utils::thread;
-use rustc_hash::FxHashSet;
+use rustc_hash::{FxHashMap, FxHashSet};
 
 /// A type to simplify the result of charge summing functions
 type ChargeSumResult = Result<(Vec<Vec<f64>>, Vec<f64>, Vec<f64>)>;
@@ -54,7 +54,7 @@ fn maxima_to_atom(chunk: &[isize],
     let mut min_dist = Vec::with_capacity(chunk_size);
     for m in chunk.iter() {
         // convert the point first to cartesian, then to the reduced basis
-        let m_cartesian = grid.to_cartesian(*m as isize);
+        let m_cartesian = grid.to_cartesian(*m);
         let m_reduced_cartesian = atoms.reduced_lattice.to_reduced(m_cartesian);
         let mut atom_num = 0;
         let mut min_distance = f64::INFINITY;
@@ -107,39 +107,33 @@ pub fn assign_maxima(maxima: &[isize],
         std::cmp::Ordering::Greater => {
             let chunk_size =
                 (maxima.len() / threads) + (maxima.len() % threads).min(1);
-            thread::scope(|s| {
+            thread::scope(|s| -> Result<()> {
                 let spawned_threads =
                     maxima.chunks(chunk_size)
                           .enumerate()
                           .map(|(index, chunk)| {
                               s.spawn(move |_| {
-                                  match maxima_to_atom(chunk, atoms, grid, pbar) {
-                                      Ok(result) => (result, index),
-                                      _ => panic!("Failed to match maxima to atom"),
-                                  }
+                                  maxima_to_atom(chunk, atoms, grid, pbar).map(|result| (result, index))
                                })
                           })
                           .collect::<Vec<_>>();
                 for thread in spawned_threads {
-                    if let Ok(((ass_atom, min_dist), chunk_index)) =
-                        thread.join()
-                    {
-                        // is this required? is the collection of handles not
-                        // already sorted like this, is it possible to join as
-                        // they finish?
-                        let i = chunk_index * chunk_size;
-                        assigned_atom.splice(i..(i + ass_atom.len()), ass_atom);
-                        minimum_distance.splice(i..(i + min_dist.len()),
-                                                min_dist);
-                    } else {
-                        panic!("Failed to join thread in assign maxima.")
-                    };
+                    let ((ass_atom, min_dist), chunk_index) =
+                        thread.join().map_err(|_| Error::Thread(String::from("assign_maxima")))??;
+                    // is this required? is the collection of handles not
+                    // already sorted like this, is it possible to join as
+                    // they finish?
+                    let i = chunk_index * chunk_size;
+                    assigned_atom.splice(i..(i + ass_atom.len()), ass_atom);
+                    minimum_distance.splice(i..(i + min_dist.len()),
+                                            min_dist);
                 }
-            }).unwrap();
+                Ok(())
+            }).map_err(|_| Error::Thread(String::from("assign_maxima")))??;
         }
         _ => {
             let (ass_atom, min_dist) =
-                maxima_to_atom(maxima, atoms, grid, pbar).context("Failed to assign maxima to atom.")?;
+                maxima_to_atom(maxima, atoms, grid, pbar)?;
             assigned_atom = ass_atom;
             minimum_distance = min_dist;
         }
@@ -240,8 +234,7 @@ fn sum_densities_bader(chunk: &[isize],
              }
              progress_bar.tick();
              Ok(())
-         })
-         .context("Iterating through a chunk of the voxel map.")?;
+         })?;
     Ok((bader_charge, bader_volume, surface_distance))
 }
 
@@ -325,8 +318,7 @@ fn sum_densities_atom(chunk: &[isize],
              }
              progress_bar.tick();
              Ok(())
-         })
-         .context("Iterating through a chunk of the voxel map.")?;
+         })?;
     Ok((bader_charge, bader_volume, surface_distance))
 }
 
@@ -353,37 +345,29 @@ pub fn sum_bader_densities(densities: &[Vec<f64>],
                 // Calculate the size of the vector to be passed to each thread.
                 let chunk_size = (voxel_map.voxel_map.len() / threads)
                                  + (voxel_map.voxel_map.len() % threads).min(1);
-                thread::scope(|s| {
+                thread::scope(|s| -> Result<()> {
                 let spawned_threads = voxel_map.voxel_map
                                                .chunks(chunk_size)
                                                .enumerate()
                                                .map(|(index, chunk)| {
                                                    if let Some(am) = atoms_map {
                                                        s.spawn(move |_| {
-                                          match sum_densities_bader(chunk,
-                                                                    densities,
-                                                                    am,
-                                                                    atoms,
-                                                                    voxel_map,
-                                                                    index,
-                                                                    pbar)
-                                  {
-                                      Ok(result) => result,
-                                      _ => panic!("Unable to sum densities."),
-                                  }
+                                          sum_densities_bader(chunk,
+                                                              densities,
+                                                              am,
+                                                              atoms,
+                                                              voxel_map,
+                                                              index,
+                                                              pbar)
                                       })
                                                    } else {
                                                        s.spawn(move |_| {
-                                          match sum_densities_atom(chunk,
-                                                                   densities,
-                                                                   atoms,
-                                                                   voxel_map,
-                                                                   index,
-                                                                   pbar)
-                                  {
-                                      Ok(result) => result,
-                                      _ => panic!("Unable to sum densities."),
-                                  }
+                                          sum_densities_atom(chunk,
+                                                             densities,
+                                                             atoms,
+                                                             voxel_map,
+                                                             index,
+                                                             pbar)
                                       })
                                                    }
                                                })
@@ -393,31 +377,29 @@ pub fn sum_bader_densities(densities: &[Vec<f64>],
                 // Either use the sorted index to remove vacuum from the summation or
                 // find a way to operate on finshed threads first (ideally both).
                 for thread in spawned_threads {
-                    if let Ok((tmp_bc, tmp_bv, tmp_sd)) = thread.join() {
-                        for (bc, density) in
-                            bader_charge.iter_mut().zip(tmp_bc.into_iter())
-                        {
-                            bc.iter_mut()
-                              .zip(density.iter())
-                              .for_each(|(a, b)| {
-                                  *a += b;
-                              });
-                        }
-                        bader_volume.iter_mut()
-                                    .zip(tmp_bv.into_iter())
+                    let (tmp_bc, tmp_bv, tmp_sd) = thread.join().map_err(|_| Error::Thread(String::from("sum_bader_densities")))??;
+                    for (bc, density) in
+                        bader_charge.iter_mut().zip(tmp_bc)
+                    {
+                        bc.iter_mut()
+                          .zip(density.iter())
+                          .for_each(|(a, b)| {
+                              *a += b;
+                          });
+                    }
+                    bader_volume.iter_mut()
+                                .zip(tmp_bv)
+                                .for_each(|(a, b)| {
+                                    *a += b;
+                                });
+                    surface_distance.iter_mut()
+                                    .zip(tmp_sd)
                                     .for_each(|(a, b)| {
-                                        *a += b;
+                                        *a = a.min(b);
                                     });
-                        surface_distance.iter_mut()
-                                        .zip(tmp_sd.into_iter())
-                                        .for_each(|(a, b)| {
-                                            *a = a.min(b);
-                                        });
-                    } else {
-                        panic!("Unable to join thread in sum_bader_densities.")
-                    };
                 }
-            }).unwrap();
+                Ok(())
+            }).map_err(|_| Error::Thread(String::from("sum_bader_densities")))??;
                 // The distance isn't square rooted in the calcation of distance to save time.
                 // As we need to filter out the infinite distances (atoms with no assigned maxima)
                 // we can square root here also.
@@ -430,23 +412,21 @@ pub fn sum_bader_densities(densities: &[Vec<f64>],
                             });
                 (bader_charge, bader_volume, surface_distance)
             }
-            _ => {
-                    match atoms_map {
-                    Some(am) => sum_densities_bader(&voxel_map.voxel_map,
-                                  densities,
-                                  am,
-                                  atoms,
-                                  voxel_map,
-                                  0,
-                                  pbar).context("Unable to sum bader densities")?,
-                    None => sum_densities_atom(&voxel_map.voxel_map,
-                                               densities,
-                                               atoms,
-                                               voxel_map,
-                                               0,
-                                               pbar).context("Unable to sum bader densities")?
-                }
-            }
+            _ => match atoms_map {
+                Some(am) => sum_densities_bader(&voxel_map.voxel_map,
+                                                densities,
+                                                am,
+                                                atoms,
+                                                voxel_map,
+                                                0,
+                                                pbar)?,
+                None => sum_densities_atom(&voxel_map.voxel_map,
+                                           densities,
+                                           atoms,
+                                           voxel_map,
+                                           0,
+                                           pbar)?,
+            },
         };
     // The distance isn't square rooted in the calcation of distance to save time.
     // As we need to filter out the infinite distances (atoms with no assigned maxima)
@@ -470,6 +450,193 @@ pub fn sum_bader_densities(densities: &[Vec<f64>],
     Ok((bader_charge, bader_volume, surface_distance))
 }
 
+/// The multipole moments of the charge in an atom's Bader volume.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Moments {
+    /// The dipole moment, -∫ρ(r)(r - R)dV, with the electrons carrying a
+    /// negative charge and R the position of the nucleus.
+    pub dipole: [f64; 3],
+    /// The traceless quadrupole moment, -∫ρ(r)(3r_ir_j - r²δ_ij)dV, with r
+    /// measured from the nucleus.
+    pub quadrupole: [[f64; 3]; 3],
+}
+
+impl Moments {
+    /// The magnitude of the dipole moment.
+    pub fn dipole_magnitude(&self) -> f64 {
+        utils::norm(self.dipole)
+    }
+}
+
+impl Default for Moments {
+    fn default() -> Self {
+        Self { dipole: [0.; 3],
+               quadrupole: [[0.; 3]; 3] }
+    }
+}
+
+/// Adds the moments of the charge, q, at the cartesian point, p, relative to
+/// the closest periodic image of the atom. Points equidistant from several
+/// images, such as those on the Wigner-Seitz boundary of a lone atom, have
+/// their charge split evenly between them to avoid biasing the moments.
+fn add_minimum_image_moments(moments: &mut Moments,
+                             atoms: &Atoms,
+                             atom_number: usize,
+                             q: f64,
+                             p: [f64; 3]) {
+    let atom = atoms.positions[atom_number];
+    let r = [p[0] - atom[0], p[1] - atom[1], p[2] - atom[2]];
+    // wrap into the lll-reduced cell and then check the neighbouring images
+    let mut r_lll_fractional =
+        utils::dot(r, atoms.reduced_lattice.to_fractional);
+    for f in &mut r_lll_fractional {
+        *f -= f.round();
+    }
+    let r = utils::dot(r_lll_fractional, atoms.reduced_lattice.to_cartesian);
+    let mut images = [[0f64; 3]; 27];
+    let mut distances = [0f64; 27];
+    for ((image, distance), shift) in
+        images.iter_mut()
+              .zip(distances.iter_mut())
+              .zip(atoms.reduced_lattice.cartesian_shift_matrix.iter())
+    {
+        *image = [r[0] - shift[0], r[1] - shift[1], r[2] - shift[2]];
+        *distance = utils::vdot(*image, *image);
+    }
+    let min_distance = distances.iter().fold(f64::INFINITY, |a, b| a.min(*b));
+    let tolerance = min_distance * 1E-10 + 1E-12;
+    let closest = distances.iter()
+                           .filter(|d| **d - min_distance <= tolerance)
+                           .count();
+    let q = q / closest as f64;
+    images.iter()
+          .zip(distances.iter())
+          .filter(|(_, d)| **d - min_distance <= tolerance)
+          .for_each(|(image, _)| add_moments(moments, q, *image));
+}
+
+/// Adds the contribution of the charge, q, at the position, r, relative to
+/// the nucleus to the moments.
+fn add_moments(moments: &mut Moments, q: f64, r: [f64; 3]) {
+    let r2 = utils::vdot(r, r);
+    for i in 0..3 {
+        moments.dipole[i] -= q * r[i];
+        for j in 0..3 {
+            let delta = if i == j { r2 } else { 0. };
+            moments.quadrupole[i][j] -= q * (3. * r[i] * r[j] - delta);
+        }
+    }
+}
+
+/// Sums the moments of the density for each atom.
+/// Chunk is a slice of the voxel map starting at the voxel, start.
+fn sum_moments(chunk: &[isize],
+               start: usize,
+               density: &[f64],
+               atoms: &Atoms,
+               atoms_map: Option<&[usize]>,
+               voxel_map: &VoxelMap,
+               progress_bar: &Bar)
+               -> Vec<Moments> {
+    let mut moments = vec![Moments::default(); atoms.positions.len()];
+    let atom_of = |maxima: usize| match atoms_map {
+        Some(am) => am[maxima],
+        None => maxima,
+    };
+    chunk.iter().enumerate().for_each(|(voxel_index, voxel)| {
+                                let p = start + voxel_index;
+                                let p_cartesian =
+                                    voxel_map.grid.to_cartesian(p as isize);
+                                match voxel.cmp(&-1) {
+                                    std::cmp::Ordering::Greater => {
+                                        let atom = atom_of(*voxel as usize);
+                                        add_minimum_image_moments(&mut moments[atom],
+                                                                  atoms,
+                                                                  atom,
+                                                                  density[p],
+                                                                  p_cartesian);
+                                    }
+                                    std::cmp::Ordering::Less => {
+                                        for w in voxel_map.weight_get(*voxel) {
+                                            let maxima = *w as usize;
+                                            let weight = w - maxima as f64;
+                                            let atom = atom_of(maxima);
+                                            add_minimum_image_moments(&mut moments[atom],
+                                                                      atoms,
+                                                                      atom,
+                                                                      density[p] * weight,
+                                                                      p_cartesian);
+                                        }
+                                    }
+                                    std::cmp::Ordering::Equal => (),
+                                }
+                                progress_bar.tick();
+                            });
+    moments
+}
+
+/// Calculates the dipole and quadrupole moments of the density in each atom's
+/// Bader volume. Positions are measured from the nucleus using the minimum
+/// image of the atom, so the moments are independent of where the atom sits
+/// in the cell.
+pub fn atomic_moments(density: &[f64],
+                      voxel_map: &VoxelMap,
+                      atoms: &Atoms,
+                      atoms_map: Option<&[usize]>,
+                      threads: usize,
+                      progress_bar: Bar)
+                      -> Result<Vec<Moments>> {
+    let pbar = &progress_bar;
+    let total = voxel_map.voxel_map.len();
+    let chunk_size = (total / threads) + (total % threads).min(1);
+    let mut moments = vec![Moments::default(); atoms.positions.len()];
+    thread::scope(|s| -> Result<()> {
+        let spawned_threads = voxel_map.voxel_map
+                                       .chunks(chunk_size)
+                                       .enumerate()
+                                       .map(|(index, chunk)| {
+                                           s.spawn(move |_| {
+                                                sum_moments(chunk,
+                                                            index * chunk_size,
+                                                            density,
+                                                            atoms,
+                                                            atoms_map,
+                                                            voxel_map,
+                                                            pbar)
+                                            })
+                                       })
+                                       .collect::<Vec<_>>();
+        for thread in spawned_threads {
+            let tmp_moments =
+                thread.join().map_err(|_| {
+                                  Error::Thread(String::from("atomic_moments"))
+                              })?;
+            moments.iter_mut().zip(tmp_moments).for_each(|(m, tmp)| {
+                                                   for i in 0..3 {
+                                                       m.dipole[i] +=
+                                                           tmp.dipole[i];
+                                                       for j in 0..3 {
+                                                           m.quadrupole[i]
+                                                               [j] +=
+                                                               tmp.quadrupole
+                                                                   [i][j];
+                                                       }
+                                                   }
+                                               });
+        }
+        Ok(())
+    }).map_err(|_| Error::Thread(String::from("atomic_moments")))??;
+    let volume = voxel_map.grid.voxel_lattice.volume;
+    moments.iter_mut().for_each(|m| {
+                          m.dipole.iter_mut().for_each(|d| *d *= volume);
+                          m.quadrupole
+                           .iter_mut()
+                           .flatten()
+                           .for_each(|q| *q *= volume);
+                      });
+    Ok(moments)
+}
+
 /// Sums the densities for each atom.
 pub fn sum_atoms_densities(bader_charge: &[Vec<f64>],
                            bader_volume: &[f64],
@@ -490,6 +657,111 @@ pub fn sum_atoms_densities(bader_charge: &[Vec<f64>],
     Ok((atoms_density, atoms_volume))
 }
 
+/// Sums a field over a chunk of the voxel map starting at the voxel, start.
+fn sum_field(chunk: &[isize],
+             start: usize,
+             field: &[f64],
+             atoms_map: Option<&[usize]>,
+             voxel_map: &VoxelMap,
+             partitions: usize,
+             progress_bar: &Bar)
+             -> Vec<f64> {
+    let mut integral = vec![0.0; partitions];
+    let partition_of = |maxima: usize| match atoms_map {
+        Some(am) => am[maxima],
+        None => maxima,
+    };
+    chunk.iter().enumerate().for_each(|(voxel_index, voxel)| {
+                                let p = start + voxel_index;
+                                match voxel.cmp(&-1) {
+                                    std::cmp::Ordering::Greater => {
+                                        integral
+                                            [partition_of(*voxel as usize)] +=
+                                            field[p];
+                                    }
+                                    std::cmp::Ordering::Less => {
+                                        for w in voxel_map.weight_get(*voxel) {
+                                            let maxima = *w as usize;
+                                            let weight = w - maxima as f64;
+                                            integral[partition_of(maxima)] +=
+                                                field[p] * weight;
+                                        }
+                                    }
+                                    std::cmp::Ordering::Equal => (),
+                                }
+                                progress_bar.tick();
+                            });
+    integral
+}
+
+/// Integrates a scalar field, on the same grid as the partitioned density,
+/// over each partition of the voxel map. The partitions are the maxima stored
+/// in the voxel map or, if an atoms_map is given, the atoms they are assigned
+/// to.
+///
+/// * `field`: The value of the field at each voxel.
+/// * `voxel_map`: The filled voxel map of the partitioned density.
+/// * `atoms_map`: The atom each maxima is assigned to.
+/// * `partitions`: The number of maxima, or atoms if atoms_map is given.
+/// * `threads`: The number of threads to sum the field with.
+/// * `progress_bar`: A progress bar for monitoring the sum.
+pub fn integrate_field(field: &[f64],
+                       voxel_map: &VoxelMap,
+                       atoms_map: Option<&[usize]>,
+                       partitions: usize,
+                       threads: usize,
+                       progress_bar: Bar)
+                       -> Result<Vec<f64>> {
+    let total = voxel_map.voxel_map.len();
+    if field.len() != total {
+        return Err(Error::InvalidArgument(format!("The field has {} voxels but the density has {}.",
+              field.len(),
+              total)));
+    }
+    let pbar = &progress_bar;
+    let chunk_size = (total / threads) + (total % threads).min(1);
+    let mut integral = vec![0.0; partitions];
+    thread::scope(|s| -> Result<()> {
+        let spawned_threads = voxel_map.voxel_map
+                                       .chunks(chunk_size)
+                                       .enumerate()
+                                       .map(|(index, chunk)| {
+                                           s.spawn(move |_| {
+                                                sum_field(chunk,
+                                                          index * chunk_size,
+                                                          field,
+                                                          atoms_map,
+                                                          voxel_map,
+                                                          partitions,
+                                                          pbar)
+                                            })
+                                       })
+                                       .collect::<Vec<_>>();
+        for thread in spawned_threads {
+            let tmp_integral =
+                thread.join().map_err(|_| {
+                                  Error::Thread(String::from("integrate_field"))
+                              })?;
+            integral.iter_mut()
+                    .zip(tmp_integral)
+                    .for_each(|(a, b)| *a += b);
+        }
+        Ok(())
+    }).map_err(|_| Error::Thread(String::from("integrate_field")))??;
+    let volume = voxel_map.grid.voxel_lattice.volume;
+    integral.iter_mut().for_each(|i| *i *= volume);
+    Ok(integral)
+}
+
+/// Calculate the net charge of each atom, its valence minus the electrons of
+/// the first density partitioned to it.
+pub fn net_charges(valence: &[f64], atoms_density: &[Vec<f64>]) -> Vec<f64> {
+    valence.iter()
+           .zip(atoms_density)
+           .map(|(zval, density)| zval - density[0])
+           .collect()
+}
+
 // The unwrap here is necessary for lifetime resolution
 /// Create nearest neighbour matrix from the atoms with shared voxels.
 #[allow(clippy::unnecessary_unwrap)]
@@ -530,3 +802,262 @@ pub fn nearest_neighbours(voxel_map: &VoxelMap,
               });
     Ok(m_nn)
 }
+
+/// The weight of each atom in the voxel, p.
+fn voxel_atom_weights(voxel_map: &VoxelMap,
+                      p: isize,
+                      atoms_map: Option<&[usize]>)
+                      -> Vec<(usize, f64)> {
+    let atom_of = |maxima: usize| match atoms_map {
+        Some(am) => am[maxima],
+        None => maxima,
+    };
+    let maxima = voxel_map.voxel_map[p as usize];
+    match maxima.cmp(&-1) {
+        std::cmp::Ordering::Greater => vec![(atom_of(maxima as usize), 1.)],
+        std::cmp::Ordering::Less => {
+            let mut weights: Vec<(usize, f64)> = Vec::with_capacity(4);
+            for w in voxel_map.weight_get(maxima) {
+                let m = *w as usize;
+                let atom = atom_of(m);
+                let weight = w - m as f64;
+                match weights.iter_mut().find(|(a, _)| *a == atom) {
+                    Some((_, aw)) => *aw += weight,
+                    None => weights.push((atom, weight)),
+                }
+            }
+            weights
+        }
+        std::cmp::Ordering::Equal => Vec::with_capacity(0),
+    }
+}
+
+/// Sums the contributions to the surface and contact areas for a chunk of
+/// the voxel map, from start to end.
+fn sum_contact_areas(voxel_map: &VoxelMap,
+                     atoms_map: Option<&[usize]>,
+                     n_atoms: usize,
+                     start: usize,
+                     end: usize,
+                     progress_bar: &Bar)
+                     -> (Vec<f64>, FxHashMap<(usize, usize), f64>) {
+    let mut surface_area = vec![0.; n_atoms];
+    let mut contact_area = FxHashMap::<(usize, usize), f64>::default();
+    let to_voxel = voxel_map.grid.voxel_lattice.to_fractional;
+    for p in start..end {
+        progress_bar.tick();
+        let p = p as isize;
+        // [ +x, -x, +y, -y, +z, -z ]
+        let shift = voxel_map.grid.reduced_shift(p);
+        let neighbours = [p + shift[0],
+                          p + shift[1],
+                          p + shift[2],
+                          p + shift[3],
+                          p + shift[4],
+                          p + shift[5]];
+        // the gradient of the weights is zero if all the neighbours are
+        // interior voxels of the same atom
+        let first = voxel_map.voxel_map[neighbours[0] as usize];
+        if first >= 0
+           && neighbours.iter().all(|pn| {
+                                   let m = voxel_map.voxel_map[*pn as usize];
+                                   m >= 0
+                                   && match atoms_map {
+                                       Some(am) => {
+                                           am[m as usize] == am[first as usize]
+                                       }
+                                       None => m == first,
+                                   }
+                               })
+        {
+            continue;
+        }
+        let weights =
+            neighbours.iter()
+                      .map(|pn| voxel_atom_weights(voxel_map, *pn, atoms_map))
+                      .collect::<Vec<_>>();
+        let mut atoms = weights.iter()
+                               .flatten()
+                               .map(|(atom, _)| *atom)
+                               .collect::<Vec<usize>>();
+        atoms.sort_unstable();
+        atoms.dedup();
+        let weight = |i: usize, atom: usize| {
+            weights[i].iter()
+                      .find(|(a, _)| *a == atom)
+                      .map_or(0., |(_, w)| *w)
+        };
+        // central difference gradients of each atom's weight in cartesian
+        let gradients =
+            atoms.iter()
+                 .map(|atom| {
+                     let g = [(weight(0, *atom) - weight(1, *atom)) / 2.,
+                              (weight(2, *atom) - weight(3, *atom)) / 2.,
+                              (weight(4, *atom) - weight(5, *atom)) / 2.];
+                     [utils::vdot(to_voxel[0], g),
+                      utils::vdot(to_voxel[1], g),
+                      utils::vdot(to_voxel[2], g)]
+                 })
+                 .collect::<Vec<[f64; 3]>>();
+        let norms = gradients.iter()
+                             .map(|g| utils::norm(*g))
+                             .collect::<Vec<f64>>();
+        for (i, atom_i) in atoms.iter().enumerate() {
+            surface_area[*atom_i] += norms[i];
+            for (j, atom_j) in atoms.iter().enumerate().skip(i + 1) {
+                let g_ij = [gradients[i][0] + gradients[j][0],
+                            gradients[i][1] + gradients[j][1],
+                            gradients[i][2] + gradients[j][2]];
+                let area = (norms[i] + norms[j] - utils::norm(g_ij)) / 2.;
+                *contact_area.entry((*atom_i, *atom_j)).or_insert(0.) += area;
+            }
+        }
+    }
+    (surface_area, contact_area)
+}
+
+/// Calculates the total surface area of each atom and the area of the
+/// zero-flux surface shared between each pair of atoms.
+///
+/// The areas are estimated from the boundary voxels using the co-area formula,
+/// the area of the surface of atom i is the integral of |∇w_i|, where w_i is
+/// the fraction of each voxel belonging to atom i. The surface shared between
+/// atoms i and j is where their weights change together, and its area is half
+/// the integral of |∇w_i| + |∇w_j| - |∇(w_i + w_j)|. Returns the surface area of
+/// each atom and the symmetric contact-area matrix.
+pub fn contact_areas(voxel_map: &VoxelMap,
+                     atoms_map: Option<&[usize]>,
+                     n_atoms: usize,
+                     threads: usize,
+                     progress_bar: Bar)
+                     -> Result<(Vec<f64>, Vec<Vec<f64>>)> {
+    let pbar = &progress_bar;
+    let total = voxel_map.voxel_map.len();
+    let chunk_size = (total / threads) + (total % threads).min(1);
+    let mut surface_area = vec![0.; n_atoms];
+    let mut contact_area = vec![vec![0.; n_atoms]; n_atoms];
+    thread::scope(|s| -> Result<()> {
+        let spawned_threads =
+            (0..total).step_by(chunk_size)
+                      .map(|start| {
+                          let end = (start + chunk_size).min(total);
+                          s.spawn(move |_| {
+                               sum_contact_areas(voxel_map, atoms_map, n_atoms,
+                                                 start, end, pbar)
+                           })
+                      })
+                      .collect::<Vec<_>>();
+        for thread in spawned_threads {
+            let (tmp_sa, tmp_ca) =
+                thread.join().map_err(|_| {
+                                  Error::Thread(String::from("contact_areas"))
+                              })?;
+            surface_area.iter_mut()
+                        .zip(tmp_sa)
+                        .for_each(|(a, b)| *a += b);
+            tmp_ca.into_iter().for_each(|((i, j), area)| {
+                                  contact_area[i][j] += area;
+                                  contact_area[j][i] += area;
+                              });
+        }
+        Ok(())
+    }).map_err(|_| Error::Thread(String::from("contact_areas")))??;
+    let volume = voxel_map.grid.voxel_lattice.volume;
+    surface_area.iter_mut().for_each(|a| *a *= volume);
+    contact_area.iter_mut().flatten().for_each(|a| *a *= volume);
+    Ok((surface_area, contact_area))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atoms::Lattice;
+
+    #[test]
+    fn analysis_atomic_moments() {
+        // a single atom at the origin of a 4x4x4 cubic cell with one off
+        // centre voxel of charge, the image across the boundary is closer
+        let lattice = [[4., 0., 0.], [0., 4., 0.], [0., 0., 4.]];
+        let atoms = Atoms::new(Lattice::new(lattice).unwrap(),
+                               vec![[0., 0., 0.]],
+                               String::new()).unwrap();
+        let grid = Grid::new([4, 4, 4], lattice, [0., 0., 0.]).unwrap();
+        let mut density = vec![0.; 64];
+        // voxel (3, 0, 0) sits at x = 3 but is at x = -1 from the atom
+        density[48] = 2.;
+        let voxel_map = VoxelMap::new(vec![0; 64], vec![], grid);
+        let moments = atomic_moments(&density,
+                                     &voxel_map,
+                                     &atoms,
+                                     None,
+                                     2,
+                                     Bar::new(64, 100, String::new())).unwrap();
+        assert_eq!(moments[0].dipole, [2., 0., 0.]);
+        assert_eq!(moments[0].quadrupole,
+                   [[-4., 0., 0.], [0., 2., 0.], [0., 0., 2.]]);
+        assert_eq!(moments[0].dipole_magnitude(), 2.);
+    }
+
+    #[test]
+    fn analysis_integrate_field() {
+        // two maxima in a 2x2x2 cubic cell of volume 8, split by a row of
+        // voxels weighted between them and with one voxel of vacuum
+        let lattice = [[2., 0., 0.], [0., 2., 0.], [0., 0., 2.]];
+        let grid = Grid::new([2, 2, 2], lattice, [0., 0., 0.]).unwrap();
+        let voxel_map = VoxelMap::new(vec![0, 0, 0, -2, 1, 1, 1, -1],
+                                      vec![vec![0.25, 1.75]],
+                                      grid);
+        let field = (1..=8).map(|f| f as f64).collect::<Vec<f64>>();
+        for threads in 1..4 {
+            let integral =
+                integrate_field(&field,
+                                &voxel_map,
+                                None,
+                                2,
+                                threads,
+                                Bar::new(8, 100, String::new())).unwrap();
+            assert_eq!(integral, vec![7., 21.]);
+            let integral =
+                integrate_field(&field,
+                                &voxel_map,
+                                Some(&[0, 0]),
+                                1,
+                                threads,
+                                Bar::new(8, 100, String::new())).unwrap();
+            assert_eq!(integral, vec![28.]);
+        }
+        assert!(integrate_field(&field[1..],
+                                &voxel_map,
+                                None,
+                                2,
+                                1,
+                                Bar::new(8, 100, String::new())).is_err());
+    }
+
+    #[test]
+    fn analysis_contact_areas() {
+        // two slabs of atoms 0 and 1 along x in a 8x4x4 cell with a weighted
+        // layer at x = 0 and x = 4, giving two 4x4 interfaces
+        let lattice = [[8., 0., 0.], [0., 4., 0.], [0., 0., 4.]];
+        let grid = Grid::new([8, 4, 4], lattice, [0., 0., 0.]).unwrap();
+        let voxel_map = (0..128).map(|p| match p / 16 {
+                                    1..=3 => 0,
+                                    5..=7 => 1,
+                                    _ => -2,
+                                })
+                                .collect::<Vec<isize>>();
+        let voxel_map = VoxelMap::new(voxel_map, vec![vec![0.5, 1.5]], grid);
+        let (surface_area, contact_area) =
+            contact_areas(&voxel_map,
+                          None,
+                          2,
+                          2,
+                          Bar::new(128, 100, String::new())).unwrap();
+        for area in surface_area {
+            assert!((area - 32.).abs() < 1E-10);
+        }
+        assert!((contact_area[0][1] - 32.).abs() < 1E-10);
+        assert_eq!(contact_area[0][1], contact_area[1][0]);
+        assert_eq!(contact_area[0][0], 0.);
+    }
+}
@@ -1,4 +1,7 @@
-use crate::io::{FileType, WriteType};
+use crate::error::{Error, Result};
+use crate::io::mesh::MeshFormat;
+use crate::io::{reader, FileType, IndexMap, WriteType};
+use crate::methods::Method;
 use clap::{crate_authors, App, Arg, ArgMatches};
 
 /// Indicates how many reference files are passed
@@ -30,7 +33,7 @@ impl<'a> ClapApp {
             .arg(Arg::new("file")
                 .required(true)
                 .index(1)
-                .about("The file to analyse."))
+                .about("The file to analyse, - to read it from stdin."))
             .arg(Arg::new("output")
                 .short('o')
                 .long("output")
@@ -55,17 +58,146 @@ volumes."))
 "An index of a Bader atom or volume to be written out, starting at 1. This flag
 requires the output flag to be set. Multiple atoms or volumes can be written by
 repeating the flag ie. bca CHGCAR -o atoms -i 1 -i 2."))
+            .arg(Arg::new("surface")
+                .long("surface")
+                .takes_value(true)
+                .possible_value("obj")
+                .possible_value("ply")
+                .possible_value("stl")
+                .case_insensitive(false)
+                .requires("output")
+                .about("Output the zero-flux surfaces instead of the densities.")
+                .long_about(
+"Output the zero-flux surfaces of the Bader atoms or volumes chosen with the
+output and index flags as triangulated meshes in the OBJ, PLY or STL format,
+instead of writing their densities. The surfaces are found with marching cubes
+and are unwrapped around the atom or maxima so that each is a single closed
+surface."))
+            .arg(Arg::new("xsf")
+                .long("xsf")
+                .requires("output")
+                .about("Write the output densities as XSF files.")
+                .long_about(
+"Write the densities of the Bader atoms or volumes chosen with the output and
+index flags as XSF files, whatever the format of the input, so that they can
+be viewed in XCrySDen or VESTA."))
+            .arg(Arg::new("index map")
+                .long("index-map")
+                .takes_value(true)
+                .possible_value("atoms")
+                .possible_value("volumes")
+                .case_insensitive(false)
+                .about("Write the index of the Bader atom or volume of each voxel.")
+                .long_about(
+"Write a single grid, in the same file format as the input density, where each
+voxel holds the index, starting at 1, of the Bader atom or volume it belongs to
+and vacuum is 0. A voxel split between several atoms or volumes takes the one
+with the largest weight. Writing the Bader volumes requires higher verbosity."))
+            .arg(Arg::new("index weight")
+                .long("index-weight")
+                .takes_value(false)
+                .requires("index map")
+                .about("Also write the weight of each voxel in the index map.")
+                .long_about(
+"Alongside the index map, write a grid of the weight each voxel contributes to
+the Bader atom or volume it is labelled with. This is 1 for voxels entirely
+inside a Bader atom or volume and less on the boundaries between them."))
+            .arg(Arg::new("json")
+                .long("json")
+                .takes_value(false)
+                .about("Write the results as JSON to results.json.")
+                .long_about(
+"Write everything the run computes to results.json. This holds the charge,
+spin, volume and surface distance of each atom, the position of each Bader
+maxima and the atom it is assigned to, the vacuum charge and volume and the
+settings and timings of the run. The charge and volume of each Bader volume are
+included at higher verbosity."))
+            .arg(Arg::new("henkelman")
+                .long("henkelman")
+                .takes_value(false)
+                .conflicts_with("moments")
+                .about("Write ACF.dat, BCF.dat and AVF.dat as the Henkelman bader program does.")
+                .long_about(
+"Write the Atomic Charge File (ACF.dat), the Bader Charge File (BCF.dat) and the
+Atomic Volume File (AVF.dat) with the same layout as the Henkelman group's
+bader program, so that they can be read by tools that parse its output, such
+as pymatgen. Only the charge is written, positions are in the cartesian axes of
+the input file and the Bader volumes with a charge below the maxima tolerance
+are left out of BCF.dat and AVF.dat."))
+            .arg(Arg::new("csv")
+                .long("csv")
+                .takes_value(false)
+                .about("Write the atoms and Bader volumes as CSV files.")
+                .long_about(
+"Write the atoms to ACF.csv and the Bader maxima to BCF.csv as comma separated
+tables, with the positions in the axis order of the input file. The charge and
+volume of each Bader volume are included at higher verbosity."))
+            .arg(Arg::new("save map")
+                .long("save-map")
+                .takes_value(true)
+                .conflicts_with("load map")
+                .about("Save the partitioned voxel map to a file.")
+                .long_about(
+"Save the partitioned voxel map, along with the Bader maxima and the atoms they
+are assigned to, to a compressed binary file. This can be loaded with the
+--load-map flag to partition other densities on the same basins without
+partitioning again. The map of the atoms is saved unless the verbosity is
+raised, in which case the map of the Bader volumes is saved."))
+            .arg(Arg::new("load map")
+                .long("load-map")
+                .takes_value(true)
+                .about("Load a partitioned voxel map instead of partitioning.")
+                .long_about(
+"Load a voxel map saved with the --save-map flag and use its basins rather than
+partitioning the density. The density must be on the same grid and lattice as
+the saved map and the verbosity must match that it was saved at ie. bca LOCPOT
+--load-map CHGCAR.map."))
             .arg(Arg::new("file type")
                 .short('t')
                 .long("type")
                 .takes_value(true)
+                .possible_value("abinit")
+                .possible_value("castep")
                 .possible_value("cube")
+                .possible_value("qe")
+                .possible_value("siesta")
                 .possible_value("vasp")
+                .possible_value("xsf")
                 .case_insensitive(false)
                 .about("The file type of the charge density.")
                 .long_about(
 "The file type of the input file. If this is not supplied the type will attempt
-to be infered from the filename."))
+to be infered from the filename, files ending in .pp are read as Quantum
+ESPRESSO filplot files, .xsf as XCrySDen files, .den_fmt as CASTEP files, _DEN
+as ABINIT files and .RHO or .DRHO as SIESTA files. The atoms of a CASTEP density
+are read from the seedname.cell file and of a SIESTA density from seedname.XV."))
+            .arg(Arg::new("xsf units")
+                .long("xsf-units")
+                .takes_value(true)
+                .possible_value("angstrom")
+                .possible_value("bohr")
+                .case_insensitive(false)
+                .about("The units of the values of an XSF datagrid.")
+                .long_about(
+"The units of the values of an XSF datagrid, which the format does not record.
+By default they are read as per cubic Angstrom, e/Angstrom^3 for a density, but
+the XSF files written by Quantum ESPRESSO's pp.x are per cubic bohr and need
+--xsf-units bohr to give the right charges."))
+            .arg(Arg::new("method")
+                .short('b')
+                .long("method")
+                .takes_value(true)
+                .possible_value("weight")
+                .possible_value("ongrid")
+                .possible_value("neargrid")
+                .case_insensitive(false)
+                .about("The method by which to partition the density.")
+                .long_about(
+"The method by which to partition the density. The default is the weight method
+which splits the boundary voxels between the maxima they contribute to. The
+ongrid and neargrid methods assign every voxel entirely to a single maxima, with
+ongrid being the quickest but biased by the grid and neargrid correcting for
+this bias."))
             .arg(Arg::new("reference")
                 .short('r')
                 .long("ref")
@@ -87,12 +219,102 @@ passed they are summed together."))
 for cube files as if spin density exists in a CHGCAR it will be read automatically.
 If using with VASP outputs then the files for charge and spin density must only
 contain a single density (ie. the original file has been split)."))
+            .arg(Arg::new("potcar")
+                .long("potcar")
+                .takes_value(true)
+                .conflicts_with("nuclear charge")
+                .about("A POTCAR to read the valence of each element from.")
+                .long_about(
+"A POTCAR to read the valence, ZVAL, of each element from. The net charge of
+each atom, its valence minus its partitioned electrons, is then written to
+every output."))
+            .arg(Arg::new("zval")
+                .long("zval")
+                .multiple_occurrences(true)
+                .number_of_values(1)
+                .conflicts_with("nuclear charge")
+                .about("The valence of an element, ie. --zval Fe=8.")
+                .long_about(
+"The valence of an element, given as element=valence. Multiple elements can be
+given by repeating the flag ie. bca CHGCAR --zval Fe=8 --zval O=6. These replace
+any valence read from a POTCAR. The net charge of each atom, its valence minus
+its partitioned electrons, is then written to every output."))
+            .arg(Arg::new("nuclear charge")
+                .long("nuclear-charge")
+                .takes_value(false)
+                .about("Use the nuclear charge of each atom as its valence.")
+                .long_about(
+"Use the nuclear charge of each atom, its atomic number, as its valence for an
+all-electron density such as a Gaussian cube file. The net charge of each atom,
+its valence minus its partitioned electrons, is then written to every
+output."))
+            .arg(Arg::new("channel")
+                .long("channel")
+                .takes_value(true)
+                .conflicts_with_all(&["reference", "all electron"])
+                .about("The channel of a multi-valued density to partition.")
+                .long_about(
+"The channel of the density to partition, starting at 1. Files can hold more
+than one value per voxel, such as the orbitals of a Gaussian or ORCA cube file
+or the spin of a CHGCAR, and each of these is a channel. By default the first
+channel is partitioned."))
+            .arg(Arg::new("integrate channel")
+                .long("integrate-channel")
+                .multiple_occurrences(true)
+                .number_of_values(1)
+                .about("A channel of the density to integrate over the basins.")
+                .long_about(
+"A channel of the density, starting at 1, to integrate over the partitioned
+basins. Multiple channels can be integrated by repeating the flag ie. bca
+orbitals.cube --channel 1 --integrate-channel 1 --integrate-channel 3. By
+default every channel is integrated."))
+            .arg(Arg::new("integrate")
+                .long("integrate")
+                .multiple_occurrences(true)
+                .number_of_values(1)
+                .about("A file of a scalar field to integrate over the basins.")
+                .long_about(
+"A file, in the same format and on the same grid as the density, of a scalar
+field to integrate over the partitioned basins, such as a LOCPOT, ELFCAR or
+the PARCHG of a band. Multiple files can be integrated by repeating the flag
+ie. bca CHGCAR --integrate PARCHG.0001 --integrate PARCHG.0002. The first
+field of each file is read in the same way as the density and its integral is
+written alongside the densities, named after the file."))
             .arg(Arg::new("all electron")
                 .short('a')
                 .long("aec")
                 .about("Convience flag for reading both aeccars.")
                 .takes_value(false)
                 .conflicts_with("reference"))
+            .arg(Arg::new("critical")
+                .long("critical")
+                .takes_value(false)
+                .about("Find and classify the critical points of the density.")
+                .long_about(
+"Find the critical points of the reference density and classify them by the
+signature of the Hessian as nuclear (3,-3), bond (3,-1), ring (3,+1) or cage
+(3,+3). The critical points are written to the Critical Point File (CPF.dat)
+along with their density, Laplacian and ellipticity. The bond paths traced from
+the bond critical points to the atoms they connect are written to the Bond Path
+File (BPF.dat)."))
+            .arg(Arg::new("moments")
+                .long("moments")
+                .takes_value(false)
+                .about("Calculate the dipole and quadrupole moments of each atom.")
+                .long_about(
+"Calculate the dipole and quadrupole moments of the charge in each atom's Bader
+volume. Positions are measured from the nucleus using the closest periodic
+image of the atom. The moments are appended to the Atomic Charge File (ACF.dat)
+with the quadrupole being traceless."))
+            .arg(Arg::new("contacts")
+                .long("contacts")
+                .takes_value(false)
+                .about("Calculate the contact areas between the atoms.")
+                .long_about(
+"Calculate the area of the zero-flux surface shared between each pair of atoms
+and the total surface area of each atom. The areas are estimated from the
+gradients of the weights of the boundary voxels and written as a contact-area
+matrix to the Contact Area File (CAF.dat)."))
             .arg(Arg::new("vacuum tolerance")
                 .long("vac")
                 .takes_value(true)
@@ -148,16 +370,54 @@ pub struct Args {
     pub file: String,
     /// The file format.
     pub file_type: FileType,
+    /// The method for partitioning the density.
+    pub method: Method,
     /// Tolerance to disregard weights at.
     pub weight_tolerance: f64,
     /// Tolerance to disregard maxima at.
     pub maxima_tolerance: f64,
     /// Output Writing
     pub output: WriteType,
+    /// Write the zero-flux surfaces of the output in this format.
+    pub surface: Option<MeshFormat>,
+    /// Whether to write the output densities as XSF files.
+    pub xsf: bool,
+    /// Write the index of the Bader atom or volume of each voxel.
+    pub index_map: Option<IndexMap>,
+    /// Whether to write the weight of each voxel in the index map.
+    pub index_weight: bool,
+    /// Whether to write the charge files in the Henkelman group's layout.
+    pub henkelman: bool,
+    /// Whether to write the results as JSON.
+    pub json: bool,
+    /// Whether to write the results as CSV.
+    pub csv: bool,
+    /// A file to save the partitioned voxel map to.
+    pub save_map: Option<String>,
+    /// A file to load a partitioned voxel map from.
+    pub load_map: Option<String>,
     /// Is there a reference file.
     pub reference: Reference,
     /// Is there a spin density to include as well.
     pub spin: Option<String>,
+    /// A POTCAR to read the valence of each element from.
+    pub potcar: Option<String>,
+    /// The valence of each element given on the command-line.
+    pub zval: Vec<(String, f64)>,
+    /// Whether to use the nuclear charge of each atom as its valence.
+    pub nuclear_charge: bool,
+    /// The channel of the density to partition.
+    pub channel: Option<usize>,
+    /// The channels of the density to integrate, all of them if empty.
+    pub integrate_channels: Vec<usize>,
+    /// Files of scalar fields to integrate over the basins.
+    pub integrate: Vec<String>,
+    /// Whether to search for the critical points of the density.
+    pub critical: bool,
+    /// Whether to calculate the multipole moments of each atom.
+    pub moments: bool,
+    /// Whether to calculate the contact areas between atoms.
+    pub contacts: bool,
     /// How many threads to use in the calculation.
     pub threads: usize,
     /// Is there a tolerance to consider a density vacuum.
@@ -166,32 +426,35 @@ pub struct Args {
 }
 
 impl Args {
-    /// Initialises the structure from the command-line arguments.
-    pub fn new(arguments: ArgMatches) -> Self {
+    /// Initialises the structure from the command-line arguments, returning an
+    /// [`Error::InvalidArgument`] if a value cannot be parsed.
+    pub fn new(arguments: ArgMatches) -> Result<Self> {
         // Collect file
         let file = match arguments.value_of("file") {
             Some(f) => String::from(f),
             None => String::new(),
         };
 
-        // Collect write charge info
+        // Collect write charge info, counting from 1
+        let index = |s: &str| {
+            match s.parse::<usize>() {
+            Ok(u) => match u.checked_sub(1) {
+                Some(u) => Ok(u as isize),
+                None => Err(Error::InvalidArgument(String::from(
+                    "Counting for index starts at 1.",
+                ))),
+            },
+            Err(_) => Err(Error::InvalidArgument(format!(
+                "Unable to parse index, ({}) to usize.",
+                s
+            ))),
+        }
+        };
         let output = match arguments.value_of("output") {
             Some("atoms") => {
                 let atoms = match arguments.values_of("index") {
                     Some(vec) => {
-                        vec.map(|s| match s.parse::<usize>() {
-                            Ok(u) => match u.checked_sub(1) {
-                                Some(u) => u as isize,
-                                None => {
-                                    panic!("Counting for index starts at 1.")
-                                }
-                            },
-                            Err(_) => {
-                                panic!("Unable to parse index, ({}) to usize.",
-                                       s)
-                            }
-                        })
-                        .collect::<Vec<isize>>()
+                        vec.map(index).collect::<Result<Vec<isize>>>()?
                     }
                     None => Vec::with_capacity(0),
                 };
@@ -200,19 +463,7 @@ impl Args {
             Some("volumes") => {
                 let volumes = match arguments.values_of("index") {
                     Some(vec) => {
-                        vec.map(|s| match s.parse::<usize>() {
-                            Ok(u) => match u.checked_sub(1) {
-                                Some(u) => u as isize,
-                                None => {
-                                    panic!("Counting for index starts at 1.")
-                                }
-                            },
-                            Err(_) => {
-                                panic!("Unable to parse index, ({}) to usize.",
-                                       s)
-                            }
-                        })
-                        .collect::<Vec<isize>>()
+                        vec.map(index).collect::<Result<Vec<isize>>>()?
                     }
                     None => Vec::with_capacity(0),
                 };
@@ -221,33 +472,60 @@ impl Args {
             _ => WriteType::None,
         };
 
+        // Collect the surface format
+        let surface = match arguments.value_of("surface") {
+            Some("obj") => Some(MeshFormat::Obj),
+            Some("ply") => Some(MeshFormat::Ply),
+            Some("stl") => Some(MeshFormat::Stl),
+            _ => None,
+        };
+        let xsf = arguments.is_present("xsf");
+        let index_map = match arguments.value_of("index map") {
+            Some("atoms") => Some(IndexMap::Atoms),
+            Some("volumes") => Some(IndexMap::Volumes),
+            _ => None,
+        };
+        let index_weight = arguments.is_present("index weight");
+        let henkelman = arguments.is_present("henkelman");
+        let json = arguments.is_present("json");
+        let csv = arguments.is_present("csv");
+        let save_map = arguments.value_of("save map").map(String::from);
+        let load_map = arguments.value_of("load map").map(String::from);
+
         // Collect file type
         let file_type = arguments.value_of("file type").map(String::from);
-        let file_type = match file_type {
-            Some(ftype) => {
-                if ftype.eq("cube") {
-                    FileType::Cube
-                } else {
-                    FileType::Vasp
-                }
-            }
-            None => {
-                if file.to_lowercase().contains("cube") {
-                    FileType::Cube
-                } else if file.to_lowercase().contains("car") {
-                    FileType::Vasp
-                } else {
-                    println!("Error: File-type cannot be infered, attempting to read as VASP");
-                    FileType::Vasp
+        let mut file_type = match file_type {
+            Some(ftype) => ftype.parse::<FileType>()?,
+            None => FileType::from_filename(&file).unwrap_or_else(|| {
+                println!("Error: File-type cannot be infered, attempting to read as VASP");
+                FileType::Vasp
+            }),
+        };
+        // Collect the units of an XSF datagrid
+        if let Some(units) = arguments.value_of("xsf units") {
+            match &mut file_type {
+                FileType::Xsf(u) => *u = units.parse()?,
+                _ => {
+                    return Err(Error::InvalidArgument(String::from(
+                        "XSF units can only be given for XSF files.",
+                    )))
                 }
             }
+        }
+        // Collect method
+        let method = match arguments.value_of("method") {
+            Some(m) => m.parse::<Method>()?,
+            None => Method::Weight,
         };
         // Collect weight tolerance
         let weight_tolerance = match arguments.value_of("weight tolerance") {
             Some(x) => match x.parse::<f64>() {
                 Ok(x) => x.max(1E-13),
                 Err(e) => {
-                    panic!("Couldn't parse weight tolerance into float:\n{}", e)
+                    return Err(Error::InvalidArgument(format!(
+                        "Couldn't parse weight tolerance into float:\n{}",
+                        e
+                    )))
                 }
             },
             _ => 1E-8,
@@ -257,7 +535,10 @@ impl Args {
             Some(x) => match x.parse::<f64>() {
                 Ok(x) => x,
                 Err(e) => {
-                    panic!("Couldn't parse maxima tolerance into float:\n{}", e)
+                    return Err(Error::InvalidArgument(format!(
+                        "Couldn't parse maxima tolerance into float:\n{}",
+                        e
+                    )))
                 }
             },
             _ => 1E-6,
@@ -268,7 +549,12 @@ impl Args {
             match arguments.value_of("threads").unwrap().parse::<usize>() {
                 Ok(0) => num_cpus::get().min(12),
                 Ok(x) => x,
-                Err(e) => panic!("Couldn't parse threads into integer:\n{}", e),
+                Err(e) => {
+                    return Err(Error::InvalidArgument(format!(
+                        "Couldn't parse threads into integer:\n{}",
+                        e
+                    )))
+                }
             }
         };
         // Collect vacuum tolerance
@@ -279,10 +565,12 @@ impl Args {
                 } else {
                     match s.parse::<f64>() {
                         Ok(x) => Some(x),
-                        Err(e) => panic!(
-                            "Couldn't parse vacuum tolerance into float:\n{}",
-                            e
-                        ),
+                        Err(e) => {
+                            return Err(Error::InvalidArgument(format!(
+                                "Couldn't parse vacuum tolerance into float:\n{}",
+                                e
+                            )))
+                        }
                     }
                 }
             }
@@ -293,7 +581,9 @@ impl Args {
             if let FileType::Vasp = file_type {
                 vec!["AECCAR0", "AECCAR2"]
             } else {
-                panic!("Error: Cannot use AECCAR flag for non VASP file-types.")
+                return Err(Error::InvalidArgument(String::from(
+                    "Cannot use AECCAR flag for non VASP file-types.",
+                )));
             }
         } else {
             match arguments.values_of("reference") {
@@ -308,27 +598,106 @@ impl Args {
             _ => Reference::None,
         };
         let spin = arguments.value_of("spin").map(String::from);
+        // Collect the valence of the elements
+        let potcar = arguments.value_of("potcar").map(String::from);
+        let zval = match arguments.values_of("zval") {
+            Some(vec) => vec.map(|s| match s.split_once('=') {
+                                 Some((e, z)) => match z.trim().parse::<f64>() {
+                                     Ok(z) => Ok((String::from(e.trim()), z)),
+                                     Err(_) => Err(Error::InvalidArgument(format!("Unable to parse valence, ({}) to float.", z))),
+                                 },
+                                 None => Err(Error::InvalidArgument(format!("Valence ({}) should be given as element=valence.", s))),
+                             })
+                            .collect::<Result<Vec<(String, f64)>>>()?,
+            None => Vec::with_capacity(0),
+        };
+        let nuclear_charge = arguments.is_present("nuclear charge");
+        // Collect the channels, counting from 1
+        let channel_index = |s: &str| {
+            match s.parse::<usize>() {
+            Ok(u) => match u.checked_sub(1) {
+                Some(u) => Ok(u),
+                None => Err(Error::InvalidArgument(String::from(
+                    "Counting for channels starts at 1.",
+                ))),
+            },
+            Err(_) => Err(Error::InvalidArgument(format!(
+                "Unable to parse channel, ({}) to usize.",
+                s
+            ))),
+        }
+        };
+        let channel = arguments.value_of("channel")
+                               .map(channel_index)
+                               .transpose()?;
+        let integrate_channels = match arguments.values_of("integrate channel")
+        {
+            Some(vec) => {
+                vec.map(channel_index).collect::<Result<Vec<usize>>>()?
+            }
+            None => Vec::with_capacity(0),
+        };
+        let integrate = match arguments.values_of("integrate") {
+            Some(vec) => vec.map(String::from).collect::<Vec<String>>(),
+            None => Vec::with_capacity(0),
+        };
+        // stdin can only be read once
+        let stdin = std::iter::once(&file).chain(spin.iter())
+                                          .chain(integrate.iter())
+                                          .map(String::as_str)
+                                          .chain(references.iter().copied())
+                                          .filter(|f| *f == reader::STDIN)
+                                          .count();
+        if stdin > 1 {
+            return Err(Error::InvalidArgument(String::from(
+                "Only one file can be read from stdin, (-).",
+            )));
+        }
+        let critical = arguments.is_present("critical");
+        let moments = arguments.is_present("moments");
+        let contacts = arguments.is_present("contacts");
+        // the Henkelman files need the Bader volumes for BCF.dat and AVF.dat
         let verbosity = match arguments.occurrences_of("verbosity") {
-            0 => Verbosity::Atoms,
-            1 => Verbosity::Bader,
+            0 if !henkelman => Verbosity::Atoms,
+            0 | 1 => Verbosity::Bader,
             _ => Verbosity::Full,
         };
-        Self { file,
-               file_type,
-               weight_tolerance,
-               maxima_tolerance,
-               output,
-               reference,
-               spin,
-               threads,
-               vacuum_tolerance,
-               verbosity }
+        Ok(Self { file,
+                  file_type,
+                  method,
+                  weight_tolerance,
+                  maxima_tolerance,
+                  output,
+                  surface,
+                  xsf,
+                  index_map,
+                  index_weight,
+                  henkelman,
+                  json,
+                  csv,
+                  save_map,
+                  load_map,
+                  reference,
+                  spin,
+                  potcar,
+                  zval,
+                  nuclear_charge,
+                  channel,
+                  integrate_channels,
+                  integrate,
+                  critical,
+                  moments,
+                  contacts,
+                  threads,
+                  vacuum_tolerance,
+                  verbosity })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::io::xsf::Units;
 
     #[test]
     fn clapapp_get() {
@@ -340,7 +709,7 @@ mod tests {
     fn argument_file() {
         let app = ClapApp::get();
         let matches = app.get_matches_from(vec!["bca", "CHGCAR"]);
-        let args = Args::new(matches);
+        let args = Args::new(matches).unwrap();
         assert_eq!(args.file, String::from("CHGCAR"));
     }
 
@@ -356,7 +725,7 @@ mod tests {
     fn argument_file_type_default_vasp() {
         let app = ClapApp::get();
         let matches = app.get_matches_from(vec!["bca", "CHGCAR"]);
-        let args = Args::new(matches);
+        let args = Args::new(matches).unwrap();
         let flag = matches!(args.file_type, FileType::Vasp);
         assert!(flag);
     }
@@ -365,7 +734,7 @@ mod tests {
     fn argument_file_type_default_unknown() {
         let app = ClapApp::get();
         let matches = app.get_matches_from(vec!["bca", "CHG"]);
-        let args = Args::new(matches);
+        let args = Args::new(matches).unwrap();
         let flag = matches!(args.file_type, FileType::Vasp);
         assert!(flag);
     }
@@ -374,7 +743,7 @@ mod tests {
     fn argument_file_type_vasp() {
         let app = ClapApp::get();
         let matches = app.get_matches_from(vec!["bca", "CHGCAR", "-t", "vasp"]);
-        let args = Args::new(matches);
+        let args = Args::new(matches).unwrap();
         let flag = matches!(args.file_type, FileType::Vasp);
         assert!(flag);
     }
@@ -383,7 +752,7 @@ mod tests {
     fn argument_file_type_default_cube() {
         let app = ClapApp::get();
         let matches = app.get_matches_from(vec!["bca", "charge.cube"]);
-        let args = Args::new(matches);
+        let args = Args::new(matches).unwrap();
         let flag = matches!(args.file_type, FileType::Cube);
         assert!(flag);
     }
@@ -393,11 +762,95 @@ mod tests {
         let app = ClapApp::get();
         let matches =
             app.get_matches_from(vec!["bca", "charge.cube", "--type", "cube"]);
-        let args = Args::new(matches);
+        let args = Args::new(matches).unwrap();
         let flag = matches!(args.file_type, FileType::Cube);
         assert!(flag);
     }
 
+    #[test]
+    fn argument_file_type_default_qe() {
+        let app = ClapApp::get();
+        let matches = app.get_matches_from(vec!["bca", "charge.pp"]);
+        let args = Args::new(matches).unwrap();
+        let flag = matches!(args.file_type, FileType::Qe);
+        assert!(flag);
+    }
+
+    #[test]
+    fn argument_file_type_qe() {
+        let app = ClapApp::get();
+        let matches =
+            app.get_matches_from(vec!["bca", "density", "--type", "qe"]);
+        let args = Args::new(matches).unwrap();
+        let flag = matches!(args.file_type, FileType::Qe);
+        assert!(flag);
+    }
+
+    #[test]
+    fn argument_file_type_default_xsf() {
+        let app = ClapApp::get();
+        let matches = app.get_matches_from(vec!["bca", "charge.xsf"]);
+        let args = Args::new(matches).unwrap();
+        let flag = matches!(args.file_type, FileType::Xsf(_));
+        assert!(flag);
+    }
+
+    #[test]
+    fn argument_file_type_xsf() {
+        let app = ClapApp::get();
+        let matches =
+            app.get_matches_from(vec!["bca", "density", "--type", "xsf"]);
+        let args = Args::new(matches).unwrap();
+        let flag = matches!(args.file_type, FileType::Xsf(_));
+        assert!(flag);
+    }
+
+    #[test]
+    fn argument_file_type_default_castep() {
+        let app = ClapApp::get();
+        let matches = app.get_matches_from(vec!["bca", "Si.den_fmt"]);
+        let args = Args::new(matches).unwrap();
+        let flag = matches!(args.file_type, FileType::Castep);
+        assert!(flag);
+    }
+
+    #[test]
+    fn argument_file_type_castep() {
+        let app = ClapApp::get();
+        let matches =
+            app.get_matches_from(vec!["bca", "density", "--type", "castep"]);
+        let args = Args::new(matches).unwrap();
+        let flag = matches!(args.file_type, FileType::Castep);
+        assert!(flag);
+    }
+
+    #[test]
+    fn argument_file_type_default_abinit() {
+        let app = ClapApp::get();
+        let matches = app.get_matches_from(vec!["bca", "run_o_DEN"]);
+        let args = Args::new(matches).unwrap();
+        let flag = matches!(args.file_type, FileType::Abinit);
+        assert!(flag);
+    }
+
+    #[test]
+    fn argument_file_type_default_siesta() {
+        let app = ClapApp::get();
+        let matches = app.get_matches_from(vec!["bca", "Si.RHO"]);
+        let args = Args::new(matches).unwrap();
+        let flag = matches!(args.file_type, FileType::Siesta);
+        assert!(flag);
+    }
+
+    #[test]
+    fn argument_file_type_default_compressed() {
+        let app = ClapApp::get();
+        let matches = app.get_matches_from(vec!["bca", "charge.pp.gz"]);
+        let args = Args::new(matches).unwrap();
+        let flag = matches!(args.file_type, FileType::Qe);
+        assert!(flag);
+    }
+
     #[test]
     #[should_panic]
     fn argument_file_type_not_type() {
@@ -406,12 +859,49 @@ mod tests {
                    .unwrap_or_else(|e| panic!("An error occurs: {}", e));
     }
 
+    #[test]
+    fn argument_method_default() {
+        let app = ClapApp::get();
+        let matches = app.get_matches_from(vec!["bca", "CHGCAR"]);
+        let args = Args::new(matches).unwrap();
+        let flag = matches!(args.method, Method::Weight);
+        assert!(flag);
+    }
+
+    #[test]
+    fn argument_method_ongrid() {
+        let app = ClapApp::get();
+        let matches =
+            app.get_matches_from(vec!["bca", "CHGCAR", "-b", "ongrid"]);
+        let args = Args::new(matches).unwrap();
+        let flag = matches!(args.method, Method::Ongrid);
+        assert!(flag);
+    }
+
+    #[test]
+    fn argument_method_neargrid() {
+        let app = ClapApp::get();
+        let matches =
+            app.get_matches_from(vec!["bca", "CHGCAR", "--method", "neargrid"]);
+        let args = Args::new(matches).unwrap();
+        let flag = matches!(args.method, Method::Neargrid);
+        assert!(flag);
+    }
+
+    #[test]
+    #[should_panic]
+    fn argument_method_not_method() {
+        let app = ClapApp::get();
+        let _ = app.try_get_matches_from(vec!["bca", "CHGCAR", "-b", "grid"])
+                   .unwrap_or_else(|e| panic!("An error occurs: {}", e));
+    }
+
     #[test]
     fn argument_output_atoms() {
         let app = ClapApp::get();
         let matches =
             app.get_matches_from(vec!["bca", "CHGCAR", "-o", "atoms"]);
-        let args = Args::new(matches);
+        let args = Args::new(matches).unwrap();
         match args.output {
             WriteType::Atom(v) => assert!(v.is_empty()),
             _ => panic!(),
@@ -423,7 +913,7 @@ mod tests {
         let app = ClapApp::get();
         let matches =
             app.get_matches_from(vec!["bca", "CHGCAR", "-o", "volumes"]);
-        let args = Args::new(matches);
+        let args = Args::new(matches).unwrap();
         match args.output {
             WriteType::Volume(v) => assert!(v.is_empty()),
             _ => panic!(),
@@ -443,7 +933,7 @@ mod tests {
         let app = ClapApp::get();
         let matches = app.get_matches_from(vec!["bca", "CHGCAR", "-o",
                                                 "volumes", "-i", "1",]);
-        let args = Args::new(matches);
+        let args = Args::new(matches).unwrap();
         match args.output {
             WriteType::Volume(v) => assert_eq!(v, vec![0]),
             _ => panic!(),
@@ -456,7 +946,7 @@ mod tests {
         let matches = app.get_matches_from(vec!["bca", "CHGCAR", "-o",
                                                 "atoms", "--index", "1",
                                                 "-i", "3",]);
-        let args = Args::new(matches);
+        let args = Args::new(matches).unwrap();
         match args.output {
             WriteType::Atom(v) => assert_eq!(v, vec![0, 2]),
             _ => panic!(),
@@ -464,12 +954,11 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
     fn argument_index_zero() {
         let app = ClapApp::get();
         let matches = app.get_matches_from(vec!["bca", "CHGCAR", "-o",
                                                 "atoms", "-i", "0"]);
-        let _ = Args::new(matches);
+        assert!(matches!(Args::new(matches), Err(Error::InvalidArgument(_))));
     }
 
     #[test]
@@ -488,6 +977,118 @@ mod tests {
                    .unwrap_or_else(|e| panic!("An error occurs: {}", e));
     }
 
+    #[test]
+    fn argument_surface() {
+        let app = ClapApp::get();
+        let matches = app.get_matches_from(vec!["bca",
+                                                "CHGCAR",
+                                                "-o",
+                                                "atoms",
+                                                "--surface",
+                                                "ply"]);
+        let args = Args::new(matches).unwrap();
+        assert!(matches!(args.surface, Some(MeshFormat::Ply)))
+    }
+
+    #[test]
+    #[should_panic]
+    fn argument_surface_no_output() {
+        let app = ClapApp::get();
+        let _ =
+            app.try_get_matches_from(vec!["bca", "CHGCAR", "--surface", "obj"])
+               .unwrap_or_else(|e| panic!("An error occurs: {}", e));
+    }
+
+    #[test]
+    fn argument_xsf() {
+        let app = ClapApp::get();
+        let matches =
+            app.get_matches_from(vec!["bca", "CHGCAR", "-o", "atoms", "--xsf"]);
+        let args = Args::new(matches).unwrap();
+        assert!(args.xsf)
+    }
+
+    #[test]
+    #[should_panic]
+    fn argument_xsf_no_output() {
+        let app = ClapApp::get();
+        let _ = app.try_get_matches_from(vec!["bca", "CHGCAR", "--xsf"])
+                   .unwrap_or_else(|e| panic!("An error occurs: {}", e));
+    }
+
+    #[test]
+    fn argument_henkelman() {
+        let app = ClapApp::get();
+        let matches =
+            app.get_matches_from(vec!["bca", "CHGCAR", "--henkelman"]);
+        let args = Args::new(matches).unwrap();
+        assert!(args.henkelman);
+        assert!(matches!(args.verbosity, Verbosity::Bader))
+    }
+
+    #[test]
+    #[should_panic]
+    fn argument_henkelman_moments() {
+        let app = ClapApp::get();
+        let _ = app.try_get_matches_from(vec!["bca",
+                                              "CHGCAR",
+                                              "--henkelman",
+                                              "--moments"])
+                   .unwrap_or_else(|e| panic!("An error occurs: {}", e));
+    }
+
+    #[test]
+    fn argument_zval() {
+        let app = ClapApp::get();
+        let matches = app.get_matches_from(vec!["bca", "CHGCAR", "--potcar",
+                                                "POTCAR", "--zval", "Fe=8",
+                                                "--zval", "O = 6.5"]);
+        let args = Args::new(matches).unwrap();
+        assert_eq!(args.potcar, Some(String::from("POTCAR")));
+        assert_eq!(args.zval,
+                   vec![(String::from("Fe"), 8.), (String::from("O"), 6.5)]);
+        assert!(!args.nuclear_charge)
+    }
+
+    #[test]
+    fn argument_zval_no_equals() {
+        let app = ClapApp::get();
+        let matches =
+            app.get_matches_from(vec!["bca", "CHGCAR", "--zval", "Fe8"]);
+        assert!(matches!(Args::new(matches), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    #[should_panic]
+    fn argument_nuclear_charge_zval() {
+        let app = ClapApp::get();
+        let _ = app.try_get_matches_from(vec!["bca",
+                                              "density.cube",
+                                              "--nuclear-charge",
+                                              "--zval",
+                                              "O=6"])
+                   .unwrap_or_else(|e| panic!("An error occurs: {}", e));
+    }
+
+    #[test]
+    fn argument_json_csv() {
+        let app = ClapApp::get();
+        let matches =
+            app.get_matches_from(vec!["bca", "CHGCAR", "--json", "--csv"]);
+        let args = Args::new(matches).unwrap();
+        assert!(args.json);
+        assert!(args.csv)
+    }
+
+    #[test]
+    fn argument_json_csv_default() {
+        let app = ClapApp::get();
+        let matches = app.get_matches_from(vec!["bca", "CHGCAR"]);
+        let args = Args::new(matches).unwrap();
+        assert!(!args.json);
+        assert!(!args.csv)
+    }
+
     #[test]
     fn argument_spin() {
         let app = ClapApp::get();
@@ -495,16 +1096,163 @@ mod tests {
                                                 "density.cube",
                                                 "-s",
                                                 "spin.cube",]);
-        let args = Args::new(matches);
+        let args = Args::new(matches).unwrap();
         assert_eq!(args.spin, Some(String::from("spin.cube")))
     }
 
+    #[test]
+    fn argument_channel() {
+        let app = ClapApp::get();
+        let matches = app.get_matches_from(vec!["bca",
+                                                "orbitals.cube",
+                                                "--channel",
+                                                "2",
+                                                "--integrate-channel",
+                                                "1",
+                                                "--integrate-channel",
+                                                "3"]);
+        let args = Args::new(matches).unwrap();
+        assert_eq!(args.channel, Some(1));
+        assert_eq!(args.integrate_channels, vec![0, 2]);
+    }
+
+    #[test]
+    fn argument_integrate() {
+        let app = ClapApp::get();
+        let matches = app.get_matches_from(vec!["bca",
+                                                "CHGCAR",
+                                                "--integrate",
+                                                "PARCHG.0001",
+                                                "--integrate",
+                                                "LOCPOT"]);
+        let args = Args::new(matches).unwrap();
+        assert_eq!(args.integrate,
+                   vec![String::from("PARCHG.0001"), String::from("LOCPOT")]);
+    }
+
+    #[test]
+    fn argument_map() {
+        let app = ClapApp::get();
+        let matches = app.get_matches_from(vec!["bca",
+                                                "CHGCAR",
+                                                "--save-map",
+                                                "CHGCAR.map"]);
+        let args = Args::new(matches).unwrap();
+        assert_eq!(args.save_map, Some(String::from("CHGCAR.map")));
+        assert_eq!(args.load_map, None);
+        let app = ClapApp::get();
+        let matches = app.get_matches_from(vec!["bca",
+                                                "LOCPOT",
+                                                "--load-map",
+                                                "CHGCAR.map"]);
+        let args = Args::new(matches).unwrap();
+        assert_eq!(args.save_map, None);
+        assert_eq!(args.load_map, Some(String::from("CHGCAR.map")));
+    }
+
+    #[test]
+    #[should_panic]
+    fn argument_save_load_map() {
+        let app = ClapApp::get();
+        let _ = app.try_get_matches_from(vec!["bca",
+                                              "CHGCAR",
+                                              "--save-map",
+                                              "a.map",
+                                              "--load-map",
+                                              "b.map"])
+                   .unwrap_or_else(|e| panic!("An error occurs: {}", e));
+    }
+
+    #[test]
+    fn argument_index_map() {
+        let app = ClapApp::get();
+        let matches = app.get_matches_from(vec!["bca",
+                                                "CHGCAR",
+                                                "--index-map",
+                                                "volumes",
+                                                "--index-weight"]);
+        let args = Args::new(matches).unwrap();
+        assert!(matches!(args.index_map, Some(IndexMap::Volumes)));
+        assert!(args.index_weight);
+        let app = ClapApp::get();
+        let matches = app.get_matches_from(vec!["bca", "CHGCAR"]);
+        let args = Args::new(matches).unwrap();
+        assert!(args.index_map.is_none());
+        assert!(!args.index_weight);
+    }
+
+    #[test]
+    #[should_panic]
+    fn argument_index_weight_no_map() {
+        let app = ClapApp::get();
+        let _ =
+            app.try_get_matches_from(vec!["bca", "CHGCAR", "--index-weight"])
+               .unwrap_or_else(|e| panic!("An error occurs: {}", e));
+    }
+
+    #[test]
+    fn argument_channel_default() {
+        let app = ClapApp::get();
+        let matches = app.get_matches_from(vec!["bca", "orbitals.cube"]);
+        let args = Args::new(matches).unwrap();
+        assert_eq!(args.channel, None);
+        assert!(args.integrate_channels.is_empty());
+        assert!(args.integrate.is_empty());
+    }
+
+    #[test]
+    fn argument_channel_zero() {
+        let app = ClapApp::get();
+        let matches = app.get_matches_from(vec!["bca",
+                                                "orbitals.cube",
+                                                "--channel",
+                                                "0"]);
+        assert!(matches!(Args::new(matches), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    #[should_panic]
+    fn argument_channel_reference() {
+        let app = ClapApp::get();
+        let _ = app.try_get_matches_from(vec!["bca",
+                                              "CHGCAR",
+                                              "--channel",
+                                              "2",
+                                              "-r",
+                                              "AECCAR0"])
+                   .unwrap_or_else(|e| panic!("An error occurs: {}", e));
+    }
+
+    #[test]
+    fn argument_critical() {
+        let app = ClapApp::get();
+        let matches = app.get_matches_from(vec!["bca", "CHGCAR", "--critical"]);
+        let args = Args::new(matches).unwrap();
+        assert!(args.critical)
+    }
+
+    #[test]
+    fn argument_moments() {
+        let app = ClapApp::get();
+        let matches = app.get_matches_from(vec!["bca", "CHGCAR", "--moments"]);
+        let args = Args::new(matches).unwrap();
+        assert!(args.moments)
+    }
+
+    #[test]
+    fn argument_contacts() {
+        let app = ClapApp::get();
+        let matches = app.get_matches_from(vec!["bca", "CHGCAR", "--contacts"]);
+        let args = Args::new(matches).unwrap();
+        assert!(args.contacts)
+    }
+
     #[test]
     fn argument_reference_one() {
         let app = ClapApp::get();
         let matches =
             app.get_matches_from(vec!["bca", "CHGCAR", "-r", "CHGCAR_sum"]);
-        let args = Args::new(matches);
+        let args = Args::new(matches).unwrap();
         let flag = matches!(args.reference, Reference::One(_));
         assert!(flag)
     }
@@ -514,7 +1262,7 @@ mod tests {
         let app = ClapApp::get();
         let v = vec!["bca", "CHGCAR", "-r", "AECCAR0", "--ref", "AECCAR2"];
         let matches = app.get_matches_from(v);
-        let args = Args::new(matches);
+        let args = Args::new(matches).unwrap();
         let flag = matches!(args.reference, Reference::Two(_, _));
         assert!(flag)
     }
@@ -524,7 +1272,7 @@ mod tests {
         let app = ClapApp::get();
         let v = vec!["bca", "CHGCAR"];
         let matches = app.get_matches_from(v);
-        let args = Args::new(matches);
+        let args = Args::new(matches).unwrap();
         let flag = matches!(args.reference, Reference::None);
         assert!(flag)
     }
@@ -534,7 +1282,7 @@ mod tests {
         let app = ClapApp::get();
         let v = vec!["bca", "CHGCAR", "-a"];
         let matches = app.get_matches_from(v);
-        let args = Args::new(matches);
+        let args = Args::new(matches).unwrap();
         let flag = match args.reference {
             Reference::Two(x, y) => (x == *"AECCAR0") && (y == *"AECCAR2"),
             _ => false,
@@ -543,12 +1291,11 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
     fn argument_aeccar_cube() {
         let app = ClapApp::get();
         let v = vec!["bca", "charge.cube", "-a"];
         let matches = app.get_matches_from(v);
-        let _ = Args::new(matches);
+        assert!(matches!(Args::new(matches), Err(Error::InvalidArgument(_))));
     }
 
     #[test]
@@ -556,7 +1303,7 @@ mod tests {
         let app = ClapApp::get();
         let v = vec!["bca", "CHGCAR", "--vac", "auto"];
         let matches = app.get_matches_from(v);
-        let args = Args::new(matches);
+        let args = Args::new(matches).unwrap();
         assert_eq!(args.vacuum_tolerance, Some(1E-6))
     }
 
@@ -565,17 +1312,16 @@ mod tests {
         let app = ClapApp::get();
         let v = vec!["bca", "CHGCAR", "--vac", "1E-4"];
         let matches = app.get_matches_from(v);
-        let args = Args::new(matches);
+        let args = Args::new(matches).unwrap();
         assert_eq!(args.vacuum_tolerance, Some(1E-4))
     }
 
     #[test]
-    #[should_panic]
     fn argument_vacuum_tolerance_not_float() {
         let app = ClapApp::get();
         let v = vec!["bca", "CHGCAR", "--vac", "0.00.1"];
         let matches = app.get_matches_from(v);
-        let _ = Args::new(matches);
+        assert!(matches!(Args::new(matches), Err(Error::InvalidArgument(_))));
     }
 
     #[test]
@@ -583,17 +1329,16 @@ mod tests {
         let app = ClapApp::get();
         let v = vec!["bca", "CHGCAR", "--weight", "1E-4"];
         let matches = app.get_matches_from(v);
-        let args = Args::new(matches);
+        let args = Args::new(matches).unwrap();
         assert_eq!(args.weight_tolerance, 1E-4)
     }
 
     #[test]
-    #[should_panic]
     fn argument_weight_tolerance_not_float() {
         let app = ClapApp::get();
         let v = vec!["bca", "CHGCAR", "-w", "0.00.1"];
         let matches = app.get_matches_from(v);
-        let _ = Args::new(matches);
+        assert!(matches!(Args::new(matches), Err(Error::InvalidArgument(_))));
     }
 
     #[test]
@@ -601,17 +1346,16 @@ mod tests {
         let app = ClapApp::get();
         let v = vec!["bca", "CHGCAR", "--maxima", "1E-4"];
         let matches = app.get_matches_from(v);
-        let args = Args::new(matches);
+        let args = Args::new(matches).unwrap();
         assert_eq!(args.maxima_tolerance, 1E-4)
     }
 
     #[test]
-    #[should_panic]
     fn argument_maxima_tolerance_not_float() {
         let app = ClapApp::get();
         let v = vec!["bca", "CHGCAR", "-m", "0.00.1"];
         let matches = app.get_matches_from(v);
-        let _ = Args::new(matches);
+        assert!(matches!(Args::new(matches), Err(Error::InvalidArgument(_))));
     }
 
     #[test]
@@ -619,7 +1363,7 @@ mod tests {
         let app = ClapApp::get();
         let v = vec!["bca", "CHGCAR"];
         let matches = app.get_matches_from(v);
-        let args = Args::new(matches);
+        let args = Args::new(matches).unwrap();
         let threads = num_cpus::get().min(12);
         assert_eq!(args.threads, threads)
     }
@@ -629,16 +1373,51 @@ mod tests {
         let app = ClapApp::get();
         let v = vec!["bca", "CHGCAR", "--threads", "1"];
         let matches = app.get_matches_from(v);
-        let args = Args::new(matches);
+        let args = Args::new(matches).unwrap();
         assert_eq!(args.threads, 1)
     }
 
     #[test]
-    #[should_panic]
     fn argument_threads_not_int() {
         let app = ClapApp::get();
         let v = vec!["bca", "CHGCAR", "-J", "0.1"];
         let matches = app.get_matches_from(v);
-        let _ = Args::new(matches);
+        assert!(matches!(Args::new(matches), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn argument_stdin() {
+        let app = ClapApp::get();
+        let v = vec!["bca", "-", "-t", "cube", "-r", "AECCAR0"];
+        let matches = app.get_matches_from(v);
+        let args = Args::new(matches).unwrap();
+        assert_eq!(args.file, String::from("-"));
+        assert!(matches!(args.file_type, FileType::Cube));
+    }
+
+    #[test]
+    fn argument_stdin_twice() {
+        let app = ClapApp::get();
+        let v = vec!["bca", "-", "-s", "-"];
+        let matches = app.get_matches_from(v);
+        assert!(matches!(Args::new(matches), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn argument_xsf_units() {
+        let app = ClapApp::get();
+        let v = vec!["bca", "charge.xsf", "--xsf-units", "bohr"];
+        let matches = app.get_matches_from(v);
+        let args = Args::new(matches).unwrap();
+        let flag = matches!(args.file_type, FileType::Xsf(Units::Bohr));
+        assert!(flag);
+    }
+
+    #[test]
+    fn argument_xsf_units_not_xsf() {
+        let app = ClapApp::get();
+        let v = vec!["bca", "CHGCAR", "--xsf-units", "bohr"];
+        let matches = app.get_matches_from(v);
+        assert!(matches!(Args::new(matches), Err(Error::InvalidArgument(_))));
     }
 }
@@ -1,3 +1,4 @@
+use crate::error::{Error, Result};
 use crate::utils;
 
 /// struct for containing the information about the atoms.
@@ -12,6 +13,10 @@ pub struct Atoms {
     pub reduced_lattice: ReducedLattice,
     /// The positions of the atoms in the LLL-reduced basis.
     pub reduced_positions: Vec<[f64; 3]>,
+    /// The chemical symbol of each atom, X if it is not known.
+    pub species: Vec<String>,
+    /// The atomic number of each atom, 0 if it is not known.
+    pub atomic_numbers: Vec<usize>,
 }
 
 impl Atoms {
@@ -19,20 +24,116 @@ impl Atoms {
     pub fn new(lattice: Lattice,
                positions: Vec<[f64; 3]>,
                text: String)
-               -> Self {
-        let reduced_lattice = ReducedLattice::from_lattice(&lattice);
+               -> Result<Self> {
+        let reduced_lattice = ReducedLattice::from_lattice(&lattice)?;
         let reduced_positions =
             positions.iter()
                      .map(|p| reduced_lattice.to_reduced(*p))
                      .collect::<Vec<[f64; 3]>>();
-        Self { lattice,
-               positions,
-               text,
-               reduced_lattice,
-               reduced_positions }
+        let species = vec![String::from("X"); positions.len()];
+        let atomic_numbers = vec![0; positions.len()];
+        Ok(Self { lattice,
+                  positions,
+                  text,
+                  reduced_lattice,
+                  reduced_positions,
+                  species,
+                  atomic_numbers })
+    }
+
+    /// Sets the chemical symbol of each atom, and from them the atomic numbers.
+    /// The species are left unknown if there isn't one for every atom.
+    pub fn with_species(mut self, species: Vec<String>) -> Self {
+        if species.len() == self.positions.len() {
+            self.atomic_numbers =
+                species.iter().map(|s| atomic_number(s)).collect();
+            self.species = species;
+        }
+        self
+    }
+
+    /// Sets the atomic number of each atom, and from them the chemical
+    /// symbols. The species are left unknown if there isn't one for every atom.
+    pub fn with_atomic_numbers(self, atomic_numbers: Vec<usize>) -> Self {
+        let species = atomic_numbers.iter()
+                                    .map(|z| String::from(symbol(*z)))
+                                    .collect();
+        self.with_species(species)
+    }
+
+    /// Whether the species of the atoms were read from the file.
+    pub fn has_species(&self) -> bool {
+        self.species.iter().any(|s| s != "X")
+    }
+
+    /// The valence of each atom from a table of elements and their valence,
+    /// where a later entry for an element replaces an earlier one.
+    pub fn valence(&self, table: &[(String, f64)]) -> Result<Vec<f64>> {
+        self.species
+            .iter()
+            .map(|s| {
+                match table.iter()
+                           .rev()
+                           .find(|(e, _)| e.eq_ignore_ascii_case(s))
+                {
+                    Some((_, zval)) => Ok(*zval),
+                    None if s == "X" => Err(Error::InvalidArgument(String::from("The species of the atoms are not known."))),
+                    None => Err(Error::InvalidArgument(format!("No valence has been given for {}.", s))),
+                }
+            })
+            .collect()
+    }
+
+    /// The nuclear charge of each atom, the valence of an all-electron
+    /// calculation.
+    pub fn nuclear_charges(&self) -> Result<Vec<f64>> {
+        self.atomic_numbers
+            .iter()
+            .map(|z| match z {
+                0 => Err(Error::InvalidArgument(String::from("The atomic numbers of the atoms are not known."))),
+                z => Ok(*z as f64),
+            })
+            .collect()
+    }
+
+    /// The species in the order that they first appear.
+    pub fn unique_species(&self) -> Vec<String> {
+        let mut unique: Vec<String> = Vec::new();
+        self.species.iter().for_each(|s| {
+                               if !unique.contains(s) {
+                                   unique.push(s.clone())
+                               }
+                           });
+        unique
     }
 }
 
+/// The chemical symbols indexed by atomic number, with X as an unknown atom.
+const ELEMENTS: [&str; 119] =
+    ["X", "H", "He", "Li", "Be", "B", "C", "N", "O", "F", "Ne", "Na", "Mg",
+     "Al", "Si", "P", "S", "Cl", "Ar", "K", "Ca", "Sc", "Ti", "V", "Cr", "Mn",
+     "Fe", "Co", "Ni", "Cu", "Zn", "Ga", "Ge", "As", "Se", "Br", "Kr", "Rb",
+     "Sr", "Y", "Zr", "Nb", "Mo", "Tc", "Ru", "Rh", "Pd", "Ag", "Cd", "In",
+     "Sn", "Sb", "Te", "I", "Xe", "Cs", "Ba", "La", "Ce", "Pr", "Nd", "Pm",
+     "Sm", "Eu", "Gd", "Tb", "Dy", "Ho", "Er", "Tm", "Yb", "Lu", "Hf", "Ta",
+     "W", "Re", "Os", "Ir", "Pt", "Au", "Hg", "Tl", "Pb", "Bi", "Po", "At",
+     "Rn", "Fr", "Ra", "Ac", "Th", "Pa", "U", "Np", "Pu", "Am", "Cm", "Bk",
+     "Cf", "Es", "Fm", "Md", "No", "Lr", "Rf", "Db", "Sg", "Bh", "Hs", "Mt",
+     "Ds", "Rg", "Cn", "Nh", "Fl", "Mc", "Lv", "Ts", "Og"];
+
+/// The atomic number of a chemical symbol, ignoring case, or 0 if the symbol
+/// isn't an element.
+pub fn atomic_number(symbol: &str) -> usize {
+    ELEMENTS.iter()
+            .position(|e| e.eq_ignore_ascii_case(symbol.trim()))
+            .unwrap_or(0)
+}
+
+/// The chemical symbol of an atomic number, or X if there is no such element.
+pub fn symbol(atomic_number: usize) -> &'static str {
+    ELEMENTS.get(atomic_number).unwrap_or(&ELEMENTS[0])
+}
+
 /// Lattice - structure for containing information on the cell
 ///
 /// <pre class="rust">
@@ -79,7 +180,9 @@ impl Lattice {
     ///     [cx, cy, cz],
     /// ]
     /// </pre>
-    pub fn new(lattice: [[f64; 3]; 3]) -> Self {
+    ///
+    /// Returns a [`Error::Numerical`] if the lattice doesn't span 3D space.
+    pub fn new(lattice: [[f64; 3]; 3]) -> Result<Self> {
         let a_vector = utils::norm(lattice[0]);
         let b_vector = utils::norm(lattice[1]);
         let c_vector = utils::norm(lattice[2]);
@@ -126,10 +229,7 @@ impl Lattice {
         for i in 0..26 {
             distance_matrix[i] = utils::norm(shift_matrix[i]);
         }
-        let to_fractional = match utils::invert_lattice(&lattice) {
-            Ok(inv) => inv,
-            Err(e) => panic!("{}", e),
-        };
+        let to_fractional = utils::invert_lattice(&lattice)?;
         let to_cartesian = lattice;
         let volume = {
             (lattice[0][0]
@@ -143,15 +243,15 @@ impl Lattice {
                                                    .abs()
         };
         let gradient_transform = utils::transpose_square(to_fractional);
-        Self { a: a_vector,
-               b: b_vector,
-               c: c_vector,
-               distance_matrix,
-               shift_matrix,
-               gradient_transform,
-               to_fractional,
-               to_cartesian,
-               volume }
+        Ok(Self { a: a_vector,
+                  b: b_vector,
+                  c: c_vector,
+                  distance_matrix,
+                  shift_matrix,
+                  gradient_transform,
+                  to_fractional,
+                  to_cartesian,
+                  volume })
     }
 }
 
@@ -171,9 +271,9 @@ pub struct ReducedLattice {
 
 impl ReducedLattice {
     /// Creates a lll-reduced lattice from the cell lattice.
-    pub fn from_lattice(lattice: &Lattice) -> Self {
+    pub fn from_lattice(lattice: &Lattice) -> Result<Self> {
         let reduced_lattice =
-            Lattice::new(ReducedLattice::lll_lattice(lattice.to_cartesian));
+            Lattice::new(ReducedLattice::lll_lattice(lattice.to_cartesian))?;
         let to_cartesian = reduced_lattice.to_cartesian;
         let to_fractional = reduced_lattice.to_fractional;
         let distance_matrix = reduced_lattice.distance_matrix;
@@ -204,11 +304,11 @@ impl ReducedLattice {
                 shift_vec
             });
         }
-        Self { shift_matrix,
-               cartesian_shift_matrix,
-               distance_matrix,
-               to_cartesian,
-               to_fractional }
+        Ok(Self { shift_matrix,
+                  cartesian_shift_matrix,
+                  distance_matrix,
+                  to_cartesian,
+                  to_fractional })
     }
 
     /// Calculates the lll reduction of a lattice.
@@ -222,8 +322,9 @@ impl ReducedLattice {
                 match mu[i][j] {
                     q if q.abs() <= 0.5 => (),
                     q => {
-                        for k in 0..3 {
-                            a[i][k] -= q.round() * a[j][k];
+                        let a_j = a[j];
+                        for (a_ik, a_jk) in a[i].iter_mut().zip(&a_j) {
+                            *a_ik -= q.round() * a_jk;
                         }
                         let (b_temp, mu_temp) =
                             ReducedLattice::gram_schmidt(&a);
@@ -238,11 +339,7 @@ impl ReducedLattice {
             {
                 i += 1;
             } else {
-                for j in 0..3 {
-                    b[0][0] = a[i][j];
-                    a[i][j] = a[i - 1][j];
-                    a[i - 1][j] = b[0][0];
-                }
+                a.swap(i, i - 1);
                 let (b_temp, mu_temp) = ReducedLattice::gram_schmidt(&a);
                 b = b_temp;
                 mu = mu_temp;
@@ -283,14 +380,61 @@ impl ReducedLattice {
 mod tests {
     use super::*;
 
+    #[test]
+    fn atoms_species() {
+        let lattice =
+            Lattice::new([[3., 0., 0.], [0., 3., 0.], [0., 0., 3.]]).unwrap();
+        let positions = vec![[0.; 3], [1.5; 3], [0.5; 3]];
+        let atoms = Atoms::new(lattice, positions, String::new()).unwrap();
+        assert!(!atoms.has_species());
+        let atoms = atoms.with_atomic_numbers(vec![8, 1, 1]);
+        assert!(atoms.has_species());
+        assert_eq!(atoms.species, vec!["O", "H", "H"]);
+        assert_eq!(atoms.unique_species(), vec!["O", "H"]);
+        let atoms = atoms.with_species(vec![String::from("Fe"),
+                                            String::from("fe"),
+                                            String::from("Q")]);
+        assert_eq!(atoms.atomic_numbers, vec![26, 26, 0]);
+        let atoms = atoms.with_species(vec![String::from("Si")]);
+        assert_eq!(atoms.atomic_numbers, vec![26, 26, 0]);
+    }
+
+    #[test]
+    fn atoms_valence() {
+        let lattice =
+            Lattice::new([[3., 0., 0.], [0., 3., 0.], [0., 0., 3.]]).unwrap();
+        let positions = vec![[0.; 3], [1.5; 3], [0.5; 3]];
+        let atoms = Atoms::new(lattice, positions, String::new()).unwrap();
+        assert!(atoms.nuclear_charges().is_err());
+        assert!(atoms.valence(&[(String::from("O"), 6.)]).is_err());
+        let atoms = atoms.with_atomic_numbers(vec![8, 1, 1]);
+        assert_eq!(atoms.nuclear_charges().unwrap(), vec![8., 1., 1.]);
+        let table = vec![(String::from("H"), 1.),
+                         (String::from("O"), 8.),
+                         (String::from("o"), 6.)];
+        assert_eq!(atoms.valence(&table).unwrap(), vec![6., 1., 1.]);
+        assert!(atoms.valence(&table[..1]).is_err());
+    }
+
+    #[test]
+    fn atoms_element_lookup() {
+        assert_eq!(atomic_number("Og"), 118);
+        assert_eq!(atomic_number(" Si "), 14);
+        assert_eq!(atomic_number("Xx"), 0);
+        assert_eq!(symbol(79), "Au");
+        assert_eq!(symbol(200), "X");
+    }
+
     #[test]
     fn atoms_new() {
         let positions = vec![[0.; 3]];
-        let lattice = Lattice::new([[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]]);
+        let lattice =
+            Lattice::new([[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]]).unwrap();
         let text = String::new();
-        let atoms = Atoms::new(lattice, positions, text);
+        let atoms = Atoms::new(lattice, positions, text).unwrap();
         let positions = vec![[0.; 3]];
-        let lattice = Lattice::new([[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]]);
+        let lattice =
+            Lattice::new([[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]]).unwrap();
         let text = String::new();
         assert_eq!(atoms.lattice.to_cartesian, lattice.to_cartesian);
         assert_eq!(atoms.positions, positions);
@@ -299,7 +443,8 @@ mod tests {
 
     #[test]
     fn lattice_new() {
-        let lattice = Lattice::new([[1., 0., 0.], [0., 2., 0.], [0., 0., 2.]]);
+        let lattice =
+            Lattice::new([[1., 0., 0.], [0., 2., 0.], [0., 0., 2.]]).unwrap();
         let distance_matrix = [3.,
                                5f64.powf(0.5),
                                3.,
@@ -330,9 +475,9 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
     fn lattice_new_non_invert() {
-        let _ = Lattice::new([[1., 0., 0.], [1., 0., 0.], [0., 0., 2.]]);
+        let lattice = Lattice::new([[1., 0., 0.], [1., 0., 0.], [0., 0., 2.]]);
+        assert!(matches!(lattice, Err(Error::Numerical(_))));
     }
 
     #[test]
@@ -1,167 +1,476 @@
-use anyhow::{bail, Context, Result};
-use bader::analysis::{
-    assign_maxima, sum_atoms_densities, sum_bader_densities,
-};
-use bader::arguments::{Args, ClapApp, Verbosity};
-use bader::io::{self, FileFormat, FileType, WriteType};
-use bader::methods::{maxima_finder, weight};
+use anyhow::{Context, Result};
+use bader::analysis::{atomic_moments, contact_areas, net_charges};
+use bader::arguments::{Args, ClapApp, Reference, Verbosity};
+use bader::bond_paths::bond_paths;
+use bader::calculation::{BaderCalculation, BaderResult, Partitions};
+use bader::critical_points::{self, critical_points};
+use bader::io::checkpoint::Checkpoint;
+use bader::io::{self, results, IndexMap, WriteType};
 use bader::progress::Bar;
-use bader::utils::{dot, vacuum_index};
-use bader::voxel_map::{BlockingVoxelMap, NonBlockingVoxelMap};
+use bader::surface::zero_flux_surface;
 use rustc_hash::FxHashSet;
+use std::path::Path;
+use std::time::Instant;
 
-fn main() -> Result<()> {
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {:?}", e);
+        // exit with the code of the crate's error, if there is one
+        let code = e.chain()
+                    .find_map(|c| c.downcast_ref::<bader::Error>())
+                    .map_or(1, |e| e.exit_code());
+        std::process::exit(code);
+    }
+}
+
+fn run() -> Result<()> {
     // argument parsing
     let app = ClapApp::get();
-    let args = Args::new(app.get_matches());
+    let args = Args::new(app.get_matches())?;
     // print splash
     println!("Multi-threaded Bader Charge Analysis ({})",
              env!("CARGO_PKG_VERSION"));
     // read the input files into a densities vector and a Grid struct
-    let file_type: Box<dyn FileFormat> = match args.file_type {
-        FileType::Vasp => Box::new(io::vasp::Vasp {}),
-        FileType::Cube => Box::new(io::cube::Cube {}),
-    };
+    let file_type = args.file_type.format();
     println!("Running on {} threads.", args.threads);
-    let (densities, rho, atoms, grid, voxel_origin) = file_type.init(&args);
+    let start = Instant::now();
+    let (densities, rho, atoms, grid, voxel_origin) = file_type.init(&args)?;
+    // read the fields to integrate over the basins
+    let fields = args.integrate
+                     .iter()
+                     .map(|f| -> Result<Vec<f64>> {
+                         let (_, g, _, mut d) =
+                             file_type.read(f.clone(), args.threads)
+                                      .with_context(|| {
+                                          format!("Failed to read {}", f)
+                                      })?;
+                         if g != grid {
+                             return Err(bader::Error::GridMismatch { source:
+                                                                f.clone(),
+                                                            expected: grid,
+                                                            found: g }.into());
+                         }
+                         Ok(d.swap_remove(0))
+                     })
+                     .collect::<Result<Vec<Vec<f64>>>>()?;
+    let read_time = start.elapsed().as_secs_f64();
+    // the valence of each atom, to give their net charges
+    let valence = if args.nuclear_charge {
+        Some(atoms.nuclear_charges()?)
+    } else if args.potcar.is_some() || !args.zval.is_empty() {
+        let mut table = match &args.potcar {
+            Some(potcar) => io::vasp::read_zval(potcar).with_context(|| {
+                                format!("Failed to read ZVAL from {}", potcar)
+                            })?,
+            None => Vec::with_capacity(0),
+        };
+        table.extend(args.zval.iter().cloned());
+        Some(atoms.valence(&table)?)
+    } else {
+        None
+    };
     let reference = if rho.is_empty() { &densities[0] } else { &rho };
-    let voxel_map =
-        BlockingVoxelMap::new(grid, atoms.lattice.to_cartesian, voxel_origin);
-    let total_density = densities.iter()
-                                 .map(|d| {
-                                     d.iter().sum::<f64>()
-                                     * voxel_map.grid.voxel_lattice.volume
-                                 })
-                                 .collect::<Vec<f64>>();
-    // create the index list which will tell us in which order to evaluate the
-    // voxels
-    let mut index: Vec<usize> = (0..voxel_map.grid.size.total).collect();
-    let pbar =
-        Bar::visible(index.len() as u64, 100, String::from("Maxima Finding: "));
-    let bader_maxima =
-        maxima_finder(&mut index, reference, &voxel_map, args.threads, pbar)?;
-    index.sort_unstable_by(|a, b| {
-             reference[*b].partial_cmp(&reference[*a]).unwrap()
-         });
-    // remove from the indices any voxel that is below the vacuum limit
-    index.truncate(vacuum_index(reference, &index, args.vacuum_tolerance)
-         .context("Failed to apply vacuum tolerance")?);
-    // Start a thread-safe progress bar and run the main calculation
-    let pbar = Bar::visible(bader_maxima.len() as u64,
-                            100,
-                            String::from("Assigning to Atoms: "));
-    let (atom_map, minimum_distance) = assign_maxima(&bader_maxima,
-                                                     &atoms,
-                                                     &voxel_map.grid,
-                                                     args.threads,
-                                                     pbar)?;
-    let pbar = Bar::visible(index.len() as u64,
-                            100,
-                            String::from("Bader Partitioning: "));
-    // input the maxima into the voxel map
-    if let Verbosity::Atoms = args.verbosity {
-        bader_maxima.iter().enumerate().for_each(|(i, maxima)| {
-                                           voxel_map.maxima_store(*maxima,
-                                                                  atom_map[i]
-                                                                  as isize);
-                                       });
+    // name the density columns after the channels if they have been chosen
+    let mut density_names = if args.integrate_channels.is_empty() {
+        file_type.density_names(&atoms, densities.len())
+    } else {
+        args.integrate_channels
+            .iter()
+            .map(|c| format!("Channel {}", c + 1))
+            .collect()
+    };
+    // name the fields after their files, less any directory or compression
+    density_names.extend(args.integrate.iter().map(|f| {
+                     let name = Path::new(f).file_name()
+                                            .and_then(|n| n.to_str())
+                                            .unwrap_or(f);
+                     String::from(io::reader::strip_compression(name))
+                 }));
+    // the voxel map stores the atoms unless the Bader volumes are needed
+    let by_atom = matches!(args.verbosity, Verbosity::Atoms);
+    let calculation =
+        BaderCalculation::new(&densities, &atoms, grid, voxel_origin)
+            .with_reference(reference)
+            .with_fields(&fields)
+            .with_method(args.method)
+            .with_weight_tolerance(args.weight_tolerance)
+            .with_vacuum_tolerance(args.vacuum_tolerance)
+            .with_threads(args.threads)
+            .with_volumes(!by_atom)
+            .with_progress(true);
+    // load the partitioned voxel map or partition the reference density
+    let checkpoint = if let Some(load_map) = &args.load_map {
+        let checkpoint = Checkpoint::load(load_map).with_context(|| {
+                             format!("Failed to load the voxel map from {}",
+                                     load_map)
+                         })?;
+        checkpoint.check(grid,
+                         atoms.lattice.to_cartesian,
+                         atoms.positions.len())
+                  .with_context(|| {
+                      format!("{} does not match the voxel map in {}",
+                              args.file, load_map)
+                  })?;
+        if checkpoint.by_atom != by_atom {
+            return Err(bader::Error::InvalidArgument(format!(
+                "The voxel map in {} was saved at a different verbosity, run \
+                 with{} -v to use it.",
+                load_map,
+                if by_atom { "" } else { "out" }
+            )).into());
+        }
+        checkpoint
     } else {
-        bader_maxima.iter()
-                    .enumerate()
-                    .for_each(|(i, maxima)| {
-                        voxel_map.maxima_store(*maxima, i as isize);
-                    })
+        calculation.partition()?
+    };
+    let partition_time = start.elapsed().as_secs_f64() - read_time;
+    if let Some(save_map) = &args.save_map {
+        checkpoint.save(save_map)
+                  .with_context(|| {
+                      format!("Failed to save the voxel map to {}", save_map)
+                  })?;
+    }
+    // sum the densities and fields over the atoms and Bader volumes
+    let result = calculation.sum(checkpoint)?;
+    let maxima_positions = result.maxima_positions();
+    let BaderResult { atoms: atoms_partitions,
+                      surface_distance: min_surf_dist,
+                      volumes,
+                      maxima: bader_maxima,
+                      atom_map,
+                      minimum_distance,
+                      total_density,
+                      voxel_map, } = result;
+    let Partitions { density: atoms_density,
+                     volume: atoms_volume, } = atoms_partitions;
+    if args.critical {
+        let pbar = Bar::visible(voxel_map.grid.size.total as u64,
+                                100,
+                                String::from("Critical Points: "));
+        let critical_points = critical_points(reference,
+                                              &voxel_map.grid,
+                                              args.vacuum_tolerance,
+                                              args.threads,
+                                              pbar)?;
+        let positions =
+            critical_points.iter()
+                           .map(|cp| file_type.coordinate_format(cp.position))
+                           .collect();
+        let critical_points_file =
+            io::output::critical_points_file(positions, &critical_points)?;
+        io::output::write(critical_points_file, String::from("CPF.dat"))?;
+        let pbar = Bar::visible(critical_points::count(&critical_points)[1]
+                                as u64,
+                                100,
+                                String::from("Bond Paths: "));
+        let bond_paths = bond_paths(reference,
+                                    &voxel_map.grid,
+                                    &atoms,
+                                    &critical_points,
+                                    args.threads,
+                                    pbar)?;
+        let images = bond_paths.iter()
+                               .map(|bp| file_type.image_format(bp.image))
+                               .collect();
+        let bond_paths_file = io::output::bond_paths_file(images, &bond_paths)?;
+        io::output::write(bond_paths_file, String::from("BPF.dat"))?;
     }
-    // calculate the weights
-    weight(reference,
-           &voxel_map,
-           &index,
-           pbar,
-           args.threads,
-           args.weight_tolerance);
-    // convert into a NonBlockingVoxelMap as the map is filled
-    let voxel_map = NonBlockingVoxelMap::from_blocking_voxel_map(voxel_map);
-    let pbar = Bar::visible(index.len() as u64,
-                            100,
-                            String::from("Summing Densities: "));
-    // sum the densities and then write the charge partition files
-    if let Verbosity::Atoms = args.verbosity {
-        let (atoms_density, atoms_volume, min_surf_dist) =
-            sum_bader_densities(&densities,
-                                &voxel_map,
-                                &atoms,
-                                None,
-                                args.threads,
-                                atoms.positions.len(),
-                                pbar)?;
+    // calculate the multipole moments of each atom if requested
+    let moments_table = if args.moments {
+        let pbar = Bar::visible(voxel_map.grid.size.total as u64,
+                                100,
+                                String::from("Atomic Moments: "));
+        let atoms_map = match args.verbosity {
+            Verbosity::Atoms => None,
+            _ => Some(&atom_map[..]),
+        };
+        let moments = atomic_moments(&densities[0],
+                                     &voxel_map,
+                                     &atoms,
+                                     atoms_map,
+                                     args.threads,
+                                     pbar)?;
+        let dipoles = moments.iter()
+                             .map(|m| file_type.coordinate_format(m.dipole))
+                             .collect();
+        let diagonals =
+            moments.iter()
+                   .map(|m| {
+                       let q = m.quadrupole;
+                       file_type.coordinate_format([q[0][0], q[1][1], q[2][2]])
+                   })
+                   .collect();
+        // indexed by the axis they don't contain the off-diagonals reorder
+        // like a vector when the axes are swapped
+        let off_diagonals =
+            moments.iter()
+                   .map(|m| {
+                       let q = m.quadrupole;
+                       file_type.coordinate_format([q[1][2], q[0][2], q[0][1]])
+                   })
+                   .collect();
+        Some(io::output::moments_file(dipoles,
+                                      diagonals,
+                                      off_diagonals,
+                                      &moments)?)
+    } else {
+        None
+    };
+    // calculate the contact areas between the atoms if requested
+    if args.contacts {
+        let pbar = Bar::visible(voxel_map.grid.size.total as u64,
+                                100,
+                                String::from("Contact Areas: "));
+        let atoms_map = match args.verbosity {
+            Verbosity::Atoms => None,
+            _ => Some(&atom_map[..]),
+        };
+        let (surface_area, contact_area) = contact_areas(&voxel_map,
+                                                         atoms_map,
+                                                         atoms.positions
+                                                              .len(),
+                                                         args.threads,
+                                                         pbar)?;
+        let contact_areas_file =
+            io::output::contact_areas_file(&surface_area, &contact_area)?;
+        io::output::write(contact_areas_file, String::from("CAF.dat"))?;
+    }
+    // a function to put positions into the axis order of the file
+    let axes = file_type.axes();
+    let order = |p: [f64; 3]| [p[axes[0]], p[axes[1]], p[axes[2]]];
+    // collect everything the run has computed to write as JSON and CSV
+    let write_results = |atoms_density: &[Vec<f64>],
+                         atoms_volume: &[f64],
+                         min_surf_dist: &[f64],
+                         bader: Option<(&[Vec<f64>], &[f64])>|
+     -> Result<()> {
+        if !(args.json || args.csv) {
+            return Ok(());
+        }
+        let net_charge =
+            valence.as_ref().map(|v| net_charges(v, atoms_density));
+        let atom_partitions = atoms.positions
+                                   .iter()
+                                   .enumerate()
+                                   .map(|(i, p)| {
+                                       results::AtomPartition { element:
+                                                  atoms.species[i].clone(),
+                                              position: order(*p),
+                                              density:
+                                                  atoms_density[i].clone(),
+                                              volume: atoms_volume[i],
+                                              surface_distance: min_surf_dist
+                                                  [i],
+                                              net_charge:
+                                                  net_charge.as_ref()
+                                                            .map(|n| n[i]) }
+                                   })
+                                   .collect();
+        let volume_partitions = maxima_positions.iter()
+                                                .enumerate()
+                                                .map(|(i, p)| {
+                                                    results::VolumePartition {
+                position: order(*p),
+                atom: atom_map[i] + 1,
+                atom_distance: minimum_distance[i],
+                density: bader.map(|(d, _)| d[i].clone()),
+                volume: bader.map(|(_, v)| v[i]),
+            }
+                                                })
+                                                .collect();
+        let reference = match &args.reference {
+            Reference::One(f) => vec![f.clone()],
+            Reference::Two(f, g) => vec![f.clone(), g.clone()],
+            Reference::None => Vec::with_capacity(0),
+        };
+        let metadata =
+            results::Metadata { version:
+                                    String::from(env!("CARGO_PKG_VERSION")),
+                                file: args.file.clone(),
+                                reference,
+                                spin: args.spin.clone(),
+                                method: args.method.to_string(),
+                                weight_tolerance: args.weight_tolerance,
+                                maxima_tolerance: args.maxima_tolerance,
+                                vacuum_tolerance: args.vacuum_tolerance,
+                                threads: args.threads,
+                                timing:
+                                    results::Timing { read: read_time,
+                                                      partition:
+                                                          partition_time,
+                                                      total:
+                                                          start.elapsed()
+                                                               .as_secs_f64() } };
+        let results = results::Results::new(metadata,
+                                            density_names.clone(),
+                                            atom_partitions,
+                                            volume_partitions,
+                                            &total_density,
+                                            atoms.lattice.volume);
+        if args.json {
+            io::output::write(results.to_json()?,
+                              String::from("results.json"))?;
+        }
+        if args.csv {
+            io::output::write(results.atoms_csv(), String::from("ACF.csv"))?;
+            io::output::write(results.volumes_csv(), String::from("BCF.csv"))?;
+        }
+        Ok(())
+    };
+    // label the atoms with their elements if the file gave them
+    let species = if atoms.has_species() {
+        Some(&atoms.species[..])
+    } else {
+        None
+    };
+    // write the charge partition files
+    if let Some(Partitions { density: bader_density,
+                             volume: bader_volume, }) = volumes
+    {
+        if args.henkelman {
+            // only the charge is written to the Henkelman files
+            let bader_charge =
+                bader_density.iter().map(|d| d[0]).collect::<Vec<f64>>();
+            let atoms_charge =
+                atoms_density.iter().map(|d| d[0]).collect::<Vec<f64>>();
+            let positions = maxima_positions.iter()
+                                            .map(|p| order(*p))
+                                            .collect::<Vec<[f64; 3]>>();
+            let bader_charge_file =
+                io::output::henkelman_bader_file(&positions,
+                                                 &bader_charge,
+                                                 &atom_map,
+                                                 &minimum_distance,
+                                                 args.maxima_tolerance);
+            io::output::write(bader_charge_file, String::from("BCF.dat"))?;
+            let atoms_volume_file =
+                io::output::henkelman_volumes_file(&bader_charge,
+                                                   &atom_map,
+                                                   atoms.positions.len(),
+                                                   args.maxima_tolerance);
+            io::output::write(atoms_volume_file, String::from("AVF.dat"))?;
+            let positions = atoms.positions
+                                 .iter()
+                                 .map(|p| order(*p))
+                                 .collect::<Vec<[f64; 3]>>();
+            let atoms_charge_file =
+                io::output::henkelman_atoms_file(&positions,
+                                                 &atoms_charge,
+                                                 &atoms_volume,
+                                                 &min_surf_dist,
+                                                 total_density[0],
+                                                 atoms.lattice.volume);
+            io::output::write(atoms_charge_file, String::from("ACF.dat"))?;
+        } else {
+            let net_charge =
+                valence.as_ref().map(|v| net_charges(v, &atoms_density));
+            let positions =
+                maxima_positions.iter()
+                                .map(|p| file_type.coordinate_format(*p))
+                                .collect();
+            let bader_charge_file =
+                io::output::partitions_file(positions,
+                                            &bader_density,
+                                            &bader_volume,
+                                            &total_density,
+                                            atoms.lattice.volume,
+                                            &minimum_distance,
+                                            Some(&atom_map),
+                                            &density_names,
+                                            None,
+                                            None)?;
+            let positions =
+                atoms.positions
+                     .iter()
+                     .map(|coords| file_type.coordinate_format(*coords))
+                     .collect();
+            // check that the write was successfull
+            io::output::write(bader_charge_file, String::from("BCF.dat"))?;
+            let mut atoms_charge_file =
+                io::output::partitions_file(positions,
+                                            &atoms_density,
+                                            &atoms_volume,
+                                            &total_density,
+                                            atoms.lattice.volume,
+                                            &min_surf_dist,
+                                            None,
+                                            &density_names,
+                                            species,
+                                            net_charge.as_deref())?;
+            if let Verbosity::Full = args.verbosity {
+                atoms_charge_file.push_str(&format!("\n  Bader Maxima: {}\n Boundary Voxels: {}",
+                                                    bader_maxima.len(),
+                                                    voxel_map.weight_map.len())
+                                           );
+            }
+            if let Some(moments_table) = &moments_table {
+                atoms_charge_file.push_str(&format!("\n\n{}", moments_table));
+            }
+            // check that the write was successfull
+            io::output::write(atoms_charge_file, String::from("ACF.dat"))?;
+        }
+        write_results(&atoms_density,
+                      &atoms_volume,
+                      &min_surf_dist,
+                      Some((&bader_density, &bader_volume)))?;
+    } else {
+        let net_charge =
+            valence.as_ref().map(|v| net_charges(v, &atoms_density));
         let positions = atoms.positions
                              .iter()
                              .map(|coords| file_type.coordinate_format(*coords))
                              .collect();
-        let atoms_charge_file = io::output::partitions_file(positions,
+        let mut atoms_charge_file = io::output::partitions_file(positions,
                                                             &atoms_density,
                                                             &atoms_volume,
                                                             &total_density,
                                                             atoms.lattice
                                                                  .volume,
                                                             &min_surf_dist,
-                                                            None).context("Building the Atom output file")?;
-        // check that the write was successfull
-        io::output::write(atoms_charge_file, String::from("ACF.dat"))?;
-    } else {
-        let (bader_density, bader_volume, min_surf_dist) =
-            sum_bader_densities(&densities,
-                                &voxel_map,
-                                &atoms,
-                                Some(&atom_map),
-                                args.threads,
-                                bader_maxima.len(),
-                                pbar)?;
-        let positions = bader_maxima.iter()
-                                    .map(|p| {
-                                        let p = voxel_map.grid.to_cartesian(*p);
-                                        let p = dot(p,
-                                                    voxel_map.grid
-                                                             .voxel_lattice
-                                                             .to_cartesian);
-                                        file_type.coordinate_format(p)
-                                    })
-                                    .collect();
-        let bader_charge_file = io::output::partitions_file(positions,
-                                                            &bader_density,
-                                                            &bader_volume,
-                                                            &total_density,
-                                                            atoms.lattice
-                                                                 .volume,
-                                                            &minimum_distance,
-                                                            Some(&atom_map))?;
-        let (atoms_density, atoms_volume) =
-            sum_atoms_densities(&bader_density,
-                                &bader_volume,
-                                &atom_map,
-                                atoms.positions.len())?;
-        let positions = atoms.positions
-                             .iter()
-                             .map(|coords| file_type.coordinate_format(*coords))
-                             .collect();
-        // check that the write was successfull
-        io::output::write(bader_charge_file, String::from("BCF.dat"))?;
-        let mut atoms_charge_file =
-            io::output::partitions_file(positions,
-                                        &atoms_density,
-                                        &atoms_volume,
-                                        &total_density,
-                                        atoms.lattice.volume,
-                                        &min_surf_dist,
-                                        None)?;
-        if let Verbosity::Full = args.verbosity {
-            atoms_charge_file.push_str(&format!("\n  Bader Maxima: {}\n Boundary Voxels: {}",
-                                                bader_maxima.len(),
-                                                voxel_map.weight_map.len())
-                                       );
+                                                            None,
+                                                            &density_names,
+                                                            species,
+                                                            net_charge.as_deref()).context("Building the Atom output file")?;
+        if let Some(moments_table) = &moments_table {
+            atoms_charge_file.push_str(&format!("\n\n{}", moments_table));
         }
         // check that the write was successfull
         io::output::write(atoms_charge_file, String::from("ACF.dat"))?;
+        write_results(&atoms_density, &atoms_volume, &min_surf_dist, None)?;
+    }
+    // write the index of the atom or volume that dominates each voxel
+    if let Some(index_map) = &args.index_map {
+        let (name, atoms_map) = match (index_map, by_atom) {
+            (IndexMap::Volumes, true) => {
+                return Err(bader::Error::InvalidArgument(String::from(
+"Unable to write the Bader volume index map at this level of verbosity, either
+increase verbosity or write the atoms index map."
+                )).into())
+            }
+            (IndexMap::Volumes, false) => ("volumes", None),
+            (IndexMap::Atoms, true) => ("atoms", None),
+            (IndexMap::Atoms, false) => ("atoms", Some(&atom_map[..])),
+        };
+        let dominant = voxel_map.dominant_map(atoms_map);
+        // undo the scaling of the writer so the file holds the values as is
+        let scale = file_type.write_scale(&atoms);
+        let index = dominant.iter()
+                            .map(|d| d.map(|(i, _)| (i + 1) as f64 / scale))
+                            .collect();
+        let pbar = Bar::visible(voxel_map.grid.size.total as u64,
+                                100,
+                                String::from("Writing Index Map: "));
+        file_type.write(&atoms, index, format!("{}_index", name), pbar)
+                 .context("Failed to write the index map")?;
+        if args.index_weight {
+            let weight =
+                dominant.iter().map(|d| d.map(|(_, w)| w / scale)).collect();
+            let pbar = Bar::visible(voxel_map.grid.size.total as u64,
+                                    100,
+                                    String::from("Writing Index Weights: "));
+            file_type.write(&atoms, weight, format!("{}_weight", name), pbar)
+                     .context("Failed to write the index map weights")?;
+        }
     }
     // Prepare to write any densities that have been requested.
     let filename = match densities.len().cmp(&2) {
@@ -178,13 +487,30 @@ fn main() -> Result<()> {
     // with an id for each volume that is to be outputted. Save this as a lazy
     // iterator as to save memory? This is now a large part of the binary,
     // should it be moved?
+    let atoms_output = matches!(args.output, WriteType::Atom(_));
+    let xsf = args.xsf;
+    // the requested atoms or volumes have to exist before any are written
+    let check_indices = |indices: &[isize], len: usize, name: &str| {
+        match indices.iter().find(|i| **i as usize >= len) {
+            Some(i) => Err(bader::Error::InvalidArgument(format!(
+                "Cannot write {} {} as there are only {}.",
+                name,
+                i + 1,
+                len
+            ))),
+            None => Ok(()),
+        }
+    };
     let write_map: Box<dyn Iterator<Item = (isize, Vec<Option<f64>>)>> =
         match (args.output, args.verbosity) {
-            (WriteType::Volume(_), Verbosity::Atoms) => bail!(
+            (WriteType::Volume(_), Verbosity::Atoms) => {
+                return Err(bader::Error::InvalidArgument(String::from(
 "Unable to write Bader volumes at this level of verbosity, either increase
 verbosity or export atoms."
-                ),
+                )).into())
+            }
             (WriteType::Volume(v), _) => {
+                check_indices(&v, bader_maxima.len(), "volume")?;
                 let volume_iter = if v.is_empty() {
                     (0..bader_maxima.len() as isize).collect()
                 } else {
@@ -194,6 +520,7 @@ verbosity or export atoms."
                        .map(|volume_number| (volume_number, voxel_map.volume_map(volume_number))))
             }
             (WriteType::Atom(a), Verbosity::Atoms) => {
+                check_indices(&a, atoms.positions.len(), "atom")?;
                 let atom_iter = if a.is_empty() {
                     (0..atoms.positions.len() as isize).collect()
                 } else {
@@ -206,6 +533,7 @@ verbosity or export atoms."
                      }))
             }
             (WriteType::Atom(a), _) => {
+                check_indices(&a, atoms.positions.len(), "atom")?;
                 let atom_iter: Vec<FxHashSet<isize>> =
                     if a.is_empty() {
                         let mut a_i =
@@ -244,25 +572,50 @@ verbosity or export atoms."
             }
             (WriteType::None, _) => Box::new(Vec::with_capacity(0).into_iter()),
         };
-    write_map.for_each(|(id, weight_map)| {
-        densities.iter()
-                 .zip(&filename)
-                 .for_each(|(rho, flnm)| {
-                     let pbar = Bar::visible(index.len() as u64,
-                                             100,
-                                             format!("Writing file {}:", flnm));
-                     if file_type.write(
-                         &atoms,
-                         weight_map.iter()
-                                   .zip(rho)
-                                   .map(|(weight, charge)| weight.map(|w| w * charge))
-                                   .collect(),
-                         format!("{}_{}", id + 1, flnm),
-                         pbar).is_err()
-                     {
-                         panic!("Error in writing {}", flnm)
-                     }
-                 });
-    });
+    if let Some(format) = &args.surface {
+        for (id, weight_map) in write_map {
+            // unwrap the surface around the atom or maxima it belongs to
+            let centre = if atoms_output {
+                atoms.positions[id as usize]
+            } else {
+                voxel_map.grid.to_cartesian(bader_maxima[id as usize])
+            };
+            let pbar = Bar::visible(voxel_map.grid.size.total as u64,
+                                    100,
+                                    format!("Surface {}:", id + 1));
+            let mut mesh = zero_flux_surface(&weight_map,
+                                             &voxel_map.grid,
+                                             centre,
+                                             args.threads,
+                                             pbar)?;
+            mesh.permute_axes(file_type.axes());
+            io::mesh::write(&mesh, format, format!("{}_surface", id + 1))?;
+        }
+        return Ok(());
+    }
+    for (id, weight_map) in write_map {
+        for (rho, flnm) in densities.iter().zip(&filename) {
+            let pbar = Bar::visible(voxel_map.grid.size.total as u64,
+                                    100,
+                                    format!("Writing file {}:", flnm));
+            let data =
+                weight_map.iter()
+                          .zip(rho)
+                          .map(|(weight, charge)| weight.map(|w| w * charge))
+                          .collect();
+            let filename = format!("{}_{}", id + 1, flnm);
+            if xsf {
+                io::xsf::write_grid(&atoms,
+                                    data,
+                                    grid,
+                                    voxel_origin,
+                                    file_type.axes(),
+                                    filename,
+                                    pbar)
+            } else {
+                file_type.write(&atoms, data, filename, pbar)
+            }.with_context(|| format!("Error in writing {}", flnm))?;
+        }
+    }
     Ok(())
 }
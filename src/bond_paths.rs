@@ -0,0 +1,247 @@
+use crate::atoms::Atoms;
+use crate::critical_points::{interpolate, CriticalPoint, CriticalPointKind};
+use crate::error::{Error, Result};
+use crate::grid::Grid;
+use crate::progress::Bar;
+use crate::utils::{dot, norm, vdot};
+use crossbeam_utils::thread;
+
+/// A bond path between two atoms, traced from a (3,-1) critical point.
+pub struct BondPath {
+    /// The index of the bond critical point in the critical point list.
+    pub critical_point: usize,
+    /// The two atoms connected by the path, atoms\[0\] <= atoms\[1\].
+    pub atoms: [usize; 2],
+    /// The lattice translation of atoms\[1\] relative to atoms\[0\].
+    pub image: [isize; 3],
+    /// The length of the path in cartesian units.
+    pub length: f64,
+    /// The density at the bond critical point.
+    pub density: f64,
+}
+
+/// Converts a cartesian position to the voxel basis.
+fn cartesian_to_voxel(grid: &Grid, x: [f64; 3]) -> [f64; 3] {
+    let u = dot(x, grid.voxel_lattice.to_fractional);
+    [u[0] - grid.voxel_origin[0],
+     u[1] - grid.voxel_origin[1],
+     u[2] - grid.voxel_origin[2]]
+}
+
+/// Follows the gradient of the density uphill from the cartesian position,
+/// start, until a maximum is reached. The step size is halved every time a
+/// step would pass the maximum, reversing the gradient, and the ascent stops
+/// once it has shrunk by a factor of 1000. Returns the unwrapped end point and the path length.
+fn ascend(density: &[f64],
+          grid: &Grid,
+          start: [f64; 3],
+          step: f64)
+          -> Option<([f64; 3], f64)> {
+    let value = |x: [f64; 3]| {
+        interpolate(density, grid, cartesian_to_voxel(grid, x)).to_cartesian(grid)
+    };
+    let mut x = start;
+    let mut h = step;
+    let mut length = 0.;
+    let mut current = value(x);
+    for _ in 0..100_000 {
+        let g = current.gradient;
+        let g_norm = norm(g);
+        if g_norm < 1E-12 {
+            return Some((x, length));
+        }
+        let next = [x[0] + h * g[0] / g_norm,
+                    x[1] + h * g[1] / g_norm,
+                    x[2] + h * g[2] / g_norm];
+        let trial = value(next);
+        if vdot(trial.gradient, g) > 0. {
+            x = next;
+            current = trial;
+            length += h;
+        } else {
+            h /= 2.;
+            if h < step * 1E-3 {
+                return Some((x, length));
+            }
+        }
+    }
+    None
+}
+
+/// Finds the atom, and the lattice translation of it, closest to the
+/// unwrapped cartesian position, x.
+fn nearest_atom_image(atoms: &Atoms, x: [f64; 3]) -> (usize, [isize; 3]) {
+    let fractional = dot(x, atoms.lattice.to_fractional);
+    let mut nearest = (0, [0isize; 3]);
+    let mut min_distance = f64::INFINITY;
+    for (i, atom) in atoms.positions.iter().enumerate() {
+        let atom = dot(*atom, atoms.lattice.to_fractional);
+        let centre = [(fractional[0] - atom[0]).round() as isize,
+                      (fractional[1] - atom[1]).round() as isize,
+                      (fractional[2] - atom[2]).round() as isize];
+        // check the neighbouring images as rounding is only exact for
+        // orthogonal cells
+        for shift in 0..27 {
+            let image = [centre[0] + shift / 9 - 1,
+                         centre[1] + (shift / 3) % 3 - 1,
+                         centre[2] + shift % 3 - 1];
+            let d = dot([fractional[0] - atom[0] - image[0] as f64,
+                         fractional[1] - atom[1] - image[1] as f64,
+                         fractional[2] - atom[2] - image[2] as f64],
+                        atoms.lattice.to_cartesian);
+            let distance = norm(d);
+            if distance < min_distance {
+                min_distance = distance;
+                nearest = (i, image);
+            }
+        }
+    }
+    nearest
+}
+
+/// Traces the two gradient paths leaving the bond critical point along the
+/// eigenvector of the positive eigenvalue and assigns each end to an atom.
+fn trace(density: &[f64],
+         grid: &Grid,
+         atoms: &Atoms,
+         index: usize,
+         cp: &CriticalPoint)
+         -> Option<BondPath> {
+    let step = 0.25
+               * grid.voxel_lattice
+                     .a
+                     .min(grid.voxel_lattice.b)
+                     .min(grid.voxel_lattice.c);
+    let e = cp.eigenvectors[2];
+    let mut ends = [(0, [0isize; 3]); 2];
+    let mut length = 0.;
+    for (end, sign) in ends.iter_mut().zip([1., -1.]) {
+        let start = [cp.position[0] + sign * step * e[0],
+                     cp.position[1] + sign * step * e[1],
+                     cp.position[2] + sign * step * e[2]];
+        let (x, l) = ascend(density, grid, start, step)?;
+        length += step + l;
+        *end = nearest_atom_image(atoms, x);
+    }
+    let [(i, image_i), (j, image_j)] = ends;
+    let image = [image_j[0] - image_i[0],
+                 image_j[1] - image_i[1],
+                 image_j[2] - image_i[2]];
+    // a path that returns to the same atom is not a bond
+    if i == j && image == [0, 0, 0] {
+        return None;
+    }
+    let (atoms, image) = if i <= j {
+        ([i, j], image)
+    } else {
+        ([j, i], [-image[0], -image[1], -image[2]])
+    };
+    Some(BondPath { critical_point: index,
+                    atoms,
+                    image,
+                    length,
+                    density: cp.density })
+}
+
+/// Traces the bond paths from every bond critical point to the two atoms it
+/// connects.
+///
+/// Each path is followed uphill, in both directions, from the bond critical
+/// point along the eigenvector of the positive eigenvalue of the Hessian. The
+/// maxima at the end of each path are assigned to the nearest atom, keeping
+/// track of the periodic image so that bonds across the cell boundary, and to
+/// an atom's own image, are recovered.
+pub fn bond_paths(density: &[f64],
+                  grid: &Grid,
+                  atoms: &Atoms,
+                  critical_points: &[CriticalPoint],
+                  threads: usize,
+                  progress_bar: Bar)
+                  -> Result<Vec<BondPath>> {
+    let pbar = &progress_bar;
+    let bonds =
+        critical_points.iter()
+                       .enumerate()
+                       .filter(|(_, cp)| cp.kind == CriticalPointKind::Bond)
+                       .collect::<Vec<_>>();
+    if bonds.is_empty() {
+        return Ok(Vec::with_capacity(0));
+    }
+    let chunk_size = (bonds.len() / threads) + (bonds.len() % threads).min(1);
+    let mut bond_paths = Vec::with_capacity(bonds.len());
+    thread::scope(|s| -> Result<()> {
+        let th = bonds.chunks(chunk_size)
+                      .map(|chunk| {
+                          s.spawn(move |_| {
+                               chunk.iter()
+                                    .filter_map(|(i, cp)| {
+                                        pbar.tick();
+                                        trace(density, grid, atoms, *i, cp)
+                                    })
+                                    .collect::<Vec<BondPath>>()
+                           })
+                      })
+                      .collect::<Vec<_>>();
+        for thread in th {
+            let mut paths =
+                thread.join()
+                      .map_err(|_| Error::Thread(String::from("bond_paths")))?;
+            bond_paths.append(&mut paths);
+        }
+        Ok(())
+    }).map_err(|_| Error::Thread(String::from("bond_paths")))??;
+    Ok(bond_paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atoms::Lattice;
+    use crate::critical_points::critical_points;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn bond_paths_simple_cubic() {
+        // a single gaussian-like atom in a simple cubic cell bonds to its six
+        // nearest images
+        let n = 16;
+        let mut density = Vec::with_capacity(n * n * n);
+        for x in 0..n {
+            for y in 0..n {
+                for z in 0..n {
+                    let f = |i: usize| {
+                        (2. * PI * (i as f64 + 0.3) / n as f64).cos()
+                    };
+                    density.push((f(x) + f(y) + f(z)).exp());
+                }
+            }
+        }
+        let lattice = [[4., 0., 0.], [0., 4., 0.], [0., 0., 4.]];
+        let grid = Grid::new([n, n, n], lattice, [0., 0., 0.]).unwrap();
+        let atoms = Atoms::new(Lattice::new(lattice).unwrap(),
+                               vec![[-0.075, -0.075, -0.075]],
+                               String::new()).unwrap();
+        let cps =
+            critical_points(&density,
+                            &grid,
+                            None,
+                            1,
+                            Bar::new(n as u64, 100, String::new())).unwrap();
+        let paths = bond_paths(&density,
+                               &grid,
+                               &atoms,
+                               &cps,
+                               2,
+                               Bar::new(3, 100, String::new())).unwrap();
+        assert_eq!(paths.len(), 3);
+        let mut images = paths.iter()
+                              .map(|bp| {
+                                  assert_eq!(bp.atoms, [0, 0]);
+                                  assert!((bp.length - 4.).abs() < 1E-2);
+                                  bp.image.iter().map(|i| i.abs()).sum()
+                              })
+                              .collect::<Vec<isize>>();
+        images.sort_unstable();
+        assert_eq!(images, vec![1, 1, 1]);
+    }
+}
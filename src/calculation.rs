@@ -0,0 +1,487 @@
+use crate::analysis::{
+    assign_maxima, integrate_field, sum_atoms_densities, sum_bader_densities,
+};
+use crate::atoms::Atoms;
+use crate::error::{Error, Result};
+use crate::io::checkpoint::Checkpoint;
+use crate::methods::{maxima_finder, neargrid, ongrid, weight, Method};
+use crate::progress::Bar;
+use crate::utils::vacuum_index;
+use crate::voxel_map::{BlockingVoxelMap, NonBlockingVoxelMap};
+
+/// The integrated densities and volumes of a set of partitions.
+pub struct Partitions {
+    /// The integrated value of each density, followed by each field, for each
+    /// partition.
+    pub density: Vec<Vec<f64>>,
+    /// The volume of each partition.
+    pub volume: Vec<f64>,
+}
+
+/// The partitioned density, summed over the atoms and, if they were kept, the
+/// Bader volumes.
+pub struct BaderResult {
+    /// The partition of each atom.
+    pub atoms: Partitions,
+    /// The minimum distance from each atom to the surface of its partition.
+    pub surface_distance: Vec<f64>,
+    /// The partition of each Bader maximum, if the voxel map stores them.
+    pub volumes: Option<Partitions>,
+    /// The position of each Bader maximum in the grid.
+    pub maxima: Vec<isize>,
+    /// The atom each Bader maximum is assigned to.
+    pub atom_map: Vec<usize>,
+    /// The distance from each Bader maximum to its atom.
+    pub minimum_distance: Vec<f64>,
+    /// The total of each density, followed by each field, over the cell.
+    pub total_density: Vec<f64>,
+    /// The filled voxel map of the partitioned density.
+    pub voxel_map: NonBlockingVoxelMap,
+}
+
+impl BaderResult {
+    /// The atom of each maximum if the voxel map stores the Bader maxima,
+    /// rather than the atoms, for the functions that take an atoms_map.
+    pub fn atoms_map(&self) -> Option<&[usize]> {
+        match self.volumes {
+            Some(_) => Some(&self.atom_map),
+            None => None,
+        }
+    }
+
+    /// The cartesian position of each Bader maximum.
+    pub fn maxima_positions(&self) -> Vec<[f64; 3]> {
+        self.maxima
+            .iter()
+            .map(|m| self.voxel_map.grid.to_cartesian(*m))
+            .collect()
+    }
+}
+
+/// Partitions densities on a grid into the Bader atoms and volumes.
+///
+/// The reference density, which defaults to the first density, is partitioned
+/// and every density, and any fields, integrated over the partitions.
+///
+/// ```
+/// use bader::atoms::{Atoms, Lattice};
+/// use bader::calculation::BaderCalculation;
+///
+/// let lattice = [[3.0, 0.0, 0.0], [0.0, 3.0, 0.0], [0.0, 0.0, 3.0]];
+/// let atoms = Atoms::new(Lattice::new(lattice).unwrap(),
+///                        vec![[0.0, 0.0, 0.0]],
+///                        String::new()).unwrap();
+/// // a single peak at the atom, falling away from it in every direction
+/// let step = |c: usize| if c == 0 { 0.0 } else { 1.0 };
+/// let density = (0..27).map(|i| 4.0 - step(i / 9) - step(i / 3 % 3) - step(i % 3))
+///                      .collect();
+/// let densities = vec![density];
+/// let result = BaderCalculation::new(&densities, &atoms, [3, 3, 3],
+///                                    [0.0, 0.0, 0.0]).with_threads(1)
+///                                                    .run()
+///                                                    .unwrap();
+/// assert_eq!(result.atom_map, vec![0]);
+/// assert!((result.atoms.density[0][0] - 54.0).abs() < 1E-12);
+/// ```
+pub struct BaderCalculation<'a> {
+    densities: &'a [Vec<f64>],
+    reference: Option<&'a [f64]>,
+    fields: &'a [Vec<f64>],
+    atoms: &'a Atoms,
+    grid: [usize; 3],
+    voxel_origin: [f64; 3],
+    method: Method,
+    weight_tolerance: f64,
+    vacuum_tolerance: Option<f64>,
+    threads: usize,
+    volumes: bool,
+    progress: bool,
+}
+
+impl<'a> BaderCalculation<'a> {
+    /// Sets up a calculation of the densities, on a grid of the given size
+    /// with its first voxel at voxel_origin, around the atoms. The weight
+    /// method is used with a tolerance of 1E-8, no vacuum and a thread for
+    /// each cpu, up to 12.
+    pub fn new(densities: &'a [Vec<f64>],
+               atoms: &'a Atoms,
+               grid: [usize; 3],
+               voxel_origin: [f64; 3])
+               -> Self {
+        Self { densities,
+               reference: None,
+               fields: &[],
+               atoms,
+               grid,
+               voxel_origin,
+               method: Method::Weight,
+               weight_tolerance: 1E-8,
+               vacuum_tolerance: None,
+               threads: num_cpus::get().min(12),
+               volumes: false,
+               progress: false }
+    }
+
+    /// Sets the density to partition, rather than the first density.
+    pub fn with_reference(mut self, reference: &'a [f64]) -> Self {
+        self.reference = Some(reference);
+        self
+    }
+
+    /// Sets scalar fields on the same grid to integrate over the partitions.
+    pub fn with_fields(mut self, fields: &'a [Vec<f64>]) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    /// Sets the method to partition the density with.
+    pub fn with_method(mut self, method: Method) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Sets the tolerance to disregard weights at.
+    pub fn with_weight_tolerance(mut self, weight_tolerance: f64) -> Self {
+        self.weight_tolerance = weight_tolerance;
+        self
+    }
+
+    /// Sets the tolerance, if any, below which the density is vacuum.
+    pub fn with_vacuum_tolerance(mut self,
+                                 vacuum_tolerance: Option<f64>)
+                                 -> Self {
+        self.vacuum_tolerance = vacuum_tolerance;
+        self
+    }
+
+    /// Sets the number of threads to use.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Sets whether the voxel map stores the Bader maxima, so that the Bader
+    /// volumes are summed as well as the atoms.
+    pub fn with_volumes(mut self, volumes: bool) -> Self {
+        self.volumes = volumes;
+        self
+    }
+
+    /// Sets whether to display progress bars.
+    pub fn with_progress(mut self, progress: bool) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Partitions the reference density and sums the partitions.
+    pub fn run(&self) -> Result<BaderResult> {
+        self.sum(self.partition()?)
+    }
+
+    /// Finds the maxima of the reference density, assigns them to the atoms
+    /// and partitions the density, returning the filled voxel map as a
+    /// [`Checkpoint`] that can be saved or passed to [`sum`](Self::sum).
+    pub fn partition(&self) -> Result<Checkpoint> {
+        let reference = self.reference()?;
+        let lattice = self.atoms.lattice.to_cartesian;
+        let voxel_map =
+            BlockingVoxelMap::new(self.grid, lattice, self.voxel_origin)?;
+        // create the index list which will tell us in which order to evaluate
+        // the voxels
+        let mut index: Vec<usize> = (0..voxel_map.grid.size.total).collect();
+        let pbar = self.bar(index.len(), "Maxima Finding: ");
+        let maxima = maxima_finder(&mut index,
+                                   reference,
+                                   &voxel_map,
+                                   self.threads,
+                                   pbar)?;
+        index.sort_unstable_by(|a, b| {
+                 reference[*b].partial_cmp(&reference[*a]).unwrap()
+             });
+        // remove from the indices any voxel that is below the vacuum limit
+        index.truncate(vacuum_index(reference, &index, self.vacuum_tolerance)?);
+        let pbar = self.bar(maxima.len(), "Assigning to Atoms: ");
+        let (atom_map, minimum_distance) = assign_maxima(&maxima,
+                                                         self.atoms,
+                                                         &voxel_map.grid,
+                                                         self.threads,
+                                                         pbar)?;
+        // input the maxima, or the atoms they are assigned to, into the map
+        for (i, m) in maxima.iter().enumerate() {
+            let id = if self.volumes { i } else { atom_map[i] };
+            voxel_map.maxima_store(*m, id as isize);
+        }
+        let pbar = self.bar(index.len(), "Bader Partitioning: ");
+        match self.method {
+            Method::Weight => weight(reference,
+                                     &voxel_map,
+                                     &index,
+                                     pbar,
+                                     self.threads,
+                                     self.weight_tolerance)?,
+            Method::Ongrid => {
+                ongrid(reference, &voxel_map, &index, pbar, self.threads)?
+            }
+            Method::Neargrid => {
+                neargrid(reference, &voxel_map, &index, pbar, self.threads)?
+            }
+        }
+        Ok(Checkpoint { voxel_map:
+                            NonBlockingVoxelMap::from_blocking_voxel_map(voxel_map),
+                        lattice,
+                        maxima,
+                        atom_map,
+                        minimum_distance,
+                        by_atom: !self.volumes })
+    }
+
+    /// Sums the densities, and integrates the fields, over the partitions of a
+    /// voxel map, which has to be on the same grid and lattice and store the
+    /// Bader maxima if the volumes are to be summed.
+    pub fn sum(&self, checkpoint: Checkpoint) -> Result<BaderResult> {
+        checkpoint.check(self.grid,
+                         self.atoms.lattice.to_cartesian,
+                         self.atoms.positions.len())?;
+        if checkpoint.by_atom == self.volumes {
+            return Err(Error::InvalidArgument(String::from(if self.volumes {
+                                                               "The voxel map stores the atoms so the Bader volumes cannot be summed."
+                                                           } else {
+                                                               "The voxel map stores the Bader volumes rather than the atoms."
+                                                           })));
+        }
+        let Checkpoint { voxel_map,
+                         maxima,
+                         atom_map,
+                         minimum_distance,
+                         .. } = checkpoint;
+        let total = voxel_map.voxel_map.len();
+        if let Some(d) = self.densities
+                             .iter()
+                             .chain(self.fields)
+                             .find(|d| d.len() != total)
+        {
+            return Err(Error::InvalidArgument(format!("A density has {} voxels \
+                                                       but the grid has {}.",
+                                                      d.len(),
+                                                      total)));
+        }
+        let atoms_len = self.atoms.positions.len();
+        let (atoms_map, partitions) = if self.volumes {
+            (Some(&atom_map[..]), maxima.len())
+        } else {
+            (None, atoms_len)
+        };
+        let pbar = self.bar(total, "Summing Densities: ");
+        let (mut density, volume, surface_distance) =
+            sum_bader_densities(self.densities,
+                                &voxel_map,
+                                self.atoms,
+                                atoms_map,
+                                self.threads,
+                                partitions,
+                                pbar)?;
+        // integrate the fields over each partition and add them to the
+        // densities
+        for field in self.fields.iter() {
+            let pbar = self.bar(total, "Integrating Field: ");
+            let integral = integrate_field(field,
+                                           &voxel_map,
+                                           None,
+                                           partitions,
+                                           self.threads,
+                                           pbar)?;
+            density.iter_mut()
+                   .zip(integral)
+                   .for_each(|(d, i)| d.push(i));
+        }
+        let (atoms, volumes) = if self.volumes {
+            let (atoms_density, atoms_volume) =
+                sum_atoms_densities(&density, &volume, &atom_map, atoms_len)?;
+            (Partitions { density: atoms_density,
+                          volume: atoms_volume },
+             Some(Partitions { density, volume }))
+        } else {
+            (Partitions { density, volume }, None)
+        };
+        let total_density = self.densities
+                                .iter()
+                                .chain(self.fields)
+                                .map(|d| {
+                                    d.iter().sum::<f64>()
+                                    * voxel_map.grid.voxel_lattice.volume
+                                })
+                                .collect();
+        Ok(BaderResult { atoms,
+                         surface_distance,
+                         volumes,
+                         maxima,
+                         atom_map,
+                         minimum_distance,
+                         total_density,
+                         voxel_map })
+    }
+
+    /// The density to partition, checked against the size of the grid and for
+    /// values that cannot be ordered.
+    fn reference(&self) -> Result<&'a [f64]> {
+        let reference = match (self.reference, self.densities.first()) {
+            (Some(r), _) => r,
+            (None, Some(d)) => d,
+            (None, None) => {
+                return Err(Error::InvalidArgument(String::from("There is no \
+                                                                density to \
+                                                                partition.")))
+            }
+        };
+        let total = self.grid.iter().product::<usize>();
+        if reference.len() != total {
+            return Err(Error::InvalidArgument(format!("The density has {} \
+                                                       voxels but the grid \
+                                                       has {}.",
+                                                      reference.len(),
+                                                      total)));
+        }
+        if let Some(i) = reference.iter().position(|r| !r.is_finite()) {
+            return Err(Error::Numerical(format!("The density at voxel {} is \
+                                                 not finite.",
+                                                i)));
+        }
+        Ok(reference)
+    }
+
+    /// A progress bar, displayed if the calculation shows its progress.
+    fn bar(&self, len: usize, prefix: &str) -> Bar {
+        if self.progress {
+            Bar::visible(len as u64, 100, String::from(prefix))
+        } else {
+            Bar::new(len as u64, 100, String::from(prefix))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atoms::Lattice;
+    use crate::utils::{dot, vdot};
+
+    /// Two atoms in a 4x4x4 cell, each at the peak of a Gaussian.
+    fn system() -> (Vec<Vec<f64>>, Atoms) {
+        let lattice = [[4., 0., 0.], [0., 4., 0.], [0., 0., 4.]];
+        let atoms = Atoms::new(Lattice::new(lattice).unwrap(),
+                               vec![[0., 0., 0.], [2., 2., 2.]],
+                               String::new()).unwrap();
+        let mut density = vec![0.; 64];
+        for x in 0..4 {
+            for y in 0..4 {
+                for z in 0..4 {
+                    let r = |c: usize, o: usize| {
+                        let d = (c as f64 - o as f64).abs();
+                        d.min(4. - d).powi(2)
+                    };
+                    density[x * 16 + y * 4 + z] =
+                        (-(r(x, 0) + r(y, 0) + r(z, 0))).exp()
+                        + (-(r(x, 2) + r(y, 2) + r(z, 2))).exp();
+                }
+            }
+        }
+        (vec![density], atoms)
+    }
+
+    #[test]
+    fn bader_calculation_run() {
+        let (densities, atoms) = system();
+        let result = BaderCalculation::new(&densities,
+                                           &atoms,
+                                           [4, 4, 4],
+                                           [0., 0., 0.]).with_threads(2)
+                                                        .run()
+                                                        .unwrap();
+        assert_eq!(result.atom_map, vec![0, 1]);
+        assert!(result.volumes.is_none());
+        assert!(result.atoms_map().is_none());
+        let charge = result.atoms.density.iter().map(|d| d[0]).sum::<f64>();
+        assert!((charge - result.total_density[0]).abs() < 1E-12);
+        assert!((result.atoms.density[0][0] - result.atoms.density[1][0]).abs()
+                < 1E-12);
+        assert_eq!(result.atoms.volume.iter().sum::<f64>(), 64.);
+    }
+
+    #[test]
+    fn bader_calculation_volumes_fields() {
+        let (densities, atoms) = system();
+        let fields = vec![vec![1.; 64]];
+        let result = BaderCalculation::new(&densities,
+                                           &atoms,
+                                           [4, 4, 4],
+                                           [0., 0., 0.]).with_fields(&fields)
+                                                        .with_volumes(true)
+                                                        .with_method(Method::Ongrid)
+                                                        .run()
+                                                        .unwrap();
+        let volumes = result.volumes.as_ref().unwrap();
+        assert_eq!(volumes.density.len(), result.maxima.len());
+        assert_eq!(result.atoms_map(), Some(&result.atom_map[..]));
+        // the field is one everywhere so integrates to the voxel count
+        assert_eq!(result.atoms.density[0][1] + result.atoms.density[1][1],
+                   64.);
+        assert_eq!(result.total_density[1], 64.);
+    }
+
+    #[test]
+    fn bader_calculation_sum_checkpoint() {
+        let (densities, atoms) = system();
+        let calculation =
+            BaderCalculation::new(&densities, &atoms, [4, 4, 4], [0., 0., 0.]);
+        let checkpoint = calculation.partition().unwrap();
+        assert!(matches!(calculation.with_volumes(true).sum(checkpoint),
+                         Err(Error::InvalidArgument(_))));
+        let calculation =
+            BaderCalculation::new(&densities, &atoms, [4, 4, 8], [0., 0., 0.]);
+        assert!(matches!(calculation.partition(),
+                         Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn bader_calculation_not_finite() {
+        let (mut densities, atoms) = system();
+        densities[0][5] = f64::NAN;
+        let calculation =
+            BaderCalculation::new(&densities, &atoms, [4, 4, 4], [0., 0., 0.]);
+        assert!(matches!(calculation.run(), Err(Error::Numerical(_))));
+        let reference = vec![f64::INFINITY; 64];
+        assert!(matches!(calculation.with_reference(&reference).partition(),
+                         Err(Error::Numerical(_))));
+    }
+
+    #[test]
+    fn bader_calculation_ongrid_skewed() {
+        // the shortest vector of the voxel lattice, b - 3a, is not one of the
+        // 26 neighbours of the grid so the two neighbour sets differ
+        let lattice = [[8., 0., 0.], [24., 8., 0.], [0., 0., 8.]];
+        let atoms = Atoms::new(Lattice::new(lattice).unwrap(),
+                               vec![[16., 4., 4.]],
+                               String::new()).unwrap();
+        // a Gaussian centred on the atom
+        let density = (0..512).map(|p| {
+                                  let f = [(p / 64) as f64 / 8.,
+                                           (p / 8 % 8) as f64 / 8.,
+                                           (p % 8) as f64 / 8.];
+                                  let c = dot(f, atoms.lattice.to_cartesian);
+                                  let d = [c[0] - 16., c[1] - 4., c[2] - 4.];
+                                  (-vdot(d, d) / 8.).exp()
+                              })
+                              .collect::<Vec<f64>>();
+        let densities = vec![density];
+        for method in [Method::Ongrid, Method::Neargrid] {
+            let result =
+                BaderCalculation::new(&densities,
+                                      &atoms,
+                                      [8, 8, 8],
+                                      [0., 0., 0.]).with_method(method)
+                                                   .with_threads(1)
+                                                   .run();
+            assert!(result.is_ok());
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use crate::atoms::{Atoms, Lattice};
+use crate::calculation::BaderCalculation;
+use crate::error::{Error, Result};
+use crate::methods::Method;
+use std::cell::RefCell;
+use std::os::raw::{c_char, c_int};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+thread_local! {
+    /// The message of the last error on this thread.
+    static LAST_ERROR: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+/// The methods to partition the density with, the values of
+/// [`BaderOptions::method`].
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub enum BaderMethod {
+    /// Flux weighted partitioning of the boundary voxels.
+    BaderWeight = 0,
+    /// Steepest ascent on the grid.
+    BaderOngrid = 1,
+    /// Gradient ascent with a correction for the grid bias.
+    BaderNeargrid = 2,
+}
+
+/// The settings of a partition.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct BaderOptions {
+    /// The method to partition the density with, one of [`BaderMethod`].
+    pub method: c_int,
+    /// Tolerance to disregard weights at.
+    pub weight_tolerance: f64,
+    /// Density below which a voxel is vacuum, a negative value for no vacuum.
+    pub vacuum_tolerance: f64,
+    /// The number of threads to use, 0 for a thread for each cpu up to 12.
+    pub threads: usize,
+}
+
+/// The default settings: the weight method with a tolerance of 1E-8, no
+/// vacuum and a thread for each cpu.
+#[no_mangle]
+pub extern "C" fn bader_default_options() -> BaderOptions {
+    BaderOptions { method: BaderMethod::BaderWeight as c_int,
+                   weight_tolerance: 1E-8,
+                   vacuum_tolerance: -1.,
+                   threads: 0 }
+}
+
+/// The version of the library as a null terminated string.
+#[no_mangle]
+pub extern "C" fn bader_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char
+}
+
+/// Partitions a density into the Bader atoms, writing the charge and volume
+/// of each atom into the caller's arrays.
+///
+/// The density holds `grid[0] * grid[1] * grid[2]` values in electrons per unit
+/// volume, with the last axis running fastest so that voxel (i, j, k) is
+/// `density[(i * grid[1] + j) * grid[2] + k]`. The lattice holds the vector
+/// along each axis of the grid, one after another, and the positions hold the
+/// cartesian coordinates of each atom, in the same units. A Fortran array
+/// `rho(nx, ny, nz)` is laid out this way with `grid = {nz, ny, nx}`, the
+/// lattice vectors given in the order c, b, a and the components of every
+/// vector and position reversed. The options may be null to use the defaults.
+///
+/// Returns 0 on success or the exit code of bca for the error, with 1 for any
+/// other failure, whose message can be read with bader_last_error.
+///
+/// # Safety
+///
+/// The density has to hold the number of voxels in the grid, the lattice 9
+/// values, the positions 3 for each atom and the charges and volumes space for
+/// each atom.
+#[no_mangle]
+pub unsafe extern "C" fn bader_partition(density: *const f64,
+                                         grid: *const usize,
+                                         lattice: *const f64,
+                                         positions: *const f64,
+                                         atoms_len: usize,
+                                         options: *const BaderOptions,
+                                         charges: *mut f64,
+                                         volumes: *mut f64)
+                                         -> c_int {
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        if density.is_null()
+           || grid.is_null()
+           || lattice.is_null()
+           || (atoms_len > 0
+               && (positions.is_null()
+                   || charges.is_null()
+                   || volumes.is_null()))
+        {
+            return Err(Error::InvalidArgument(String::from("A required \
+                                                            array is \
+                                                            null.")));
+        }
+        let grid = [*grid, *grid.add(1), *grid.add(2)];
+        let options = options.as_ref()
+                             .copied()
+                             .unwrap_or_else(|| bader_default_options());
+        // the lengths have to fit in memory before the arrays are read
+        let length = |lengths: &[usize]| {
+            lengths.iter()
+                   .try_fold(std::mem::size_of::<f64>(), |t, l| {
+                       t.checked_mul(*l)
+                   })
+                   .filter(|bytes| *bytes <= isize::MAX as usize)
+                   .map(|bytes| bytes / std::mem::size_of::<f64>())
+        };
+        let (voxels, coordinates) =
+            match (length(&grid), length(&[3, atoms_len])) {
+                (Some(v), Some(c)) => (v, c),
+                _ => {
+                    return Err(Error::InvalidArgument(String::from("The \
+                                                                    grid or \
+                                                                    atoms are \
+                                                                    too \
+                                                                    large.")))
+                }
+            };
+        let density = std::slice::from_raw_parts(density, voxels);
+        let lattice = std::slice::from_raw_parts(lattice, 9);
+        let positions = if atoms_len > 0 {
+            std::slice::from_raw_parts(positions, coordinates)
+        } else {
+            &[]
+        };
+        let (atoms_charge, atoms_volume) =
+            partition(density, grid, lattice, positions, options)?;
+        if atoms_len > 0 {
+            std::slice::from_raw_parts_mut(charges, atoms_len)
+                .copy_from_slice(&atoms_charge);
+            std::slice::from_raw_parts_mut(volumes, atoms_len)
+                .copy_from_slice(&atoms_volume);
+        }
+        Ok(())
+    }));
+    let (code, message) = match result {
+        Ok(Ok(())) => (0, String::new()),
+        Ok(Err(e)) => (e.exit_code(), e.to_string()),
+        Err(_) => (1, String::from("The partition panicked.")),
+    };
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+    code
+}
+
+/// Copies the message of the last error on this thread into the buffer, as a
+/// null terminated string truncated to fit, and returns the length of the
+/// whole message. The message is empty if the last call succeeded.
+///
+/// # Safety
+///
+/// The buffer has to hold length bytes, or be null if length is 0.
+#[no_mangle]
+pub unsafe extern "C" fn bader_last_error(buffer: *mut c_char,
+                                          length: usize)
+                                          -> usize {
+    LAST_ERROR.with(|last| {
+                  let message = last.borrow();
+                  if !buffer.is_null() && length > 0 {
+                      let n = message.len().min(length - 1);
+                      let buffer = std::slice::from_raw_parts_mut(buffer
+                                                                  as *mut u8,
+                                                                  length);
+                      buffer[..n].copy_from_slice(&message.as_bytes()[..n]);
+                      buffer[n] = 0;
+                  }
+                  message.len()
+              })
+}
+
+/// Partitions the density on the safe side of the interface.
+fn partition(density: &[f64],
+             grid: [usize; 3],
+             lattice: &[f64],
+             positions: &[f64],
+             options: BaderOptions)
+             -> Result<(Vec<f64>, Vec<f64>)> {
+    let vector = |v: &[f64]| [v[0], v[1], v[2]];
+    let lattice = Lattice::new([vector(&lattice[0..3]),
+                                vector(&lattice[3..6]),
+                                vector(&lattice[6..9])])?;
+    let atoms = Atoms::new(lattice,
+                           positions.chunks_exact(3).map(vector).collect(),
+                           String::new())?;
+    let method = match options.method {
+        m if m == BaderMethod::BaderWeight as c_int => Method::Weight,
+        m if m == BaderMethod::BaderOngrid as c_int => Method::Ongrid,
+        m if m == BaderMethod::BaderNeargrid as c_int => Method::Neargrid,
+        m => {
+            return Err(Error::InvalidArgument(format!("Unknown method {}.",
+                                                      m)))
+        }
+    };
+    let densities = vec![density.to_vec()];
+    let mut calculation =
+        BaderCalculation::new(&densities, &atoms, grid, [0.; 3])
+            .with_method(method)
+            .with_weight_tolerance(options.weight_tolerance);
+    if options.vacuum_tolerance >= 0. {
+        calculation =
+            calculation.with_vacuum_tolerance(Some(options.vacuum_tolerance));
+    }
+    if options.threads > 0 {
+        calculation = calculation.with_threads(options.threads);
+    }
+    let result = calculation.run()?;
+    let charges = result.atoms.density.iter().map(|d| d[0]).collect();
+    Ok((charges, result.atoms.volume))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bader_partition_two_atoms() {
+        // a peak at each atom, along the last axis of a 2x2x4 grid
+        let density = (0..16).map(|p| [3., 1., 3., 1.][p % 4])
+                             .collect::<Vec<f64>>();
+        let lattice = [2., 0., 0., 0., 2., 0., 0., 0., 4.];
+        let positions = [0., 0., 0., 0., 0., 2.];
+        let mut charges = [0.; 2];
+        let mut volumes = [0.; 2];
+        let code = unsafe {
+            bader_partition(density.as_ptr(),
+                            [2, 2, 4].as_ptr(),
+                            lattice.as_ptr(),
+                            positions.as_ptr(),
+                            2,
+                            std::ptr::null(),
+                            charges.as_mut_ptr(),
+                            volumes.as_mut_ptr())
+        };
+        assert_eq!(code, 0);
+        assert_eq!(charges, [16., 16.]);
+        assert_eq!(volumes, [8., 8.]);
+        assert_eq!(unsafe { bader_last_error(std::ptr::null_mut(), 0) }, 0);
+    }
+
+    #[test]
+    fn bader_partition_error() {
+        let density = [1.; 8];
+        // a singular lattice cannot be inverted
+        let lattice = [1., 0., 0., 1., 0., 0., 0., 0., 1.];
+        let mut buffer = [1 as c_char; 8];
+        let code = unsafe {
+            bader_partition(density.as_ptr(),
+                            [2, 2, 2].as_ptr(),
+                            lattice.as_ptr(),
+                            std::ptr::null(),
+                            0,
+                            std::ptr::null(),
+                            std::ptr::null_mut(),
+                            std::ptr::null_mut())
+        };
+        assert_eq!(code, 7);
+        let length = unsafe { bader_last_error(buffer.as_mut_ptr(), 8) };
+        assert_eq!(length, "Lattice doesn't span 3D space.".len());
+        assert_eq!(buffer[7], 0);
+        assert_eq!(buffer[0] as u8, b'L');
+    }
+
+    #[test]
+    fn bader_partition_unknown_method() {
+        let density = [1.; 8];
+        let lattice = [1., 0., 0., 0., 1., 0., 0., 0., 1.];
+        let options = BaderOptions { method: 3,
+                                     ..bader_default_options() };
+        let code = unsafe {
+            bader_partition(density.as_ptr(),
+                            [2, 2, 2].as_ptr(),
+                            lattice.as_ptr(),
+                            std::ptr::null(),
+                            0,
+                            &options,
+                            std::ptr::null_mut(),
+                            std::ptr::null_mut())
+        };
+        assert_eq!(code, 2);
+        assert_eq!(unsafe { bader_last_error(std::ptr::null_mut(), 0) },
+                   "Unknown method 3.".len());
+    }
+
+    #[test]
+    fn bader_partition_grid_overflow() {
+        let density = [1.; 8];
+        let lattice = [1., 0., 0., 0., 1., 0., 0., 0., 1.];
+        // the product of the grid would wrap around to 0
+        for grid in [[usize::MAX, 2, 1], [1 << 32, 1 << 32, 1], [1 << 61, 1, 1]]
+        {
+            let code = unsafe {
+                bader_partition(density.as_ptr(),
+                                grid.as_ptr(),
+                                lattice.as_ptr(),
+                                std::ptr::null(),
+                                0,
+                                std::ptr::null(),
+                                std::ptr::null_mut(),
+                                std::ptr::null_mut())
+            };
+            assert_eq!(code, 2);
+        }
+    }
+}
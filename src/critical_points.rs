@@ -0,0 +1,446 @@
+use crate::error::{Error, Result};
+use crate::grid::Grid;
+use crate::progress::Bar;
+use crate::utils::{dot, invert_lattice, norm, symmetric_eigen, vdot};
+use crossbeam_utils::thread;
+use rustc_hash::FxHashMap;
+
+/// The type of critical point as determined by the signature of the Hessian.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CriticalPointKind {
+    /// A (3,-3) critical point, a maximum of the density.
+    Nuclear,
+    /// A (3,-1) critical point, a saddle point between two maxima.
+    Bond,
+    /// A (3,+1) critical point, a saddle point at the centre of a ring.
+    Ring,
+    /// A (3,+3) critical point, a minimum of the density.
+    Cage,
+}
+
+impl CriticalPointKind {
+    /// The kind of critical point from the number of positive eigenvalues.
+    fn from_positive_eigenvalues(positive: usize) -> Self {
+        match positive {
+            0 => Self::Nuclear,
+            1 => Self::Bond,
+            2 => Self::Ring,
+            _ => Self::Cage,
+        }
+    }
+
+    /// The signature of the Hessian: the sum of the signs of the eigenvalues.
+    pub fn signature(&self) -> isize {
+        match self {
+            Self::Nuclear => -3,
+            Self::Bond => -1,
+            Self::Ring => 1,
+            Self::Cage => 3,
+        }
+    }
+}
+
+impl std::fmt::Display for CriticalPointKind {
+    /// Write the critical point in (rank, signature) notation.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(3,{:+})", self.signature())
+    }
+}
+
+/// A critical point of the density.
+pub struct CriticalPoint {
+    /// The type of critical point.
+    pub kind: CriticalPointKind,
+    /// The position in the voxel basis, ie. the fractional grid index.
+    pub voxel_position: [f64; 3],
+    /// The position in cartesian coordinates.
+    pub position: [f64; 3],
+    /// The interpolated density at the critical point.
+    pub density: f64,
+    /// The eigenvalues of the Hessian in ascending order.
+    pub eigenvalues: [f64; 3],
+    /// The cartesian eigenvectors of the Hessian, eigenvectors\[i\] belongs to
+    /// eigenvalues\[i\].
+    pub eigenvectors: [[f64; 3]; 3],
+    /// The Laplacian of the density, the trace of the Hessian.
+    pub laplacian: f64,
+    /// The ellipticity, λ1 / λ2 - 1, of the two lowest eigenvalues.
+    pub ellipticity: f64,
+}
+
+/// The density, gradient and Hessian at a point in the voxel basis.
+pub struct Derivatives {
+    /// The value of the density.
+    pub density: f64,
+    /// The gradient of the density.
+    pub gradient: [f64; 3],
+    /// The Hessian of the density.
+    pub hessian: [[f64; 3]; 3],
+}
+
+impl Derivatives {
+    /// Converts the gradient and Hessian from the voxel basis to cartesian.
+    pub fn to_cartesian(&self, grid: &Grid) -> Self {
+        let to_voxel = grid.voxel_lattice.to_fractional;
+        let gradient = [vdot(to_voxel[0], self.gradient),
+                        vdot(to_voxel[1], self.gradient),
+                        vdot(to_voxel[2], self.gradient)];
+        let mut hessian = [[0f64; 3]; 3];
+        for (row, to_voxel_a) in hessian.iter_mut().zip(&to_voxel) {
+            let a_hessian = dot(*to_voxel_a, self.hessian);
+            for (h, to_voxel_b) in row.iter_mut().zip(&to_voxel) {
+                *h = vdot(a_hessian, *to_voxel_b);
+            }
+        }
+        Self { density: self.density,
+               gradient,
+               hessian }
+    }
+}
+
+/// The index in the density of the periodic image of point (x, y, z).
+pub fn voxel_index(grid: &Grid, p: [isize; 3]) -> usize {
+    let x = p[0].rem_euclid(grid.size.x);
+    let y = p[1].rem_euclid(grid.size.y);
+    let z = p[2].rem_euclid(grid.size.z);
+    (x * grid.size.y * grid.size.z + y * grid.size.z + z) as usize
+}
+
+/// Finite difference derivatives of the density at the voxel (x, y, z) in the
+/// voxel basis.
+fn voxel_derivatives(density: &[f64],
+                     grid: &Grid,
+                     p: [isize; 3])
+                     -> Derivatives {
+    let rho = |dx: isize, dy: isize, dz: isize| {
+        density[voxel_index(grid, [p[0] + dx, p[1] + dy, p[2] + dz])]
+    };
+    let shift = |i: usize, s: isize| {
+        let mut d = [0isize; 3];
+        d[i] = s;
+        d
+    };
+    let control = rho(0, 0, 0);
+    let mut gradient = [0f64; 3];
+    let mut hessian = [[0f64; 3]; 3];
+    for i in 0..3 {
+        let up = shift(i, 1);
+        let down = shift(i, -1);
+        let rho_up = rho(up[0], up[1], up[2]);
+        let rho_down = rho(down[0], down[1], down[2]);
+        gradient[i] = (rho_up - rho_down) / 2.;
+        hessian[i][i] = rho_up - 2. * control + rho_down;
+        for j in (i + 1)..3 {
+            let d = |si: isize, sj: isize| {
+                let mut d = shift(i, si);
+                d[j] = sj;
+                rho(d[0], d[1], d[2])
+            };
+            let h = (d(1, 1) - d(1, -1) - d(-1, 1) + d(-1, -1)) / 4.;
+            hessian[i][j] = h;
+            hessian[j][i] = h;
+        }
+    }
+    Derivatives { density: control,
+                  gradient,
+                  hessian }
+}
+
+/// Trilinear interpolation of the density and its finite difference
+/// derivatives at a position, u, in the voxel basis.
+pub fn interpolate(density: &[f64], grid: &Grid, u: [f64; 3]) -> Derivatives {
+    let base = [u[0].floor(), u[1].floor(), u[2].floor()];
+    let t = [u[0] - base[0], u[1] - base[1], u[2] - base[2]];
+    let mut out = Derivatives { density: 0.,
+                                gradient: [0.; 3],
+                                hessian: [[0.; 3]; 3] };
+    for corner in 0..8 {
+        let c = [(corner >> 2) & 1, (corner >> 1) & 1, corner & 1];
+        let mut w = 1.;
+        for i in 0..3 {
+            w *= if c[i] == 1 { t[i] } else { 1. - t[i] };
+        }
+        if w == 0. {
+            continue;
+        }
+        let p = [base[0] as isize + c[0] as isize,
+                 base[1] as isize + c[1] as isize,
+                 base[2] as isize + c[2] as isize];
+        let d = voxel_derivatives(density, grid, p);
+        out.density += w * d.density;
+        for i in 0..3 {
+            out.gradient[i] += w * d.gradient[i];
+            for j in 0..3 {
+                out.hessian[i][j] += w * d.hessian[i][j];
+            }
+        }
+    }
+    out
+}
+
+/// Converts a position in the voxel basis to cartesian coordinates.
+pub fn voxel_to_cartesian(grid: &Grid, u: [f64; 3]) -> [f64; 3] {
+    dot([u[0] + grid.voxel_origin[0],
+         u[1] + grid.voxel_origin[1],
+         u[2] + grid.voxel_origin[2]],
+        grid.voxel_lattice.to_cartesian)
+}
+
+/// The Newton step, -H^-1 g, in the voxel basis.
+fn newton_step(derivatives: &Derivatives) -> Option<[f64; 3]> {
+    match invert_lattice(&derivatives.hessian) {
+        Ok(inverse) => {
+            let step = dot(derivatives.gradient, inverse);
+            Some([-step[0], -step[1], -step[2]])
+        }
+        Err(_) => None,
+    }
+}
+
+/// Refines a candidate critical point with Newton iterations on the
+/// interpolated derivatives. Returns None if the search leaves the
+/// neighbourhood of the starting voxel or fails to converge.
+fn refine(density: &[f64],
+          grid: &Grid,
+          start: [f64; 3],
+          mut u: [f64; 3])
+          -> Option<[f64; 3]> {
+    for _ in 0..100 {
+        let step = newton_step(&interpolate(density, grid, u))?;
+        for i in 0..3 {
+            u[i] += step[i];
+        }
+        if (0..3).any(|i| (u[i] - start[i]).abs() > 1.5) {
+            return None;
+        }
+        if norm(step) < 1E-8 {
+            return Some(u);
+        }
+    }
+    None
+}
+
+/// Builds the critical point from the position in the voxel basis.
+fn classify(density: &[f64],
+            grid: &Grid,
+            u: [f64; 3])
+            -> Option<CriticalPoint> {
+    let derivatives = interpolate(density, grid, u).to_cartesian(grid);
+    let (eigenvalues, eigenvectors) = symmetric_eigen(derivatives.hessian);
+    // a critical point of rank less than 3 is unstable and ignored
+    let scale = eigenvalues.iter().fold(0f64, |max, e| max.max(e.abs()));
+    if eigenvalues.iter().any(|e| e.abs() <= scale * 1E-8) {
+        return None;
+    }
+    let positive = eigenvalues.iter().filter(|e| **e > 0.).count();
+    Some(CriticalPoint { kind:
+                             CriticalPointKind::from_positive_eigenvalues(positive),
+                         voxel_position: u,
+                         position: voxel_to_cartesian(grid, u),
+                         density: derivatives.density,
+                         eigenvalues,
+                         eigenvectors,
+                         laplacian: eigenvalues.iter().sum(),
+                         ellipticity: eigenvalues[0] / eigenvalues[1] - 1. })
+}
+
+/// Searches a range of voxels for critical points. Any voxel whose Newton step
+/// stays within the voxel is refined and classified.
+fn search_chunk(density: &[f64],
+                grid: &Grid,
+                start: usize,
+                end: usize,
+                vacuum_tolerance: Option<f64>,
+                progress_bar: &Bar)
+                -> Vec<CriticalPoint> {
+    let mut critical_points = Vec::new();
+    for p in start..end {
+        progress_bar.tick();
+        if let Some(tol) = vacuum_tolerance {
+            if density[p] <= tol {
+                continue;
+            }
+        }
+        let p = p as isize;
+        let voxel = [p / (grid.size.y * grid.size.z),
+                     (p / grid.size.z).rem_euclid(grid.size.y),
+                     p.rem_euclid(grid.size.z)];
+        let derivatives = voxel_derivatives(density, grid, voxel);
+        let step = match newton_step(&derivatives) {
+            Some(step) => step,
+            None => continue,
+        };
+        if step.iter().any(|s| s.abs() > 0.5) {
+            continue;
+        }
+        let start = [voxel[0] as f64, voxel[1] as f64, voxel[2] as f64];
+        let u = [start[0] + step[0], start[1] + step[1], start[2] + step[2]];
+        if let Some(u) = refine(density, grid, start, u) {
+            if let Some(cp) = classify(density, grid, u) {
+                critical_points.push(cp);
+            }
+        }
+    }
+    critical_points
+}
+
+/// Removes the critical points that have been found more than once, keeping
+/// the first found. Points are considered the same if they are within half a
+/// voxel of each other, including periodic images.
+fn deduplicate(critical_points: Vec<CriticalPoint>,
+               grid: &Grid)
+               -> Vec<CriticalPoint> {
+    let size = [grid.size.x as f64, grid.size.y as f64, grid.size.z as f64];
+    let wrap = |u: [f64; 3]| {
+        [u[0].rem_euclid(size[0]),
+         u[1].rem_euclid(size[1]),
+         u[2].rem_euclid(size[2])]
+    };
+    let mut cells = FxHashMap::<[isize; 3], Vec<[f64; 3]>>::default();
+    let mut unique = Vec::with_capacity(critical_points.len());
+    'cp: for mut cp in critical_points.into_iter() {
+        let u = wrap(cp.voxel_position);
+        let cell = [u[0].round() as isize,
+                    u[1].round() as isize,
+                    u[2].round() as isize];
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let neighbour = [(cell[0] + dx).rem_euclid(grid.size.x),
+                                     (cell[1] + dy).rem_euclid(grid.size.y),
+                                     (cell[2] + dz).rem_euclid(grid.size.z)];
+                    if let Some(points) = cells.get(&neighbour) {
+                        for point in points {
+                            let mut d = [0f64; 3];
+                            for i in 0..3 {
+                                d[i] = u[i] - point[i];
+                                d[i] -= (d[i] / size[i]).round() * size[i];
+                            }
+                            if d.iter().all(|d| d.abs() < 0.5) {
+                                continue 'cp;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        let cell = [cell[0].rem_euclid(grid.size.x),
+                    cell[1].rem_euclid(grid.size.y),
+                    cell[2].rem_euclid(grid.size.z)];
+        cells.entry(cell).or_insert_with(Vec::new).push(u);
+        cp.voxel_position = u;
+        cp.position = voxel_to_cartesian(grid, u);
+        unique.push(cp);
+    }
+    unique
+}
+
+/// Finds and classifies the critical points of the density.
+///
+/// Every voxel above the vacuum tolerance is checked for a critical point
+/// within it by taking a Newton step on the finite difference derivatives. The
+/// candidates are refined using the interpolated derivatives and classified by
+/// the signature of the interpolated Hessian.
+pub fn critical_points(density: &[f64],
+                       grid: &Grid,
+                       vacuum_tolerance: Option<f64>,
+                       threads: usize,
+                       progress_bar: Bar)
+                       -> Result<Vec<CriticalPoint>> {
+    let pbar = &progress_bar;
+    let total = grid.size.total;
+    let chunk_size = (total / threads) + (total % threads).min(1);
+    let mut critical_points = Vec::new();
+    thread::scope(|s| -> Result<()> {
+        let th = (0..total).step_by(chunk_size)
+                           .map(|start| {
+                               let end = (start + chunk_size).min(total);
+                               s.spawn(move |_| {
+                                    search_chunk(density,
+                                                 grid,
+                                                 start,
+                                                 end,
+                                                 vacuum_tolerance,
+                                                 pbar)
+                                })
+                           })
+                           .collect::<Vec<_>>();
+        for thread in th {
+            let mut cps =
+                thread.join().map_err(|_| {
+                                  Error::Thread(String::from("critical_points"))
+                              })?;
+            critical_points.append(&mut cps);
+        }
+        Ok(())
+    }).map_err(|_| Error::Thread(String::from("critical_points")))??;
+    Ok(deduplicate(critical_points, grid))
+}
+
+/// Counts the critical points of each kind, [nuclear, bond, ring, cage].
+pub fn count(critical_points: &[CriticalPoint]) -> [usize; 4] {
+    let mut count = [0usize; 4];
+    critical_points.iter().for_each(|cp| match cp.kind {
+                              CriticalPointKind::Nuclear => count[0] += 1,
+                              CriticalPointKind::Bond => count[1] += 1,
+                              CriticalPointKind::Ring => count[2] += 1,
+                              CriticalPointKind::Cage => count[3] += 1,
+                          });
+    count
+}
+
+/// The Poincaré–Hopf sum, n - b + r - c, which is zero for a complete set of
+/// critical points in a periodic cell.
+pub fn poincare_hopf(critical_points: &[CriticalPoint]) -> isize {
+    let [n, b, r, c] = count(critical_points);
+    n as isize - b as isize + r as isize - c as isize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    fn cosine_density(n: usize, offset: f64) -> Vec<f64> {
+        let mut density = Vec::with_capacity(n * n * n);
+        for x in 0..n {
+            for y in 0..n {
+                for z in 0..n {
+                    let f = |i: usize| {
+                        (2. * PI * (i as f64 / n as f64 - offset)).cos()
+                    };
+                    density.push(f(x) + f(y) + f(z) + 3.);
+                }
+            }
+        }
+        density
+    }
+
+    #[test]
+    fn critical_points_kind_display() {
+        assert_eq!(format!("{}", CriticalPointKind::Nuclear), "(3,-3)");
+        assert_eq!(format!("{}", CriticalPointKind::Ring), "(3,+1)");
+    }
+
+    #[test]
+    fn critical_points_cosine() {
+        let grid = Grid::new([16, 16, 16],
+                             [[4., 0., 0.], [0., 4., 0.], [0., 0., 4.]],
+                             [0., 0., 0.]).unwrap();
+        let density = cosine_density(16, 0.01);
+        let cps = critical_points(&density,
+                                  &grid,
+                                  None,
+                                  2,
+                                  Bar::new(16 * 16 * 16, 100, String::new()))
+                  .unwrap();
+        assert_eq!(count(&cps), [1, 3, 3, 1]);
+        assert_eq!(poincare_hopf(&cps), 0);
+        let maxima = cps.iter()
+                        .find(|cp| cp.kind == CriticalPointKind::Nuclear)
+                        .unwrap();
+        for (u, expected) in maxima.voxel_position.iter().zip([0.16; 3]) {
+            assert!((u - expected).abs() < 1E-2);
+        }
+    }
+}
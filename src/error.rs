@@ -0,0 +1,119 @@
+use std::io;
+
+/// The errors that can occur whilst reading, partitioning and writing a
+/// density.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file failed.
+    Io(io::Error),
+    /// A file, or a value given to the program, could not be parsed.
+    Parse(String),
+    /// The file uses a layout or feature of its format that isn't supported.
+    UnsupportedFormat(String),
+    /// A density is on a different grid to the one being partitioned.
+    GridMismatch {
+        /// The file, or other source, of the mismatched density.
+        source: String,
+        /// The grid being partitioned.
+        expected: [usize; 3],
+        /// The grid of the mismatched density.
+        found: [usize; 3],
+    },
+    /// The arguments given are invalid or cannot be used together.
+    InvalidArgument(String),
+    /// A calculation could not be carried out, such as inverting a singular
+    /// lattice.
+    Numerical(String),
+    /// A thread of a threaded calculation panicked.
+    Thread(String),
+}
+
+/// A [`Result`](std::result::Result) with the [`Error`] of this crate.
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// The code for a process to exit with when it fails with this error.
+    ///
+    /// | Error | Code |
+    /// |---|---|
+    /// | InvalidArgument | 2 |
+    /// | Io | 3 |
+    /// | Parse | 4 |
+    /// | UnsupportedFormat | 5 |
+    /// | GridMismatch | 6 |
+    /// | Numerical | 7 |
+    /// | Thread | 8 |
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::InvalidArgument(_) => 2,
+            Self::Io(_) => 3,
+            Self::Parse(_) => 4,
+            Self::UnsupportedFormat(_) => 5,
+            Self::GridMismatch { .. } => 6,
+            Self::Numerical(_) => 7,
+            Self::Thread(_) => 8,
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Parse(s) => write!(f, "{}", s),
+            Self::UnsupportedFormat(s) => write!(f, "Unsupported: {}", s),
+            Self::GridMismatch { source,
+                                 expected,
+                                 found, } => {
+                write!(f,
+                       "{} has a grid of {:?} but the density has {:?}.",
+                       source, found, expected)
+            }
+            Self::InvalidArgument(s) => write!(f, "{}", s),
+            Self::Numerical(s) => write!(f, "{}", s),
+            Self::Thread(s) => write!(f, "A thread panicked in {}.", s),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Malformed data found by the readers, flagged with
+/// [`InvalidData`](io::ErrorKind::InvalidData), is a parse error and every
+/// other I/O error is kept as it is.
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::InvalidData => Self::Parse(e.to_string()),
+            _ => Self::Io(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_from_io() {
+        let e = Error::from(io::Error::new(io::ErrorKind::InvalidData,
+                                           "Cannot parse (x) as a number."));
+        assert!(matches!(e, Error::Parse(_)));
+        assert_eq!(e.to_string(), "Cannot parse (x) as a number.");
+        assert_eq!(e.exit_code(), 4);
+        let e = Error::from(io::Error::new(io::ErrorKind::NotFound, "CHGCAR"));
+        assert!(matches!(e, Error::Io(_)));
+        assert_eq!(e.exit_code(), 3);
+    }
+
+    #[test]
+    fn error_grid_mismatch() {
+        let e = Error::GridMismatch { source: String::from("AECCAR0"),
+                                      expected: [2, 2, 2],
+                                      found: [4, 4, 4] };
+        assert_eq!(e.to_string(),
+                   "AECCAR0 has a grid of [4, 4, 4] but the density has \
+                    [2, 2, 2].");
+        assert_eq!(e.exit_code(), 6);
+    }
+}
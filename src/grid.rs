@@ -1,4 +1,5 @@
 use crate::atoms::Lattice;
+use crate::error::{Error, Result};
 use crate::utils::dot;
 use crate::voronoi::Voronoi;
 
@@ -21,8 +22,8 @@ impl Grid {
     pub fn new(grid: [usize; 3],
                lattice: [[f64; 3]; 3],
                voxel_origin: [f64; 3])
-               -> Self {
-        let size = Size::new(grid[0], grid[1], grid[2]);
+               -> Result<Self> {
+        let size = Size::new(grid[0], grid[1], grid[2])?;
         let shift = Shift::new(&size);
         let voxel_lattice = Lattice::new([[lattice[0][0] / grid[0] as f64,
                                            lattice[0][1] / grid[0] as f64,
@@ -32,13 +33,13 @@ impl Grid {
                                            lattice[1][2] / grid[1] as f64],
                                           [lattice[2][0] / grid[2] as f64,
                                            lattice[2][1] / grid[2] as f64,
-                                           lattice[2][2] / grid[2] as f64]]);
-        let voronoi = Voronoi::new(&voxel_lattice);
-        Self { shift,
-               size,
-               voronoi,
-               voxel_lattice,
-               voxel_origin }
+                                           lattice[2][2] / grid[2] as f64]])?;
+        let voronoi = Voronoi::new(&voxel_lattice)?;
+        Ok(Self { shift,
+                  size,
+                  voronoi,
+                  voxel_lattice,
+                  voxel_origin })
     }
 
     /// get the full shift to visit the surrounding 26 voxels
@@ -1016,23 +1017,28 @@ pub struct Size {
 
 impl Size {
     /// The length of the flattened array for the density data in 3d
-    fn new(x: usize, y: usize, z: usize) -> Self {
+    fn new(x: usize, y: usize, z: usize) -> Result<Self> {
+        // the shifts to the neighbouring voxels need 2 voxels in each direction
+        if x < 2 || y < 2 || z < 2 {
+            return Err(Error::UnsupportedFormat(format!("A grid of {:?} needs at least 2 voxels in each direction.",
+                                                        [x, y, z])));
+        }
+        let too_large = || {
+            Error::UnsupportedFormat(format!("A grid of {:?} is too large.",
+                                             [x, y, z]))
+        };
         let x = x as isize;
         let y = y as isize;
         let z = z as isize;
-        let total = match x.checked_mul(y) {
-            Some(xy) => match xy.checked_mul(z) {
-                Some(xyz) => xyz as usize,
-                None => panic!("Grid size is too large!"),
-            },
-            None => panic!("Grid size is too large!"),
-        };
+        let total = x.checked_mul(y)
+                     .and_then(|xy| xy.checked_mul(z))
+                     .ok_or_else(too_large)? as usize;
         // VoxelMap stores the maxima of weighs as -2 - maxima the largest
         // value maxima can be is Size::total so must check
         if total as isize + 1 == isize::MAX {
-            panic!("Grid size is too large!");
+            return Err(too_large());
         }
-        Self { x, y, z, total }
+        Ok(Self { x, y, z, total })
     }
 }
 
@@ -1042,23 +1048,27 @@ mod tests {
 
     #[test]
     fn grid_new() {
-        let lattice = Lattice::new([[3., 3., 0.], [-3., 3., 0.], [1., 1., 1.]]);
-        let grid = Grid::new([4, 4, 4], lattice.to_cartesian, [0., 0., 0.0]);
+        let lattice =
+            Lattice::new([[3., 3., 0.], [-3., 3., 0.], [1., 1., 1.]]).unwrap();
+        let grid =
+            Grid::new([4, 4, 4], lattice.to_cartesian, [0., 0., 0.0]).unwrap();
         assert!((grid.voxel_lattice.volume - (lattice.volume / 64.0f64)).abs()
                 < f64::EPSILON)
     }
 
     #[test]
-    #[should_panic]
     fn grid_new_bad_grid() {
-        let lattice = Lattice::new([[3., 3., 0.], [-3., 3., 0.], [1., 1., 1.]]);
-        let _ = Grid::new([1, 4, 4], lattice.to_cartesian, [0., 0., 0.0]);
+        let lattice =
+            Lattice::new([[3., 3., 0.], [-3., 3., 0.], [1., 1., 1.]]).unwrap();
+        assert!(Grid::new([1, 4, 4], lattice.to_cartesian, [0., 0., 0.0]).is_err());
     }
 
     #[test]
     fn grid_full_shift() {
-        let lattice = Lattice::new([[3., 3., 0.], [-3., 3., 0.], [1., 1., 1.]]);
-        let grid = Grid::new([3, 4, 5], lattice.to_cartesian, [0., 0., 0.0]);
+        let lattice =
+            Lattice::new([[3., 3., 0.], [-3., 3., 0.], [1., 1., 1.]]).unwrap();
+        let grid =
+            Grid::new([3, 4, 5], lattice.to_cartesian, [0., 0., 0.0]).unwrap();
         let shift = [-26, -25, -24, -21, -20, -19, -16, -15, -14, -6, -5, -4,
                      -1, 1, 4, 5, 6, 14, 15, 16, 19, 20, 21, 24, 25, 26];
         assert_eq!(shift, grid.full_shift(26))
@@ -1066,22 +1076,26 @@ mod tests {
 
     #[test]
     fn grid_reduced_shift() {
-        let lattice = Lattice::new([[3., 3., 0.], [-3., 3., 0.], [1., 1., 1.]]);
-        let grid = Grid::new([3, 4, 5], lattice.to_cartesian, [0., 0., 0.0]);
+        let lattice =
+            Lattice::new([[3., 3., 0.], [-3., 3., 0.], [1., 1., 1.]]).unwrap();
+        let grid =
+            Grid::new([3, 4, 5], lattice.to_cartesian, [0., 0., 0.0]).unwrap();
         let shift = [20, -20, 5, -5, 1, -1];
         assert_eq!(shift, grid.reduced_shift(26))
     }
 
     #[test]
     fn grid_gradient_shift() {
-        let lattice = Lattice::new([[3., 3., 0.], [-3., 3., 0.], [1., 1., 1.]]);
-        let grid = Grid::new([3, 4, 5], lattice.to_cartesian, [0., 0., 0.0]);
+        let lattice =
+            Lattice::new([[3., 3., 0.], [-3., 3., 0.], [1., 1., 1.]]).unwrap();
+        let grid =
+            Grid::new([3, 4, 5], lattice.to_cartesian, [0., 0., 0.0]).unwrap();
         assert_eq!(1, grid.gradient_shift(26, [0., 0., 1.]))
     }
 
     #[test]
     fn shift_index_gen() {
-        let index = Shift::index_gen(&Size::new(3, 4, 5));
+        let index = Shift::index_gen(&Size::new(3, 4, 5).unwrap());
         let test_index: [usize; 27] = [0, 1, 4, 5, 6, 9, 15, 16, 19, 20, 21,
                                        24, 25, 26, 29, 35, 36, 39, 40, 41, 44,
                                        45, 46, 49, 55, 56, 59];
@@ -1095,7 +1109,7 @@ mod tests {
 
     #[test]
     fn shift_new() {
-        let shift = Shift::new(&Size::new(3, 4, 5));
+        let shift = Shift::new(&Size::new(3, 4, 5).unwrap());
         let di =
             [[-26, -25, -24, -21, -20, -19, -16, -15, -14, -6, -5, -4, -1, 0,
               1, 4, 5, 6, 14, 15, 16, 19, 20, 21, 24, 25, 26],
@@ -1156,24 +1170,22 @@ mod tests {
 
     #[test]
     fn shift_get() {
-        assert_eq!(Shift::new(&Size::new(3, 4, 5)).get(25)[0], -21)
+        assert_eq!(Shift::new(&Size::new(3, 4, 5).unwrap()).get(25)[0], -21)
     }
 
     #[test]
     fn size_new() {
-        assert_eq!(Size::new(3, 4, 5).total, 3 * 4 * 5)
+        assert_eq!(Size::new(3, 4, 5).unwrap().total, 3 * 4 * 5)
     }
 
     #[test]
-    #[should_panic]
     #[cfg(target_pointer_width = "32")]
     fn size_too_large_32() {
-        let _ = Size::new(1300, 1300, 1300);
+        assert!(Size::new(1300, 1300, 1300).is_err());
     }
 
     #[test]
-    #[should_panic]
     fn size_too_large() {
-        let _ = Size::new(2100000, 2100000, 2100000);
+        assert!(Size::new(2100000, 2100000, 2100000).is_err());
     }
 }
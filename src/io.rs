@@ -1,15 +1,35 @@
 use crate::arguments::{Args, Reference};
 use crate::atoms::Atoms;
+use crate::error::{Error, Result};
 use crate::progress::Bar;
+use std::io::BufRead;
 
+/// File I/O for the ABINIT _DEN format.
+pub mod abinit;
+/// File I/O for the CASTEP formatted density.
+pub mod castep;
+/// Save and load a partitioned voxel map.
+pub mod checkpoint;
 /// File I/O for the gaussian cube format.
 pub mod cube;
+/// Fortran unformatted record reader.
+pub mod fortran;
+/// Write surface meshes.
+pub mod mesh;
 /// Write analysis files.
 pub mod output;
+/// File I/O for the Quantum ESPRESSO filplot format.
+pub mod qe;
 /// Custom BufReader.
 pub mod reader;
+/// Machine-readable results of a run.
+pub mod results;
+/// File I/O for the SIESTA grid formats.
+pub mod siesta;
 /// File I/O for the VASP file format.
 pub mod vasp;
+/// File I/O for the XCrySDen structure file format.
+pub mod xsf;
 
 /// Indicates the available file types of the density file.
 pub enum FileType {
@@ -17,6 +37,77 @@ pub enum FileType {
     Vasp,
     /// Guassian, CP2K etc.
     Cube,
+    /// Quantum ESPRESSO pp.x filplot output.
+    Qe,
+    /// XCrySDen structure file with a 3D datagrid, and the units of its
+    /// values.
+    Xsf(xsf::Units),
+    /// CASTEP formatted density, .den_fmt.
+    Castep,
+    /// ABINIT _DEN.
+    Abinit,
+    /// SIESTA .RHO, .DRHO and other grid files.
+    Siesta,
+}
+
+impl FileType {
+    /// Infers the file type from the name of the file, ignoring any
+    /// compression suffix.
+    pub fn from_filename(filename: &str) -> Option<Self> {
+        // a compressed file is named after what it holds
+        let lowercase = reader::strip_compression(filename).to_lowercase();
+        if lowercase.contains("cube") {
+            Some(Self::Cube)
+        } else if lowercase.ends_with(".pp") || lowercase.contains("filplot") {
+            Some(Self::Qe)
+        } else if lowercase.ends_with(".xsf") {
+            Some(Self::Xsf(xsf::Units::Angstrom))
+        } else if lowercase.ends_with(".den_fmt") {
+            Some(Self::Castep)
+        } else if lowercase.ends_with("_den") {
+            Some(Self::Abinit)
+        } else if lowercase.ends_with(".rho") || lowercase.ends_with(".drho") {
+            Some(Self::Siesta)
+        } else if lowercase.contains("car") {
+            Some(Self::Vasp)
+        } else {
+            None
+        }
+    }
+
+    /// The reader and writer of the file type.
+    pub fn format(&self) -> Box<dyn FileFormat> {
+        match self {
+            Self::Vasp => Box::new(vasp::Vasp {}),
+            Self::Cube => Box::new(cube::Cube {}),
+            Self::Qe => Box::new(qe::Qe {}),
+            Self::Xsf(units) => Box::new(xsf::Xsf { units: *units }),
+            Self::Castep => Box::new(castep::Castep {}),
+            Self::Abinit => Box::new(abinit::Abinit {}),
+            Self::Siesta => Box::new(siesta::Siesta {}),
+        }
+    }
+}
+
+impl std::str::FromStr for FileType {
+    type Err = Error;
+
+    /// Parse the name of the file type as it is passed on the command-line.
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "vasp" => Ok(Self::Vasp),
+            "cube" => Ok(Self::Cube),
+            "qe" => Ok(Self::Qe),
+            "xsf" => Ok(Self::Xsf(xsf::Units::Angstrom)),
+            "castep" => Ok(Self::Castep),
+            "abinit" => Ok(Self::Abinit),
+            "siesta" => Ok(Self::Siesta),
+            _ => {
+                Err(Error::InvalidArgument(format!("Unknown file type ({}).",
+                                                   s)))
+            }
+        }
+    }
 }
 
 /// What type of density to write.
@@ -29,6 +120,14 @@ pub enum WriteType {
     None,
 }
 
+/// Which partitions to label the voxels of the index map with.
+pub enum IndexMap {
+    /// Label each voxel with its Bader atom.
+    Atoms,
+    /// Label each voxel with its Bader volume.
+    Volumes,
+}
+
 /// Turn a float into fortran "scientific" notation (leading digit is zero).
 pub struct FortranFormat {
     /// The float to convert to a string. Wrapped in an option as we need to log
@@ -41,20 +140,18 @@ pub struct FortranFormat {
 impl std::fmt::Display for FortranFormat {
     /// Format the structure into a fortran style exponential.
     fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let prec = if let Some(prec) = formatter.precision() {
-            prec
-        } else {
-            6
-        };
-        match self.float {
-            None => {
-                write!(formatter, " 0.{:0<width$}E{:+03}", 0, 0, width = prec)
-            }
-            Some(f) => {
-                let float = f * self.mult;
-                let exponant = float.log10() as i32 + 1;
+        let prec = formatter.precision().unwrap_or(6);
+        match self.float.map(|f| f * self.mult) {
+            Some(float) if float != 0. => {
+                // the leading digit is zero so 1 <= |float| < 10 has exponent 1
+                let mut exponant = float.abs().log10().floor() as i32 + 1;
                 let decimals = float.abs() * 10f64.powi(prec as i32 - exponant);
-                let decimals = decimals.round() as usize;
+                let mut decimals = decimals.round() as usize;
+                // rounding up can carry into an extra digit, ie. 0.9999999
+                if decimals >= 10usize.pow(prec as u32) {
+                    decimals /= 10;
+                    exponant += 1;
+                }
                 if float.is_sign_negative() {
                     write!(formatter,
                            "-0.{:0<width$}E{:+03}",
@@ -69,116 +166,224 @@ impl std::fmt::Display for FortranFormat {
                            width = prec)
                 }
             }
+            _ => {
+                write!(formatter, " 0.{:0<width$}E{:+03}", 0, 0, width = prec)
+            }
         }
     }
 }
 
 /// Return type of the read function in FileFormat.
-pub type ReadFunction =
-    std::io::Result<([f64; 3], [usize; 3], Atoms, Vec<Vec<f64>>)>;
+pub type ReadFunction = Result<([f64; 3], [usize; 3], Atoms, Vec<Vec<f64>>)>;
 /// Return type of the init function in FileFormat.
 type InitReturn = (Vec<Vec<f64>>, Vec<f64>, Atoms, [usize; 3], [f64; 3]);
 
+/// Checks that a density read from filename is on the same grid as the density
+/// being partitioned.
+fn check_grid(filename: &str,
+              expected: [usize; 3],
+              found: [usize; 3])
+              -> Result<()> {
+    if expected != found {
+        return Err(Error::GridMismatch { source: String::from(filename),
+                                         expected,
+                                         found });
+    }
+    Ok(())
+}
+
 /// FileFormat trait. Used for handling input from a file.
 pub trait FileFormat {
     /// Returns the parts required to build [`Grid`] and [`Atoms`] structures.
     ///
     /// * `args`: [`Args`] parsed from the command line.
-    fn init(&self, args: &Args) -> InitReturn {
+    fn init(&self, args: &Args) -> Result<InitReturn> {
         let (voxel_origin, grid, atoms, mut densities) =
-            match self.read(args.file.clone()) {
-                Ok(x) => x,
-                Err(e) => panic!("Error: Problem reading file.\n{}", e),
-            };
+            self.read(args.file.clone(), args.threads)?;
         if let Some(x) = args.spin.clone() {
             match densities.len() {
                 1 => {
-                    let (_, g, _, d) = match self.read(x.clone()) {
-                        Ok(r) => r,
-                        Err(e) => panic!("{}", e),
-                    };
+                    let (_, g, _, d) = self.read(x.clone(), args.threads)?;
                     if 1 != d.len() {
-                        panic!(
-                               "Number of densities in original file is not 1.
+                        return Err(Error::InvalidArgument(format!(
+                            "Number of densities in original file is not 1.
 Ambiguous how to handle spin density when {} contains {} densities.",
-                               x,
-                               d.len()
-                        );
+                            x,
+                            d.len()
+                        )));
                     }
-                    assert_eq!(g, grid,
-                               "Error: Spin density has different grid size.");
+                    check_grid(&x, grid, g)?;
                     densities.push(d[0].clone());
                 }
-                x => panic!(
-                            "Number of densities in original file is not 1.
+                x => {
+                    return Err(Error::InvalidArgument(format!(
+                        "Number of densities in original file is not 1.
 Ambiguous how to handle new spin when {} already has {} spin densities.",
-                            args.file,
-                            x - 1
-                ),
+                        args.file,
+                        x - 1
+                    )))
+                }
             }
         }
-        let rho = match args.reference.clone() {
+        let mut rho = match args.reference.clone() {
             Reference::None => Vec::with_capacity(0),
             Reference::One(f) => {
-                let (_, g, _, densities) = match self.read(f) {
-                    Ok(r) => r,
-                    Err(e) => panic!("{}", e),
-                };
-                assert_eq!(g, grid,
-                           "Error: Reference density has different grid size.");
+                let (_, g, _, densities) = self.read(f.clone(), args.threads)?;
+                check_grid(&f, grid, g)?;
                 densities[0].clone()
             }
             Reference::Two(f1, f2) => {
-                let (_, g, _, densities) = match self.read(f1) {
-                    Ok(r) => r,
-                    Err(e) => panic!("{}", e),
-                };
-                assert_eq!(g, grid,
-                           "Error: Reference density has different grid size.");
-                let (_, g2, _, densities2) = match self.read(f2) {
-                    Ok(r) => r,
-                    Err(e) => panic!("{}", e),
-                };
-
-                assert_eq!(g2, grid,
-                           "Error: Reference density has different grid size.");
+                let (_, g, _, densities) = self.read(f1.clone(), args.threads)?;
+                check_grid(&f1, grid, g)?;
+                let (_, g2, _, densities2) =
+                    self.read(f2.clone(), args.threads)?;
+                check_grid(&f2, grid, g2)?;
                 densities[0].iter()
                             .zip(&densities2[0])
                             .map(|(a, b)| a + b)
                             .collect::<Vec<f64>>()
             }
         };
-        (densities, rho, atoms, grid, voxel_origin)
+        // pick out the channel to partition and the channels to integrate
+        let channels = densities.len();
+        if let Some(c) = args.channel
+                             .iter()
+                             .chain(args.integrate_channels.iter())
+                             .find(|c| **c >= channels)
+        {
+            return Err(Error::InvalidArgument(format!("Cannot use channel {} \
+                                                       as {} has {} channels.",
+                                                      c + 1,
+                                                      args.file,
+                                                      channels)));
+        }
+        if let Some(c) = args.channel {
+            rho = densities[c].clone();
+        }
+        if !args.integrate_channels.is_empty() {
+            if rho.is_empty() && args.integrate_channels[0] != 0 {
+                rho = densities[0].clone();
+            }
+            densities = args.integrate_channels
+                            .iter()
+                            .map(|c| densities[*c].clone())
+                            .collect();
+        }
+        Ok((densities, rho, atoms, grid, voxel_origin))
     }
 
     /// Reads the file into a [`ReadFunction`] containing the information
-    /// needed from the file to build a [`Grid`].
+    /// needed from the file to build a [`Grid`]. A filename of
+    /// [`reader::STDIN`] reads from stdin.
     ///
     /// * `filename`: The name of the file to read.
-    fn read(&self, filename: String) -> ReadFunction;
+    /// * `threads`: The number of threads to parse the density with.
+    fn read(&self, filename: String, threads: usize) -> ReadFunction {
+        let mut source = reader::open_source(&filename)?;
+        self.read_from(&mut source, &filename, threads)
+    }
+
+    /// Reads a source, such as a network stream or an entry of an archive,
+    /// into a [`ReadFunction`], decompressing it if needed. Wrap a [`Read`]
+    /// in a [`BufReader`](std::io::BufReader) to use it as a source.
+    ///
+    /// * `source`: The source of the density.
+    /// * `name`: The name of the source, used in messages and to find the
+    ///   files that some formats read the atoms from.
+    /// * `threads`: The number of threads to parse the density with.
+    ///
+    /// [`Read`]: std::io::Read
+    fn read_from(&self,
+                 source: &mut dyn BufRead,
+                 name: &str,
+                 threads: usize)
+                 -> ReadFunction;
 
     /// Reads the non-density section of the file into an [`Atoms`] object.
     ///
     /// * `atom_text`: The full string of non-density information from the
-    /// density file.
-    fn to_atoms(&self, atom_text: String) -> Atoms;
+    ///   density file.
+    fn to_atoms(&self, atom_text: String) -> Result<Atoms>;
 
     /// Writes a specific density, data, to tile in the correct format.
     ///
     /// * `atoms`: The associated &[`Atoms`] object for the density file.
     /// * `data`: The density to write to file wrapped in options with None representing 0.
     /// * `filename`: Where to save the file, minus any suffix as this should
-    /// be applied in the function.
+    ///   be applied in the function.
     /// * `pbar`: A progress bar for monitoring the write.
     fn write(&self,
              atoms: &Atoms,
              data: Vec<Option<f64>>,
              filename: String,
              pbar: Bar)
-             -> std::io::Result<()>;
+             -> Result<()>;
+
+    /// The factor the data is multiplied by as it is written, such as the
+    /// volume of the cell for formats that store the density times the volume.
+    ///
+    /// * `atoms`: The associated &[`Atoms`] object for the density file.
+    fn write_scale(&self, _atoms: &Atoms) -> f64 {
+        1.0
+    }
 
     /// How the format the positions of maxima and atoms
     ///
     /// * `coords`: The 3d representation of the position.
     fn coordinate_format(&self, coords: [f64; 3]) -> (String, String, String);
+
+    /// How to format a lattice translation, such as the image of a bonded atom.
+    ///
+    /// * `image`: The translation in units of the lattice vectors.
+    fn image_format(&self, image: [isize; 3]) -> String;
+
+    /// The order of the file's cartesian axes, axis i is written as axes\[i\].
+    fn axes(&self) -> [usize; 3];
+
+    /// The names of the densities read from the file, used to label them in
+    /// the output.
+    ///
+    /// * `atoms`: The associated &[`Atoms`] object for the density file.
+    /// * `density_num`: The number of densities read.
+    fn density_names(&self, _atoms: &Atoms, density_num: usize) -> Vec<String> {
+        output::density_names(density_num)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fortran(float: Option<f64>) -> String {
+        format!("{:.5}", FortranFormat { float, mult: 1. })
+    }
+
+    #[test]
+    fn fortran_format() {
+        assert_eq!(fortran(Some(1.)), " 0.10000E+01");
+        assert_eq!(fortran(Some(12.5)), " 0.12500E+02");
+        assert_eq!(fortran(Some(0.148)), " 0.14800E+00");
+        assert_eq!(fortran(Some(0.0012)), " 0.12000E-02");
+        assert_eq!(fortran(Some(-0.05)), "-0.50000E-01");
+        assert_eq!(fortran(Some(-250.)), "-0.25000E+03");
+        assert_eq!(fortran(Some(9.999999)), " 0.10000E+02");
+        assert_eq!(fortran(Some(0.)), " 0.00000E+00");
+        assert_eq!(fortran(None), " 0.00000E+00");
+        assert_eq!(format!("{:.3}",
+                           FortranFormat { float: Some(0.5),
+                                           mult: 4. }),
+                   " 0.200E+01");
+    }
+    #[test]
+    fn file_type_from_filename() {
+        assert!(matches!(FileType::from_filename("CHGCAR.gz"),
+                         Some(FileType::Vasp)));
+        assert!(matches!(FileType::from_filename("Si.den_fmt"),
+                         Some(FileType::Castep)));
+        assert!(matches!(FileType::from_filename("spin.cube.xz"),
+                         Some(FileType::Cube)));
+        assert!(FileType::from_filename("density.dat").is_none());
+        assert!(matches!("qe".parse::<FileType>(), Ok(FileType::Qe)));
+        assert!("chgcar".parse::<FileType>().is_err());
+    }
 }
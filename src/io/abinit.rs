@@ -0,0 +1,289 @@
+use crate::atoms::{Atoms, Lattice};
+use crate::error::{Error, Result};
+use crate::io::fortran::{FortranReader, Record};
+use crate::io::{reader, xsf, FileFormat, ReadFunction};
+use crate::progress::Bar;
+use crate::utils;
+use std::io::{self, BufRead, Read};
+
+/// Convert from Hartree atomic units.
+const LENGTH_UNITS: f64 = 0.52917721067;
+/// Convert from Hartree atomic units.
+const VOLUME_UNITS: f64 = LENGTH_UNITS * LENGTH_UNITS * LENGTH_UNITS;
+
+/// The ABINIT _DEN file, the Fortran unformatted density written with a
+/// header of form 80 or newer (ABINIT 8 onwards).
+///
+/// The header is binary so [`Atoms::text`] holds a summary of it instead and
+/// densities are written as XSF files.
+pub struct Abinit {}
+
+/// The parts of the header needed to read the density.
+struct Header {
+    /// The version of ABINIT that wrote the file.
+    version: String,
+    /// The FFT grid, ngfft(1:3).
+    grid: [usize; 3],
+    /// The number of spin density components.
+    nspden: usize,
+    /// The lattice vectors, rprimd, in Bohr.
+    lattice: [[f64; 3]; 3],
+    /// The reduced positions of the atoms, xred.
+    positions: Vec<[f64; 3]>,
+}
+
+/// Turns a malformed header into an error.
+fn invalid(message: &str) -> Error {
+    Error::Parse(String::from(message))
+}
+
+/// Reads a count from the header, which should never be negative.
+fn count(record: &mut Record) -> Result<usize> {
+    let n = record.i32()?;
+    if n < 0 {
+        return Err(invalid("Negative dimension in ABINIT header."));
+    }
+    Ok(n as usize)
+}
+
+/// Reads the header of the file leaving the reader at the first record after
+/// the pseudopotential information.
+fn header<R: io::Read>(reader: &mut FortranReader<R>) -> Result<Header> {
+    // codvsn, headform, fform
+    let mut record = reader.next_record()?;
+    if record.len() != 16 {
+        return Err(Error::UnsupportedFormat(String::from("ABINIT header \
+                                                          forms older than \
+                                                          80.")));
+    }
+    let version = record.string(8)?;
+    let headform = record.i32()?;
+    if headform < 80 {
+        return Err(Error::UnsupportedFormat(format!("ABINIT header form {}.",
+                                                    headform)));
+    }
+    // bantot, date, intxc, ixc, natom, ngfft(3), nkpt, nspden, nspinor,
+    // nsppol, nsym, npsp, ntypat, occopt, pertcase, usepaw, ecut, ecutdg,
+    // ecutsm, ecut_eff, qptn(3), rprimd(3,3), ...
+    let mut record = reader.next_record()?;
+    let bantot = count(&mut record)?;
+    record.skip(3 * 4)?;
+    let natom = count(&mut record)?;
+    let ngfft = [count(&mut record)?,
+                 count(&mut record)?,
+                 count(&mut record)?];
+    let nkpt = count(&mut record)?;
+    let nspden = count(&mut record)?;
+    record.skip(4)?;
+    let nsppol = count(&mut record)?;
+    let nsym = count(&mut record)?;
+    let npsp = count(&mut record)?;
+    let ntypat = count(&mut record)?;
+    record.skip(3 * 4)?;
+    record.skip(7 * 8)?;
+    let r = record.f64_vec(9)?;
+    let lattice = [[r[0], r[1], r[2]], [r[3], r[4], r[5]], [r[6], r[7], r[8]]];
+    // istwfk, nband, npwarr, so_psp, symafm, symrel, typat, kptns, occ, tnons,
+    // znucltypat, wtk
+    let record = reader.next_record()?;
+    let ints = nkpt + nkpt * nsppol + nkpt + npsp + nsym + 9 * nsym + natom;
+    let doubles = 3 * nkpt + bantot + 3 * nsym + ntypat + nkpt;
+    if record.len() != 4 * ints + 8 * doubles {
+        return Err(invalid("Unexpected length of ABINIT header record."));
+    }
+    // residm, xred, etotal, fermie, amu
+    let mut record = reader.next_record()?;
+    record.skip(8)?;
+    let xred = record.f64_vec(3 * natom)?;
+    let positions = xred.chunks(3).map(|x| [x[0], x[1], x[2]]).collect();
+    // kptopt etc. and then a record for each pseudopotential
+    let _ = reader.next_record()?;
+    for _ in 0..npsp {
+        let _ = reader.next_record()?;
+    }
+    Ok(Header { version,
+                grid: ngfft,
+                nspden,
+                lattice,
+                positions })
+}
+
+impl Abinit {
+    /// Reads the records of an ABINIT _DEN file.
+    fn parse<R: Read>(&self,
+                      mut reader: FortranReader<R>,
+                      name: &str)
+                      -> ReadFunction {
+        // the first point of the FFT grid is at the origin
+        let voxel_origin = [0f64; 3];
+        println!("Reading {} as ABINIT format:", name);
+        let header = header(&mut reader)?;
+        let [n1, n2, n3] = header.grid;
+        let nfft = n1 * n2 * n3;
+        // any PAW occupancies follow the header so keep the last nspden
+        // records that are the size of the grid
+        let mut records: Vec<Record> = Vec::with_capacity(header.nspden);
+        while let Some(record) = reader.read_record() {
+            let record = record?;
+            if record.len() == 8 * nfft {
+                records.push(record);
+            } else {
+                records.clear();
+            }
+        }
+        if records.len() < header.nspden {
+            return Err(Error::Parse(format!("Found {} densities in {} but \
+                                             expected {}.",
+                                            records.len(),
+                                            name,
+                                            header.nspden)));
+        }
+        let mut densities = records.split_off(records.len() - header.nspden)
+                                   .into_iter()
+                                   .map(|mut r| {
+                                       r.f64_vec(nfft).map(|d| {
+                                                          d.iter()
+                                           .map(|x| x / VOLUME_UNITS)
+                                           .collect::<Vec<f64>>()
+                                                      })
+                                   })
+                                   .collect::<io::Result<Vec<Vec<f64>>>>()?;
+        // collinear spin is stored as total and spin up
+        if header.nspden == 2 {
+            let (total, up) = densities.split_at_mut(1);
+            up[0].iter_mut()
+                 .zip(&total[0])
+                 .for_each(|(up, total)| *up = 2. * *up - total);
+        }
+        let atoms = self.to_atoms(summary(&header))?;
+        // flip the grid points as the density is stored density[z, y, x]
+        let grid_pts = [n3, n2, n1];
+        println!("File read successfully.");
+        Ok((voxel_origin, grid_pts, atoms, densities))
+    }
+}
+
+impl FileFormat for Abinit {
+    /// Read an ABINIT _DEN file.
+    fn read(&self, filename: String, _threads: usize) -> ReadFunction {
+        self.parse(FortranReader::open(&filename)?, &filename)
+    }
+
+    /// Read an ABINIT _DEN file from a source.
+    fn read_from(&self,
+                 source: &mut dyn BufRead,
+                 name: &str,
+                 _threads: usize)
+                 -> ReadFunction {
+        // the length of a source is unknown until it has been read
+        let (source, _) = reader::decompress(source)?;
+        self.parse(FortranReader::new(source, u64::MAX)?, name)
+    }
+
+    /// Read atom information from the summary of the header.
+    fn to_atoms(&self, atoms_text: String) -> Result<Atoms> {
+        let mut lines = atoms_text.lines().skip(2);
+        let a = parse_vector(lines.next())?;
+        let b = parse_vector(lines.next())?;
+        let c = parse_vector(lines.next())?;
+        // density[z, y, x] so lets swap the c and a
+        let lattice = Lattice::new([[c[2], c[1], c[0]],
+                                    [b[2], b[1], b[0]],
+                                    [a[2], a[1], a[0]]])?;
+        let mut lines = atoms_text.lines().skip(5);
+        let natom = lines.next()
+                         .and_then(|l| l.split_whitespace().last())
+                         .and_then(|n| n.parse::<usize>().ok())
+                         .unwrap_or(0);
+        let positions = (0..natom).map(|_| {
+                                      let x = parse_vector(lines.next())?;
+                                      Ok(utils::dot([x[2].rem_euclid(1.),
+                                                     x[1].rem_euclid(1.),
+                                                     x[0].rem_euclid(1.)],
+                                                    lattice.to_cartesian))
+                                  })
+                                  .collect::<Result<Vec<[f64; 3]>>>()?;
+        Atoms::new(lattice, positions, atoms_text)
+    }
+
+    /// Write the data as an XSF file as the binary header is not kept.
+    fn write(&self,
+             atoms: &Atoms,
+             data: Vec<Option<f64>>,
+             filename: String,
+             pbar: Bar)
+             -> Result<()> {
+        let grid = match atoms.text.lines().nth(1).map(|l| {
+                                                      l.split_whitespace()
+                                                       .skip(1)
+                                                       .filter_map(|x| {
+                                                           x.parse::<usize>()
+                                                            .ok()
+                                                       })
+                                                       .collect::<Vec<usize>>()
+                                                  }) {
+            Some(g) if g.len() == 3 => [g[2], g[1], g[0]],
+            _ => {
+                return Err(Error::Parse(String::from("Cannot find the grid of \
+                                                      the ABINIT density.")))
+            }
+        };
+        xsf::write_grid(atoms, data, grid, [0.; 3], self.axes(), filename, pbar)
+    }
+
+    /// Deals with fortran indexing.
+    fn coordinate_format(&self, coords: [f64; 3]) -> (String, String, String) {
+        let z = format!("{:.6}", coords[0]);
+        let y = format!("{:.6}", coords[1]);
+        let x = format!("{:.6}", coords[2]);
+        (x, y, z)
+    }
+
+    /// Deals with fortran indexing.
+    fn image_format(&self, image: [isize; 3]) -> String {
+        format!("({}, {}, {})", image[2], image[1], image[0])
+    }
+
+    /// Deals with fortran indexing.
+    fn axes(&self) -> [usize; 3] {
+        [2, 1, 0]
+    }
+}
+
+/// Parses a vector from a line of the summary.
+fn parse_vector(line: Option<&str>) -> Result<[f64; 3]> {
+    let line =
+        line.ok_or_else(|| invalid("Unexpected end of ABINIT summary."))?;
+    let v = line.split_whitespace()
+                .map(|x| x.parse::<f64>())
+                .collect::<std::result::Result<Vec<f64>, _>>()
+                .map_err(|_| {
+                    Error::Parse(format!("Cannot parse vector from line:\n{}",
+                                         line))
+                })?;
+    if v.len() < 3 {
+        return Err(Error::Parse(format!("Expected a vector in line:\n{}",
+                                        line)));
+    }
+    Ok([v[0], v[1], v[2]])
+}
+
+/// A text summary of the header, the lattice in Angstrom and the reduced
+/// positions of the atoms.
+fn summary(header: &Header) -> String {
+    let mut text =
+        format!("ABINIT {} density\ngrid: {} {} {}\n",
+                header.version, header.grid[0], header.grid[1], header.grid[2]);
+    for v in header.lattice.iter() {
+        text.push_str(&format!("{:>16.10} {:>16.10} {:>16.10}\n",
+                               v[0] * LENGTH_UNITS,
+                               v[1] * LENGTH_UNITS,
+                               v[2] * LENGTH_UNITS));
+    }
+    text.push_str(&format!("atoms: {}\n", header.positions.len()));
+    for x in header.positions.iter() {
+        text.push_str(&format!("{:>16.10} {:>16.10} {:>16.10}\n",
+                               x[0], x[1], x[2]));
+    }
+    text
+}
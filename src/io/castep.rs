@@ -0,0 +1,340 @@
+use crate::atoms::{Atoms, Lattice};
+use crate::error::{Error, Result};
+use crate::io::{reader, FileFormat, ReadFunction};
+use crate::progress::Bar;
+use crate::utils;
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
+use std::path::Path;
+
+/// Convert from Bohr for positions given in atomic units.
+const LENGTH_UNITS: f64 = 0.52917721067;
+
+/// The CASTEP formatted density, .den_fmt, for reading/writing densities.
+///
+/// The density file does not contain the atoms so these are read from the
+/// POSITIONS_FRAC or POSITIONS_ABS block of the seedname.cell file next to it.
+pub struct Castep {}
+
+/// The .cell file that belongs to a .den_fmt file.
+fn cell_filename(filename: &str) -> String {
+    let filename = reader::strip_compression(filename);
+    let path = Path::new(filename);
+    match filename.strip_suffix(".den_fmt") {
+        Some(seed) => format!("{}.cell", seed),
+        None => path.with_extension("cell").to_string_lossy().to_string(),
+    }
+}
+
+/// Extracts the block holding the atomic positions, including its %BLOCK and
+/// %ENDBLOCK lines, from the text of a .cell file.
+fn positions_block(cell: &str) -> Option<String> {
+    let mut block = String::new();
+    let mut inside = false;
+    for line in cell.lines() {
+        // remove comments
+        let line = line.split(['!', '#']).next().unwrap_or("");
+        let upper = line.trim().to_uppercase();
+        if !inside {
+            if upper.starts_with("%BLOCK") && upper.contains("POSITIONS_") {
+                inside = true;
+                block.push_str(line.trim());
+                block.push('\n');
+            }
+        } else {
+            block.push_str(line.trim());
+            block.push('\n');
+            if upper.starts_with("%ENDBLOCK") {
+                return Some(block);
+            }
+        }
+    }
+    None
+}
+
+/// Reads the size of the fine FFT grid from the header.
+fn grid_points(header: &str) -> Result<[usize; 3]> {
+    let line = header.lines()
+                     .find(|l| l.contains("fine FFT grid"))
+                     .ok_or_else(|| {
+                         Error::Parse(String::from("Cannot find the grid size \
+                                                    in CASTEP header."))
+                     })?;
+    let g = line.split_whitespace()
+                .take(3)
+                .map(|x| x.parse::<usize>().map_err(|_| cannot_parse(x, line)))
+                .collect::<Result<Vec<usize>>>()?;
+    if g.len() < 3 {
+        return Err(Error::Parse(format!("Cannot read the grid size in \
+                                         line:\n{}",
+                                        line)));
+    }
+    Ok([g[0], g[1], g[2]])
+}
+
+/// The error for a value that cannot be parsed.
+fn cannot_parse(x: &str, line: &str) -> Error {
+    Error::Parse(format!("Cannot parse ({}) in line:\n{}", x, line))
+}
+
+/// Parses the three numbers following a label in a line.
+fn parse_vector(line: &str, skip: usize) -> Result<[f64; 3]> {
+    let v = line.split_whitespace()
+                .skip(skip)
+                .take(3)
+                .map(|x| x.parse::<f64>().map_err(|_| cannot_parse(x, line)))
+                .collect::<Result<Vec<f64>>>()?;
+    if v.len() < 3 {
+        return Err(Error::Parse(format!("Expected a vector in line:\n{}",
+                                        line)));
+    }
+    Ok([v[0], v[1], v[2]])
+}
+
+impl FileFormat for Castep {
+    /// Read a CASTEP formatted density and the atoms from the .cell file.
+    fn read_from(&self,
+                 source: &mut dyn BufRead,
+                 name: &str,
+                 _threads: usize)
+                 -> ReadFunction {
+        // the first grid point, (1, 1, 1), is at the origin
+        let voxel_origin = [0f64; 3];
+        println!("Reading {} as CASTEP format:", name);
+        let text = reader::source_to_string(source)?;
+        let header_end = match text.find("END header") {
+            Some(i) => match text[i..].find('\n') {
+                Some(j) => i + j + 1,
+                None => text.len(),
+            },
+            None => {
+                return Err(Error::Parse(String::from("Cannot find the end of \
+                                                      the CASTEP header.")))
+            }
+        };
+        let cell_file = cell_filename(name);
+        let cell = reader::read_to_string(&cell_file).map_err(|e| {
+                       std::io::Error::new(e.kind(),
+                                           format!("The atoms of {} are read \
+                                                    from {}.\n{}",
+                                                   name, cell_file, e))
+                   })?;
+        let positions = positions_block(&cell).ok_or_else(|| {
+                            Error::Parse(format!("No atomic positions found \
+                                                  in {}.",
+                                                 cell_file))
+                        })?;
+        let atoms =
+            self.to_atoms(format!("{}{}", &text[..header_end], positions))?;
+        let grid_pts = grid_points(&text[..header_end])?;
+        let total = grid_pts[0] * grid_pts[1] * grid_pts[2];
+        // the densities are in electrons per grid point times the number of
+        // grid points so divide by the volume
+        let mut densities: Vec<Vec<f64>> = vec![];
+        let mut count = 0;
+        for line in text[header_end..].lines() {
+            let mut split = line.split_whitespace();
+            let index =
+                (0..3).map(|_| split.next().map(|x| x.parse::<usize>()))
+                      .collect::<Vec<_>>();
+            let [i, j, k] = match index[..] {
+                [Some(Ok(i)), Some(Ok(j)), Some(Ok(k))] => [i, j, k],
+                [None, ..] => continue,
+                _ => {
+                    return Err(Error::Parse(format!("Cannot read grid point \
+                                                     from line:\n{}",
+                                                    line)))
+                }
+            };
+            if i == 0
+               || j == 0
+               || k == 0
+               || i > grid_pts[0]
+               || j > grid_pts[1]
+               || k > grid_pts[2]
+            {
+                return Err(Error::Parse(format!("Grid point ({}, {}, {}) is \
+                                                 outside the grid.",
+                                                i, j, k)));
+            }
+            let p =
+                (i - 1) * grid_pts[1] * grid_pts[2] + (j - 1) * grid_pts[2] + k
+                - 1;
+            let values = split.map(|x| {
+                                  x.parse::<f64>()
+                                   .map_err(|_| cannot_parse(x, line))
+                              })
+                              .collect::<Result<Vec<f64>>>()?;
+            if densities.is_empty() {
+                densities = vec![vec![0f64; total]; values.len()];
+            } else if values.len() != densities.len() {
+                return Err(Error::Parse(format!("Inconsistent number of \
+                                                 columns in line:\n{}",
+                                                line)));
+            }
+            for (density, value) in densities.iter_mut().zip(values) {
+                density[p] = value / atoms.lattice.volume;
+            }
+            count += 1;
+        }
+        if count != total {
+            return Err(Error::Parse(format!("CASTEP density has {} grid points \
+                                             but expected {}.",
+                                            count, total)));
+        }
+        println!("File read successfully.");
+        Ok((voxel_origin, grid_pts, atoms, densities))
+    }
+
+    /// Read atom information from the header and the positions block.
+    fn to_atoms(&self, atoms_text: String) -> Result<Atoms> {
+        let mut lines = atoms_text.lines();
+        let lattice = match lines.by_ref()
+                                 .position(|l| l.contains("Real Lattice"))
+        {
+            Some(_) => [parse_vector(lines.next().unwrap_or(""), 0)?,
+                        parse_vector(lines.next().unwrap_or(""), 0)?,
+                        parse_vector(lines.next().unwrap_or(""), 0)?],
+            None => {
+                return Err(Error::Parse(String::from("Cannot find the lattice \
+                                                      in CASTEP header.")))
+            }
+        };
+        let lattice = Lattice::new(lattice)?;
+        let mut lines =
+            atoms_text.lines()
+                      .skip_while(|l| !l.to_uppercase().starts_with("%BLOCK"));
+        let fractional = match lines.next() {
+            Some(l) => l.to_uppercase().contains("POSITIONS_FRAC"),
+            None => {
+                return Err(Error::Parse(String::from("No atomic positions \
+                                                      found for CASTEP file.")))
+            }
+        };
+        let mut units = 1f64;
+        let mut positions: Vec<[f64; 3]> = vec![];
+        for line in lines {
+            let upper = line.to_uppercase();
+            if upper.starts_with("%ENDBLOCK") {
+                break;
+            }
+            let mut split = line.split_whitespace();
+            match (split.next(), split.next()) {
+                (Some(u), None) => {
+                    // the optional units line of POSITIONS_ABS
+                    units = match u.to_lowercase().as_str() {
+                        "bohr" | "a0" => LENGTH_UNITS,
+                        "nm" => 10.,
+                        _ => 1.,
+                    };
+                }
+                (Some(_), Some(_)) => {
+                    let p = parse_vector(line, 1)?;
+                    let p = if fractional {
+                        p
+                    } else {
+                        utils::dot([p[0] * units, p[1] * units, p[2] * units],
+                                   lattice.to_fractional)
+                    };
+                    positions.push(utils::dot([p[0].rem_euclid(1.),
+                                               p[1].rem_euclid(1.),
+                                               p[2].rem_euclid(1.)],
+                                              lattice.to_cartesian));
+                }
+                _ => (),
+            }
+        }
+        Atoms::new(lattice, positions, atoms_text)
+    }
+
+    /// Write a .den_fmt file from a vector of options where None will be
+    /// written as zero.
+    fn write(&self,
+             atoms: &Atoms,
+             data: Vec<Option<f64>>,
+             filename: String,
+             pbar: Bar)
+             -> Result<()> {
+        let filename = format!("{}.den_fmt", filename);
+        let mut buffer = BufWriter::new(File::create(filename)?);
+        // only write the header, the positions came from the .cell file
+        let header_end = match atoms.text.find("END header") {
+            Some(i) => i + atoms.text[i..].find('\n').unwrap_or(0),
+            None => atoms.text.len(),
+        };
+        let header = &atoms.text[..header_end];
+        // each density is written to its own file so there is only one spin
+        for line in header.lines() {
+            if line.contains("! nspins") {
+                let nspins = line.split_whitespace().next().unwrap_or("1");
+                writeln!(buffer, "{}", line.replacen(nspins, "1", 1))?;
+            } else {
+                writeln!(buffer, "{}", line)?;
+            }
+        }
+        writeln!(buffer)?;
+        let grid_pts = grid_points(header)?;
+        pbar.set_length(grid_pts[2]);
+        // the a index runs fastest
+        for k in 0..grid_pts[2] {
+            for j in 0..grid_pts[1] {
+                for i in 0..grid_pts[0] {
+                    let p = i * grid_pts[1] * grid_pts[2] + j * grid_pts[2] + k;
+                    writeln!(buffer,
+                             "{:>6}{:>6}{:>6}{:>20.10E}",
+                             i + 1,
+                             j + 1,
+                             k + 1,
+                             data[p].unwrap_or(0.) * self.write_scale(atoms))?;
+                }
+            }
+            pbar.tick();
+        }
+        Ok(())
+    }
+
+    /// Coordinate format for dealing with fortran indexing (doesn't affect
+    /// CASTEP as the grid indices are explicit).
+    fn coordinate_format(&self, coords: [f64; 3]) -> (String, String, String) {
+        let x = format!("{:.6}", coords[0]);
+        let y = format!("{:.6}", coords[1]);
+        let z = format!("{:.6}", coords[2]);
+        (x, y, z)
+    }
+
+    /// The density is stored times the volume of the cell.
+    fn write_scale(&self, atoms: &Atoms) -> f64 {
+        atoms.lattice.volume
+    }
+
+    /// Image format for dealing with fortran indexing (doesn't affect CASTEP).
+    fn image_format(&self, image: [isize; 3]) -> String {
+        format!("({}, {}, {})", image[0], image[1], image[2])
+    }
+
+    /// The axes are not reordered in CASTEP files.
+    fn axes(&self) -> [usize; 3] {
+        [0, 1, 2]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn castep_cell_filename() {
+        assert_eq!(cell_filename("run/Si.den_fmt"), "run/Si.cell");
+        assert_eq!(cell_filename("Si.den"), "Si.cell");
+    }
+
+    #[test]
+    fn castep_positions_block() {
+        let cell = "%BLOCK LATTICE_CART\n1 0 0\n%ENDBLOCK LATTICE_CART\n\
+                    %block positions_frac ! comment\nSi 0 0 0\n\
+                    %endblock positions_frac\n";
+        assert_eq!(positions_block(cell),
+                   Some(String::from("%block positions_frac\nSi 0 0 0\n\
+                                      %endblock positions_frac\n")));
+    }
+}
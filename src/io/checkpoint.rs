@@ -0,0 +1,392 @@
+use crate::error::{Error, Result};
+use crate::grid::Grid;
+use crate::io::reader;
+use crate::voxel_map::NonBlockingVoxelMap;
+use flate2::write::GzEncoder;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{self, prelude::*, BufWriter};
+
+/// The bytes that start every checkpoint file.
+const MAGIC: &[u8; 8] = b"BCAVMAP\0";
+/// The version of the layout, bumped whenever it changes.
+const VERSION: u64 = 1;
+/// The most elements reserved up front for a length read from a file, longer
+/// vectors grow as they are read so a corrupt length cannot exhaust memory.
+const MAX_RESERVE: usize = 1 << 20;
+
+/// A partitioned density, saved so that other fields can be partitioned on
+/// the same basins without finding the maxima and weights again.
+///
+/// The file is gzip compressed and, once decompressed, holds little-endian
+/// numbers: the magic bytes and version, whether the voxel map stores atoms,
+/// the grid size, lattice and voxel origin, then each Bader maximum with its
+/// atom and distance, the voxel map and finally the weight map with the
+/// length of each set of weights before it.
+pub struct Checkpoint {
+    /// The filled voxel map of the partitioned density.
+    pub voxel_map: NonBlockingVoxelMap,
+    /// The lattice of the density, to check that new inputs match it.
+    pub lattice: [[f64; 3]; 3],
+    /// The position of each Bader maximum in the grid.
+    pub maxima: Vec<isize>,
+    /// The atom each Bader maximum is assigned to.
+    pub atom_map: Vec<usize>,
+    /// The distance from each Bader maximum to its atom.
+    pub minimum_distance: Vec<f64>,
+    /// Whether the voxel map stores the atoms, rather than the Bader maxima.
+    pub by_atom: bool,
+}
+
+impl Checkpoint {
+    /// Writes the checkpoint to a file, compressed with gzip.
+    pub fn save(&self, filename: &str) -> Result<()> {
+        let buffer = BufWriter::new(File::create(filename)?);
+        let mut encoder = GzEncoder::new(buffer, flate2::Compression::fast());
+        self.write(&mut encoder)?;
+        encoder.finish()?.flush()?;
+        Ok(())
+    }
+
+    /// Reads a checkpoint from a file, which may be compressed.
+    pub fn load(filename: &str) -> Result<Self> {
+        let (mut reader, _) = reader::open_file(filename)?;
+        Self::read(&mut reader)
+    }
+
+    /// Writes the uncompressed checkpoint to a writer.
+    pub fn write(&self, writer: &mut impl Write) -> Result<()> {
+        let grid = &self.voxel_map.grid;
+        writer.write_all(MAGIC)?;
+        write_u64(writer, VERSION)?;
+        write_u64(writer, self.by_atom as u64)?;
+        for n in [grid.size.x, grid.size.y, grid.size.z].iter() {
+            write_u64(writer, *n as u64)?;
+        }
+        for f in self.lattice.iter().flatten().chain(&grid.voxel_origin) {
+            writer.write_all(&f.to_le_bytes())?;
+        }
+        write_u64(writer, self.maxima.len() as u64)?;
+        for ((m, a), d) in self.maxima
+                               .iter()
+                               .zip(&self.atom_map)
+                               .zip(&self.minimum_distance)
+        {
+            writer.write_all(&(*m as i64).to_le_bytes())?;
+            write_u64(writer, *a as u64)?;
+            writer.write_all(&d.to_le_bytes())?;
+        }
+        for v in self.voxel_map.voxel_map.iter() {
+            writer.write_all(&(*v as i64).to_le_bytes())?;
+        }
+        write_u64(writer, self.voxel_map.weight_map.len() as u64)?;
+        for weights in self.voxel_map.weight_map.iter() {
+            write_u64(writer, weights.len() as u64)?;
+            for w in weights.iter() {
+                writer.write_all(&w.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads an uncompressed checkpoint from a reader.
+    pub fn read(reader: &mut impl Read) -> Result<Self> {
+        let magic = read_bytes(reader)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("Not a voxel map checkpoint."));
+        }
+        let version = read_u64(reader)?;
+        if version != VERSION {
+            return Err(invalid_data(&format!("Unsupported checkpoint version \
+                                              ({}).",
+                                             version)));
+        }
+        let by_atom = read_u64(reader)? != 0;
+        let mut size = [0usize; 3];
+        for n in size.iter_mut() {
+            *n = read_len(reader)?;
+        }
+        let mut lattice = [[0f64; 3]; 3];
+        for f in lattice.iter_mut().flatten() {
+            *f = read_f64(reader)?;
+        }
+        let mut voxel_origin = [0f64; 3];
+        for f in voxel_origin.iter_mut() {
+            *f = read_f64(reader)?;
+        }
+        let maxima_len = read_len(reader)?;
+        let mut maxima = Vec::with_capacity(maxima_len.min(MAX_RESERVE));
+        let mut atom_map = Vec::with_capacity(maxima_len.min(MAX_RESERVE));
+        let mut minimum_distance =
+            Vec::with_capacity(maxima_len.min(MAX_RESERVE));
+        for _ in 0..maxima_len {
+            maxima.push(read_i64(reader)? as isize);
+            atom_map.push(read_u64(reader)? as usize);
+            minimum_distance.push(read_f64(reader)?);
+        }
+        let total = size.iter()
+                        .try_fold(1usize, |total, n| total.checked_mul(*n))
+                        .ok_or_else(|| {
+                            invalid_data("The grid of the checkpoint is too \
+                                          large.")
+                        })?;
+        let mut voxel_map = Vec::with_capacity(total.min(MAX_RESERVE));
+        for _ in 0..total {
+            voxel_map.push(read_i64(reader)? as isize);
+        }
+        let weight_len = read_len(reader)?;
+        let mut weight_map = Vec::with_capacity(weight_len.min(MAX_RESERVE));
+        for _ in 0..weight_len {
+            let n = read_len(reader)?;
+            let mut weights = Vec::with_capacity(n.min(MAX_RESERVE));
+            for _ in 0..n {
+                weights.push(read_f64(reader)?);
+            }
+            weight_map.push(weights);
+        }
+        let grid = Grid::new(size, lattice, voxel_origin)?;
+        let checkpoint = Self { voxel_map:
+                                    NonBlockingVoxelMap::new(voxel_map,
+                                                             weight_map, grid),
+                                lattice,
+                                maxima,
+                                atom_map,
+                                minimum_distance,
+                                by_atom };
+        // every voxel and weight has to point at a partition or set of weights
+        let partitions = if by_atom {
+            checkpoint.atom_map.iter().max().map_or(0, |a| a + 1)
+        } else {
+            maxima_len
+        };
+        if !checkpoint.points_within(partitions) {
+            return Err(invalid_data("The voxel map of the checkpoint is \
+                                     corrupt."));
+        }
+        Ok(checkpoint)
+    }
+
+    /// Whether every voxel of the voxel map points at one of partitions
+    /// maxima, or atoms, or at a set of weights that exists, and every weight
+    /// at one of the partitions.
+    fn points_within(&self, partitions: usize) -> bool {
+        let weight_len = self.voxel_map.weight_map.len() as isize;
+        self.voxel_map
+            .voxel_map
+            .iter()
+            .all(|v| *v < partitions as isize && -2 - *v < weight_len)
+        && self.voxel_map.weight_map.iter().flatten().all(|w| {
+                                                         *w >= 0.
+                                                         && (*w as usize)
+                                                            < partitions
+                                                     })
+    }
+
+    /// Checks that a density, with its grid size, lattice and number of atoms,
+    /// lies on the same grid as the checkpoint and has the atoms it was
+    /// partitioned into, returning a [`Error::GridMismatch`] if the grid size
+    /// differs.
+    pub fn check(&self,
+                 grid: [usize; 3],
+                 lattice: [[f64; 3]; 3],
+                 atoms_len: usize)
+                 -> Result<()> {
+        let size = &self.voxel_map.grid.size;
+        if grid != [size.x as usize, size.y as usize, size.z as usize] {
+            return Err(Error::GridMismatch { source:
+                                                 String::from("The voxel map"),
+                                             expected: grid,
+                                             found: [size.x as usize,
+                                                     size.y as usize,
+                                                     size.z as usize] });
+        }
+        let matches =
+            lattice.iter()
+                   .flatten()
+                   .zip(self.lattice.iter().flatten())
+                   .all(|(a, b)| (a - b).abs() <= 1E-6 * b.abs().max(1.));
+        if !matches {
+            return Err(Error::InvalidArgument(format!("The lattice {:?} does not \
+                                                       match the lattice of \
+                                                       the voxel map, {:?}.",
+                                                      lattice, self.lattice)));
+        }
+        let partitions = if self.by_atom {
+            atoms_len
+        } else {
+            self.maxima.len()
+        };
+        if self.atom_map.len() != self.maxima.len()
+           || self.atom_map.iter().any(|a| *a >= atoms_len)
+           || !self.points_within(partitions)
+        {
+            return Err(Error::InvalidArgument(format!("The voxel map does \
+                                                       not match the {} \
+                                                       atoms of the density.",
+                                                      atoms_len)));
+        }
+        Ok(())
+    }
+}
+
+/// Builds an error for malformed checkpoint data.
+fn invalid_data(message: &str) -> Error {
+    Error::Parse(String::from(message))
+}
+
+/// Writes an unsigned integer as 8 little-endian bytes.
+fn write_u64(writer: &mut impl Write, n: u64) -> io::Result<()> {
+    writer.write_all(&n.to_le_bytes())
+}
+
+/// Reads 8 bytes from the reader, a checkpoint that ends early is malformed.
+fn read_bytes(reader: &mut impl Read) -> io::Result<[u8; 8]> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes).map_err(|e| match e.kind() {
+                                     io::ErrorKind::UnexpectedEof => {
+                                         io::Error::new(io::ErrorKind::InvalidData,
+                                                        "The checkpoint ends \
+                                                         early.")
+                                     }
+                                     _ => e,
+                                 })?;
+    Ok(bytes)
+}
+
+/// Reads a little-endian unsigned integer.
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    Ok(u64::from_le_bytes(read_bytes(reader)?))
+}
+
+/// Reads a length, which has to fit in a usize.
+fn read_len(reader: &mut impl Read) -> Result<usize> {
+    let n = read_u64(reader)?;
+    usize::try_from(n).map_err(|_| {
+                          invalid_data(&format!("The length ({}) in the \
+                                                 checkpoint is too large.",
+                                                n))
+                      })
+}
+
+/// Reads a little-endian signed integer.
+fn read_i64(reader: &mut impl Read) -> io::Result<i64> {
+    Ok(i64::from_le_bytes(read_bytes(reader)?))
+}
+
+/// Reads a little-endian float.
+fn read_f64(reader: &mut impl Read) -> io::Result<f64> {
+    Ok(f64::from_le_bytes(read_bytes(reader)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkpoint() -> Checkpoint {
+        let lattice = [[2., 0., 0.], [0., 2., 0.], [0., 0., 2.]];
+        let grid = Grid::new([2, 2, 2], lattice, [0.5, 0.5, 0.5]).unwrap();
+        let voxel_map = NonBlockingVoxelMap::new(vec![0, 0, 0, -2, 1, 1, 1,
+                                                      -1],
+                                                 vec![vec![0.25, 1.75]],
+                                                 grid);
+        Checkpoint { voxel_map,
+                     lattice,
+                     maxima: vec![0, 6],
+                     atom_map: vec![0, 0],
+                     minimum_distance: vec![0.5, 1.5],
+                     by_atom: false }
+    }
+
+    #[test]
+    fn checkpoint_read_write() {
+        let mut bytes = Vec::new();
+        checkpoint().write(&mut bytes).unwrap();
+        let read = Checkpoint::read(&mut &bytes[..]).unwrap();
+        assert_eq!(read.voxel_map.voxel_map, vec![0, 0, 0, -2, 1, 1, 1, -1]);
+        assert_eq!(read.voxel_map.weight_map, vec![vec![0.25, 1.75]]);
+        assert_eq!(read.voxel_map.grid.voxel_origin, [0.5, 0.5, 0.5]);
+        assert_eq!(read.voxel_map.grid.voxel_lattice.volume, 1.);
+        assert_eq!(read.maxima, vec![0, 6]);
+        assert_eq!(read.atom_map, vec![0, 0]);
+        assert_eq!(read.minimum_distance, vec![0.5, 1.5]);
+        assert!(!read.by_atom);
+    }
+
+    #[test]
+    fn checkpoint_read_invalid() {
+        let mut bytes = Vec::new();
+        checkpoint().write(&mut bytes).unwrap();
+        assert!(Checkpoint::read(&mut &bytes[..bytes.len() - 1]).is_err());
+        assert!(Checkpoint::read(&mut &b"CHGCAR\n 1.0\n"[..]).is_err());
+        // a voxel pointing at a maximum that does not exist
+        let mut corrupt = checkpoint();
+        corrupt.voxel_map.voxel_map[0] = 2;
+        bytes.clear();
+        corrupt.write(&mut bytes).unwrap();
+        assert!(Checkpoint::read(&mut &bytes[..]).is_err());
+        // a weight pointing at a maximum that does not exist
+        let mut corrupt = checkpoint();
+        corrupt.voxel_map.weight_map[0][1] = 2.75;
+        bytes.clear();
+        corrupt.write(&mut bytes).unwrap();
+        assert!(matches!(Checkpoint::read(&mut &bytes[..]),
+                         Err(Error::Parse(_))));
+    }
+
+    #[test]
+    fn checkpoint_read_huge_length() {
+        let mut bytes = Vec::new();
+        checkpoint().write(&mut bytes).unwrap();
+        // the number of maxima follows the header, grid, lattice and origin
+        let start = 8 + 8 + 8 + 3 * 8 + 9 * 8 + 3 * 8;
+        bytes[start..(start + 8)].copy_from_slice(&(1u64 << 62).to_le_bytes());
+        assert!(matches!(Checkpoint::read(&mut &bytes[..]),
+                         Err(Error::Parse(_))));
+        // a grid whose number of voxels overflows
+        let mut bytes = Vec::new();
+        checkpoint().write(&mut bytes).unwrap();
+        for i in 0..3 {
+            let start = 24 + 8 * i;
+            bytes[start..(start + 8)].copy_from_slice(&(1u64 << 40).to_le_bytes());
+        }
+        assert!(matches!(Checkpoint::read(&mut &bytes[..]),
+                         Err(Error::Parse(_))));
+    }
+
+    #[test]
+    fn checkpoint_save_load() {
+        let filename = std::env::temp_dir().join("bader_checkpoint_save_load");
+        let filename = filename.to_str().unwrap();
+        checkpoint().save(filename).unwrap();
+        let read = Checkpoint::load(filename).unwrap();
+        std::fs::remove_file(filename).unwrap();
+        assert_eq!(read.voxel_map.voxel_map, checkpoint().voxel_map.voxel_map);
+    }
+
+    #[test]
+    fn checkpoint_check() {
+        let checkpoint = checkpoint();
+        let lattice = [[2., 0., 0.], [0., 2., 0.], [0., 0., 2.]];
+        assert!(checkpoint.check([2, 2, 2], lattice, 1).is_ok());
+        assert!(matches!(checkpoint.check([2, 2, 4], lattice, 1),
+                         Err(Error::GridMismatch { .. })));
+        // the maxima are assigned to an atom the density does not have
+        assert!(matches!(checkpoint.check([2, 2, 2], lattice, 0),
+                         Err(Error::InvalidArgument(_))));
+        let lattice = [[2., 0., 0.], [0., 2., 0.], [0., 0., 2.1]];
+        assert!(checkpoint.check([2, 2, 2], lattice, 1).is_err());
+    }
+
+    #[test]
+    fn checkpoint_check_by_atom() {
+        let mut checkpoint = checkpoint();
+        checkpoint.by_atom = true;
+        checkpoint.atom_map = vec![0, 1];
+        let lattice = [[2., 0., 0.], [0., 2., 0.], [0., 0., 2.]];
+        assert!(checkpoint.check([2, 2, 2], lattice, 2).is_ok());
+        // voxels and weights of the second atom with only one atom
+        checkpoint.atom_map = vec![0, 0];
+        assert!(matches!(checkpoint.check([2, 2, 2], lattice, 1),
+                         Err(Error::InvalidArgument(_))));
+    }
+}
@@ -1,10 +1,10 @@
 use crate::atoms::{Atoms, Lattice};
-use crate::io::reader::BufReader;
-use crate::io::{FileFormat, FortranFormat, ReadFunction};
+use crate::error::{Error, Result};
+use crate::io::{output, reader, FileFormat, FortranFormat, ReadFunction};
 use crate::progress::Bar;
 use crate::utils;
 use std::fs::File;
-use std::io::{BufWriter, Read, Write};
+use std::io::{BufRead, BufWriter, Write};
 
 /// Convert from chemists.
 const LENGTH_UNITS: f64 = 0.52917721067;
@@ -14,166 +14,239 @@ const VOLUME_UNITS: f64 = LENGTH_UNITS * LENGTH_UNITS * LENGTH_UNITS;
 /// Structure for reading/writing a cube file.
 pub struct Cube {}
 
-impl FileFormat for Cube {
-    /// reads a cube file from filename.
-    fn read(&self, filename: String) -> ReadFunction {
+/// Rewrites the header of a cube file with multiple values per voxel for a
+/// single value, dropping the number of values and any orbital indices.
+fn single_value_header(text: &str) -> String {
+    let mut lines = text.lines();
+    let mut header = String::new();
+    for line in lines.by_ref().take(2) {
+        header.push_str(line);
+        header.push('\n');
+    }
+    let natoms = match lines.next() {
+        Some(line) => {
+            let split = line.split_whitespace().collect::<Vec<&str>>();
+            let natoms = split.first()
+                              .and_then(|x| x.parse::<isize>().ok())
+                              .unwrap_or(0);
+            let n = natoms.unsigned_abs();
+            if natoms < 0 || split.len() > 4 {
+                header.push_str(&format!("{:>5}", n));
+                split.iter()
+                     .skip(1)
+                     .take(3)
+                     .for_each(|x| header.push_str(&format!(" {:>11}", x)));
+                header.push('\n');
+            } else {
+                header.push_str(line);
+                header.push('\n');
+            }
+            n
+        }
+        None => return header,
+    };
+    // the grid and the atoms
+    for line in lines.take(3 + natoms) {
+        header.push_str(line);
+        header.push('\n');
+    }
+    header
+}
+
+/// Parses a line of the header into floats, which has to hold at least len of
+/// them.
+fn parse_line(line: Option<&str>, len: usize) -> Result<Vec<f64>> {
+    let line = line.ok_or_else(|| {
+                       Error::Parse(String::from("Unexpected end of cube \
+                                                  header."))
+                   })?;
+    let floats = line.split_whitespace()
+                     .map(|x| {
+                         x.parse::<f64>().map_err(|_| {
+                                             Error::Parse(format!("Cannot parse ({}) in line:\n{}", x, line))
+                                         })
+                     })
+                     .collect::<Result<Vec<f64>>>()?;
+    if floats.len() < len {
+        return Err(Error::Parse(format!("Expected {} values in line:\n{}",
+                                        len, line)));
+    }
+    Ok(floats)
+}
+
+impl Cube {
+    /// Parses the bytes of a cube file.
+    fn parse(&self, bytes: &[u8], name: &str, threads: usize) -> ReadFunction {
         // the voxel origin in cube files is (0.5, 0.5, 0.5)
         let mut voxel_origin = [0.5f64; 3];
 
-        println!("Reading {} as cube format:", filename);
+        println!("Reading {} as cube format:", name);
         // find the start and end points of the density as well as the total file size
-        let (start, grid_pts) = {
-            // open the file in a buffer reader
-            let mut reader = BufReader::open(filename.clone())?;
-
-            let mut buffer = String::new();
+        let malformed =
+            || Error::Parse(format!("Cannot read {} as cube file.", name));
+        let (start, grid_pts, nval) = {
+            // read the header a line at a time
+            let mut lines =
+                bytes.split_inclusive(|b| *b == b'\n')
+                     .map(|l| (String::from_utf8_lossy(l), l.len()));
             let mut pos = 0;
             // first two lines are comments
             for _ in 0..2 {
-                let size = match reader.read_line(&mut buffer) {
-                    Some(line) => {
-                        let (_, size) = line?;
-                        size
-                    }
+                let size = match lines.next() {
+                    Some((_, size)) => size,
                     None => 0,
                 };
                 pos += size;
             }
             // lets start trying to match
-            let natoms = match reader.read_line(&mut buffer) {
-                Some(line) => {
-                    let (text, size) = line?;
+            let (natoms, nval) = match lines.next() {
+                Some((text, size)) => {
                     pos += size;
                     let split = text
-                        .trim()
                         .split_whitespace()
                         .map(|x| x.parse::<f64>())
-                        .collect::<Vec<Result<f64, std::num::ParseFloatError>>>(
-                        );
-                    if split.len() == 5 && split[4] != Ok(1.) {
-                        panic!(
-                            "Error(Unsuppoerted): Multiple values per voxel."
+                        .collect::<Vec<std::result::Result<f64, std::num::ParseFloatError>>>(
                         );
+                    // an optional fifth field is the number of values per voxel
+                    let nval = match split.get(4) {
+                        Some(Ok(x)) if *x >= 1. => *x as usize,
+                        Some(_) => {
+                            return Err(Error::UnsupportedFormat(format!("The number of values per voxel of {}.", name)))
+                        }
+                        None => 1,
+                    };
+                    if split.len() < 4 {
+                        return Err(malformed());
                     }
                     let natoms = match split[0] {
                         Ok(x) => x as isize,
-                        Err(_) => panic!("Error: Cannot read {} as cube file.",
-                                         filename),
+                        Err(_) => return Err(malformed()),
                     };
                     for i in 0..3 {
                         voxel_origin[i] += match split[i + 1] {
                             Ok(x) => x,
-                            Err(_) => {
-                                panic!("Error: Cannot read {} as cube file.",
-                                       filename)
-                            }
+                            Err(_) => return Err(malformed()),
                         };
                     }
-                    natoms
+                    (natoms, nval)
                 }
-                None => panic!("Error: Cannot read {} as cube file.", filename),
+                None => return Err(malformed()),
             };
-            if natoms < 0 {
-                panic!("Error(Unsuppoerted): Multiple values per voxel.");
-            }
             let mut grid_pts = [0usize; 3];
             for gp in &mut grid_pts {
-                *gp = match reader.read_line(&mut buffer) {
-                    Some(line) => {
-                        let (text, size) = line?;
+                *gp = match lines.next() {
+                    Some((text, size)) => {
                         pos += size;
-                        match text.trim().split_whitespace().next() {
+                        match text.split_whitespace().next() {
                             Some(x) => match x.parse::<usize>() {
                                 Ok(x) => x,
-                                Err(_) => panic!(
-                                    "Error: Cannot read {} as cube file.",
-                                    filename
-                                ),
+                                Err(_) => return Err(malformed()),
                             },
-                            None => {
-                                panic!("Error: Cannot read {} as cube file.",
-                                       filename)
-                            }
+                            None => return Err(malformed()),
                         }
                     }
-                    None => {
-                        panic!("Error: Cannot read {} as cube file.", filename)
-                    }
+                    None => return Err(malformed()),
                 }
             }
             for _ in 0..natoms.abs() {
-                match reader.read_line(&mut buffer) {
-                    Some(line) => {
-                        let (_, size) = line?;
+                match lines.next() {
+                    Some((_, size)) => {
                         pos += size;
                     }
-                    None => {
-                        panic!("Error: Cannot read {} as cube file.", filename)
-                    }
+                    None => return Err(malformed()),
                 }
             }
-            (pos, grid_pts)
+            // a negative number of atoms is followed by the number of
+            // orbitals and their indices, which can run over several lines
+            let nval = if natoms < 0 {
+                let mut orbitals: Vec<usize> = vec![];
+                while orbitals.is_empty() || orbitals.len() <= orbitals[0] {
+                    match lines.next() {
+                        Some((text, size)) => {
+                            pos += size;
+                            for x in text.split_whitespace() {
+                                match x.parse::<usize>() {
+                                    Ok(x) => orbitals.push(x),
+                                    Err(_) => return Err(malformed()),
+                                }
+                            }
+                        }
+                        None => return Err(malformed()),
+                    }
+                }
+                orbitals[0].max(1)
+            } else {
+                nval
+            };
+            (pos, grid_pts, nval)
         };
-        // Now we know where everything is so let's work out what to do
-        // Start by making vector of start and end points of the densities
-        let mut file = File::open(filename)?;
-        let total = file.metadata()?.len();
-        // assign Vectos with the capacity of what it is to hold
-        let mut xyz_b = Vec::with_capacity(start);
-        let mut density_b = Vec::with_capacity(total as usize - start);
-        // read the xyz information into xyz_b
-        let _ = <File as Read>::by_ref(&mut file).take(start as u64)
-                                                 .read_to_end(&mut xyz_b)?;
-        // read the total charge density into density_b
-        let _ = <File as Read>::by_ref(&mut file).take(total - start as u64)
-                                                 .read_to_end(&mut density_b)?;
         // convert the bytes we have read into a String and an Atoms struct
-        let xyz = String::from_utf8(xyz_b).unwrap();
-        let atoms = self.to_atoms(xyz);
+        let xyz = String::from_utf8(bytes[..start].to_vec()).map_err(|_| {
+                                                                malformed()
+                                                            })?;
+        let atoms = self.to_atoms(xyz)?;
         // convert out of Bohr
-        let density = String::from_utf8(density_b).unwrap()
-                                                  .split_whitespace()
-                                                  .map(|x| {
-                                                      x.parse::<f64>().unwrap()
-                                                      / VOLUME_UNITS
-                                                  })
-                                                  .collect::<Vec<f64>>();
+        let data = reader::parse_floats(&bytes[start..], threads, |x| {
+            x / VOLUME_UNITS
+        })?;
+        let total = grid_pts.iter().product::<usize>();
+        if data.len() != total * nval {
+            return Err(Error::Parse(format!("Expected {} values in the density but found {}.",
+                                            total * nval,
+                                            data.len())));
+        }
+        // the values of each voxel are stored together so split them into
+        // a density for each
+        let densities = if nval == 1 {
+            vec![data]
+        } else {
+            (0..nval).map(|i| {
+                         data.iter().skip(i).step_by(nval).copied().collect()
+                     })
+                     .collect()
+        };
         println!("File read successfully.");
-        Ok((voxel_origin, grid_pts, atoms, vec![density]))
+        Ok((voxel_origin, grid_pts, atoms, densities))
+    }
+}
+
+impl FileFormat for Cube {
+    /// reads a cube file from filename, memory-mapping the file.
+    fn read(&self, filename: String, threads: usize) -> ReadFunction {
+        let bytes = reader::map_file(&filename)?;
+        self.parse(&bytes, &filename, threads)
+    }
+
+    /// reads a cube file from a source.
+    fn read_from(&self,
+                 source: &mut dyn BufRead,
+                 name: &str,
+                 threads: usize)
+                 -> ReadFunction {
+        let bytes = reader::source_to_bytes(source)?;
+        self.parse(&bytes, name, threads)
     }
 
     /// Read atoms information from file header.
-    fn to_atoms(&self, atoms_text: String) -> Atoms {
+    fn to_atoms(&self, atoms_text: String) -> Result<Atoms> {
         let mut lines = atoms_text.lines();
         // skip the 2 comment lines + voxel info and then read the lattice information
         let _ = lines.next();
         let _ = lines.next();
-        let _ = lines.next();
-        let mut a = {
-            lines.next()
-                 .unwrap()
-                 .to_string()
-                 .split_whitespace()
-                 .map(|x| x.parse::<f64>().unwrap())
-                 .collect::<Vec<f64>>()
+        let natoms = match lines.next()
+                                .and_then(|l| l.split_whitespace().next())
+                                .and_then(|x| x.parse::<isize>().ok())
+        {
+            Some(x) => x.unsigned_abs(),
+            None => {
+                return Err(Error::Parse(String::from("Cannot read the number \
+                                                      of atoms.")))
+            }
         };
+        let mut a = parse_line(lines.next(), 4)?;
         // density[z, y, x] so lets swap the c and a
-        let mut b = {
-            lines.next()
-                 .unwrap()
-                 .to_string()
-                 .split_whitespace()
-                 .map(|x| x.parse::<f64>().unwrap())
-                 .collect::<Vec<f64>>()
-        };
-        let mut c = {
-            lines.next()
-                 .unwrap()
-                 .to_string()
-                 .split_whitespace()
-                 .map(|x| x.parse::<f64>().unwrap())
-                 .collect::<Vec<f64>>()
-        };
+        let mut b = parse_line(lines.next(), 4)?;
+        let mut c = parse_line(lines.next(), 4)?;
         for i in 1..4 {
             c[i] *= c[0] * LENGTH_UNITS;
             b[i] *= b[0] * LENGTH_UNITS;
@@ -181,13 +254,16 @@ impl FileFormat for Cube {
         }
         let lattice = Lattice::new([[a[1], a[2], a[3]],
                                     [b[1], b[2], b[3]],
-                                    [c[1], c[2], c[3]]]);
+                                    [c[1], c[2], c[3]]])?;
         let mut positions: Vec<[f64; 3]> = vec![];
-        // make the positions fractional and swap c and a
-        for line in lines {
-            let pos = line.split_whitespace()
-                          .map(|x| x.parse::<f64>().unwrap() * LENGTH_UNITS)
-                          .collect::<Vec<f64>>();
+        let mut atomic_numbers: Vec<usize> = vec![];
+        // make the positions fractional and swap c and a, any orbital indices
+        // follow the atoms
+        for _ in 0..natoms {
+            let pos = parse_line(lines.next(), 5)?;
+            atomic_numbers.push(pos[0] as usize);
+            let pos =
+                pos.iter().map(|x| x * LENGTH_UNITS).collect::<Vec<f64>>();
             let pos_frac =
                 utils::dot([pos[2], pos[3], pos[4]], lattice.to_fractional)
                     .iter()
@@ -199,7 +275,7 @@ impl FileFormat for Cube {
             };
             positions.push(pos_cart);
         }
-        Atoms::new(lattice, positions, atoms_text)
+        Ok(Atoms::new(lattice, positions, atoms_text)?.with_atomic_numbers(atomic_numbers))
     }
 
     /// Write a cube file from a vector of options where None will be written as
@@ -209,30 +285,22 @@ impl FileFormat for Cube {
              data: Vec<Option<f64>>,
              filename: String,
              pbar: Bar)
-             -> std::io::Result<()> {
+             -> Result<()> {
         let filename = format!("{}.cube", filename);
         let mut buffer = BufWriter::new(File::create(filename)?);
         pbar.set_length(data.len() / 6 + (data.len() % 6 != 0) as usize);
-        buffer.write_all(atoms.text.as_bytes())?;
-        data.chunks(6).for_each(|line| {
-                          if let Err(e) = line.iter().try_for_each(|f| {
-                                                         write!(
-                    buffer,
-                    " {:.5}",
-                    FortranFormat {
-                        float: *f,
-                        mult: VOLUME_UNITS
-                    }
-                )
-                                                     })
-                          {
-                              panic!("Error occured during write: {}", e)
-                          };
-                          if let Err(e) = writeln!(buffer) {
-                              panic!("Error occured during write: {}", e)
-                          };
-                          pbar.tick();
-                      });
+        buffer.write_all(single_value_header(&atoms.text).as_bytes())?;
+        let mult = self.write_scale(atoms);
+        data.chunks(6).try_for_each(|line| -> std::io::Result<()> {
+                           for f in line {
+                               write!(buffer,
+                                      " {:.5}",
+                                      FortranFormat { float: *f, mult })?;
+                           }
+                           writeln!(buffer)?;
+                           pbar.tick();
+                           Ok(())
+                       })?;
         Ok(())
     }
 
@@ -243,4 +311,91 @@ impl FileFormat for Cube {
         let z = format!("{:.6}", coords[2]);
         (x, y, z)
     }
+
+    /// Cube files store the density in bohr^-3.
+    fn write_scale(&self, _atoms: &Atoms) -> f64 {
+        VOLUME_UNITS
+    }
+
+    /// Image format for dealing with fortran indexing (doesn't affect cube).
+    fn image_format(&self, image: [isize; 3]) -> String {
+        format!("({}, {}, {})", image[0], image[1], image[2])
+    }
+
+    /// The axes are not reordered in cube files.
+    fn axes(&self) -> [usize; 3] {
+        [0, 1, 2]
+    }
+
+    /// Orbital cube files are named by their orbitals and any other file with
+    /// multiple values per voxel by channel.
+    fn density_names(&self, atoms: &Atoms, density_num: usize) -> Vec<String> {
+        let mut lines = atoms.text.lines().skip(2);
+        let split = lines.next()
+                         .unwrap_or("")
+                         .split_whitespace()
+                         .map(String::from)
+                         .collect::<Vec<String>>();
+        let natoms = split.first()
+                          .and_then(|x| x.parse::<isize>().ok())
+                          .unwrap_or(0);
+        if natoms < 0 {
+            // the orbital indices follow the atoms
+            let orbitals = lines.skip(3 + natoms.unsigned_abs())
+                                .flat_map(|l| l.split_whitespace())
+                                .skip(1)
+                                .take(density_num)
+                                .map(|x| format!("Orbital {}", x))
+                                .collect::<Vec<String>>();
+            if orbitals.len() == density_num {
+                return orbitals;
+            }
+        }
+        if natoms < 0 || split.len() > 4 && split[4] != "1" {
+            (1..=density_num).map(|i| format!("Channel {}", i))
+                             .collect()
+        } else {
+            output::density_names(density_num)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cube_single_value_header_orbitals() {
+        let text = "comment\ncomment\n   -1    0.000000    0.000000    0.000000    1\n\
+                    2 1.0 0.0 0.0\n2 0.0 1.0 0.0\n2 0.0 0.0 1.0\n\
+                    1 1.0 0.0 0.0 0.0\n    2    5    6\n";
+        assert_eq!(single_value_header(text),
+                   "comment\ncomment\n    1    0.000000    0.000000    0.000000\n\
+                    2 1.0 0.0 0.0\n2 0.0 1.0 0.0\n2 0.0 0.0 1.0\n\
+                    1 1.0 0.0 0.0 0.0\n");
+    }
+
+    #[test]
+    fn cube_density_names() {
+        let text = "comment\ncomment\n   -1    0.000000    0.000000    0.000000    1\n\
+                    2 1.0 0.0 0.0\n2 0.0 1.0 0.0\n2 0.0 0.0 1.0\n\
+                    1 1.0 0.0 0.0 0.0\n    2    5    6\n";
+        let atoms = Cube {}.to_atoms(String::from(text)).unwrap();
+        assert_eq!(Cube {}.density_names(&atoms, 2),
+                   vec![String::from("Orbital 5"), String::from("Orbital 6")]);
+        let text = text.replace("   -1", "    1")
+                       .replace("    2    5    6\n", "");
+        let atoms = Cube {}.to_atoms(text).unwrap();
+        assert_eq!(Cube {}.density_names(&atoms, 2),
+                   vec![String::from("Charge"), String::from("Spin")]);
+    }
+
+    #[test]
+    fn cube_single_value_header_unchanged() {
+        let text =
+            "comment\ncomment\n    1    0.000000    0.000000    0.000000\n\
+                    2 1.0 0.0 0.0\n2 0.0 1.0 0.0\n2 0.0 0.0 1.0\n\
+                    1 1.0 0.0 0.0 0.0\n";
+        assert_eq!(single_value_header(text), text);
+    }
 }
@@ -0,0 +1,330 @@
+use crate::io::reader::{self, Compression};
+use std::convert::TryInto;
+use std::io::{self, prelude::*};
+use std::path::Path;
+
+/// The byte order of a Fortran unformatted file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Endian {
+    /// Least significant byte first, x86 and ARM.
+    Little,
+    /// Most significant byte first, POWER and older machines.
+    Big,
+}
+
+/// Reads the records of a sequential Fortran unformatted file.
+///
+/// Each record is surrounded by 4-byte markers holding its length in bytes.
+/// Records longer than 2 GiB are split by gfortran into subrecords, a negative
+/// leading marker signalling that another subrecord follows, and these are
+/// joined back together. The endianness is detected from the first marker.
+pub struct FortranReader<R: Read> {
+    reader: R,
+    endian: Endian,
+    first: Option<[u8; 4]>,
+    /// The bytes left to read, if the length of the data is known.
+    remaining: u64,
+}
+
+impl FortranReader<Box<dyn BufRead>> {
+    /// Opens the file from the path, or stdin if it is [`reader::STDIN`], into
+    /// a reader, decompressing it if needed.
+    pub fn open(path: impl AsRef<std::path::Path>) -> io::Result<Self> {
+        let (reader, compression) = reader::open_file(&path)?;
+        // the decompressed length is unknown until it has been read
+        let length = match compression {
+            Compression::None if path.as_ref() != Path::new(reader::STDIN) => {
+                std::fs::metadata(path)?.len()
+            }
+            _ => u64::MAX,
+        };
+        Self::new(reader, length)
+    }
+}
+
+impl<R: Read> FortranReader<R> {
+    /// Wraps a reader, using the length of the data, in bytes, to decide the
+    /// endianness from the first record marker. Use u64::MAX if the length is
+    /// not known.
+    pub fn new(mut reader: R, length: u64) -> io::Result<Self> {
+        let mut marker = [0u8; 4];
+        reader.read_exact(&mut marker)?;
+        let little = i32::from_le_bytes(marker).unsigned_abs() as u64;
+        let big = i32::from_be_bytes(marker).unsigned_abs() as u64;
+        // the first record and its two markers have to fit in the file
+        let endian =
+            if little + 8 <= length && (big + 8 > length || little <= big) {
+                Endian::Little
+            } else if big + 8 <= length {
+                Endian::Big
+            } else {
+                return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                          "Not a Fortran unformatted file."));
+            };
+        Ok(Self { reader,
+                  endian,
+                  first: Some(marker),
+                  remaining: length.saturating_sub(4) })
+    }
+
+    /// The endianness of the file.
+    pub fn endian(&self) -> Endian {
+        self.endian
+    }
+
+    /// Reads a record marker.
+    fn marker(&mut self) -> io::Result<i32> {
+        let bytes = match self.first.take() {
+            Some(bytes) => bytes,
+            None => {
+                let mut bytes = [0u8; 4];
+                self.reader.read_exact(&mut bytes)?;
+                self.remaining = self.remaining.saturating_sub(4);
+                bytes
+            }
+        };
+        Ok(match self.endian {
+            Endian::Little => i32::from_le_bytes(bytes),
+            Endian::Big => i32::from_be_bytes(bytes),
+        })
+    }
+
+    /// Reads the next record, returning None at the end of the file.
+    pub fn read_record(&mut self) -> Option<io::Result<Record>> {
+        let mut bytes = Vec::new();
+        loop {
+            let head = match self.marker() {
+                Ok(head) => head,
+                Err(e)
+                    if e.kind() == io::ErrorKind::UnexpectedEof
+                       && bytes.is_empty() =>
+                {
+                    return None
+                }
+                Err(e) => return Some(Err(e)),
+            };
+            let length = head.unsigned_abs() as u64;
+            // the record and its closing marker have to fit in what is left
+            if length + 4 > self.remaining {
+                return Some(Err(io::Error::new(io::ErrorKind::InvalidData,
+                                               "A Fortran record marker is longer than the file.")));
+            }
+            self.remaining -= length;
+            // grow the record as it is read rather than trusting the marker
+            match self.reader.by_ref().take(length).read_to_end(&mut bytes) {
+                Ok(n) if n as u64 == length => (),
+                Ok(_) => {
+                    return Some(Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                                   "Unexpected end of Fortran file.")))
+                }
+                Err(e) => return Some(Err(e)),
+            }
+            match self.marker() {
+                Ok(tail) if tail.unsigned_abs() as u64 == length => (),
+                Ok(_) => {
+                    return Some(Err(io::Error::new(io::ErrorKind::InvalidData,
+                                                   "Mismatched Fortran record markers.")))
+                }
+                Err(e) => return Some(Err(e)),
+            }
+            if head >= 0 {
+                break;
+            }
+        }
+        Some(Ok(Record { bytes,
+                         endian: self.endian,
+                         position: 0 }))
+    }
+
+    /// Reads the next record, treating the end of the file as an error.
+    pub fn next_record(&mut self) -> io::Result<Record> {
+        match self.read_record() {
+            Some(record) => record,
+            None => Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                       "Unexpected end of Fortran file.")),
+        }
+    }
+}
+
+/// A single record of a Fortran unformatted file, read from the front.
+pub struct Record {
+    bytes: Vec<u8>,
+    endian: Endian,
+    position: usize,
+}
+
+impl Record {
+    /// The length of the record in bytes.
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Whether the record is empty.
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Takes the next n bytes of the record.
+    fn take<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        match self.bytes.get(self.position..(self.position + N)) {
+            Some(bytes) => {
+                self.position += N;
+                Ok(bytes.try_into().unwrap())
+            }
+            None => {
+                Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                   "Read past the end of a Fortran record."))
+            }
+        }
+    }
+
+    /// Skips the next n bytes of the record.
+    pub fn skip(&mut self, n: usize) -> io::Result<()> {
+        if self.position + n > self.bytes.len() {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                      "Read past the end of a Fortran record."));
+        }
+        self.position += n;
+        Ok(())
+    }
+
+    /// Reads a 4-byte integer.
+    pub fn i32(&mut self) -> io::Result<i32> {
+        let bytes = self.take::<4>()?;
+        Ok(match self.endian {
+            Endian::Little => i32::from_le_bytes(bytes),
+            Endian::Big => i32::from_be_bytes(bytes),
+        })
+    }
+
+    /// Reads a 4-byte real.
+    pub fn f32(&mut self) -> io::Result<f32> {
+        let bytes = self.take::<4>()?;
+        Ok(match self.endian {
+            Endian::Little => f32::from_le_bytes(bytes),
+            Endian::Big => f32::from_be_bytes(bytes),
+        })
+    }
+
+    /// Reads an 8-byte real.
+    pub fn f64(&mut self) -> io::Result<f64> {
+        let bytes = self.take::<8>()?;
+        Ok(match self.endian {
+            Endian::Little => f64::from_le_bytes(bytes),
+            Endian::Big => f64::from_be_bytes(bytes),
+        })
+    }
+
+    /// Reads n 4-byte integers.
+    pub fn i32_vec(&mut self, n: usize) -> io::Result<Vec<i32>> {
+        (0..n).map(|_| self.i32()).collect()
+    }
+
+    /// Reads n 4-byte reals, converted to f64.
+    pub fn f32_vec(&mut self, n: usize) -> io::Result<Vec<f64>> {
+        (0..n).map(|_| self.f32().map(f64::from)).collect()
+    }
+
+    /// Reads n 8-byte reals.
+    pub fn f64_vec(&mut self, n: usize) -> io::Result<Vec<f64>> {
+        (0..n).map(|_| self.f64()).collect()
+    }
+
+    /// Reads a character string of length n, trimming any padding.
+    pub fn string(&mut self, n: usize) -> io::Result<String> {
+        let start = self.position;
+        self.skip(n)?;
+        Ok(String::from_utf8_lossy(&self.bytes[start..self.position]).trim()
+                                                                     .to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a record with markers in the given byte order.
+    fn record(data: &[u8], endian: Endian) -> Vec<u8> {
+        let marker = match endian {
+            Endian::Little => (data.len() as i32).to_le_bytes(),
+            Endian::Big => (data.len() as i32).to_be_bytes(),
+        };
+        let mut bytes = marker.to_vec();
+        bytes.extend_from_slice(data);
+        bytes.extend_from_slice(&marker);
+        bytes
+    }
+
+    #[test]
+    fn fortran_little_endian() {
+        let mut data = 7i32.to_le_bytes().to_vec();
+        data.extend_from_slice(&1.5f64.to_le_bytes());
+        let mut file = record(&data, Endian::Little);
+        file.extend(record(&2.5f32.to_le_bytes(), Endian::Little));
+        let length = file.len() as u64;
+        let mut reader = FortranReader::new(&file[..], length).unwrap();
+        assert_eq!(reader.endian(), Endian::Little);
+        let mut r = reader.next_record().unwrap();
+        assert_eq!(r.len(), 12);
+        assert_eq!(r.i32().unwrap(), 7);
+        assert_eq!(r.f64().unwrap(), 1.5);
+        assert!(r.i32().is_err());
+        let mut r = reader.next_record().unwrap();
+        assert_eq!(r.f32_vec(1).unwrap(), vec![2.5]);
+        assert!(reader.read_record().is_none());
+    }
+
+    #[test]
+    fn fortran_big_endian() {
+        let mut data = b"abinit  ".to_vec();
+        data.extend_from_slice(&(-3i32).to_be_bytes());
+        let file = record(&data, Endian::Big);
+        let length = file.len() as u64;
+        let mut reader = FortranReader::new(&file[..], length).unwrap();
+        assert_eq!(reader.endian(), Endian::Big);
+        let mut r = reader.next_record().unwrap();
+        assert_eq!(r.string(8).unwrap(), "abinit");
+        assert_eq!(r.i32().unwrap(), -3);
+    }
+
+    #[test]
+    fn fortran_subrecords() {
+        // a record split in two, the first leading marker is negative
+        let mut file = (-4i32).to_le_bytes().to_vec();
+        file.extend_from_slice(&1i32.to_le_bytes());
+        file.extend_from_slice(&(-4i32).to_le_bytes());
+        file.extend(record(&2i32.to_le_bytes(), Endian::Little));
+        let length = file.len() as u64;
+        let mut reader = FortranReader::new(&file[..], length).unwrap();
+        let mut r = reader.next_record().unwrap();
+        assert_eq!(r.i32_vec(2).unwrap(), vec![1, 2]);
+    }
+
+    #[test]
+    fn fortran_mismatched_markers() {
+        let mut file = record(&1i32.to_le_bytes(), Endian::Little);
+        let end = file.len() - 4;
+        file[end] = 8;
+        let length = file.len() as u64;
+        let mut reader = FortranReader::new(&file[..], length).unwrap();
+        assert!(reader.next_record().is_err());
+    }
+
+    #[test]
+    fn fortran_long_marker() {
+        // a record followed by a marker claiming one of nearly 2 GiB
+        let mut file = record(&1i32.to_le_bytes(), Endian::Little);
+        file.extend_from_slice(&i32::MAX.to_le_bytes());
+        file.extend_from_slice(&[0; 12]);
+        let length = file.len() as u64;
+        let mut reader = FortranReader::new(&file[..], length).unwrap();
+        assert!(reader.next_record().is_ok());
+        let e = reader.next_record().err().unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        // without the length the record ends early instead
+        let mut reader = FortranReader::new(&file[..], u64::MAX).unwrap();
+        assert!(reader.next_record().is_ok());
+        let e = reader.next_record().err().unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
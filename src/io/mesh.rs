@@ -0,0 +1,103 @@
+use crate::surface::Mesh;
+use crate::utils::{cross, norm};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// Indicates the available file types for writing a surface mesh.
+pub enum MeshFormat {
+    /// Wavefront OBJ.
+    Obj,
+    /// Stanford PLY (ascii).
+    Ply,
+    /// Stereolithography STL (ascii).
+    Stl,
+}
+
+impl MeshFormat {
+    /// The file extension of the format.
+    pub fn extension(&self) -> &str {
+        match self {
+            Self::Obj => "obj",
+            Self::Ply => "ply",
+            Self::Stl => "stl",
+        }
+    }
+}
+
+/// Write the mesh to filename, with the extension of the format appended.
+pub fn write(mesh: &Mesh,
+             format: &MeshFormat,
+             filename: String)
+             -> std::io::Result<()> {
+    let filename = format!("{}.{}", filename, format.extension());
+    let mut buffer = BufWriter::new(File::create(filename)?);
+    match format {
+        MeshFormat::Obj => write_obj(mesh, &mut buffer),
+        MeshFormat::Ply => write_ply(mesh, &mut buffer),
+        MeshFormat::Stl => write_stl(mesh, &mut buffer),
+    }
+}
+
+/// Write the vertices and then the faces, indexed from 1.
+fn write_obj(mesh: &Mesh, buffer: &mut impl Write) -> std::io::Result<()> {
+    writeln!(buffer, "# Bader zero-flux surface")?;
+    for v in mesh.vertices.iter() {
+        writeln!(buffer, "v {:.6} {:.6} {:.6}", v[0], v[1], v[2])?;
+    }
+    for t in mesh.triangles.iter() {
+        writeln!(buffer, "f {} {} {}", t[0] + 1, t[1] + 1, t[2] + 1)?;
+    }
+    Ok(())
+}
+
+/// Write the header followed by the vertices and faces, indexed from 0.
+fn write_ply(mesh: &Mesh, buffer: &mut impl Write) -> std::io::Result<()> {
+    writeln!(buffer, "ply")?;
+    writeln!(buffer, "format ascii 1.0")?;
+    writeln!(buffer, "comment Bader zero-flux surface")?;
+    writeln!(buffer, "element vertex {}", mesh.vertices.len())?;
+    writeln!(buffer, "property float x")?;
+    writeln!(buffer, "property float y")?;
+    writeln!(buffer, "property float z")?;
+    writeln!(buffer, "element face {}", mesh.triangles.len())?;
+    writeln!(buffer, "property list uchar int vertex_indices")?;
+    writeln!(buffer, "end_header")?;
+    for v in mesh.vertices.iter() {
+        writeln!(buffer, "{:.6} {:.6} {:.6}", v[0], v[1], v[2])?;
+    }
+    for t in mesh.triangles.iter() {
+        writeln!(buffer, "3 {} {} {}", t[0], t[1], t[2])?;
+    }
+    Ok(())
+}
+
+/// Write each triangle as a facet with its normal.
+fn write_stl(mesh: &Mesh, buffer: &mut impl Write) -> std::io::Result<()> {
+    writeln!(buffer, "solid bader")?;
+    for t in mesh.triangles.iter() {
+        let a = mesh.vertices[t[0]];
+        let b = mesh.vertices[t[1]];
+        let c = mesh.vertices[t[2]];
+        let n = cross([b[0] - a[0], b[1] - a[1], b[2] - a[2]],
+                      [c[0] - a[0], c[1] - a[1], c[2] - a[2]]);
+        let length = norm(n);
+        let n = if length > 0. {
+            [n[0] / length, n[1] / length, n[2] / length]
+        } else {
+            n
+        };
+        writeln!(buffer,
+                 "  facet normal {:.6E} {:.6E} {:.6E}",
+                 n[0], n[1], n[2])?;
+        writeln!(buffer, "    outer loop")?;
+        for v in [a, b, c].iter() {
+            writeln!(buffer,
+                     "      vertex {:.6E} {:.6E} {:.6E}",
+                     v[0], v[1], v[2])?;
+        }
+        writeln!(buffer, "    endloop")?;
+        writeln!(buffer, "  endfacet")?;
+    }
+    writeln!(buffer, "endsolid bader")?;
+    Ok(())
+}
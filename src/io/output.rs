@@ -1,16 +1,28 @@
-use anyhow::Result;
+use crate::analysis::Moments;
+use crate::bond_paths::BondPath;
+use crate::critical_points::{self, CriticalPoint};
+use crate::error::Result;
 use std::fs::File;
 use std::io::Write;
 
-/// Create the partitioned charge files using an optional atom map to decide the format
-pub fn partitions_file(positions: Vec<(String, String, String)>,
-                       partitioned_density: &[Vec<f64>],
-                       partitioned_volume: &[f64],
-                       total_density: &[f64],
-                       total_volume: f64,
-                       distance: &[f64],
-                       atom_map: Option<&[usize]>)
-                       -> Result<String> {
+/// The names of the density columns for a number of densities, either the
+/// charge followed by the spin or, for any other number, numbered channels.
+pub fn density_names(density_num: usize) -> Vec<String> {
+    match density_num {
+        1 => vec![String::from("Charge")],
+        2 => vec![String::from("Charge"), String::from("Spin")],
+        4 => vec![String::from("Charge"),
+                  String::from("Spin X"),
+                  String::from("Spin Y"),
+                  String::from("Spin Z")],
+        n => (1..=n).map(|i| format!("Channel {}", i)).collect(),
+    }
+}
+
+/// Sum the partitioned densities and volumes over every partition.
+pub fn partition_totals(partitioned_density: &[Vec<f64>],
+                        partitioned_volume: &[f64])
+                        -> (Vec<f64>, f64) {
     // calculate the total density for each density supplied
     let total_partitioned_density =
         partitioned_density.iter().fold(vec![
@@ -26,7 +38,24 @@ pub fn partitions_file(positions: Vec<(String, String, String)>,
                                             sum
                                         });
     // the volume is the same for all densities
-    let total_partitioned_volume = partitioned_volume.iter().sum();
+    (total_partitioned_density, partitioned_volume.iter().sum())
+}
+
+/// Create the partitioned charge files using an optional atom map to decide the format
+#[allow(clippy::too_many_arguments)]
+pub fn partitions_file(positions: Vec<(String, String, String)>,
+                       partitioned_density: &[Vec<f64>],
+                       partitioned_volume: &[f64],
+                       total_density: &[f64],
+                       total_volume: f64,
+                       distance: &[f64],
+                       atom_map: Option<&[usize]>,
+                       density_names: &[String],
+                       species: Option<&[String]>,
+                       net_charge: Option<&[f64]>)
+                       -> Result<String> {
+    let (total_partitioned_density, total_partitioned_volume) =
+        partition_totals(partitioned_density, partitioned_volume);
     let vacuum_density = total_partitioned_density.iter()
                                                   .zip(total_density)
                                                   .map(|(a, b)| b - a)
@@ -34,8 +63,10 @@ pub fn partitions_file(positions: Vec<(String, String, String)>,
     let vacuum_volume = total_volume - total_partitioned_volume;
     // which charge file to write, if there's an atom_map -> BCF
     if let Some(atom_map) = atom_map {
-        let mut table =
-            Table::new(TableType::BaderCharge, partitioned_density[0].len());
+        let mut table = Table::new(TableType::BaderCharge,
+                                   density_names.to_vec(),
+                                   false,
+                                   false);
         let mut index: Vec<usize> = (0..atom_map.len()).collect();
         index.sort_by(|a, b| atom_map[*a].cmp(&atom_map[*b]));
         let mut atom_num = atom_map[index[0]];
@@ -47,6 +78,7 @@ pub fn partitions_file(positions: Vec<(String, String, String)>,
                                  atom_num = a
                              }
                              table.add_row(i,
+                                           None,
                                            positions[i].clone(),
                                            &partitioned_density[i],
                                            partitioned_volume[i],
@@ -58,15 +90,36 @@ pub fn partitions_file(positions: Vec<(String, String, String)>,
                             total_partitioned_volume))
     // if no atom_map -> ACF
     } else {
-        let mut table =
-            Table::new(TableType::AtomsCharge, partitioned_density[0].len());
+        let mut table = Table::new(TableType::AtomsCharge,
+                                   density_names.to_vec(),
+                                   species.is_some(),
+                                   net_charge.is_some());
+        // the net charge is written after the densities
+        let partitioned_density = match net_charge {
+            Some(net_charge) => partitioned_density.iter()
+                                                   .zip(net_charge)
+                                                   .map(|(d, n)| {
+                                                       let mut d = d.clone();
+                                                       d.push(*n);
+                                                       d
+                                                   })
+                                                   .collect(),
+            None => partitioned_density.to_vec(),
+        };
+        if let Some(species) = species {
+            table.element_totals = element_totals(species,
+                                                  &partitioned_density,
+                                                  partitioned_volume);
+        }
         let mut index = 1;
         positions.into_iter()
-                 .zip(partitioned_density)
+                 .zip(&partitioned_density)
                  .zip(partitioned_volume)
                  .zip(distance)
                  .for_each(|(((coord, density), volume), distance)| {
-                     table.add_row(index, coord, density, *volume, *distance);
+                     let element = species.map(|s| s[index - 1].as_str());
+                     table.add_row(index, element, coord, density, *volume,
+                                   *distance);
                      index += 1;
                  });
         Ok(table.get_string(&vacuum_density,
@@ -76,6 +129,315 @@ pub fn partitions_file(positions: Vec<(String, String, String)>,
     }
 }
 
+/// Sum the partitioned densities and volumes of the atoms of each element, in
+/// the order that the elements first appear.
+pub fn element_totals(species: &[String],
+                      partitioned_density: &[Vec<f64>],
+                      partitioned_volume: &[f64])
+                      -> Vec<(String, Vec<f64>, f64)> {
+    let mut totals: Vec<(String, Vec<f64>, f64)> = Vec::new();
+    species.iter()
+           .zip(partitioned_density)
+           .zip(partitioned_volume)
+           .for_each(|((s, density), volume)| {
+               match totals.iter_mut().find(|(e, _, _)| e == s) {
+                   Some((_, d, v)) => {
+                       d.iter_mut().zip(density).for_each(|(d, pd)| *d += pd);
+                       *v += volume;
+                   }
+                   None => totals.push((s.clone(), density.clone(), *volume)),
+               }
+           });
+    totals
+}
+
+/// Create the critical point file from the critical points and their formatted
+/// positions.
+pub fn critical_points_file(positions: Vec<(String, String, String)>,
+                            critical_points: &[CriticalPoint])
+                            -> Result<String> {
+    let rows = critical_points.iter()
+                              .zip(positions)
+                              .enumerate()
+                              .map(|(i, (cp, (x, y, z)))| {
+                                  vec![format!("{}", i + 1),
+                                       format!("{}", cp.kind),
+                                       x,
+                                       y,
+                                       z,
+                                       scientific_format(cp.density),
+                                       scientific_format(cp.laplacian),
+                                       format!("{:.6}", cp.ellipticity)]
+                              })
+                              .collect::<Vec<Vec<String>>>();
+    let mut table = simple_table(&["#",
+                                   "Type",
+                                   "X",
+                                   "Y",
+                                   "Z",
+                                   "Density",
+                                   "Laplacian",
+                                   "Ellipticity"],
+                                 &rows);
+    let [n, b, r, c] = critical_points::count(critical_points);
+    table.push_str(&format!(
+        "\n  Nuclear (3,-3): {:>14}\n  Bond (3,-1): {:>17}\n  Ring (3,+1): {:>17}\n  Cage (3,+3): {:>17}\n  Poincare-Hopf (n-b+r-c): {:>5}",
+        n,
+        b,
+        r,
+        c,
+        critical_points::poincare_hopf(critical_points)
+    ));
+    Ok(table)
+}
+
+/// Create the bond path file from the bond paths and their formatted images.
+pub fn bond_paths_file(images: Vec<String>,
+                       bond_paths: &[BondPath])
+                       -> Result<String> {
+    let rows = bond_paths.iter()
+                         .zip(images)
+                         .enumerate()
+                         .map(|(i, (bp, image))| {
+                             vec![format!("{}", i + 1),
+                                  format!("{}", bp.critical_point + 1),
+                                  format!("{}", bp.atoms[0] + 1),
+                                  format!("{}", bp.atoms[1] + 1),
+                                  image,
+                                  format!("{:.6}", bp.length),
+                                  scientific_format(bp.density)]
+                         })
+                         .collect::<Vec<Vec<String>>>();
+    let mut table = simple_table(&["#", "CP", "Atom I", "Atom J", "Image",
+                                   "Length", "Density"],
+                                 &rows);
+    table.push_str(&format!("\n  Bond Paths: {}", bond_paths.len()));
+    Ok(table)
+}
+
+/// Create the table of atomic multipole moments to append to the atoms charge
+/// file. The dipole and the off-diagonal quadrupole components, ordered
+/// [yz, xz, xy], have already been formatted into the file's axis order.
+pub fn moments_file(dipoles: Vec<(String, String, String)>,
+                    quadrupole_diagonals: Vec<(String, String, String)>,
+                    quadrupole_off_diagonals: Vec<(String, String, String)>,
+                    moments: &[Moments])
+                    -> Result<String> {
+    let rows = dipoles.into_iter()
+                      .zip(quadrupole_diagonals)
+                      .zip(quadrupole_off_diagonals)
+                      .zip(moments)
+                      .enumerate()
+                      .map(|(i, (((d, q_diag), q_off), m))| {
+                          vec![format!("{}", i + 1),
+                               d.0,
+                               d.1,
+                               d.2,
+                               format!("{:.6}", m.dipole_magnitude()),
+                               q_diag.0,
+                               q_diag.1,
+                               q_diag.2,
+                               q_off.2,
+                               q_off.1,
+                               q_off.0]
+                      })
+                      .collect::<Vec<Vec<String>>>();
+    Ok(simple_table(&["#", "Dipole X", "Dipole Y", "Dipole Z",
+                      "|Dipole|", "Qxx", "Qyy", "Qzz", "Qxy",
+                      "Qxz", "Qyz"],
+                    &rows))
+}
+
+/// Create the contact area file from the surface area of each atom and the
+/// contact-area matrix.
+pub fn contact_areas_file(surface_area: &[f64],
+                          contact_area: &[Vec<f64>])
+                          -> Result<String> {
+    let mut header = vec![String::from("#")];
+    header.extend((1..=surface_area.len()).map(|i| format!("{}", i)));
+    header.push(String::from("Surface"));
+    let rows = contact_area.iter()
+                           .zip(surface_area)
+                           .enumerate()
+                           .map(|(i, (row, area))| {
+                               let mut r = vec![format!("{}", i + 1)];
+                               r.extend(row.iter()
+                                           .map(|a| format!("{:.6}", a)));
+                               r.push(format!("{:.6}", area));
+                               r
+                           })
+                           .collect::<Vec<Vec<String>>>();
+    let header = header.iter().map(|h| h.as_str()).collect::<Vec<&str>>();
+    Ok(simple_table(&header, &rows))
+}
+
+/// The rule that separates the rows from the header and footer of the
+/// Henkelman group's charge files.
+const HENKELMAN_RULE: &str = " --------------------------------------------------------------------------------";
+
+/// Create the atomic charge file, ACF.dat, in the layout of the Henkelman
+/// group's bader program from the cartesian positions of the atoms and their
+/// partitioned charge.
+pub fn henkelman_atoms_file(positions: &[[f64; 3]],
+                            charge: &[f64],
+                            volume: &[f64],
+                            distance: &[f64],
+                            total_charge: f64,
+                            total_volume: f64)
+                            -> String {
+    let mut table = String::from(
+        "    #         X           Y           Z       CHARGE      MIN DIST   ATOMIC VOL\n",
+    );
+    table.push_str(HENKELMAN_RULE);
+    table.push('\n');
+    positions.iter()
+             .zip(charge)
+             .zip(volume)
+             .zip(distance)
+             .enumerate()
+             .for_each(|(i, (((p, c), v), d))| {
+                 table.push_str(&format!("{:>5}{:>12.6}{:>12.6}{:>12.6}{:>12.6}{:>13.6}{:>13.6}\n",
+                                         i + 1,
+                                         p[0],
+                                         p[1],
+                                         p[2],
+                                         c,
+                                         d,
+                                         v))
+             });
+    table.push_str(HENKELMAN_RULE);
+    table.push('\n');
+    table.push_str(&format!("    VACUUM CHARGE:{:>21.4}\n    VACUUM VOLUME:{:>21.4}\n    NUMBER OF ELECTRONS:{:>15.4}\n",
+                            total_charge - charge.iter().sum::<f64>(),
+                            total_volume - volume.iter().sum::<f64>(),
+                            total_charge));
+    table
+}
+
+/// The Bader volumes with a charge above the tolerance, the only ones that are
+/// written to the Henkelman group's files, these are numbered in order.
+fn henkelman_volumes(charge: &[f64], tolerance: f64) -> Vec<usize> {
+    (0..charge.len()).filter(|i| charge[*i] > tolerance)
+                     .collect()
+}
+
+/// Create the Bader charge file, BCF.dat, in the layout of the Henkelman
+/// group's bader program. Only the volumes with a charge above the tolerance
+/// are written.
+pub fn henkelman_bader_file(positions: &[[f64; 3]],
+                            charge: &[f64],
+                            atom_map: &[usize],
+                            distance: &[f64],
+                            tolerance: f64)
+                            -> String {
+    let mut table = String::from(
+        "    #         X           Y           Z       CHARGE     ATOM     DISTANCE\n",
+    );
+    table.push_str(HENKELMAN_RULE);
+    table.push('\n');
+    henkelman_volumes(charge, tolerance).into_iter()
+                                        .enumerate()
+                                        .for_each(|(n, i)| {
+                                            let p = positions[i];
+                                            table.push_str(&format!("{:>5}{:>12.6}{:>12.6}{:>12.6}{:>12.6}{:>9}{:>13.6}\n",
+                                                                    n + 1,
+                                                                    p[0],
+                                                                    p[1],
+                                                                    p[2],
+                                                                    charge[i],
+                                                                    atom_map[i] + 1,
+                                                                    distance[i]))
+                                        });
+    table.push_str(HENKELMAN_RULE);
+    table.push('\n');
+    table
+}
+
+/// Create the atomic volume file, AVF.dat, in the layout of the Henkelman
+/// group's bader program, listing the Bader volumes, as numbered in BCF.dat,
+/// that make up each atom.
+pub fn henkelman_volumes_file(charge: &[f64],
+                              atom_map: &[usize],
+                              atoms_num: usize,
+                              tolerance: f64)
+                              -> String {
+    let mut volumes = vec![Vec::new(); atoms_num];
+    henkelman_volumes(charge, tolerance).into_iter()
+                                        .enumerate()
+                                        .for_each(|(n, i)| {
+                                            volumes[atom_map[i]].push(n + 1)
+                                        });
+    let mut table = String::from("   Atom                     Volume(s)\n");
+    table.push_str(HENKELMAN_RULE);
+    table.push('\n');
+    volumes.iter().enumerate().for_each(|(i, v)| {
+                                  table.push_str(&format!("{:>6}      ",
+                                                          i + 1));
+                                  v.iter()
+                                   .for_each(|n| {
+                                       table.push_str(&format!("{:>5}", n))
+                                   });
+                                  table.push('\n');
+                              });
+    table.push_str(HENKELMAN_RULE);
+    table.push('\n');
+    table
+}
+
+/// Formats a float in scientific notation with a signed two digit exponent.
+fn scientific_format(float: f64) -> String {
+    let formatted = format!("{:.6E}", float);
+    match formatted.split_once('E') {
+        Some((mantissa, exponent)) => {
+            let exponent = exponent.parse::<i32>().unwrap_or(0);
+            format!("{}E{:+03}", mantissa, exponent)
+        }
+        None => formatted,
+    }
+}
+
+/// Formats a header and rows into a table with the same layout as the charge
+/// files, with a closing separator.
+fn simple_table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut column_width = header.iter().map(|h| h.len()).collect::<Vec<_>>();
+    rows.iter().for_each(|row| {
+                   row.iter()
+                      .zip(column_width.iter_mut())
+                      .for_each(|(col, w)| *w = (*w).max(col.len()))
+               });
+    let mut separator = String::new();
+    column_width.iter().for_each(|w| {
+                           separator.push_str(&format!("-{:-^width$}-+",
+                                                       "-",
+                                                       width = w));
+                       });
+    separator.pop();
+    separator.pop();
+    let mut table = String::new();
+    let mut line = String::new();
+    header.iter().zip(&column_width).for_each(|(h, w)| {
+                                        line.push_str(&format!(" {:^width$} |",
+                                                               h,
+                                                               width = w))
+                                    });
+    line.pop();
+    table.push_str(line.trim_end());
+    table.push('\n');
+    table.push_str(&separator);
+    table.push('\n');
+    rows.iter().for_each(|row| {
+                   let mut line = String::new();
+                   row.iter().zip(&column_width).for_each(|(s, w)| {
+                       line.push_str(&format!(" {:>width$} |", s, width = w))
+                   });
+                   line.pop();
+                   table.push_str(&line);
+                   table.push('\n');
+               });
+    table.push_str(&separator);
+    table
+}
+
 /// Enum of available tables.
 pub enum TableType {
     /// Table for the ACF file.
@@ -88,8 +450,14 @@ pub enum TableType {
 struct Table {
     /// How wide each column is.
     column_width: Vec<usize>,
-    /// The number of charge and spin densities.
-    density_num: usize,
+    /// The names of the density columns.
+    density_names: Vec<String>,
+    /// Whether there is a column for the element of each atom.
+    elements: bool,
+    /// Whether there is a column for the net charge of each atom.
+    net_charge: bool,
+    /// The densities and volume summed over each element, for the footer.
+    element_totals: Vec<(String, Vec<f64>, f64)>,
     /// The rows of the table as a vector of strings.
     rows: Vec<Vec<String>>,
     separators: Vec<usize>,
@@ -99,23 +467,25 @@ struct Table {
 
 impl Table {
     /// Creates a new structure and sets the minimum widths of each.
-    fn new(table_type: TableType, density_num: usize) -> Self {
+    fn new(table_type: TableType,
+           density_names: Vec<String>,
+           elements: bool,
+           net_charge: bool)
+           -> Self {
         let rows = vec![Vec::with_capacity(0)];
-        let mut column_width = Vec::with_capacity(6 + density_num);
+        let mut column_width = Vec::with_capacity(7 + density_names.len());
         column_width.push(1);
+        if elements {
+            column_width.push(7);
+        }
         column_width.push(1);
         column_width.push(1);
         column_width.push(1);
-        column_width.push(6);
-        match density_num.cmp(&2) {
-            std::cmp::Ordering::Greater => {
-                column_width.push(6);
-                column_width.push(6);
-                column_width.push(6);
-            }
-            std::cmp::Ordering::Equal => column_width.push(6),
-            std::cmp::Ordering::Less => (),
-        };
+        density_names.iter()
+                     .for_each(|name| column_width.push(name.len().max(6)));
+        if net_charge {
+            column_width.push(10);
+        }
         column_width.push(6);
         column_width.push(8);
         let separators = match table_type {
@@ -123,7 +493,10 @@ impl Table {
             TableType::BaderCharge => vec![],
         };
         Self { column_width,
-               density_num,
+               density_names,
+               elements,
+               net_charge,
+               element_totals: Vec::with_capacity(0),
                rows,
                separators,
                table_type }
@@ -132,12 +505,17 @@ impl Table {
     /// Adds a row the table.
     fn add_row(&mut self,
                index: usize,
+               element: Option<&str>,
                p: (String, String, String),
                density: &[f64],
                volume: f64,
                distance: f64) {
-        let mut row: Vec<String> = Vec::with_capacity(6 + self.density_num);
+        let mut row: Vec<String> =
+            Vec::with_capacity(7 + self.density_names.len());
         row.push(format!("{}", index));
+        if let Some(element) = element {
+            row.push(String::from(element));
+        }
         row.push(p.0);
         row.push(p.1);
         row.push(p.2);
@@ -166,37 +544,43 @@ impl Table {
         match self.table_type {
             TableType::AtomsCharge => {
                 let mut separator = self.format_separator(0);
-                let footer = match self.density_num.cmp(&2) {
-                        std::cmp::Ordering::Less => format!(
-                            "\n  Vacuum Charge: {:>18.4}\n  Vacuum Volume: {:>18.4}\n  Partitioned Charge: {:>13.4}\n  Partitioned Volume: {:>13.4}",
-                            vacuum_density[0],
-                            vacuum_volume,
-                            partitioned_density[0],
-                            partitioned_volume,
-                        ),
-                        std::cmp::Ordering::Equal =>  format!(
-                            "\n  Vacuum Charge: {:>18.4}\n  Vacuum Spin: {:>20.4}\n  Vacuum Volume: {:>18.4}\n  Partitioned Charge: {:>13.4}\n  Partitioned Spin: {:>15.4}\n  Partitioned Volume: {:>13.4}",
-                            vacuum_density[0],
-                            vacuum_density[1],
-                            vacuum_volume,
-                            partitioned_density[0],
-                            partitioned_density[1],
-                            partitioned_volume,
-                        ),
-                        std::cmp::Ordering::Greater =>  format!(
-                            "\n  Vacuum Charge: {:>18.4}\n  Vacuum Spin X: {:>18.4}\n  Vacuum Spin Y: {:>18.4}\n  Vacuum Spin Z: {:>18.4}\n  Vacuum Volume: {:>18.4}\n  Partitioned Charge: {:>13.4}\n  Partitioned Spin X: {:>13.4}\n  Partitioned Spin Y: {:>13.4}\n  Partitioned Spin Z: {:>13.4}\n  Partitioned Volume: {:>13.4}",
-                            vacuum_density[0],
-                            vacuum_density[1],
-                            vacuum_density[2],
-                            vacuum_density[3],
-                            vacuum_volume,
-                            partitioned_density[0],
-                            partitioned_density[1],
-                            partitioned_density[2],
-                            partitioned_density[3],
-                            partitioned_volume,
-                        ),
+                // the values are aligned to the same column
+                let line = |label: String, value: f64| {
+                    format!("\n  {}: {:>width$.4}",
+                            label,
+                            value,
+                            width = 31usize.saturating_sub(label.len()).max(1))
                 };
+                let mut footer = String::new();
+                self.density_names
+                    .iter()
+                    .zip(vacuum_density)
+                    .for_each(|(name, d)| {
+                        footer.push_str(&line(format!("Vacuum {}", name), *d))
+                    });
+                footer.push_str(&line(String::from("Vacuum Volume"),
+                                      vacuum_volume));
+                self.density_names
+                    .iter()
+                    .zip(partitioned_density)
+                    .for_each(|(name, d)| {
+                        footer.push_str(&line(format!("Partitioned {}", name),
+                                              *d))
+                    });
+                footer.push_str(&line(String::from("Partitioned Volume"),
+                                      partitioned_volume));
+                let names = self.density_names
+                                .iter()
+                                .map(|name| name.as_str())
+                                .chain(self.net_charge.then_some("Net Charge"))
+                                .collect::<Vec<&str>>();
+                self.element_totals.iter().for_each(|(element, d, v)| {
+                    names.iter().zip(d).for_each(|(name, d)| {
+                        footer.push_str(&line(format!("{} {}", element, name),
+                                              *d))
+                    });
+                    footer.push_str(&line(format!("{} Volume", element), *v));
+                });
                 separator.push_str(&footer);
                 separator
             }
@@ -211,6 +595,11 @@ impl Table {
         header.push_str(&format!(" {:^width$} |",
                                  "#",
                                  width = iter.next().unwrap()));
+        if self.elements {
+            header.push_str(&format!(" {:^width$} |",
+                                     "Element",
+                                     width = iter.next().unwrap()));
+        }
         header.push_str(&format!(" {:^width$} |",
                                  "X",
                                  width = iter.next().unwrap()));
@@ -220,31 +609,15 @@ impl Table {
         header.push_str(&format!(" {:^width$} |",
                                  "Z",
                                  width = iter.next().unwrap()));
-        header.push_str(&format!(" {:^width$} |",
-                                 "Charge",
-                                 width = iter.next().unwrap()));
-        match self.table_type {
-            TableType::AtomsCharge | TableType::BaderCharge => {
-                match self.density_num.cmp(&2) {
-                    std::cmp::Ordering::Equal => {
-                        header.push_str(&format!(" {:^width$} |",
-                                                 "Spin",
-                                                 width = iter.next().unwrap()));
-                    }
-                    std::cmp::Ordering::Greater => {
-                        header.push_str(&format!(" {:^width$} |",
-                                                 "Spin X",
-                                                 width = iter.next().unwrap()));
-                        header.push_str(&format!(" {:^width$} |",
-                                                 "Spin Y",
-                                                 width = iter.next().unwrap()));
-                        header.push_str(&format!(" {:^width$} |",
-                                                 "Spin Z",
-                                                 width = iter.next().unwrap()));
-                    }
-                    std::cmp::Ordering::Less => (),
-                }
-            }
+        for name in self.density_names.iter() {
+            header.push_str(&format!(" {:^width$} |",
+                                     name,
+                                     width = iter.next().unwrap()));
+        }
+        if self.net_charge {
+            header.push_str(&format!(" {:^width$} |",
+                                     "Net Charge",
+                                     width = iter.next().unwrap()));
         }
         header.push_str(&format!(" {:^width$} |",
                                  "Volume",
@@ -314,3 +687,130 @@ pub fn write(string: String, filename: String) -> std::io::Result<()> {
     bader_file.write_all(string.as_bytes())?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atoms::{Atoms, Lattice};
+    use crate::calculation::BaderCalculation;
+
+    #[test]
+    fn output_partitions_file_species() {
+        let positions = (0..3).map(|_| {
+                                  (String::from("0.0"),
+                                   String::from("0.0"),
+                                   String::from("0.0"))
+                              })
+                              .collect();
+        let species =
+            vec![String::from("O"), String::from("H"), String::from("H")];
+        let file = partitions_file(positions,
+                                   &[vec![8.5], vec![0.75], vec![0.5]],
+                                   &[10., 2., 3.],
+                                   &[10.],
+                                   16.,
+                                   &[0.; 3],
+                                   None,
+                                   &[String::from("Charge")],
+                                   Some(&species),
+                                   Some(&[-0.5, 0.25, 0.5])).unwrap();
+        let lines = file.lines().collect::<Vec<&str>>();
+        assert!(lines[0].starts_with(" # | Element |"));
+        assert!(lines[0].contains("| Net Charge |"));
+        assert!(lines[3].starts_with(" 2 |       H |"));
+        assert!(lines[3].contains("|   0.250000 |"));
+        assert_eq!(lines[10], "  O Charge:                  8.5000");
+        assert_eq!(lines[11], "  O Net Charge:             -0.5000");
+        assert_eq!(lines[12], "  O Volume:                 10.0000");
+        assert_eq!(lines[13], "  H Charge:                  1.2500");
+        assert_eq!(lines[14], "  H Net Charge:              0.7500");
+        assert_eq!(lines[15], "  H Volume:                  5.0000");
+    }
+
+    #[test]
+    fn output_henkelman_atoms_file() {
+        let file = henkelman_atoms_file(&[[0., 0., 0.], [1.5, 1.5, 1.5]],
+                                        &[3.25, 4.5],
+                                        &[10., 12.],
+                                        &[1.1, 0.9],
+                                        8.,
+                                        23.);
+        let lines = file.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 8);
+        assert_eq!(lines[2],
+                   "    1    0.000000    0.000000    0.000000    3.250000     1.100000    10.000000");
+        assert_eq!(lines[3],
+                   "    2    1.500000    1.500000    1.500000    4.500000     0.900000    12.000000");
+        assert_eq!(lines[5], "    VACUUM CHARGE:               0.2500");
+        assert_eq!(lines[6], "    VACUUM VOLUME:               1.0000");
+        assert_eq!(lines[7], "    NUMBER OF ELECTRONS:         8.0000");
+        assert!(file.ends_with('\n'))
+    }
+
+    #[test]
+    fn output_henkelman_bader_file() {
+        let file = henkelman_bader_file(&[[0.; 3], [1.; 3], [2.; 3]],
+                                        &[3., 1E-8, 2.],
+                                        &[1, 0, 0],
+                                        &[0.1, 0.2, 0.3],
+                                        1E-6);
+        let lines = file.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[2],
+                   "    1    0.000000    0.000000    0.000000    3.000000        2     0.100000");
+        assert_eq!(lines[3],
+                   "    2    2.000000    2.000000    2.000000    2.000000        1     0.300000");
+    }
+
+    #[test]
+    fn output_henkelman_bader_file_partition() {
+        // a peak at each atom, on a grid with 0.5 voxels
+        let lattice = [[3., 0., 0.], [0., 3., 0.], [0., 0., 6.]];
+        let atoms = Atoms::new(Lattice::new(lattice).unwrap(),
+                               vec![[1.5, 1.5, 1.5], [1.5, 1.5, 4.5]],
+                               String::new()).unwrap();
+        let r = |c: usize, o: usize| (c as f64 - o as f64).powi(2);
+        let densities = vec![(0..432).map(|p| {
+                                         let (x, y, z) =
+                                             (p / 72, p / 12 % 6, p % 12);
+                                         (-(r(x, 3) + r(y, 3) + r(z, 3))).exp()
+                             + (-(r(x, 3) + r(y, 3) + r(z, 9))).exp()
+                                     })
+                                     .collect::<Vec<f64>>()];
+        let result = BaderCalculation::new(&densities,
+                                           &atoms,
+                                           [6, 6, 12],
+                                           [0., 0., 0.]).with_threads(1)
+                                                        .with_volumes(true)
+                                                        .run()
+                                                        .unwrap();
+        let charge = result.volumes
+                           .as_ref()
+                           .unwrap()
+                           .density
+                           .iter()
+                           .map(|d| d[0])
+                           .collect::<Vec<f64>>();
+        let file = henkelman_bader_file(&result.maxima_positions(),
+                                        &charge,
+                                        &result.atom_map,
+                                        &result.minimum_distance,
+                                        1E-6);
+        let lines = file.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[2].starts_with("    1    1.500000    1.500000    1.500000"));
+        assert!(lines[2].ends_with("        1     0.000000"));
+        assert!(lines[3].starts_with("    2    1.500000    1.500000    4.500000"));
+        assert!(lines[3].ends_with("        2     0.000000"));
+    }
+
+    #[test]
+    fn output_henkelman_volumes_file() {
+        let file =
+            henkelman_volumes_file(&[3., 1E-8, 2., 1.], &[1, 0, 0, 1], 3, 1E-6);
+        let lines = file.lines().collect::<Vec<&str>>();
+        assert_eq!(lines[2], "     1          2");
+        assert_eq!(lines[3], "     2          1    3");
+        assert_eq!(lines[4], "     3      ");
+    }
+}
@@ -0,0 +1,436 @@
+use crate::atoms::{Atoms, Lattice};
+use crate::error::{Error, Result};
+use crate::io::{reader, FileFormat, ReadFunction};
+use crate::progress::Bar;
+use crate::utils;
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
+
+/// Convert from Rydberg atomic units.
+const LENGTH_UNITS: f64 = 0.52917721067;
+/// Convert from Rydberg atomic units.
+const VOLUME_UNITS: f64 = LENGTH_UNITS * LENGTH_UNITS * LENGTH_UNITS;
+
+/// The Quantum ESPRESSO filplot format, as written by pp.x, for
+/// reading/writing densities.
+pub struct Qe {}
+
+/// The information from the second line of the header.
+struct Dimensions {
+    /// The leading dimensions of the density array, nr1x, nr2x, nr3x.
+    array: [usize; 3],
+    /// The size of the FFT grid, nr1, nr2, nr3.
+    grid: [usize; 3],
+    /// The number of atoms.
+    nat: usize,
+    /// The number of species.
+    ntyp: usize,
+}
+
+/// The error for a header that ends too soon.
+fn unexpected_end() -> Error {
+    Error::Parse(String::from("Unexpected end of Quantum ESPRESSO header."))
+}
+
+/// Parses a line of whitespace separated values, returning the line in the
+/// error on failure.
+fn parse_line<T: std::str::FromStr>(line: Option<&str>) -> Result<Vec<T>> {
+    let line = line.ok_or_else(unexpected_end)?;
+    line.split_whitespace()
+        .map(|x| {
+            x.parse::<T>().map_err(|_| {
+                               Error::Parse(format!("Cannot parse ({}) in line:\n{}", x, line))
+                           })
+        })
+        .collect()
+}
+
+/// Reads the dimensions from the second line of the header.
+fn dimensions(line: Option<&str>) -> Result<Dimensions> {
+    let dims = parse_line::<usize>(line)?;
+    if dims.len() < 8 {
+        return Err(Error::Parse(String::from("Cannot read the dimensions of \
+                                              Quantum ESPRESSO file.")));
+    }
+    Ok(Dimensions { array: [dims[0], dims[1], dims[2]],
+                    grid: [dims[3], dims[4], dims[5]],
+                    nat: dims[6],
+                    ntyp: dims[7] })
+}
+
+/// Number of lines in the header: the title, dimensions, ibrav and celldm,
+/// the lattice vectors if ibrav = 0, the cutoffs, the species and the atoms.
+fn header_length(text: &str) -> Result<usize> {
+    let mut lines = text.lines();
+    let _ = lines.next();
+    let dims = dimensions(lines.next())?;
+    let ibrav = parse_line::<f64>(lines.next())?;
+    let lattice_lines = match ibrav.first() {
+        Some(ibrav) if *ibrav as isize == 0 => 3,
+        Some(_) => 0,
+        None => return Err(unexpected_end()),
+    };
+    Ok(4 + lattice_lines + dims.ntyp + dims.nat)
+}
+
+/// Builds the lattice vectors, in Bohr, from ibrav and celldm following the
+/// conventions of Quantum ESPRESSO's latgen.
+pub fn ibrav_lattice(ibrav: isize,
+                     celldm: [f64; 6],
+                     at: Option<[[f64; 3]; 3]>)
+                     -> Result<[[f64; 3]; 3]> {
+    let a = celldm[0];
+    let b = a * celldm[1];
+    let c = a * celldm[2];
+    let lattice = match ibrav {
+        0 => {
+            let at = at.ok_or_else(|| {
+                           Error::Parse(String::from("ibrav = 0 requires \
+                                                      lattice vectors."))
+                       })?;
+            let mut lattice = at;
+            lattice.iter_mut().flatten().for_each(|x| *x *= a);
+            lattice
+        }
+        1 => [[a, 0., 0.], [0., a, 0.], [0., 0., a]],
+        2 => [[-a / 2., 0., a / 2.],
+              [0., a / 2., a / 2.],
+              [-a / 2., a / 2., 0.]],
+        3 => [[a / 2., a / 2., a / 2.],
+              [-a / 2., a / 2., a / 2.],
+              [-a / 2., -a / 2., a / 2.]],
+        -3 => [[-a / 2., a / 2., a / 2.],
+               [a / 2., -a / 2., a / 2.],
+               [a / 2., a / 2., -a / 2.]],
+        4 => [[a, 0., 0.],
+              [-a / 2., a * 3f64.sqrt() / 2., 0.],
+              [0., 0., c]],
+        5 | -5 => {
+            let cos_gamma = celldm[3];
+            let tx = ((1. - cos_gamma) / 2.).sqrt();
+            let ty = ((1. - cos_gamma) / 6.).sqrt();
+            let tz = ((1. + 2. * cos_gamma) / 3.).sqrt();
+            if ibrav == 5 {
+                [[a * tx, -a * ty, a * tz],
+                 [0., 2. * a * ty, a * tz],
+                 [-a * tx, -a * ty, a * tz]]
+            } else {
+                let a_prime = a / 3f64.sqrt();
+                let u = tz - 2. * 2f64.sqrt() * ty;
+                let v = tz + 2f64.sqrt() * ty;
+                [[a_prime * u, a_prime * v, a_prime * v],
+                 [a_prime * v, a_prime * u, a_prime * v],
+                 [a_prime * v, a_prime * v, a_prime * u]]
+            }
+        }
+        6 => [[a, 0., 0.], [0., a, 0.], [0., 0., c]],
+        7 => [[a / 2., -a / 2., c / 2.],
+              [a / 2., a / 2., c / 2.],
+              [-a / 2., -a / 2., c / 2.]],
+        8 => [[a, 0., 0.], [0., b, 0.], [0., 0., c]],
+        9 => [[a / 2., b / 2., 0.], [-a / 2., b / 2., 0.], [0., 0., c]],
+        -9 => [[a / 2., -b / 2., 0.], [a / 2., b / 2., 0.], [0., 0., c]],
+        91 => [[a, 0., 0.], [0., b / 2., -c / 2.], [0., b / 2., c / 2.]],
+        10 => [[a / 2., 0., c / 2.],
+               [a / 2., b / 2., 0.],
+               [0., b / 2., c / 2.]],
+        11 => [[a / 2., b / 2., c / 2.],
+               [-a / 2., b / 2., c / 2.],
+               [-a / 2., -b / 2., c / 2.]],
+        12 => {
+            let cos_gamma = celldm[3];
+            let sin_gamma = (1. - cos_gamma * cos_gamma).sqrt();
+            [[a, 0., 0.], [b * cos_gamma, b * sin_gamma, 0.], [0., 0., c]]
+        }
+        -12 => {
+            let cos_beta = celldm[4];
+            let sin_beta = (1. - cos_beta * cos_beta).sqrt();
+            [[a, 0., 0.], [0., b, 0.], [c * cos_beta, 0., c * sin_beta]]
+        }
+        13 => {
+            let cos_gamma = celldm[3];
+            let sin_gamma = (1. - cos_gamma * cos_gamma).sqrt();
+            [[a / 2., 0., -c / 2.],
+             [b * cos_gamma, b * sin_gamma, 0.],
+             [a / 2., 0., c / 2.]]
+        }
+        -13 => {
+            let cos_beta = celldm[4];
+            let sin_beta = (1. - cos_beta * cos_beta).sqrt();
+            [[a / 2., b / 2., 0.],
+             [-a / 2., b / 2., 0.],
+             [c * cos_beta, 0., c * sin_beta]]
+        }
+        14 => {
+            let cos_alpha = celldm[3];
+            let cos_beta = celldm[4];
+            let cos_gamma = celldm[5];
+            let sin_gamma = (1. - cos_gamma * cos_gamma).sqrt();
+            let volume = (1. + 2. * cos_alpha * cos_beta * cos_gamma
+                          - cos_alpha * cos_alpha
+                          - cos_beta * cos_beta
+                          - cos_gamma * cos_gamma)
+                                                  .sqrt();
+            [[a, 0., 0.],
+             [b * cos_gamma, b * sin_gamma, 0.],
+             [c * cos_beta,
+              c * (cos_alpha - cos_beta * cos_gamma) / sin_gamma,
+              c * volume / sin_gamma]]
+        }
+        x => return Err(Error::UnsupportedFormat(format!("ibrav = {}.", x))),
+    };
+    Ok(lattice)
+}
+
+impl FileFormat for Qe {
+    /// Read a Quantum ESPRESSO filplot density.
+    fn read_from(&self,
+                 source: &mut dyn BufRead,
+                 name: &str,
+                 _threads: usize)
+                 -> ReadFunction {
+        // the voxel origin in filplot files is (0, 0, 0)
+        let voxel_origin = [0f64; 3];
+        println!("Reading {} as Quantum ESPRESSO format:", name);
+        let text = reader::source_to_string(source)?;
+        // split the header from the density
+        let header_length = header_length(&text)?;
+        let mut start = 0;
+        for _ in 0..header_length {
+            start += match text[start..].find('\n') {
+                Some(i) => i + 1,
+                None => return Err(unexpected_end()),
+            };
+        }
+        let dims = dimensions(text.lines().nth(1))?;
+        let atoms = self.to_atoms(text[..start].to_string())?;
+        let density_raw =
+            text[start..].split_whitespace()
+                         .map(|x| {
+                             x.parse::<f64>().map_err(|_| {
+                                                 Error::Parse(format!("Cannot parse ({}) as a number.", x))
+                                             })
+                         })
+                         .collect::<Result<Vec<f64>>>()?;
+        let [nr1x, nr2x, _] = dims.array;
+        let [nr1, nr2, nr3] = dims.grid;
+        if density_raw.len() < nr1x * nr2x * nr3 {
+            return Err(Error::Parse(String::from("Quantum ESPRESSO density is \
+                                                  shorter than the grid.")));
+        }
+        // drop any padding of the leading dimensions and convert out of Bohr
+        let mut density = Vec::with_capacity(nr1 * nr2 * nr3);
+        for k in 0..nr3 {
+            for j in 0..nr2 {
+                let row = (k * nr2x + j) * nr1x;
+                density.extend(density_raw[row..(row + nr1)].iter()
+                                                           .map(|x| x / VOLUME_UNITS));
+            }
+        }
+        // flip the grid points as the density is stored density[z, y, x]
+        let grid_pts: [usize; 3] = [nr3, nr2, nr1];
+        println!("File read successfully.");
+        Ok((voxel_origin, grid_pts, atoms, vec![density]))
+    }
+
+    /// Read atom information.
+    fn to_atoms(&self, atoms_text: String) -> Result<Atoms> {
+        let mut lines = atoms_text.lines();
+        // skip the title
+        let _ = lines.next();
+        let dims = dimensions(lines.next())?;
+        let cell = parse_line::<f64>(lines.next())?;
+        if cell.len() < 7 {
+            return Err(Error::Parse(String::from("Cannot read ibrav and celldm \
+                                                  of Quantum ESPRESSO file.")));
+        }
+        let ibrav = cell[0] as isize;
+        let celldm = [cell[1], cell[2], cell[3], cell[4], cell[5], cell[6]];
+        let at = if ibrav == 0 {
+            let mut at = [[0f64; 3]; 3];
+            for a in at.iter_mut() {
+                let v = parse_line::<f64>(lines.next())?;
+                if v.len() < 3 {
+                    return Err(Error::Parse(String::from("Expected a lattice \
+                                                          vector in Quantum \
+                                                          ESPRESSO header.")));
+                }
+                *a = [v[0], v[1], v[2]];
+            }
+            Some(at)
+        } else {
+            None
+        };
+        // skip the cutoffs and plot number
+        let _ = lines.next();
+        // the species table: index, label and valence
+        let species =
+            (0..dims.ntyp).map(|_| match lines.next() {
+                              Some(line) => Ok(line.split_whitespace()
+                                                   .nth(1)
+                                                   .unwrap_or("X")
+                                                   .to_string()),
+                              None => Err(unexpected_end()),
+                          })
+                          .collect::<Result<Vec<String>>>()?;
+        let lattice = ibrav_lattice(ibrav, celldm, at)?;
+        let alat = celldm[0] * LENGTH_UNITS;
+        // density[z, y, x] so lets swap the c and a
+        let [a, b, c] = lattice;
+        let lattice = Lattice::new([[c[2] * LENGTH_UNITS,
+                                     c[1] * LENGTH_UNITS,
+                                     c[0] * LENGTH_UNITS],
+                                    [b[2] * LENGTH_UNITS,
+                                     b[1] * LENGTH_UNITS,
+                                     b[0] * LENGTH_UNITS],
+                                    [a[2] * LENGTH_UNITS,
+                                     a[1] * LENGTH_UNITS,
+                                     a[0] * LENGTH_UNITS]])?;
+        let mut positions: Vec<[f64; 3]> = Vec::with_capacity(dims.nat);
+        for _ in 0..dims.nat {
+            let line = parse_line::<f64>(lines.next())?;
+            if line.len() < 5 {
+                return Err(Error::Parse(String::from("Cannot read atom from \
+                                                      Quantum ESPRESSO \
+                                                      header.")));
+            }
+            let ityp = line[4] as usize;
+            if ityp == 0 || ityp > species.len() {
+                return Err(Error::Parse(format!("Atom has species {} but only \
+                                                 {} are defined.",
+                                                ityp,
+                                                species.len())));
+            }
+            // tau is cartesian in units of alat, wrap it back into the cell
+            let p =
+                utils::dot([line[3] * alat, line[2] * alat, line[1] * alat],
+                           lattice.to_fractional);
+            positions.push(utils::dot([p[0].rem_euclid(1f64),
+                                       p[1].rem_euclid(1f64),
+                                       p[2].rem_euclid(1f64)],
+                                      lattice.to_cartesian));
+        }
+        Atoms::new(lattice, positions, atoms_text)
+    }
+
+    /// Write a filplot file from a vector of options where None will be
+    /// written as zero.
+    fn write(&self,
+             atoms: &Atoms,
+             data: Vec<Option<f64>>,
+             filename: String,
+             pbar: Bar)
+             -> Result<()> {
+        let filename = format!("{}.pp", filename);
+        let mut buffer = BufWriter::new(File::create(filename)?);
+        pbar.set_length(data.len() / 5 + (data.len() % 5 != 0) as usize);
+        // the density is written without padding so the leading dimensions
+        // of the array have to match the grid
+        let mut lines = atoms.text.lines();
+        writeln!(buffer, "{}", lines.next().unwrap_or(""))?;
+        let dims = dimensions(lines.next())?;
+        writeln!(buffer,
+                 "{:>8}{:>8}{:>8}{:>8}{:>8}{:>8}{:>8}{:>8}",
+                 dims.grid[0],
+                 dims.grid[1],
+                 dims.grid[2],
+                 dims.grid[0],
+                 dims.grid[1],
+                 dims.grid[2],
+                 dims.nat,
+                 dims.ntyp)?;
+        for line in lines {
+            writeln!(buffer, "{}", line)?;
+        }
+        let mult = self.write_scale(atoms);
+        data.chunks(5).try_for_each(|line| -> std::io::Result<()> {
+                           let line =
+                               line.iter()
+                                   .map(|f| {
+                                       fortran_exponential(f.unwrap_or(0.)
+                                                           * mult)
+                                   })
+                                   .collect::<String>();
+                           writeln!(buffer, "{}", line)?;
+                           pbar.tick();
+                           Ok(())
+                       })?;
+        Ok(())
+    }
+
+    /// Deals with fortran indexing.
+    fn coordinate_format(&self, coords: [f64; 3]) -> (String, String, String) {
+        let z = format!("{:.6}", coords[0]);
+        let y = format!("{:.6}", coords[1]);
+        let x = format!("{:.6}", coords[2]);
+        (x, y, z)
+    }
+
+    /// The density is stored in bohr^-3.
+    fn write_scale(&self, _atoms: &Atoms) -> f64 {
+        VOLUME_UNITS
+    }
+
+    /// Deals with fortran indexing.
+    fn image_format(&self, image: [isize; 3]) -> String {
+        format!("({}, {}, {})", image[2], image[1], image[0])
+    }
+
+    /// Deals with fortran indexing.
+    fn axes(&self) -> [usize; 3] {
+        [2, 1, 0]
+    }
+}
+
+/// Formats a float as fortran's 1pe17.9, with a two digit signed exponent.
+fn fortran_exponential(float: f64) -> String {
+    let formatted = format!("{:.9E}", float);
+    let formatted = match formatted.split_once('E') {
+        Some((mantissa, exponent)) => {
+            let exponent = exponent.parse::<i32>().unwrap_or(0);
+            format!("{}E{:+03}", mantissa, exponent)
+        }
+        None => formatted,
+    };
+    format!("{:>17}", formatted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn qe_ibrav_fcc() {
+        let lattice =
+            ibrav_lattice(2, [10., 0., 0., 0., 0., 0.], None).unwrap();
+        assert_eq!(lattice, [[-5., 0., 5.], [0., 5., 5.], [-5., 5., 0.]]);
+    }
+
+    #[test]
+    fn qe_ibrav_hexagonal() {
+        let lattice =
+            ibrav_lattice(4, [2., 0., 1.5, 0., 0., 0.], None).unwrap();
+        assert_eq!(lattice[0], [2., 0., 0.]);
+        assert!((lattice[1][1] - 3f64.sqrt()).abs() < 1E-12);
+        assert_eq!(lattice[2], [0., 0., 3.]);
+    }
+
+    #[test]
+    fn qe_ibrav_triclinic_orthogonal() {
+        let lattice =
+            ibrav_lattice(14, [2., 1.5, 2., 0., 0., 0.], None).unwrap();
+        assert_eq!(lattice, [[2., 0., 0.], [0., 3., 0.], [0., 0., 4.]]);
+    }
+
+    #[test]
+    fn qe_fortran_exponential() {
+        assert_eq!(fortran_exponential(0.123456789), "  1.234567890E-01");
+        assert_eq!(fortran_exponential(-2.), " -2.000000000E+00");
+    }
+
+    #[test]
+    fn qe_ibrav_unsupported() {
+        let lattice = ibrav_lattice(15, [1., 0., 0., 0., 0., 0.], None);
+        assert!(matches!(lattice, Err(Error::UnsupportedFormat(_))));
+    }
+}
@@ -1,16 +1,273 @@
+use crate::error::{Error, Result};
+use bzip2::bufread::MultiBzDecoder;
+use crossbeam_utils::thread;
+use flate2::bufread::MultiGzDecoder;
+use memmap2::Mmap;
 use std::fs::File;
 use std::io::{self, prelude::*};
+use std::ops::Deref;
+use std::path::Path;
+use xz2::bufread::XzDecoder;
+
+/// The compression of a file, detected from the magic bytes at its start.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    /// A plain, uncompressed file.
+    None,
+    /// gzip, .gz.
+    Gzip,
+    /// xz, .xz.
+    Xz,
+    /// bzip2, .bz2.
+    Bzip2,
+    /// Zstandard, .zst.
+    Zstd,
+}
+
+impl Compression {
+    /// Detects the compression from the first bytes of a file.
+    pub fn detect(magic: &[u8]) -> Self {
+        if magic.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Compression::Xz
+        } else if magic.starts_with(b"BZh") {
+            Compression::Bzip2
+        } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+}
+
+/// The file extensions of the compressed formats.
+const EXTENSIONS: [&str; 5] = [".gz", ".xz", ".bz2", ".zst", ".zstd"];
+
+/// Removes any compression extension from a filename, CHGCAR.gz -> CHGCAR.
+pub fn strip_compression(filename: &str) -> &str {
+    EXTENSIONS.iter()
+              .find_map(|ext| {
+                  let start = filename.len().checked_sub(ext.len())?;
+                  match filename.get(start..) {
+                      Some(end) if end.eq_ignore_ascii_case(ext) => {
+                          filename.get(..start)
+                      }
+                      _ => None,
+                  }
+              })
+              .unwrap_or(filename)
+}
+
+/// The filename that reads from stdin rather than a file.
+pub const STDIN: &str = "-";
+
+/// Whether the path is [`STDIN`].
+fn is_stdin(path: &Path) -> bool {
+    path == Path::new(STDIN)
+}
+
+/// Opens a file for reading, or stdin if the path is [`STDIN`], without
+/// decompressing it.
+pub fn open_source(path: impl AsRef<Path>) -> io::Result<Box<dyn BufRead>> {
+    if is_stdin(path.as_ref()) {
+        Ok(Box::new(io::BufReader::new(io::stdin())))
+    } else {
+        Ok(Box::new(io::BufReader::new(File::open(path)?)))
+    }
+}
+
+/// Wraps a source, decompressing it on the fly if it is compressed, and
+/// returns the compression that was found.
+pub fn decompress<'a, R>(mut source: R)
+                         -> io::Result<(Box<dyn BufRead + 'a>, Compression)>
+    where R: BufRead + 'a
+{
+    let compression = Compression::detect(source.fill_buf()?);
+    let reader: Box<dyn BufRead + 'a> = match compression {
+        Compression::None => Box::new(source),
+        Compression::Gzip => {
+            Box::new(io::BufReader::new(MultiGzDecoder::new(source)))
+        }
+        Compression::Xz => {
+            Box::new(io::BufReader::new(XzDecoder::new_multi_decoder(source)))
+        }
+        Compression::Bzip2 => {
+            Box::new(io::BufReader::new(MultiBzDecoder::new(source)))
+        }
+        Compression::Zstd => {
+            Box::new(io::BufReader::new(zstd::Decoder::with_buffer(source)?))
+        }
+    };
+    Ok((reader, compression))
+}
+
+/// Opens a file for reading, or stdin if the path is [`STDIN`], decompressing
+/// it on the fly if it is compressed, and returns the compression that was
+/// found.
+pub fn open_file(path: impl AsRef<Path>)
+                 -> io::Result<(Box<dyn BufRead>, Compression)> {
+    decompress(open_source(path)?)
+}
+
+/// Reads the whole of a, possibly compressed, file into a string.
+pub fn read_to_string(path: impl AsRef<Path>) -> io::Result<String> {
+    source_to_string(&mut open_source(path)?)
+}
+
+/// Reads the whole of a, possibly compressed, source into a string.
+pub fn source_to_string(source: &mut dyn BufRead) -> io::Result<String> {
+    let (mut reader, _) = decompress(source)?;
+    let mut text = String::new();
+    let _ = reader.read_to_string(&mut text)?;
+    Ok(text)
+}
+
+/// Reads the whole of a, possibly compressed, source into memory.
+pub fn source_to_bytes(source: &mut dyn BufRead) -> io::Result<Vec<u8>> {
+    let (mut reader, _) = decompress(source)?;
+    let mut bytes = Vec::new();
+    let _ = reader.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// The contents of a file, memory-mapped unless it had to be decompressed.
+pub enum Bytes {
+    /// An uncompressed file mapped into memory.
+    Mapped(Mmap),
+    /// A decompressed (or empty) file read into memory.
+    Owned(Vec<u8>),
+}
+
+impl Deref for Bytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Bytes::Mapped(map) => map,
+            Bytes::Owned(bytes) => bytes,
+        }
+    }
+}
+
+/// Maps a file into memory, a compressed file or stdin is read into memory
+/// instead.
+pub fn map_file(path: impl AsRef<Path>) -> io::Result<Bytes> {
+    if is_stdin(path.as_ref()) {
+        return Ok(Bytes::Owned(source_to_bytes(&mut open_source(path)?)?));
+    }
+    let mut file = File::open(&path)?;
+    let mut magic = Vec::with_capacity(6);
+    let _ = Read::by_ref(&mut file).take(6).read_to_end(&mut magic)?;
+    match Compression::detect(&magic) {
+        Compression::None if file.metadata()?.len() > 0 => {
+            // Safety: the map is only read from and the file is not expected
+            // to be modified by another process whilst it is being read.
+            let map = unsafe { Mmap::map(&file)? };
+            Ok(Bytes::Mapped(map))
+        }
+        Compression::None => Ok(Bytes::Owned(Vec::new())),
+        _ => Ok(Bytes::Owned(source_to_bytes(&mut open_source(path)?)?)),
+    }
+}
+
+/// Returns the byte position after the next n line endings from start, or the
+/// end of the bytes if there are fewer lines.
+pub fn skip_lines(bytes: &[u8], start: usize, n: usize) -> usize {
+    let mut pos = start;
+    for _ in 0..n {
+        match bytes[pos..].iter().position(|b| *b == b'\n') {
+            Some(i) => pos += i + 1,
+            None => return bytes.len(),
+        }
+    }
+    pos
+}
+
+/// Splits the bytes into roughly n chunks that end on line boundaries.
+fn line_chunks(bytes: &[u8], n: usize) -> Vec<&[u8]> {
+    let size = bytes.len() / n.max(1) + 1;
+    let mut chunks = Vec::with_capacity(n);
+    let mut start = 0;
+    while start < bytes.len() {
+        let end = skip_lines(bytes, (start + size).min(bytes.len()), 1);
+        chunks.push(&bytes[start..end]);
+        start = end;
+    }
+    chunks
+}
+
+/// The whitespace separated words of some bytes.
+fn words(bytes: &[u8]) -> impl Iterator<Item = &[u8]> {
+    bytes.split(|b| b.is_ascii_whitespace())
+         .filter(|w| !w.is_empty())
+}
+
+/// Parses a block of whitespace separated numbers across threads, applying
+/// convert to each number.
+///
+/// The block is split at line boundaries and each thread counts the numbers in
+/// its chunk so that they can then be parsed straight into their place in the
+/// returned vector.
+pub fn parse_floats<F>(bytes: &[u8],
+                       threads: usize,
+                       convert: F)
+                       -> Result<Vec<f64>>
+    where F: Fn(f64) -> f64 + Sync
+{
+    let chunks = line_chunks(bytes, threads);
+    let thread_error = |_| Error::Thread(String::from("parse_floats"));
+    let counts =
+        thread::scope(|s| {
+            let th = chunks.iter()
+                           .map(|chunk| s.spawn(move |_| words(chunk).count()))
+                           .collect::<Vec<_>>();
+            th.into_iter()
+              .map(|thread| thread.join().map_err(thread_error))
+              .collect::<Result<Vec<usize>>>()
+        }).map_err(thread_error)??;
+    let mut numbers = vec![0f64; counts.iter().sum()];
+    let convert = &convert;
+    thread::scope(|s| {
+        let mut remainder = &mut numbers[..];
+        let mut th = Vec::with_capacity(chunks.len());
+        for (chunk, count) in chunks.iter().zip(counts) {
+            let (numbers, rest) = remainder.split_at_mut(count);
+            remainder = rest;
+            th.push(s.spawn(move |_| -> Result<()> {
+                         for (x, word) in numbers.iter_mut().zip(words(chunk)) {
+                             *x = match std::str::from_utf8(word).ok()
+                                                        .and_then(|w| {
+                                                            w.parse::<f64>()
+                                                             .ok()
+                                                        }) {
+                        Some(x) => convert(x),
+                        None => {
+                            return Err(Error::Parse(format!(
+                                "Cannot parse ({}) as a number.",
+                                String::from_utf8_lossy(word)
+                            )))
+                        }
+                    };
+                         }
+                         Ok(())
+                     }));
+        }
+        th.into_iter()
+          .try_for_each(|thread| thread.join().map_err(thread_error)?)
+    }).map_err(thread_error)??;
+    Ok(numbers)
+}
 
 /// Read a file into a mutable buffer
 pub struct BufReader {
-    reader: io::BufReader<File>,
+    reader: Box<dyn BufRead>,
 }
 
 impl BufReader {
-    /// Opens the file from the path into a reader
+    /// Opens the file from the path into a reader, decompressing it if needed
     pub fn open(path: impl AsRef<std::path::Path>) -> io::Result<Self> {
-        let file = File::open(path)?;
-        let reader = io::BufReader::new(file);
+        let (reader, _) = open_file(path)?;
 
         Ok(Self { reader })
     }
@@ -28,3 +285,72 @@ impl BufReader {
             .transpose()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compression_detect() {
+        assert_eq!(Compression::detect(&[0x1f, 0x8b, 8, 0]), Compression::Gzip);
+        assert_eq!(Compression::detect(b"\xfd7zXZ\x00\x00"), Compression::Xz);
+        assert_eq!(Compression::detect(b"BZh91AY"), Compression::Bzip2);
+        assert_eq!(Compression::detect(&[0x28, 0xb5, 0x2f, 0xfd, 0]),
+                   Compression::Zstd);
+        assert_eq!(Compression::detect(b"unknown system"), Compression::None);
+        assert_eq!(Compression::detect(&[]), Compression::None);
+    }
+
+    #[test]
+    fn reader_skip_lines() {
+        let bytes = b"a\nbb\nccc\n";
+        assert_eq!(skip_lines(bytes, 0, 0), 0);
+        assert_eq!(skip_lines(bytes, 0, 2), 5);
+        assert_eq!(skip_lines(bytes, 2, 1), 5);
+        assert_eq!(skip_lines(bytes, 0, 4), bytes.len());
+    }
+
+    #[test]
+    fn reader_line_chunks() {
+        let bytes = b"1 2 3\n4 5 6\n7 8 9\n10\n";
+        let chunks = line_chunks(bytes, 3);
+        assert!(chunks.iter().all(|c| c.ends_with(b"\n")));
+        assert_eq!(chunks.concat(), bytes.to_vec());
+        assert_eq!(line_chunks(b"", 4).len(), 0);
+    }
+
+    #[test]
+    fn reader_parse_floats() {
+        let bytes = b" 0.1E+01 -2.5\n 3 4\n\n 5.0E-01\n";
+        for threads in 1..6 {
+            assert_eq!(parse_floats(bytes, threads, |x| x * 2.).unwrap(),
+                       vec![2., -5., 6., 8., 1.]);
+        }
+    }
+
+    #[test]
+    fn reader_parse_floats_error() {
+        assert!(parse_floats(b"1.0 2.0\nthree\n", 2, |x| x).is_err());
+    }
+
+    #[test]
+    fn reader_source_to_bytes() {
+        let text = b"1.0 2.0\n3.0\n";
+        let mut gz =
+            flate2::write::GzEncoder::new(Vec::new(),
+                                          flate2::Compression::default());
+        gz.write_all(text).unwrap();
+        let gz = gz.finish().unwrap();
+        assert_eq!(source_to_bytes(&mut &gz[..]).unwrap(), text.to_vec());
+        assert_eq!(source_to_bytes(&mut &text[..]).unwrap(), text.to_vec());
+        assert_eq!(source_to_string(&mut &gz[..]).unwrap(), "1.0 2.0\n3.0\n");
+    }
+
+    #[test]
+    fn compression_strip_extension() {
+        assert_eq!(strip_compression("CHGCAR.xz"), "CHGCAR");
+        assert_eq!(strip_compression("run/charge.cube.GZ"), "run/charge.cube");
+        assert_eq!(strip_compression("Si.RHO.zst"), "Si.RHO");
+        assert_eq!(strip_compression("AECCAR0"), "AECCAR0");
+    }
+}
@@ -0,0 +1,403 @@
+use crate::error::Result;
+use crate::io::output::{element_totals, partition_totals};
+use serde::Serialize;
+
+/// The settings the program was run with and how long it took.
+#[derive(Serialize)]
+pub struct Metadata {
+    /// The version of the program.
+    pub version: String,
+    /// The density file.
+    pub file: String,
+    /// The reference files summed to give the density that is partitioned.
+    pub reference: Vec<String>,
+    /// The spin density file.
+    pub spin: Option<String>,
+    /// The method used to partition the density.
+    pub method: String,
+    /// Tolerance to disregard weights at.
+    pub weight_tolerance: f64,
+    /// Tolerance to disregard maxima at.
+    pub maxima_tolerance: f64,
+    /// Tolerance to consider a density vacuum.
+    pub vacuum_tolerance: Option<f64>,
+    /// How many threads the calculation used.
+    pub threads: usize,
+    /// How long each stage of the run took.
+    pub timing: Timing,
+}
+
+/// The wall-clock time, in seconds, of each stage of the run.
+#[derive(Serialize)]
+pub struct Timing {
+    /// Reading the density files.
+    pub read: f64,
+    /// Finding the maxima and partitioning the density.
+    pub partition: f64,
+    /// The whole run up until the results are written.
+    pub total: f64,
+}
+
+/// The partition of an atom.
+#[derive(Serialize)]
+pub struct AtomPartition {
+    /// The element of the atom, X if it is not known.
+    pub element: String,
+    /// The position of the atom in the axis order of the file.
+    pub position: [f64; 3],
+    /// The integrated value of each density.
+    pub density: Vec<f64>,
+    /// The volume of the partition.
+    pub volume: f64,
+    /// The minimum distance from the atom to the surface of the partition.
+    pub surface_distance: f64,
+    /// The valence of the atom minus its partitioned charge, if the valence
+    /// was given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub net_charge: Option<f64>,
+}
+
+/// The partitions of every atom of an element.
+#[derive(Serialize)]
+pub struct ElementPartition {
+    /// The element.
+    pub element: String,
+    /// The integrated value of each density.
+    pub density: Vec<f64>,
+    /// The volume of the partitions.
+    pub volume: f64,
+    /// The total net charge of the atoms, if their valence was given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub net_charge: Option<f64>,
+}
+
+/// A Bader maximum and the volume that ascends to it.
+#[derive(Serialize)]
+pub struct VolumePartition {
+    /// The position of the maximum in the axis order of the file.
+    pub position: [f64; 3],
+    /// The atom, starting at 1, that the maximum is assigned to.
+    pub atom: usize,
+    /// The distance from the maximum to the atom it is assigned to.
+    pub atom_distance: f64,
+    /// The integrated value of each density, only summed at higher verbosity.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub density: Option<Vec<f64>>,
+    /// The volume of the partition, only summed at higher verbosity.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<f64>,
+}
+
+/// A total of the density and volume.
+#[derive(Serialize)]
+pub struct Total {
+    /// The total of each density.
+    pub density: Vec<f64>,
+    /// The total volume.
+    pub volume: f64,
+}
+
+/// Everything that a run computes, in a form that can be written as JSON or
+/// CSV.
+#[derive(Serialize)]
+pub struct Results {
+    /// The settings and timings of the run.
+    pub metadata: Metadata,
+    /// The names of the densities, in the order they are stored.
+    pub density_names: Vec<String>,
+    /// The partition of each atom.
+    pub atoms: Vec<AtomPartition>,
+    /// The partitions of each element, in the order they first appear.
+    pub elements: Vec<ElementPartition>,
+    /// The Bader maxima and their volumes.
+    pub volumes: Vec<VolumePartition>,
+    /// The density and volume that is not assigned to an atom.
+    pub vacuum: Total,
+    /// The density and volume that is assigned to the atoms.
+    pub partitioned: Total,
+}
+
+impl Results {
+    /// Collects the results, working out the vacuum from the totals of each
+    /// density and the volume of the cell.
+    pub fn new(metadata: Metadata,
+               density_names: Vec<String>,
+               atoms: Vec<AtomPartition>,
+               volumes: Vec<VolumePartition>,
+               total_density: &[f64],
+               total_volume: f64)
+               -> Self {
+        let atoms_density = atoms.iter()
+                                 .map(|a| a.density.clone())
+                                 .collect::<Vec<Vec<f64>>>();
+        let atoms_volume = atoms.iter().map(|a| a.volume).collect::<Vec<f64>>();
+        let (density, volume) = partition_totals(&atoms_density, &atoms_volume);
+        // sum the net charge alongside the densities
+        let net = atoms.iter().all(|a| a.net_charge.is_some());
+        let species = atoms.iter()
+                           .map(|a| a.element.clone())
+                           .collect::<Vec<String>>();
+        let density_net = atoms.iter()
+                               .map(|a| {
+                                   let mut d = a.density.clone();
+                                   d.extend(a.net_charge);
+                                   d
+                               })
+                               .collect::<Vec<Vec<f64>>>();
+        let totals = element_totals(&species, &density_net, &atoms_volume);
+        let elements = totals.into_iter()
+                             .map(|(element, mut density, volume)| {
+                                 let net_charge =
+                                     if net { density.pop() } else { None };
+                                 ElementPartition { element,
+                                                    density,
+                                                    volume,
+                                                    net_charge }
+                             })
+                             .collect();
+        let vacuum = Total { density: total_density.iter()
+                                                   .zip(&density)
+                                                   .map(|(t, p)| t - p)
+                                                   .collect(),
+                             volume: total_volume - volume };
+        Self { metadata,
+               density_names,
+               atoms,
+               elements,
+               volumes,
+               vacuum,
+               partitioned: Total { density, volume } }
+    }
+
+    /// Serialises the results as pretty-printed JSON.
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self).map_err(std::io::Error::from)?)
+    }
+
+    /// A CSV table of the atoms, with a row for each atom.
+    pub fn atoms_csv(&self) -> String {
+        let net = self.atoms.iter().all(|a| a.net_charge.is_some());
+        let mut header = vec![String::from("#"),
+                              String::from("Element"),
+                              String::from("X"),
+                              String::from("Y"),
+                              String::from("Z")];
+        header.extend(self.density_names.iter().cloned());
+        if net {
+            header.push(String::from("Net Charge"));
+        }
+        header.push(String::from("Volume"));
+        header.push(String::from("Distance"));
+        let rows = self.atoms.iter().enumerate().map(|(i, atom)| {
+            let mut row = vec![(i + 1).to_string(), atom.element.clone()];
+            row.extend(atom.position.iter().map(number));
+            row.extend(atom.density.iter().map(number));
+            if net {
+                row.extend(atom.net_charge.as_ref().map(number));
+            }
+            row.push(number(&atom.volume));
+            row.push(number(&atom.surface_distance));
+            row
+        });
+        csv(header, rows)
+    }
+
+    /// A CSV table of the Bader volumes, with a row for each maximum. The
+    /// density and volume columns are only included if they were summed.
+    pub fn volumes_csv(&self) -> String {
+        let summed = self.volumes.iter().all(|v| v.density.is_some());
+        let mut header = vec![String::from("#"),
+                              String::from("X"),
+                              String::from("Y"),
+                              String::from("Z"),
+                              String::from("Atom"),
+                              String::from("Distance")];
+        if summed {
+            header.extend(self.density_names.iter().cloned());
+            header.push(String::from("Volume"));
+        }
+        let rows = self.volumes.iter().enumerate().map(|(i, volume)| {
+            let mut row = vec![(i + 1).to_string()];
+            row.extend(volume.position.iter().map(number));
+            row.push(volume.atom.to_string());
+            row.push(number(&volume.atom_distance));
+            if let (true, Some(density), Some(v)) =
+                (summed, &volume.density, volume.volume)
+            {
+                row.extend(density.iter().chain(&[v]).map(number));
+            }
+            row
+        });
+        csv(header, rows)
+    }
+}
+
+/// Formats a float as the shortest string that reads back as the same float,
+/// using an exponent for very large or small values.
+fn number(f: &f64) -> String {
+    format!("{:?}", f)
+}
+
+/// Joins a header and rows into comma separated lines.
+fn csv(header: Vec<String>, rows: impl Iterator<Item = Vec<String>>) -> String {
+    std::iter::once(header).chain(rows)
+                           .map(|row| row.join(",") + "\n")
+                           .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atoms::{Atoms, Lattice};
+    use crate::calculation::BaderCalculation;
+
+    fn metadata() -> Metadata {
+        Metadata { version: String::from("0.0.0"),
+                   file: String::from("CHGCAR"),
+                   reference: vec![String::from("AECCAR0"),
+                                   String::from("AECCAR2")],
+                   spin: None,
+                   method: String::from("weight"),
+                   weight_tolerance: 1E-6,
+                   maxima_tolerance: 1E-6,
+                   vacuum_tolerance: None,
+                   threads: 1,
+                   timing: Timing { read: 0.5,
+                                    partition: 1.0,
+                                    total: 2.0 } }
+    }
+
+    fn results(summed: bool) -> Results {
+        let metadata = metadata();
+        let atoms = vec![AtomPartition { element: String::from("Li"),
+                                         position: [0., 0., 0.],
+                                         density: vec![1.5, 0.25],
+                                         volume: 2.0,
+                                         surface_distance: 0.5,
+                                         net_charge: Some(1.5) },
+                         AtomPartition { element: String::from("Li"),
+                                         position: [0.5, 0.5, 0.5],
+                                         density: vec![2.0, -0.25],
+                                         volume: 3.0,
+                                         surface_distance: 0.75,
+                                         net_charge: Some(1.0) }];
+        let volumes = (0..3).map(|i| VolumePartition { position: [i as f64;
+                                                                  3],
+                                                       atom: i / 2 + 1,
+                                                       atom_distance: 0.,
+                                                       density: if summed {
+                                                           Some(vec![1., 0.])
+                                                       } else {
+                                                           None
+                                                       },
+                                                       volume: if summed {
+                                                           Some(1.)
+                                                       } else {
+                                                           None
+                                                       } })
+                            .collect();
+        Results::new(metadata,
+                     vec![String::from("Charge"), String::from("Spin")],
+                     atoms,
+                     volumes,
+                     &[4.0, 0.0],
+                     6.0)
+    }
+
+    #[test]
+    fn results_vacuum() {
+        let results = results(true);
+        assert_eq!(results.partitioned.density, vec![3.5, 0.0]);
+        assert_eq!(results.partitioned.volume, 5.0);
+        assert_eq!(results.vacuum.density, vec![0.5, 0.0]);
+        assert_eq!(results.vacuum.volume, 1.0);
+        assert_eq!(results.elements.len(), 1);
+        assert_eq!(results.elements[0].density, vec![3.5, 0.0]);
+        assert_eq!(results.elements[0].net_charge, Some(2.5));
+    }
+
+    #[test]
+    fn results_json() {
+        let json: serde_json::Value =
+            serde_json::from_str(&results(false).to_json().unwrap()).unwrap();
+        assert_eq!(json["metadata"]["reference"][1], "AECCAR2");
+        assert_eq!(json["atoms"][1]["density"][1], -0.25);
+        assert_eq!(json["atoms"][1]["net_charge"], 1.0);
+        assert_eq!(json["elements"][0]["element"], "Li");
+        assert_eq!(json["volumes"][2]["atom"], 2);
+        assert!(json["volumes"][0].get("density").is_none());
+        assert_eq!(json["vacuum"]["volume"], 1.0);
+    }
+
+    #[test]
+    fn results_atoms_csv() {
+        assert_eq!(results(false).atoms_csv(),
+                   "#,Element,X,Y,Z,Charge,Spin,Net Charge,Volume,Distance\n\
+                    1,Li,0.0,0.0,0.0,1.5,0.25,1.5,2.0,0.5\n\
+                    2,Li,0.5,0.5,0.5,2.0,-0.25,1.0,3.0,0.75\n");
+    }
+
+    #[test]
+    fn results_volumes_csv() {
+        let csv = results(false).volumes_csv();
+        assert_eq!(csv.lines().next(), Some("#,X,Y,Z,Atom,Distance"));
+        assert_eq!(csv.lines().nth(3), Some("3,2.0,2.0,2.0,2,0.0"));
+        let csv = results(true).volumes_csv();
+        assert_eq!(csv.lines().next(),
+                   Some("#,X,Y,Z,Atom,Distance,Charge,Spin,Volume"));
+        assert_eq!(csv.lines().nth(1), Some("1,0.0,0.0,0.0,1,0.0,1.0,0.0,1.0"));
+    }
+
+    #[test]
+    fn results_maxima_positions() {
+        // a single peak at 1.5 along each axis of a grid with 0.5 voxels
+        let lattice = [[3., 0., 0.], [0., 3., 0.], [0., 0., 3.]];
+        let atoms = Atoms::new(Lattice::new(lattice).unwrap(),
+                               vec![[1.5, 1.5, 1.5]],
+                               String::new()).unwrap();
+        let r = |c: usize| (c as f64 - 3.).powi(2);
+        let densities =
+            vec![(0..216).map(|p| {
+                             (-(r(p / 36) + r(p / 6 % 6) + r(p % 6))).exp()
+                         })
+                         .collect::<Vec<f64>>()];
+        let result = BaderCalculation::new(&densities,
+                                           &atoms,
+                                           [6, 6, 6],
+                                           [0., 0., 0.]).with_threads(1)
+                                                        .with_volumes(true)
+                                                        .run()
+                                                        .unwrap();
+        let volumes =
+            result.maxima_positions()
+                  .into_iter()
+                  .enumerate()
+                  .map(|(i, position)| {
+                      VolumePartition { position,
+                                        atom: result.atom_map[i] + 1,
+                                        atom_distance:
+                                            result.minimum_distance[i],
+                                        density: None,
+                                        volume: None }
+                  })
+                  .collect();
+        let atom = AtomPartition { element: String::from("X"),
+                                   position: [1.5; 3],
+                                   density: result.atoms.density[0].clone(),
+                                   volume: result.atoms.volume[0],
+                                   surface_distance: 0.,
+                                   net_charge: None };
+        let results = Results::new(metadata(),
+                                   vec![String::from("Charge")],
+                                   vec![atom],
+                                   volumes,
+                                   &result.total_density,
+                                   27.);
+        let json: serde_json::Value =
+            serde_json::from_str(&results.to_json().unwrap()).unwrap();
+        assert_eq!(json["volumes"][0]["position"],
+                   serde_json::json!([1.5, 1.5, 1.5]));
+        assert_eq!(results.volumes_csv().lines().nth(1),
+                   Some("1,1.5,1.5,1.5,1,0.0"));
+    }
+}
@@ -0,0 +1,230 @@
+use crate::atoms::{Atoms, Lattice};
+use crate::error::{Error, Result};
+use crate::io::fortran::FortranReader;
+use crate::io::{reader, xsf, FileFormat, ReadFunction};
+use crate::progress::Bar;
+use crate::utils;
+use std::io::{BufRead, Read};
+use std::path::Path;
+
+/// Convert from Rydberg atomic units.
+const LENGTH_UNITS: f64 = 0.52917721067;
+/// Convert from Rydberg atomic units.
+const VOLUME_UNITS: f64 = LENGTH_UNITS * LENGTH_UNITS * LENGTH_UNITS;
+
+/// The SIESTA grid files, .RHO, .DRHO and the like, written as Fortran
+/// unformatted records in single or double precision.
+///
+/// The grid files do not contain the atoms so these are read from the
+/// seedname.XV file next to it. The header is binary so [`Atoms::text`] holds
+/// the XV file instead and densities are written as XSF files.
+pub struct Siesta {}
+
+/// The .XV file that belongs to a grid file.
+fn xv_filename(filename: &str) -> String {
+    Path::new(reader::strip_compression(filename)).with_extension("XV")
+                                                  .to_string_lossy()
+                                                  .to_string()
+}
+
+/// Parses a line of the XV file into floats, which has to hold at least len of
+/// them.
+fn parse_line(line: Option<&str>, len: usize) -> Result<Vec<f64>> {
+    let line = line.ok_or_else(|| {
+                       Error::Parse(String::from("Unexpected end of XV file."))
+                   })?;
+    let floats = line.split_whitespace()
+                     .map(|x| {
+                         x.parse::<f64>().map_err(|_| {
+                                             Error::Parse(format!("Cannot parse ({}) in line:\n{}", x, line))
+                                         })
+                     })
+                     .collect::<Result<Vec<f64>>>()?;
+    if floats.len() < len {
+        return Err(Error::Parse(format!("Expected {} values in line:\n{}",
+                                        len, line)));
+    }
+    Ok(floats)
+}
+
+impl Siesta {
+    /// Reads the records of a SIESTA grid file and the atoms from the .XV file.
+    fn parse<R: Read>(&self,
+                      mut reader: FortranReader<R>,
+                      name: &str)
+                      -> ReadFunction {
+        // the first point of the mesh is at the origin
+        let voxel_origin = [0f64; 3];
+        println!("Reading {} as SIESTA format:", name);
+        // cell(3, 3) in Bohr, then mesh(3) and nspin
+        let mut record = reader.next_record()?;
+        let _ = record.f64_vec(9)?;
+        let mut record = reader.next_record()?;
+        let mesh = record.i32_vec(3)?;
+        let nspin = record.i32()?;
+        if mesh.iter().any(|n| *n <= 0) || !matches!(nspin, 1 | 2 | 4 | 8) {
+            return Err(Error::Parse(String::from("Unexpected mesh in SIESTA \
+                                                  header.")));
+        }
+        let [n1, n2, n3] =
+            [mesh[0] as usize, mesh[1] as usize, mesh[2] as usize];
+        let nfft = n1 * n2 * n3;
+        // each spin is written as a record per line of x values
+        let mut raw = vec![Vec::with_capacity(nfft); nspin as usize];
+        for density in raw.iter_mut() {
+            for _ in 0..(n2 * n3) {
+                let mut record = reader.next_record()?;
+                let line = if record.len() == 4 * n1 {
+                    record.f32_vec(n1)?
+                } else if record.len() == 8 * n1 {
+                    record.f64_vec(n1)?
+                } else {
+                    return Err(Error::Parse(String::from("Unexpected record length in SIESTA grid.")));
+                };
+                density.extend(line.iter().map(|x| x / VOLUME_UNITS));
+            }
+        }
+        // convert the spin components into the total and magnetisation
+        let densities = match raw.len() {
+            1 => raw,
+            2 => {
+                let total =
+                    raw[0].iter().zip(&raw[1]).map(|(u, d)| u + d).collect();
+                let spin =
+                    raw[0].iter().zip(&raw[1]).map(|(u, d)| u - d).collect();
+                vec![total, spin]
+            }
+            _ => {
+                // D11, D22, Re(D12) and Im(D12), any spin-orbit parts are
+                // ignored
+                let total =
+                    raw[0].iter().zip(&raw[1]).map(|(a, b)| a + b).collect();
+                let x = raw[2].iter().map(|re| 2. * re).collect();
+                let y = raw[3].iter().map(|im| -2. * im).collect();
+                let z =
+                    raw[0].iter().zip(&raw[1]).map(|(a, b)| a - b).collect();
+                vec![total, x, y, z]
+            }
+        };
+        let xv = xv_filename(name);
+        let text = reader::read_to_string(&xv).map_err(|e| {
+                                                  std::io::Error::new(e.kind(),
+                                           format!("The atoms of {} are read \
+                                                    from {}.\n{}",
+                                                   name, xv, e))
+                                              })?;
+        let atoms = self.to_atoms(format!("SIESTA grid: {} {} {}\n{}",
+                                          n1, n2, n3, text))?;
+        // flip the grid points as the density is stored density[z, y, x]
+        let grid_pts = [n3, n2, n1];
+        println!("File read successfully.");
+        Ok((voxel_origin, grid_pts, atoms, densities))
+    }
+}
+
+impl FileFormat for Siesta {
+    /// Read a SIESTA grid file and the atoms from the .XV file.
+    fn read(&self, filename: String, _threads: usize) -> ReadFunction {
+        self.parse(FortranReader::open(&filename)?, &filename)
+    }
+
+    /// Read a SIESTA grid file from a source and the atoms from the .XV file
+    /// named after it.
+    fn read_from(&self,
+                 source: &mut dyn BufRead,
+                 name: &str,
+                 _threads: usize)
+                 -> ReadFunction {
+        // the length of a source is unknown until it has been read
+        let (source, _) = reader::decompress(source)?;
+        self.parse(FortranReader::new(source, u64::MAX)?, name)
+    }
+
+    /// Read atom information from the XV file, the cell and positions are in
+    /// Bohr.
+    fn to_atoms(&self, atoms_text: String) -> Result<Atoms> {
+        let mut lines = atoms_text.lines().skip(1);
+        let a = parse_line(lines.next(), 3)?;
+        let b = parse_line(lines.next(), 3)?;
+        let c = parse_line(lines.next(), 3)?;
+        // density[z, y, x] so lets swap the c and a
+        let lattice = Lattice::new([[c[2] * LENGTH_UNITS,
+                                     c[1] * LENGTH_UNITS,
+                                     c[0] * LENGTH_UNITS],
+                                    [b[2] * LENGTH_UNITS,
+                                     b[1] * LENGTH_UNITS,
+                                     b[0] * LENGTH_UNITS],
+                                    [a[2] * LENGTH_UNITS,
+                                     a[1] * LENGTH_UNITS,
+                                     a[0] * LENGTH_UNITS]])?;
+        let natom = parse_line(lines.next(), 1)?[0] as usize;
+        // species index, atomic number, position and velocity
+        let positions = (0..natom).map(|_| {
+                                      let x = parse_line(lines.next(), 5)?;
+                                      let f = utils::dot([x[4] * LENGTH_UNITS,
+                                                          x[3] * LENGTH_UNITS,
+                                                          x[2] * LENGTH_UNITS],
+                                                         lattice.to_fractional);
+                                      Ok(utils::dot([f[0].rem_euclid(1.),
+                                                     f[1].rem_euclid(1.),
+                                                     f[2].rem_euclid(1.)],
+                                                    lattice.to_cartesian))
+                                  })
+                                  .collect::<Result<Vec<[f64; 3]>>>()?;
+        Atoms::new(lattice, positions, atoms_text)
+    }
+
+    /// Write the data as an XSF file as the binary header is not kept.
+    fn write(&self,
+             atoms: &Atoms,
+             data: Vec<Option<f64>>,
+             filename: String,
+             pbar: Bar)
+             -> Result<()> {
+        let grid = match atoms.text.lines().next().map(|l| {
+                                                      l.split_whitespace()
+                                                       .skip(2)
+                                                       .filter_map(|x| {
+                                                           x.parse::<usize>()
+                                                            .ok()
+                                                       })
+                                                       .collect::<Vec<usize>>()
+                                                  }) {
+            Some(g) if g.len() == 3 => [g[2], g[1], g[0]],
+            _ => {
+                return Err(Error::Parse(String::from("Cannot find the grid of \
+                                                      the SIESTA density.")))
+            }
+        };
+        xsf::write_grid(atoms, data, grid, [0.; 3], self.axes(), filename, pbar)
+    }
+
+    /// Deals with fortran indexing.
+    fn coordinate_format(&self, coords: [f64; 3]) -> (String, String, String) {
+        let z = format!("{:.6}", coords[0]);
+        let y = format!("{:.6}", coords[1]);
+        let x = format!("{:.6}", coords[2]);
+        (x, y, z)
+    }
+
+    /// Deals with fortran indexing.
+    fn image_format(&self, image: [isize; 3]) -> String {
+        format!("({}, {}, {})", image[2], image[1], image[0])
+    }
+
+    /// Deals with fortran indexing.
+    fn axes(&self) -> [usize; 3] {
+        [2, 1, 0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn siesta_xv_filename() {
+        assert_eq!(xv_filename("run/Si.RHO"), "run/Si.XV");
+        assert_eq!(xv_filename("Si.DRHO"), "Si.XV");
+    }
+}
@@ -1,11 +1,11 @@
 use crate::atoms::{Atoms, Lattice};
-use crate::io::reader::BufReader;
-use crate::io::{FileFormat, FortranFormat, ReadFunction};
+use crate::error::{Error, Result};
+use crate::io::{reader, FileFormat, FortranFormat, ReadFunction};
 use crate::progress::Bar;
 use crate::utils;
-use regex::{Regex, RegexSet};
+use regex::Regex;
 use std::fs::File;
-use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::io::{BufRead, BufWriter, Write};
 
 /// The coordinate system.
 enum Coord {
@@ -15,143 +15,157 @@ enum Coord {
     Cartesian,
 }
 
+/// Read the element and valence, ZVAL, of each pseudopotential in a POTCAR.
+pub fn read_zval(filename: &str) -> Result<Vec<(String, f64)>> {
+    let text = reader::read_to_string(filename)?;
+    // the element is the start of the label after the functional in TITEL
+    let titel = Regex::new(r"TITEL\s*=\s*\S+\s+([A-Za-z]+)").unwrap();
+    let zval = Regex::new(r"ZVAL\s*=\s*([-+0-9.Ee]+)").unwrap();
+    let elements = titel.captures_iter(&text).map(|c| String::from(&c[1]));
+    let valences = zval.captures_iter(&text).map(|c| {
+                                                 c[1].parse::<f64>().map_err(|_| {
+                                                     Error::Parse(format!("Cannot parse ZVAL ({}) in {}.", &c[1], filename))
+                                                 })
+                                             });
+    let table = elements.zip(valences)
+                        .map(|(e, z)| Ok((e, z?)))
+                        .collect::<Result<Vec<(String, f64)>>>()?;
+    if table.is_empty() {
+        return Err(Error::Parse(format!("No ZVAL found in {}.", filename)));
+    }
+    Ok(table)
+}
+
+/// Parses a line of the POSCAR into floats, which has to hold at least len of
+/// them.
+fn parse_line(line: Option<&str>, len: usize) -> Result<Vec<f64>> {
+    let line = line.ok_or_else(|| {
+                       Error::Parse(String::from("Unexpected end of POSCAR."))
+                   })?;
+    let floats = line.split_whitespace()
+                     .map(|x| {
+                         x.parse::<f64>().map_err(|_| {
+                                             Error::Parse(format!("Cannot parse ({}) in line:\n{}", x, line))
+                                         })
+                     })
+                     .collect::<Result<Vec<f64>>>()?;
+    if floats.len() < len {
+        return Err(Error::Parse(format!("Expected {} values in line:\n{}",
+                                        len, line)));
+    }
+    Ok(floats)
+}
+
 /// The VASP file format for reading/writing CHG, PARCHG and CHGCARs.
 pub struct Vasp {}
 
-impl FileFormat for Vasp {
-    /// Read a VASP density.
-    fn read(&self, filename: String) -> ReadFunction {
+impl Vasp {
+    /// Parses the bytes of a VASP density.
+    fn parse(&self, bytes: &[u8], name: &str, threads: usize) -> ReadFunction {
         // the voxel origin in VASP is (0, 0, 0)
         let voxel_origin = [0f64; 3];
-        println!("Reading {} as VASP format:", filename);
-        // find the start and end points of the density as well as the total file size
-        let (grid, aug, total) = {
-            // open the file in a buffer reader
-            let mut reader = BufReader::open(filename.clone())?;
-
-            let mut buffer = String::new();
-            let mut grid: Vec<[usize; 2]> = vec![];
-            let mut aug: Vec<usize> = vec![];
-            let mut pos = 0;
-            // search for the grid lines or augmentation that bound the densities
-            let regex = RegexSet::new(&[r"^\s*\d+\s+\d+\s+\d+\s*$",
-                                        r"^\s*aug"]).unwrap();
-            // the first 7 lines are useless to us
-            for _ in 0..8 {
-                let size = match reader.read_line(&mut buffer) {
-                    Some(line) => {
-                        let (_, size) = line?;
-                        size
-                    }
-                    None => 0,
-                };
-                pos += size;
-            }
-            // lets start trying to match
-            while let Some(line) = reader.read_line(&mut buffer) {
-                let (text, size) = line?;
-                if regex.is_match(text) {
-                    let matches: Vec<usize> =
-                        regex.matches(text).into_iter().collect();
-                    let matches = matches[0];
-                    match matches {
-                        0 => {
-                            let start = pos;
-                            pos += size;
-                            let end = pos;
-                            grid.push([start, end]);
-                        }
-                        1 => {
-                            aug.push(pos);
-                            pos += size;
-                        }
-                        _ => {}
+        println!("Reading {} as VASP format:", name);
+        // the grid lines bound the poscar and each of the densities
+        let regex = Regex::new(r"^\s*\d+\s+\d+\s+\d+\s*$").unwrap();
+        let next_grid = |mut pos: usize| -> Option<[usize; 2]> {
+            while pos < bytes.len() {
+                let end = reader::skip_lines(bytes, pos, 1);
+                match std::str::from_utf8(&bytes[pos..end]) {
+                    Ok(line) if regex.is_match(line) => {
+                        return Some([pos, end])
                     }
-                } else {
-                    pos += size;
+                    _ => pos = end,
                 }
             }
-            (grid, aug, pos)
+            None
         };
-        // Now we know where everything is so let's work out what to do
-        // Start by making vector of start and end points of the densities
-        let mut start = Vec::with_capacity(4);
-        let mut stop = Vec::with_capacity(4);
-        for (i, start_stop) in grid.iter().enumerate() {
-            start.push(start_stop[1]);
-            let s = if !aug.is_empty() {
-                aug[i * aug.len() / grid.len()]
-            } else if grid.len() > (i + 1) {
-                grid[i + 1][0]
-            } else {
-                total
-            };
-            stop.push(s);
-        }
-        let mut file = File::open(filename)?;
-        // assign Vectos with the capacity of what it is to hold
-        let mut poscar_b = Vec::with_capacity(grid[0][0]);
-        let mut grid_pts_b = Vec::with_capacity(grid[0][1] - grid[0][0]);
-        let mut density_b = Vec::with_capacity(stop[0] - start[0]);
+        // the first 7 lines are useless to us
+        let mut grid_line = match next_grid(reader::skip_lines(bytes, 0, 8)) {
+            Some(grid_line) => grid_line,
+            None => {
+                return Err(Error::Parse(format!("Cannot find the grid in {}.",
+                                                name)))
+            }
+        };
+        // convert the bytes we have read into a String and an Atoms struct
+        let poscar =
+            String::from_utf8(bytes[..grid_line[1]].to_vec()).map_err(|_| {
+                Error::Parse(format!("The POSCAR of {} is not UTF-8.", name))
+            })?;
+        let bad_grid =
+            || Error::Parse(format!("The grid of {} is too large.", name));
+        let grid_vec = String::from_utf8_lossy(&bytes[grid_line[0]
+                                                      ..grid_line[1]])
+            .split_whitespace()
+            .map(|x| x.parse::<usize>().map_err(|_| bad_grid()))
+            .collect::<Result<Vec<usize>>>()?;
+        let atoms = self.to_atoms(poscar)?;
+        let total = grid_vec.iter()
+                            .try_fold(1usize, |t, g| t.checked_mul(*g))
+                            .ok_or_else(bad_grid)?;
         // there could be a maximum of 4 densities 1 total and then 1 or 3 spin
         let mut density: Vec<Vec<f64>> = Vec::with_capacity(4);
-        // read the poscar information poscar_b
-        let _ = <File as Read>::by_ref(&mut file).take(grid[0][1] as u64)
-                                                 .read_to_end(&mut poscar_b)?;
-        file.seek(SeekFrom::Current((grid[0][0] as i64 - grid[0][1] as i64)
-                                    as i64))?;
-        // read the grid line into grid_pts_b
-        let _ =
-            <File as Read>::by_ref(&mut file).take((grid[0][1] - grid[0][0])
-                                                   as u64)
-                                             .read_to_end(&mut grid_pts_b)?;
-        // read the total charge density into density_b
-        let _ = <File as Read>::by_ref(&mut file).take((stop[0] - start[0])
-                                                       as u64)
-                                                 .read_to_end(&mut density_b)?;
-        // convert the bytes we have read into a String and an Atoms struct
-        let poscar = String::from_utf8(poscar_b).unwrap();
-        let grid_vec: Vec<usize> = {
-            String::from_utf8(grid_pts_b).unwrap()
-                                         .split_whitespace()
-                                         .map(|x| x.parse::<usize>().unwrap())
-                                         .collect()
-        };
-        let atoms = self.to_atoms(poscar);
-        // convert out of VASP's strange units
-        density.push(String::from_utf8(density_b).unwrap()
-                                                 .split_whitespace()
-                                                 .map(|x| {
-                                                     x.parse::<f64>().unwrap()
-                                                     / atoms.lattice.volume
-                                                 })
-                                                 .collect());
-        for i in 1..start.len() {
-            let mut spin_b = Vec::with_capacity(stop[i] - start[i]);
-            let _ = file.seek(SeekFrom::Start(start[i] as u64));
-            let _ = <File as Read>::by_ref(&mut file).take((stop[i] - start[i])
-                                                           as u64)
-                                                     .read_to_end(&mut spin_b)?;
-            density.push(String::from_utf8(spin_b).unwrap()
-                                                  .split_whitespace()
-                                                  .map(|x| {
-                                                      x.parse::<f64>().unwrap()
-                                                      / atoms.lattice.volume
-                                                  })
-                                                  .collect());
+        loop {
+            // VASP writes a fixed number of values per line so the end of the
+            // density is found by counting lines rather than reading them
+            let start = grid_line[1];
+            let first_line = &bytes[start..reader::skip_lines(bytes, start, 1)];
+            let per_line =
+                String::from_utf8_lossy(first_line).split_whitespace()
+                                                   .count()
+                                                   .max(1);
+            let stop =
+                reader::skip_lines(bytes, start, total.div_ceil(per_line));
+            // convert out of VASP's strange units
+            let d = reader::parse_floats(&bytes[start..stop], threads, |x| {
+                x / atoms.lattice.volume
+            })?;
+            if d.len() != total {
+                return Err(Error::Parse(format!("Expected {} values in the density but found {}.",
+                                                total,
+                                                d.len())));
+            }
+            density.push(d);
+            // skip any augmentation occupancies to the next grid line
+            grid_line = match next_grid(stop) {
+                Some(grid_line) => grid_line,
+                None => break,
+            };
         }
         // flip the grid points as VASP outputs density[z, y, x]
         let grid_pts: [usize; 3] = [grid_vec[2], grid_vec[1], grid_vec[0]];
         println!("File read successfully.");
         Ok((voxel_origin, grid_pts, atoms, density))
     }
+}
+
+impl FileFormat for Vasp {
+    /// Read a VASP density, memory-mapping the file.
+    fn read(&self, filename: String, threads: usize) -> ReadFunction {
+        let bytes = reader::map_file(&filename)?;
+        self.parse(&bytes, &filename, threads)
+    }
+
+    /// Read a VASP density from a source.
+    fn read_from(&self,
+                 source: &mut dyn BufRead,
+                 name: &str,
+                 threads: usize)
+                 -> ReadFunction {
+        let bytes = reader::source_to_bytes(source)?;
+        self.parse(&bytes, name, threads)
+    }
 
     /// Read atom information.
-    fn to_atoms(&self, atoms_text: String) -> Atoms {
+    fn to_atoms(&self, atoms_text: String) -> Result<Atoms> {
         // create regex for matching the (C|K)artesian | Direct line
         let coord_regex = Regex::new(r"(?m)^\s*(c|C|k|K|d|D)\w*").unwrap();
         // the last match is the one we want so we don't match carbon or the comment line
-        let matches = coord_regex.find_iter(&atoms_text).last().unwrap();
+        let matches =
+            coord_regex.find_iter(&atoms_text).last().ok_or_else(|| {
+                Error::Parse(String::from("Cannot find the coordinate system \
+                                           of the POSCAR."))
+            })?;
         let coord_text =
             &atoms_text[matches.start()..matches.end()].trim_start();
         let coord = if coord_text.starts_with('d') | coord_text.starts_with('D')
@@ -175,41 +189,39 @@ impl FileFormat for Vasp {
         for _ in 0..3 {
             pos.pop();
         }
+        if pos.len() % 3 != 0 {
+            return Err(Error::Parse(String::from("Cannot read the positions \
+                                                  of the POSCAR.")));
+        }
         let mut lines = atoms_text.lines();
         // skip the comment line  and then read the lattice information
         let _ = lines.next();
-        let mut scale = {
-            lines.next()
-                 .unwrap()
-                 .to_string()
-                 .split_whitespace()
-                 .map(|x| x.parse::<f64>().unwrap())
-                 .collect::<Vec<f64>>()
-        };
+        let mut scale = parse_line(lines.next(), 1)?;
         // density[z, y, x] so lets swap the c and a
-        let mut c = {
-            lines.next()
-                 .unwrap()
-                 .to_string()
-                 .split_whitespace()
-                 .map(|x| x.parse::<f64>().unwrap())
-                 .collect::<Vec<f64>>()
-        };
-        let mut b = {
-            lines.next()
-                 .unwrap()
-                 .to_string()
-                 .split_whitespace()
-                 .map(|x| x.parse::<f64>().unwrap())
-                 .collect::<Vec<f64>>()
-        };
-        let mut a = {
-            lines.next()
-                 .unwrap()
-                 .to_string()
-                 .split_whitespace()
-                 .map(|x| x.parse::<f64>().unwrap())
-                 .collect::<Vec<f64>>()
+        let mut c = parse_line(lines.next(), 3)?;
+        let mut b = parse_line(lines.next(), 3)?;
+        let mut a = parse_line(lines.next(), 3)?;
+        // VASP 5 onwards writes the species above the number of each, these
+        // can carry the POTCAR's suffix and hash ie. Fe_pv/2d4c2e8a
+        let species = match lines.next() {
+            Some(line)
+                if line.split_whitespace()
+                       .all(|x| x.parse::<usize>().is_err()) =>
+            {
+                let symbols =
+                    line.split_whitespace()
+                        .map(|x| x.split(['_', '/']).next().unwrap_or(x))
+                        .collect::<Vec<&str>>();
+                let counts = lines.next()
+                                  .unwrap_or("")
+                                  .split_whitespace()
+                                  .filter_map(|x| x.parse::<usize>().ok());
+                symbols.iter()
+                       .zip(counts)
+                       .flat_map(|(s, n)| vec![String::from(*s); n])
+                       .collect::<Vec<String>>()
+            }
+            _ => Vec::with_capacity(0),
         };
         let volume = {
             (c[0] * (a[1] * b[2] - a[2] * b[1])
@@ -233,7 +245,7 @@ impl FileFormat for Vasp {
         }
         let lattice = Lattice::new([[a[2], a[1], a[0]],
                                     [b[2], b[1], b[0]],
-                                    [c[2], c[1], c[0]]]);
+                                    [c[2], c[1], c[0]]])?;
         let mut positions: Vec<[f64; 3]> = vec![];
         // make the positions fractional and swap c and a
         match coord {
@@ -256,7 +268,7 @@ impl FileFormat for Vasp {
                 }
             }
         }
-        Atoms::new(lattice, positions, atoms_text)
+        Ok(Atoms::new(lattice, positions, atoms_text)?.with_species(species))
     }
 
     /// Write a CHGCAR from a vector of options where None will be written as zero.
@@ -265,30 +277,22 @@ impl FileFormat for Vasp {
              data: Vec<Option<f64>>,
              filename: String,
              pbar: Bar)
-             -> std::io::Result<()> {
+             -> Result<()> {
         let filename = format!("{}_CHGCAR", filename);
         let mut buffer = BufWriter::new(File::create(filename)?);
         pbar.set_length(data.len() / 5 + (data.len() % 5 != 0) as usize);
         buffer.write_all(atoms.text.as_bytes())?;
-        data.chunks(5).for_each(|line| {
-                          if let Err(e) = line.iter().try_for_each(|f| {
-                                                         write!(
-                    buffer,
-                    " {:.11}",
-                    FortranFormat {
-                        float: *f,
-                        mult: atoms.lattice.volume
-                    }
-                )
-                                                     })
-                          {
-                              panic!("Error occured during write: {}", e)
-                          };
-                          if let Err(e) = writeln!(buffer) {
-                              panic!("Error occured during write: {}", e)
-                          };
-                          pbar.tick();
-                      });
+        let mult = self.write_scale(atoms);
+        data.chunks(5).try_for_each(|line| -> std::io::Result<()> {
+                           for f in line {
+                               write!(buffer,
+                                      " {:.11}",
+                                      FortranFormat { float: *f, mult })?;
+                           }
+                           writeln!(buffer)?;
+                           pbar.tick();
+                           Ok(())
+                       })?;
         Ok(())
     }
 
@@ -299,4 +303,83 @@ impl FileFormat for Vasp {
         let x = format!("{:.6}", coords[2]);
         (x, y, z)
     }
+
+    /// CHGCAR files store the density times the volume of the cell.
+    fn write_scale(&self, atoms: &Atoms) -> f64 {
+        atoms.lattice.volume
+    }
+
+    /// Deals with fortran indexing.
+    fn image_format(&self, image: [isize; 3]) -> String {
+        format!("({}, {}, {})", image[2], image[1], image[0])
+    }
+
+    /// Deals with fortran indexing.
+    fn axes(&self) -> [usize; 3] {
+        [2, 1, 0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POSCAR: &str = "comment
+1.0
+4.0 0.0 0.0
+0.0 4.0 0.0
+0.0 0.0 4.0
+";
+
+    #[test]
+    fn vasp_to_atoms_species() {
+        let text = format!("{}Fe_pv/2d4c2e8a O\n1 2\nDirect\n0 0 0\n0.5 0 0\n0 0.5 0\n\n 2 2 2\n",
+                           POSCAR);
+        let atoms = Vasp {}.to_atoms(text).unwrap();
+        assert_eq!(atoms.species, vec!["Fe", "O", "O"]);
+        assert_eq!(atoms.atomic_numbers, vec![26, 8, 8]);
+    }
+
+    #[test]
+    fn vasp_read_zval() {
+        let potcar = "  PAW_PBE Fe_pv 02Aug2007
+   8.00000000000000000
+ parameters from PSCTR are:
+   VRHFIN =Fe: 3p4s3d
+   TITEL  = PAW_PBE Fe_pv 02Aug2007
+   POMASS =   55.847; ZVAL   =   14.000    mass and valenz
+ End of Dataset
+  PAW_PBE O 08Apr2002
+   6.00000000000000000
+   TITEL  = PAW_PBE O 08Apr2002
+   POMASS =   16.000; ZVAL   =    6.000    mass and valenz
+ End of Dataset
+";
+        let filename = std::env::temp_dir().join(format!("bader-POTCAR-{}",
+                                                         std::process::id()));
+        std::fs::write(&filename, potcar).unwrap();
+        let table = read_zval(&filename.to_string_lossy()).unwrap();
+        assert_eq!(table,
+                   vec![(String::from("Fe"), 14.), (String::from("O"), 6.)]);
+        std::fs::write(&filename, "not a potcar").unwrap();
+        assert!(read_zval(&filename.to_string_lossy()).is_err());
+    }
+
+    #[test]
+    fn vasp_to_atoms_no_species() {
+        let text =
+            format!("{}1 2\nDirect\n0 0 0\n0.5 0 0\n0 0.5 0\n\n 2 2 2\n",
+                    POSCAR);
+        let atoms = Vasp {}.to_atoms(text).unwrap();
+        assert_eq!(atoms.positions.len(), 3);
+        assert!(!atoms.has_species());
+    }
+
+    #[test]
+    fn vasp_to_atoms_malformed() {
+        let text = String::from("comment\n1.0\n4.0 0.0 0.0\n0.0 4.0\n");
+        assert!(matches!(Vasp {}.to_atoms(text), Err(Error::Parse(_))));
+        let text = format!("{}1\nDirect\n0 0\n\n 2 2 2\n", POSCAR);
+        assert!(matches!(Vasp {}.to_atoms(text), Err(Error::Parse(_))));
+    }
 }
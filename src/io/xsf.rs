@@ -0,0 +1,462 @@
+use crate::atoms::{self, Atoms, Lattice};
+use crate::error::{Error, Result};
+use crate::io::{reader, FileFormat, ReadFunction};
+use crate::progress::Bar;
+use crate::utils;
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
+
+/// Convert from Bohr for values given per cubic bohr.
+const LENGTH_UNITS: f64 = 0.52917721067;
+/// Convert from cubic Bohr.
+const VOLUME_UNITS: f64 = LENGTH_UNITS * LENGTH_UNITS * LENGTH_UNITS;
+
+/// The units of the values of an XSF datagrid.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Units {
+    /// Per cubic Angstrom, e/Angstrom^3 for a density.
+    Angstrom,
+    /// Per cubic bohr, e/bohr^3 for a density, as written by Quantum
+    /// ESPRESSO's pp.x.
+    Bohr,
+}
+
+impl std::str::FromStr for Units {
+    type Err = Error;
+
+    /// Parse the name of the units as it is passed on the command-line.
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "angstrom" => Ok(Self::Angstrom),
+            "bohr" => Ok(Self::Bohr),
+            _ => {
+                Err(Error::InvalidArgument(format!("Unknown XSF units ({}).",
+                                                   s)))
+            }
+        }
+    }
+}
+
+/// The XCrySDen structure file format, reading/writing the first
+/// BEGIN_BLOCK_DATAGRID_3D. Lengths are in Angstrom and the values of the grid
+/// are converted from the units given, as the format does not record them, to
+/// be per cubic Angstrom.
+pub struct Xsf {
+    /// The units of the values of the datagrid.
+    pub units: Units,
+}
+
+/// Is the line the start of a 3D datagrid.
+fn is_datagrid(line: &str) -> bool {
+    let line = line.trim();
+    line.starts_with("BEGIN_DATAGRID_3D") || line.starts_with("DATAGRID_3D_")
+}
+
+/// The error for a header that ends too soon.
+fn unexpected_end() -> Error {
+    Error::Parse(String::from("Unexpected end of XSF header."))
+}
+
+/// The error for a file without a datagrid.
+fn no_datagrid() -> Error {
+    Error::Parse(String::from("No BEGIN_DATAGRID_3D found in XSF file."))
+}
+
+/// Parses a vector from a line of the header.
+fn parse_vector(line: Option<&str>) -> Result<[f64; 3]> {
+    let line = line.ok_or_else(unexpected_end)?;
+    let v = line.split_whitespace()
+                .take(3)
+                .map(|x| {
+                    x.parse::<f64>().map_err(|_| {
+                                        Error::Parse(format!("Cannot parse ({}) in line:\n{}", x, line))
+                                    })
+                })
+                .collect::<Result<Vec<f64>>>()?;
+    if v.len() < 3 {
+        return Err(Error::Parse(String::from("Expected a vector in XSF \
+                                              header.")));
+    }
+    Ok([v[0], v[1], v[2]])
+}
+
+/// Parses the position from an atom line, skipping the species.
+fn atom_position(line: &str) -> Result<[f64; 3]> {
+    parse_vector(line.trim_start()
+                     .split_once(char::is_whitespace)
+                     .map(|(_, v)| v))
+}
+
+/// Reads the species from the start of an atom line, given as either the
+/// atomic number or the chemical symbol.
+fn atom_species(line: &str) -> String {
+    match line.split_whitespace().next() {
+        Some(x) => match x.parse::<usize>() {
+            Ok(z) => String::from(atoms::symbol(z)),
+            Err(_) => String::from(x),
+        },
+        None => String::from("X"),
+    }
+}
+
+/// Reads the number of points of the general grid, this includes the
+/// periodic duplicate of the first point along each axis.
+fn grid_points(line: Option<&str>) -> Result<[usize; 3]> {
+    let line = line.ok_or_else(unexpected_end)?;
+    let n = line.split_whitespace()
+                .take(3)
+                .map(|x| match x.parse::<usize>() {
+                    Ok(x) if x > 1 => Ok(x),
+                    _ => Err(Error::Parse(format!("Cannot parse ({}) as XSF \
+                                                   grid size.",
+                                                  x))),
+                })
+                .collect::<Result<Vec<usize>>>()?;
+    if n.len() < 3 {
+        return Err(Error::Parse(String::from("Expected three grid sizes in \
+                                              XSF header.")));
+    }
+    Ok([n[0], n[1], n[2]])
+}
+
+/// Finds the periodic grid of the datagrid header, the general grid without
+/// the duplicated points, in the file's axis order.
+fn periodic_grid(text: &str) -> Result<[usize; 3]> {
+    let mut lines = text.lines().skip_while(|line| !is_datagrid(line));
+    if lines.next().is_none() {
+        return Err(no_datagrid());
+    }
+    let [n1, n2, n3] = grid_points(lines.next())?;
+    Ok([n1 - 1, n2 - 1, n3 - 1])
+}
+
+/// Writes the data of the grid including the periodic duplicates, the first
+/// axis of the file being the fastest.
+///
+/// * `data`: The values of the grid in the order of the [`crate::grid::Grid`].
+/// * `grid`: The size of the grid.
+/// * `axes`: The axis of the grid that is written as the file's axis i.
+/// * `mult`: The factor the values are multiplied by as they are written.
+fn write_datagrid(buffer: &mut impl Write,
+                  data: &[Option<f64>],
+                  grid: [usize; 3],
+                  axes: [usize; 3],
+                  mult: f64,
+                  pbar: Bar)
+                  -> std::io::Result<()> {
+    let n = [grid[axes[0]], grid[axes[1]], grid[axes[2]]];
+    pbar.set_length(n[2] + 1);
+    let mut count = 0;
+    let mut voxel = [0usize; 3];
+    for k in 0..=n[2] {
+        voxel[axes[2]] = k % n[2];
+        for j in 0..=n[1] {
+            voxel[axes[1]] = j % n[1];
+            for i in 0..=n[0] {
+                voxel[axes[0]] = i % n[0];
+                let p = voxel[0] * grid[1] * grid[2]
+                        + voxel[1] * grid[2]
+                        + voxel[2];
+                write!(buffer, " {:.11E}", data[p].unwrap_or(0.) * mult)?;
+                count += 1;
+                if count % 6 == 0 {
+                    writeln!(buffer)?;
+                }
+            }
+        }
+        pbar.tick();
+    }
+    if count % 6 != 0 {
+        writeln!(buffer)?;
+    }
+    writeln!(buffer, "  END_DATAGRID_3D")?;
+    writeln!(buffer, "END_BLOCK_DATAGRID_3D")?;
+    Ok(())
+}
+
+/// Write a grid from any file format as an XSF file, such as the density of a
+/// Bader atom, so that it can be viewed in XCrySDen or VESTA.
+///
+/// The atoms are written as their species and the values of the grid per
+/// cubic Angstrom, as they are held.
+///
+/// * `atoms`: The [`Atoms`] of the structure.
+/// * `data`: The values of the grid with None written as zero.
+/// * `grid`: The size of the grid.
+/// * `voxel_origin`: The position of the first point of the grid, in voxels.
+/// * `axes`: The axes of the file format the grid was read from.
+/// * `filename`: Where to save the file, minus the .xsf suffix.
+/// * `pbar`: A progress bar for monitoring the write.
+pub fn write_grid(atoms: &Atoms,
+                  data: Vec<Option<f64>>,
+                  grid: [usize; 3],
+                  voxel_origin: [f64; 3],
+                  axes: [usize; 3],
+                  filename: String,
+                  pbar: Bar)
+                  -> Result<()> {
+    let filename = format!("{}.xsf", filename);
+    let mut buffer = BufWriter::new(File::create(filename)?);
+    // reorder the lattice vectors and their components back to the file's
+    let file_order = |v: [f64; 3]| [v[axes[0]], v[axes[1]], v[axes[2]]];
+    let lattice = [file_order(atoms.lattice.to_cartesian[axes[0]]),
+                   file_order(atoms.lattice.to_cartesian[axes[1]]),
+                   file_order(atoms.lattice.to_cartesian[axes[2]])];
+    writeln!(buffer, "CRYSTAL")?;
+    writeln!(buffer, "PRIMVEC")?;
+    for v in lattice.iter() {
+        writeln!(buffer, " {:>15.10} {:>15.10} {:>15.10}", v[0], v[1], v[2])?;
+    }
+    writeln!(buffer, "PRIMCOORD")?;
+    writeln!(buffer, " {} 1", atoms.positions.len())?;
+    for (p, species) in atoms.positions.iter().zip(&atoms.species) {
+        let p = file_order(*p);
+        writeln!(buffer,
+                 "{} {:>15.10} {:>15.10} {:>15.10}",
+                 species, p[0], p[1], p[2])?;
+    }
+    writeln!(buffer, "BEGIN_BLOCK_DATAGRID_3D")?;
+    writeln!(buffer, " bader")?;
+    writeln!(buffer, "  BEGIN_DATAGRID_3D_bader")?;
+    writeln!(buffer,
+             "  {} {} {}",
+             grid[axes[0]] + 1,
+             grid[axes[1]] + 1,
+             grid[axes[2]] + 1)?;
+    // the datagrid starts at the first point of the grid
+    let origin = file_order(utils::dot([voxel_origin[0] / grid[0] as f64,
+                                        voxel_origin[1] / grid[1] as f64,
+                                        voxel_origin[2] / grid[2] as f64],
+                                       atoms.lattice.to_cartesian));
+    writeln!(buffer,
+             "  {:.10} {:.10} {:.10}",
+             origin[0], origin[1], origin[2])?;
+    for v in lattice.iter() {
+        writeln!(buffer, "  {:.10} {:.10} {:.10}", v[0], v[1], v[2])?;
+    }
+    write_datagrid(&mut buffer, &data, grid, axes, 1., pbar)?;
+    Ok(())
+}
+
+impl FileFormat for Xsf {
+    /// Read the first datagrid of an XSF file.
+    fn read_from(&self,
+                 source: &mut dyn BufRead,
+                 name: &str,
+                 _threads: usize)
+                 -> ReadFunction {
+        println!("Reading {} as XSF format:", name);
+        if self.units == Units::Angstrom {
+            println!("Reading the values as per cubic Angstrom, set the XSF \
+                      units to bohr if they are per cubic bohr.");
+        }
+        let text = reader::source_to_string(source)?;
+        // the header runs to the end of the spanning vectors of the datagrid,
+        // 5 lines after it begins
+        let mut start = match text.lines().position(is_datagrid) {
+            Some(i) => i + 6,
+            None => return Err(no_datagrid()),
+        };
+        let mut header_end = 0;
+        while start > 0 {
+            header_end += match text[header_end..].find('\n') {
+                Some(i) => i + 1,
+                None => return Err(unexpected_end()),
+            };
+            start -= 1;
+        }
+        let atoms = self.to_atoms(text[..header_end].to_string())?;
+        // the origin is the first point of the grid so put it in voxel units
+        let mut lines = text[..header_end].lines()
+                                          .skip_while(|line| !is_datagrid(line))
+                                          .skip(2);
+        let origin = parse_vector(lines.next())?;
+        let [n1, n2, n3] = periodic_grid(&text[..header_end])?;
+        let grid_pts = [n3, n2, n1];
+        let origin = utils::dot([origin[2], origin[1], origin[0]],
+                                atoms.lattice.to_fractional);
+        let voxel_origin = [origin[0] * n3 as f64,
+                            origin[1] * n2 as f64,
+                            origin[2] * n1 as f64];
+        let values = text[header_end..].split_whitespace()
+                                       .take_while(|x| !x.starts_with("END_"))
+                                       .map(|x| {
+                                           x.parse::<f64>().map_err(|_| {
+                                               Error::Parse(format!("Cannot parse ({}) as XSF data.", x))
+                                           })
+                                       })
+                                       .collect::<Result<Vec<f64>>>()?;
+        if values.len() != (n1 + 1) * (n2 + 1) * (n3 + 1) {
+            return Err(Error::Parse(format!("XSF datagrid has {} values but \
+                                             expected {}.",
+                                            values.len(),
+                                            (n1 + 1) * (n2 + 1) * (n3 + 1))));
+        }
+        // drop the periodic duplicates at the end of each axis
+        let mut density = Vec::with_capacity(n1 * n2 * n3);
+        let volume = match self.units {
+            Units::Angstrom => 1.,
+            Units::Bohr => VOLUME_UNITS,
+        };
+        for k in 0..n3 {
+            for j in 0..n2 {
+                let row = (k * (n2 + 1) + j) * (n1 + 1);
+                density.extend(values[row..(row + n1)].iter()
+                                                      .map(|x| x / volume));
+            }
+        }
+        println!("File read successfully.");
+        Ok((voxel_origin, grid_pts, atoms, vec![density]))
+    }
+
+    /// Read atom information, the lattice is taken from PRIMVEC or, for
+    /// molecules, the spanning vectors of the datagrid.
+    fn to_atoms(&self, atoms_text: String) -> Result<Atoms> {
+        let mut primvec: Option<[[f64; 3]; 3]> = None;
+        let mut span: Option<[[f64; 3]; 3]> = None;
+        let mut cartesian: Vec<[f64; 3]> = vec![];
+        let mut species: Vec<String> = vec![];
+        let mut lines = atoms_text.lines()
+                                  .filter(|line| !line.trim().starts_with('#'))
+                                  .peekable();
+        while let Some(line) = lines.next() {
+            let keyword = line.trim();
+            if keyword.starts_with("PRIMVEC") {
+                primvec = Some([parse_vector(lines.next())?,
+                                parse_vector(lines.next())?,
+                                parse_vector(lines.next())?]);
+            } else if keyword.starts_with("PRIMCOORD") {
+                let nat = match lines.next()
+                                     .and_then(|l| l.split_whitespace().next())
+                                     .map(|x| x.parse::<usize>())
+                {
+                    Some(Ok(nat)) => nat,
+                    _ => {
+                        return Err(Error::Parse(String::from("Cannot read \
+                                                              number of atoms \
+                                                              in XSF file.")))
+                    }
+                };
+                for _ in 0..nat {
+                    let l = lines.next().ok_or_else(unexpected_end)?;
+                    species.push(atom_species(l));
+                    cartesian.push(atom_position(l)?);
+                }
+            } else if keyword.starts_with("ATOMS") {
+                // molecules list the atoms until the next keyword
+                while let Some(l) = lines.peek() {
+                    if l.split_whitespace().count() < 4 {
+                        break;
+                    }
+                    let l = lines.next().ok_or_else(unexpected_end)?;
+                    species.push(atom_species(l));
+                    cartesian.push(atom_position(l)?);
+                }
+            } else if is_datagrid(keyword) {
+                let _ = lines.next();
+                let _ = lines.next();
+                span = Some([parse_vector(lines.next())?,
+                             parse_vector(lines.next())?,
+                             parse_vector(lines.next())?]);
+            }
+        }
+        let [a, b, c] = primvec.or(span).ok_or_else(|| {
+                                            Error::Parse(String::from("No lattice vectors found in XSF file."))
+                                        })?;
+        // density[z, y, x] so lets swap the c and a
+        let lattice = Lattice::new([[c[2], c[1], c[0]],
+                                    [b[2], b[1], b[0]],
+                                    [a[2], a[1], a[0]]])?;
+        let positions = cartesian.iter()
+                                 .map(|p| {
+                                     let f = utils::dot([p[2], p[1], p[0]],
+                                                        lattice.to_fractional);
+                                     utils::dot([f[0].rem_euclid(1.),
+                                                 f[1].rem_euclid(1.),
+                                                 f[2].rem_euclid(1.)],
+                                                lattice.to_cartesian)
+                                 })
+                                 .collect::<Vec<[f64; 3]>>();
+        Ok(Atoms::new(lattice, positions, atoms_text)?.with_species(species))
+    }
+
+    /// Write an XSF file from a vector of options where None will be written
+    /// as zero.
+    fn write(&self,
+             atoms: &Atoms,
+             data: Vec<Option<f64>>,
+             filename: String,
+             pbar: Bar)
+             -> Result<()> {
+        let filename = format!("{}.xsf", filename);
+        let mut buffer = BufWriter::new(File::create(filename)?);
+        buffer.write_all(atoms.text.as_bytes())?;
+        let [n1, n2, n3] = periodic_grid(&atoms.text)?;
+        write_datagrid(&mut buffer,
+                       &data,
+                       [n3, n2, n1],
+                       self.axes(),
+                       self.write_scale(atoms),
+                       pbar)?;
+        Ok(())
+    }
+
+    /// The values are written in the units they were read in.
+    fn write_scale(&self, _atoms: &Atoms) -> f64 {
+        match self.units {
+            Units::Angstrom => 1.,
+            Units::Bohr => VOLUME_UNITS,
+        }
+    }
+
+    /// Deals with fortran indexing.
+    fn coordinate_format(&self, coords: [f64; 3]) -> (String, String, String) {
+        let z = format!("{:.6}", coords[0]);
+        let y = format!("{:.6}", coords[1]);
+        let x = format!("{:.6}", coords[2]);
+        (x, y, z)
+    }
+
+    /// Deals with fortran indexing.
+    fn image_format(&self, image: [isize; 3]) -> String {
+        format!("({}, {}, {})", image[2], image[1], image[0])
+    }
+
+    /// Deals with fortran indexing.
+    fn axes(&self) -> [usize; 3] {
+        [2, 1, 0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xsf_periodic_grid() {
+        let text = "CRYSTAL\nBEGIN_BLOCK_DATAGRID_3D\n test\n \
+                    BEGIN_DATAGRID_3D_test\n 5 6 7\n";
+        assert_eq!(periodic_grid(text).unwrap(), [4, 5, 6]);
+        assert!(periodic_grid("CRYSTAL\n").is_err());
+    }
+
+    #[test]
+    fn xsf_write_datagrid_duplicates() {
+        // a 2x1x1 grid written x-fastest repeats the first point of each axis
+        let data = vec![Some(1.), Some(2.)];
+        let mut buffer = Vec::new();
+        write_datagrid(&mut buffer,
+                       &data,
+                       [2, 1, 1],
+                       [0, 1, 2],
+                       1.,
+                       Bar::new(0, 100, String::new())).unwrap();
+        let values =
+            String::from_utf8(buffer).unwrap()
+                                     .split_whitespace()
+                                     .filter_map(|x| x.parse::<f64>().ok())
+                                     .collect::<Vec<f64>>();
+        assert_eq!(values,
+                   vec![1., 2., 1., 1., 2., 1., 1., 2., 1., 1., 2., 1.]);
+    }
+}
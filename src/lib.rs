@@ -31,35 +31,184 @@
 //! Add the following to your Cargo.toml:
 //! `bader = "0.4.0"`
 //!
+//! A density is partitioned by a
+//! [BaderCalculation](calculation::BaderCalculation), built from the densities,
+//! [Atoms](atoms::Atoms) and grid, which returns the charges and volumes of the
+//! atoms, and optionally the Bader volumes, in a
+//! [BaderResult](calculation::BaderResult).
+//!
+//! The capi feature exports a C interface, declared in include/bader.h, for
+//! partitioning a density held in memory from C or Fortran.
+//!
 //! ### Minimum Supported Rust Version (MSRV)
-//! This crate is guaranteed to compile on stable Rust 1.54.0 and up. It *might*
+//! This crate is guaranteed to compile on stable Rust 1.85.0 and up. It *might*
 //! compile with older versions but that may change in any new patch release.
 //! ## Usage
 //! The program takes a charge density file as input and performs Bader analysis
-//! of the data. Currently it supports density in [VASP] or [cube] formats. It
-//! is recommended to run VASP calculations with [LAECHG] = .TRUE. to print the
-//! core density and self-consistent valence density. These can then be passed
-//! as reference files to the program using the -r, --reference flag where they
-//! will be summed.
+//! of the data. Currently it supports density in [VASP], [cube], Quantum
+//! ESPRESSO [filplot], [XSF], CASTEP [den_fmt], [ABINIT] _DEN or [SIESTA] .RHO
+//! formats. The atoms of CASTEP and SIESTA densities are read from the
+//! seedname.cell and seedname.XV files next to them, and the densities of the
+//! binary ABINIT and SIESTA formats are written as XSF. Files compressed with
+//! gzip, xz, bzip2 or zstd are decompressed as they are read, for the reference
+//! and spin files as well, so `bca CHGCAR.xz -r AECCAR0.gz -r AECCAR2.gz` needs
+//! no temporary files. It is recommended to run VASP calculations with
+//! [LAECHG] = .TRUE. to print the core density and self-consistent valence
+//! density. These can then be passed as reference files to the program using
+//! the -r, --reference flag where they will be summed.
 //! ```sh
 //! $ bca CHGCAR -r AECCAR0 -r AECCAR2
 //! ```
+//! Any one of the files can be read from stdin by naming it -, such as a
+//! density streamed from an archive. Its format cannot be inferred from the
+//! name so should be given with the -t, --type flag.
+//! ```sh
+//! $ tar -xOf run.tar CHGCAR | bca - -t vasp
+//! ```
 //! VASP charge density files containing spin densities will output the the
 //! partitioned spin also. To achieve this for cube files requires using the
 //! --spin flag to pass a second file to treat as the spin density.
 //! ```sh
 //! $ bca charge-density.cube -s spin-density.cube
 //! ```
+//! Cube files with multiple values per voxel, such as the orbital cubes of
+//! Gaussian and ORCA, are read as a channel for each value. The channel to
+//! partition is chosen with the --channel flag and the channels integrated over
+//! the basins with repeated --integrate-channel flags, by default the first
+//! channel is partitioned and every channel integrated.
+//! ```sh
+//! $ bca orbitals.cube --channel 2 --integrate-channel 1 --integrate-channel 2
+//! ```
+//! Other scalar fields on the same grid as the density, such as a LOCPOT,
+//! ELFCAR or the PARCHG of each band, can be integrated over the basins of the
+//! density with repeated --integrate flags. The integral of each field is
+//! written alongside the densities and named after its file. The fields are
+//! read in the same way as the density, so the values of a VASP file are
+//! divided by the volume of the cell.
+//! ```sh
+//! $ bca CHGCAR --integrate PARCHG.0001 --integrate PARCHG.0002
+//! ```
+//! The partitioned voxel map can be saved to a compressed binary file with
+//! the --save-map flag and loaded with the --load-map flag, to partition other
+//! densities on the same basins without finding the maxima and weights again.
+//! The loaded map has to be on the same grid and lattice as the density and be
+//! used at the verbosity it was saved at.
+//! ```sh
+//! $ bca CHGCAR --save-map CHGCAR.map
+//! $ bca PARCHG --load-map CHGCAR.map
+//! ```
+//! The density can be partitioned by the weight (default), ongrid or neargrid
+//! methods using the -b, --method flag.
+//! ```sh
+//! $ bca CHGCAR -b neargrid
+//! ```
+//! The critical points of the reference density can be found and classified
+//! using the --critical flag, these are written to the Critical Point File
+//! (CPF.dat). The bond paths traced from each bond critical point are written
+//! to the Bond Path File (BPF.dat) as the pair of atoms bonded, the lattice
+//! image of the second atom, the length of the path and the density at the
+//! bond critical point.
+//! ```sh
+//! $ bca CHGCAR --critical
+//! ```
+//! The dipole and quadrupole moments of the charge in each atom's Bader volume
+//! can be calculated using the --moments flag, these are measured from the
+//! nucleus and appended to the Atomic Charge File (ACF.dat).
+//! ```sh
+//! $ bca CHGCAR --moments
+//! ```
+//! Rather than writing the densities of the Bader atoms or volumes selected
+//! with the -o, --output and -i, --index flags, their zero-flux surfaces can be
+//! written as triangulated meshes in the OBJ, PLY or STL format using the
+//! --surface flag.
+//! ```sh
+//! $ bca CHGCAR -o atoms -i 1 --surface obj
+//! ```
+//! The densities of the selected Bader atoms or volumes can instead be written
+//! as XSF files, whatever the format of the input, using the --xsf flag. These
+//! can be opened directly in XCrySDen or VESTA. The values are written per
+//! cubic Angstrom and the datagrid starts at the first voxel of the input, half
+//! a voxel in from the corner of the cell for cube files.
+//! ```sh
+//! $ bca CHGCAR -o atoms -i 1 --xsf
+//! ```
+//! XSF files do not record the units of their values, which are read as per
+//! cubic Angstrom. The XSF files written by Quantum ESPRESSO's pp.x are per
+//! cubic bohr and need the --xsf-units flag to give the right charges.
+//! ```sh
+//! $ bca charge.xsf --xsf-units bohr
+//! ```
+//! A single grid labelling each voxel with the index of the Bader atom or
+//! volume it belongs to, as the Henkelman group's bader program can write, is
+//! written in the format of the input with the --index-map flag. Voxels split
+//! between atoms or volumes take the one with the largest weight, which can be
+//! written to a second grid with the --index-weight flag. These can be used to
+//! colour isosurfaces in VESTA.
+//! ```sh
+//! $ bca CHGCAR --index-map atoms --index-weight
+//! ```
+//! The area of the zero-flux surface shared between each pair of atoms, and the
+//! total surface area of each atom, can be calculated using the --contacts flag.
+//! These are written as a contact-area matrix to the Contact Area File
+//! (CAF.dat).
+//! ```sh
+//! $ bca CHGCAR --contacts
+//! ```
+//! The net charge of each atom, its valence minus its partitioned electrons, is
+//! added to the outputs when the valence of each element is given. The valence
+//! can be read from the ZVAL of a POTCAR with the --potcar flag, given for each
+//! element with repeated --zval flags, which replace any read from a POTCAR, or
+//! taken as the nuclear charge for all-electron densities with the
+//! --nuclear-charge flag.
+//! ```sh
+//! $ bca CHGCAR --potcar POTCAR --zval O=6
+//! ```
+//! The Atomic Charge File (ACF.dat) and Bader Charge File (BCF.dat) can instead
+//! be written with the same layout as the Henkelman group's bader program, along
+//! with its Atomic Volume File (AVF.dat), using the --henkelman flag. This allows
+//! bca to be used by tools that parse those files, such as pymatgen. Only the
+//! charge is written, not the net charge, and the Bader volumes with a charge
+//! below the maxima tolerance, set with -m, are left out of BCF.dat and AVF.dat.
+//! ```sh
+//! $ bca CHGCAR --henkelman
+//! ```
+//! The results can be written in machine-readable forms as well as the ACF.dat
+//! and BCF.dat tables. The --json flag writes everything the run computes,
+//! along with the version, settings, input files and timings of the run, to
+//! results.json and the --csv flag writes the atoms and Bader maxima to ACF.csv
+//! and BCF.csv. The charge and volume of each Bader volume are included when run
+//! with -v.
+//! ```sh
+//! $ bca CHGCAR --json --csv
+//! ```
 //! For a detailed list of usage options run
 //! ```sh
 //! $ bca --help
 //! ```
+//! ### Exit Codes
+//! When the program fails it prints the error and exits with a code for the
+//! kind of error.
+//!
+//! | Code | Error |
+//! |---|---|
+//! | 1 | Any other error |
+//! | 2 | Invalid or conflicting arguments |
+//! | 3 | Reading or writing a file failed |
+//! | 4 | A file could not be parsed |
+//! | 5 | An unsupported layout of a file format |
+//! | 6 | A density on a different grid to the one being partitioned |
+//! | 7 | A calculation could not be carried out |
+//! | 8 | A thread panicked |
+//!
 //! ## Output
 //! The program outputs two files, ACF.dat & BCF.dat. The Atomic Charge File
 //! (ACF.dat) contians the charge (and spin) information for each atom and the
 //! Bader Charge File (BCF.dat) contains the information about each Bader volume.
 //! The BCF file also includes the atom number in the number column formatted as
-//! 'atom number: bader volume'.
+//! 'atom number: bader volume'. When the species of the atoms are known, read
+//! from the species line of a VASP file, the atomic numbers of a cube file or
+//! the atoms of an XSF file, the ACF file has an element column and the charge
+//! and volume of each element are totalled in its footer.
 //! ## License
 //! MIT
 //!
@@ -67,6 +216,11 @@
 //!
 //! [VASP]: <https://www.vasp.at/>
 //! [cube]: <https://gaussian.com/>
+//! [filplot]: <https://www.quantum-espresso.org/Doc/INPUT_PP.html>
+//! [XSF]: <http://www.xcrysden.org/doc/XSF.html>
+//! [den_fmt]: <http://www.tcm.phy.cam.ac.uk/castep/utilities/>
+//! [ABINIT]: <https://www.abinit.org/>
+//! [SIESTA]: <https://siesta-project.org/siesta/>
 //! [LAECHG]: <https://www.vasp.at/wiki/index.php/LAECHG>
 //! [Yu Min  and Trinkle Dallas R. 2011  J. Che.m Phys. 134 064111]: <https://doi.org/10.1063/1.3553716>
 //! [cargo]: <https://doc.rust-lang.org/cargo/getting-started/installation.html>
@@ -81,6 +235,23 @@ pub mod arguments;
 /// [ReducedLattice](atoms::ReducedLattice) for storing information about the
 /// cell in which the density is stored.
 pub mod atoms;
+/// Traces the [BondPath](bond_paths::BondPath)s from the bond critical points
+/// to the atoms they connect.
+pub mod bond_paths;
+/// Provides [BaderCalculation](calculation::BaderCalculation) for running a
+/// whole partition of a density and collecting a
+/// [BaderResult](calculation::BaderResult).
+pub mod calculation;
+/// A C interface for partitioning a density held in memory, built with the
+/// capi feature.
+#[cfg(feature = "capi")]
+pub mod capi;
+/// Finds and classifies the [CriticalPoint](critical_points::CriticalPoint)s of
+/// the density by the signature of the interpolated Hessian.
+pub mod critical_points;
+/// The [Error](error::Error) returned by the library when a density cannot be
+/// read, partitioned or written.
+pub mod error;
 /// Contains [Grid](grid::Grid) for managing the movement around the grid on
 /// which the density is stored.
 pub mod grid;
@@ -94,6 +265,13 @@ pub mod io;
 pub mod methods;
 /// Provides [Bar](progress::Bar): A quicker thread-safe version of the [indicatif::ProgressBar].
 pub mod progress;
+/// Python bindings for partitioning NumPy arrays and density files, built
+/// with the python feature.
+#[cfg(feature = "python")]
+mod python;
+/// Extracts the zero-flux [Mesh](surface::Mesh) of a Bader volume or atom using
+/// marching cubes.
+pub mod surface;
 /// Misc functions mainly for vector and matrix manipulation.
 pub mod utils;
 /// Calculates the Voronoi vectors, and their alpha values for the weight method,
@@ -102,3 +280,5 @@ pub mod voronoi;
 /// Provides the [VoxelMap](voxel_map::VoxelMap) for storing the maxima and weights of
 /// partioned voxels.
 pub mod voxel_map;
+
+pub use error::{Error, Result};
@@ -1,9 +1,50 @@
+use crate::error::{Error, Result};
+use crate::grid::Grid;
 use crate::progress::Bar;
+use crate::utils::dot;
 use crate::voxel_map::BlockingVoxelMap as VoxelMap;
-use anyhow::Result;
 use atomic_counter::{AtomicCounter, RelaxedCounter};
 use crossbeam_utils::thread;
 use rustc_hash::FxHashMap;
+use std::sync::atomic::{AtomicIsize, Ordering};
+
+/// The available methods for partitioning the density.
+#[derive(Clone, Copy)]
+pub enum Method {
+    /// Steepest ascent on the grid, restricted to the neighbouring voxels.
+    Ongrid,
+    /// Gradient ascent with a correction vector to remove the grid bias.
+    Neargrid,
+    /// Flux weighted partitioning of the boundary voxels.
+    Weight,
+}
+
+impl std::fmt::Display for Method {
+    /// Write the name of the method as it is passed on the command-line.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ongrid => f.write_str("ongrid"),
+            Self::Neargrid => f.write_str("neargrid"),
+            Self::Weight => f.write_str("weight"),
+        }
+    }
+}
+
+impl std::str::FromStr for Method {
+    type Err = Error;
+
+    /// Parse the name of the method as it is passed on the command-line.
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "ongrid" => Ok(Self::Ongrid),
+            "neargrid" => Ok(Self::Neargrid),
+            "weight" => Ok(Self::Weight),
+            _ => {
+                Err(Error::InvalidArgument(format!("Unknown method ({}).", s)))
+            }
+        }
+    }
+}
 
 pub enum WeightResult {
     Maxima,
@@ -36,7 +77,7 @@ pub enum WeightResult {
 ///                      .collect::<Vec<f64>>();
 /// let voxel_map = VoxelMap::new([4, 4, 4],
 ///                               [[3.0, 0.0, 0.0], [0.0, 3.0, 0.0], [0.0, 0.0, 3.0]],
-///                               [0.0, 0.0, 0.0]);
+///                               [0.0, 0.0, 0.0]).unwrap();
 /// // The highest gradient between point, p = 33, and it's neighbours, with
 /// // periodic boundary conditions, is with point p = 61.
 ///
@@ -104,7 +145,7 @@ pub fn weight_step(p: isize,
                                      })
                                      .collect::<Vec<(usize, f64)>>();
             if weights.len() > 1 {
-                weights.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+                weights.sort_by(|a, b| b.1.total_cmp(&a.1));
                 // re-adjust the weights
                 let mut weights =
                     weights.iter()
@@ -125,6 +166,8 @@ pub fn weight_step(p: isize,
 
 /// Assigns a maxima to the points within index.
 ///
+/// Returns a [`Error::Numerical`] if a point in index turns out to be a maxima.
+///
 /// Note: This function will deadlock if the points above it have no associated
 /// maxima in [`VoxelMap.voxel_map`]. As such make sure index is sorted.
 pub fn weight(density: &[f64],
@@ -132,22 +175,26 @@ pub fn weight(density: &[f64],
               index: &[usize],
               progress_bar: Bar,
               threads: usize,
-              weight_tolerance: f64) {
+              weight_tolerance: f64)
+              -> Result<()> {
     let counter = RelaxedCounter::new(0);
+    let new_maxima = AtomicIsize::new(-1);
     thread::scope(|s| {
         // Assign the remaining voxels to Bader maxima
         for _ in 0..threads {
             s.spawn(|_| loop {
                  let p = {
                      let i = counter.inc();
-                     if i >= index.len() {
+                     if i >= index.len()
+                        || new_maxima.load(Ordering::Relaxed) >= 0
+                     {
                          break;
                      };
                      index[i] as isize
                  };
                  match weight_step(p, density, voxel_map, weight_tolerance) {
                      WeightResult::Maxima => {
-                         panic!("Found new maxima in voxel assigning.")
+                         found_maxima(p, voxel_map, &new_maxima);
                      }
                      WeightResult::Interier(maxima) => {
                          voxel_map.maxima_store(p, maxima as isize);
@@ -165,11 +212,31 @@ pub fn weight(density: &[f64],
                  progress_bar.tick();
              });
         }
-    }).unwrap();
+    }).map_err(|_| Error::Thread(String::from("weight")))?;
     {
         let mut weights = voxel_map.lock();
         weights.shrink_to_fit();
     }
+    new_maxima_result(&new_maxima)
+}
+
+/// Stores a point that has no higher neighbours, found whilst assigning the
+/// voxels, so that the other threads stop and none are left waiting on it.
+fn found_maxima(p: isize, voxel_map: &VoxelMap, new_maxima: &AtomicIsize) {
+    new_maxima.store(p, Ordering::Relaxed);
+    voxel_map.maxima_store(p, 0);
+}
+
+/// Turns a maxima found whilst assigning the voxels into an error.
+fn new_maxima_result(new_maxima: &AtomicIsize) -> Result<()> {
+    match new_maxima.load(Ordering::Relaxed) {
+        p if p >= 0 => {
+            Err(Error::Numerical(format!("Found a new maxima, at voxel {}, \
+                                          whilst assigning the voxels.",
+                                         p)))
+        }
+        _ => Ok(()),
+    }
 }
 
 /// Find (and remove from a sorted index) the maxima within the charge density
@@ -183,7 +250,7 @@ pub fn maxima_finder(index: &mut Vec<usize>,
     let pbar = &progress_bar;
     let index_len = index.len();
     let chunk_size = (index_len / threads) + (index_len % threads).min(1);
-    thread::scope(|s| {
+    thread::scope(|s| -> Result<()> {
         // Identify all the maxima
         let th = index.chunks_mut(chunk_size)
                       .map(|chunk| {
@@ -214,14 +281,227 @@ pub fn maxima_finder(index: &mut Vec<usize>,
                       })
                       .collect::<Vec<_>>();
         for thread in th {
-            if let Ok(mut maxima_list) = thread.join() {
-                bader_maxima.append(&mut maxima_list);
-            } else {
-                panic!("Failed to join thread in maxima finder.")
-            };
+            let mut maxima_list =
+                thread.join().map_err(|_| {
+                                  Error::Thread(String::from("maxima_finder"))
+                              })?;
+            bader_maxima.append(&mut maxima_list);
         }
-    }).unwrap();
+        Ok(())
+    }).map_err(|_| Error::Thread(String::from("maxima_finder")))??;
     // remove the index of the maxima
     index.retain(|&i| i != index_len);
     Ok(bader_maxima)
 }
+
+/// Steps in the density grid, from point p, to the neighbouring voxel with the
+/// steepest ascent. Neighbouring voxels are the surrounding 26 voxels and the
+/// Voronoi neighbours, so that p is only a maxima if [`maxima_finder()`] would
+/// find it to be, and the gradient is the density difference over the distance
+/// to that voxel.
+///
+/// * `p`: The point from which to step.
+/// * `density`: The reference density.
+/// * `grid`: The [`Grid`] the density is stored on.
+///
+/// ### Returns:
+/// `isize`: The point stepped to, if this is p then p is a maxima.
+///
+/// # Examples
+/// ```
+/// use bader::grid::Grid;
+/// use bader::methods::ongrid_step;
+///
+/// let density = (0..64).map(|rho| rho as f64).collect::<Vec<f64>>();
+/// let grid = Grid::new([4, 4, 4],
+///                      [[3.0, 0.0, 0.0], [0.0, 3.0, 0.0], [0.0, 0.0, 3.0]],
+///                      [0.0, 0.0, 0.0]).unwrap();
+/// // The steepest ascent from p = 21 is in the +x direction to p = 37.
+/// assert_eq!(ongrid_step(21, &density, &grid), 37);
+/// // p = 63 is the maxima of the density.
+/// assert_eq!(ongrid_step(63, &density, &grid), 63);
+/// ```
+pub fn ongrid_step(p: isize, density: &[f64], grid: &Grid) -> isize {
+    let control = density[p as usize];
+    let mut max_gradient = 0.;
+    let mut pn = p;
+    let full_shift = grid.full_shift(p);
+    let neighbours = full_shift.iter()
+                               .zip(&grid.voxel_lattice.distance_matrix)
+                               .map(|(pt, distance)| (p + pt, *distance));
+    // in skewed cells the Voronoi neighbours reach beyond the 26 voxels
+    let voronoi = grid.voronoi_shifts(p)
+                      .into_iter()
+                      .zip(&grid.voronoi.distances)
+                      .map(|((pt, _), distance)| (pt, *distance));
+    for (pt, distance) in neighbours.chain(voronoi) {
+        let gradient = (density[pt as usize] - control) / distance;
+        if gradient > max_gradient {
+            max_gradient = gradient;
+            pn = pt;
+        }
+    }
+    pn
+}
+
+/// Steps in the density grid, from point p, following the gradient. The
+/// difference between the true gradient and the step on the grid is accumulated
+/// in `dr` and a further step is taken once this correction is large enough.
+///
+/// If the step does not increase the density then an [`ongrid_step()`] is taken
+/// and the correction is reset.
+///
+/// * `p`: The point from which to step.
+/// * `dr`: The correction vector of the current trajectory in voxel units.
+/// * `density`: The reference density.
+/// * `grid`: The [`Grid`] the density is stored on.
+///
+/// ### Returns:
+/// `isize`: The point stepped to, if this is p then p is a maxima.
+///
+/// # Examples
+/// ```
+/// use bader::grid::Grid;
+/// use bader::methods::neargrid_step;
+///
+/// // A density that increases along the z-axis and is peaked at z = 2.
+/// let density = (0..64).map(|rho| -(((rho % 4) as f64) - 2.).powi(2))
+///                      .collect::<Vec<f64>>();
+/// let grid = Grid::new([4, 4, 4],
+///                      [[3.0, 0.0, 0.0], [0.0, 3.0, 0.0], [0.0, 0.0, 3.0]],
+///                      [0.0, 0.0, 0.0]).unwrap();
+/// let mut dr = [0.; 3];
+/// assert_eq!(neargrid_step(21, &mut dr, &density, &grid), 22);
+/// assert_eq!(dr, [0.; 3]);
+/// ```
+pub fn neargrid_step(p: isize,
+                     dr: &mut [f64; 3],
+                     density: &[f64],
+                     grid: &Grid)
+                     -> isize {
+    let control = density[p as usize];
+    // [ +x, -x, +y, -y, +z, -z ]
+    let shift = grid.reduced_shift(p);
+    let gradient =
+        [(density[(p + shift[0]) as usize] - density[(p + shift[1]) as usize])
+         / 2.,
+         (density[(p + shift[2]) as usize] - density[(p + shift[3]) as usize])
+         / 2.,
+         (density[(p + shift[4]) as usize] - density[(p + shift[5]) as usize])
+         / 2.];
+    // convert the gradient in to a step in the voxel basis
+    let step = dot(gradient, grid.voxel_lattice.gradient_transform);
+    let max_step = step.iter().fold(0f64, |max, s| max.max(s.abs()));
+    if max_step > 0. {
+        let mut step_grid = [0f64; 3];
+        for i in 0..3 {
+            let s = step[i] / max_step;
+            step_grid[i] = s.round();
+            dr[i] += s - step_grid[i];
+        }
+        let mut pn = p + grid.gradient_shift(p, step_grid);
+        // apply the correction if it has grown larger than half a voxel
+        let correction = [dr[0].round(), dr[1].round(), dr[2].round()];
+        if correction.iter().any(|c| *c != 0.) {
+            pn += grid.gradient_shift(pn, correction);
+            for i in 0..3 {
+                dr[i] -= correction[i];
+            }
+        }
+        if density[pn as usize] > control {
+            return pn;
+        }
+    }
+    // the gradient step failed so reset and step on the grid
+    *dr = [0.; 3];
+    ongrid_step(p, density, grid)
+}
+
+/// Assigns a maxima to the points within index by steepest ascent on the grid.
+///
+/// Returns a [`Error::Numerical`] if a point in index turns out to be a maxima.
+///
+/// Note: This function will deadlock if the points above it have no associated
+/// maxima in [`VoxelMap.voxel_map`]. As such make sure index is sorted.
+pub fn ongrid(density: &[f64],
+              voxel_map: &VoxelMap,
+              index: &[usize],
+              progress_bar: Bar,
+              threads: usize)
+              -> Result<()> {
+    let counter = RelaxedCounter::new(0);
+    let new_maxima = AtomicIsize::new(-1);
+    thread::scope(|s| {
+        for _ in 0..threads {
+            s.spawn(|_| loop {
+                 let p = {
+                     let i = counter.inc();
+                     if i >= index.len()
+                        || new_maxima.load(Ordering::Relaxed) >= 0
+                     {
+                         break;
+                     };
+                     index[i] as isize
+                 };
+                 let pt = ongrid_step(p, density, &voxel_map.grid);
+                 if pt == p {
+                     found_maxima(p, voxel_map, &new_maxima);
+                 } else {
+                     voxel_map.maxima_store(p, voxel_map.maxima_get(pt));
+                 }
+                 progress_bar.tick();
+             });
+        }
+    }).map_err(|_| Error::Thread(String::from("ongrid")))?;
+    new_maxima_result(&new_maxima)
+}
+
+/// Assigns a maxima to the points within index by following the near-grid
+/// trajectory from each point until it terminates at a maxima.
+///
+/// Returns a [`Error::Numerical`] if a point in index turns out to be a maxima.
+///
+/// Note: This function will deadlock if the points above it have no associated
+/// maxima in [`VoxelMap.voxel_map`]. As such make sure index is sorted.
+pub fn neargrid(density: &[f64],
+                voxel_map: &VoxelMap,
+                index: &[usize],
+                progress_bar: Bar,
+                threads: usize)
+                -> Result<()> {
+    let counter = RelaxedCounter::new(0);
+    let new_maxima = AtomicIsize::new(-1);
+    thread::scope(|s| {
+        for _ in 0..threads {
+            s.spawn(|_| loop {
+                 let p = {
+                     let i = counter.inc();
+                     if i >= index.len()
+                        || new_maxima.load(Ordering::Relaxed) >= 0
+                     {
+                         break;
+                     };
+                     index[i] as isize
+                 };
+                 // follow the trajectory until it can no longer ascend
+                 let mut dr = [0f64; 3];
+                 let mut pt = p;
+                 loop {
+                     let pn =
+                         neargrid_step(pt, &mut dr, density, &voxel_map.grid);
+                     if pn == pt {
+                         break;
+                     }
+                     pt = pn;
+                 }
+                 if pt == p {
+                     found_maxima(p, voxel_map, &new_maxima);
+                 } else {
+                     voxel_map.maxima_store(p, voxel_map.maxima_get(pt));
+                 }
+                 progress_bar.tick();
+             });
+        }
+    }).map_err(|_| Error::Thread(String::from("neargrid")))?;
+    new_maxima_result(&new_maxima)
+}
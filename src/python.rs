@@ -0,0 +1,354 @@
+use crate::atoms::{Atoms, Lattice};
+use crate::calculation::{BaderCalculation, BaderResult};
+use crate::error::{Error, Result};
+use crate::io::FileType;
+use crate::methods::Method;
+use numpy::{
+    AllowTypeChange, IntoPyArray, PyArray2, PyArrayLike2, PyArrayLike3,
+    PyArrayLikeDyn, PyArrayMethods,
+};
+use pyo3::exceptions::{PyOSError, PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// I/O errors are raised as an OSError, failed calculations as a
+/// RuntimeError and every other error as a ValueError.
+impl From<Error> for PyErr {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Io(_) => PyOSError::new_err(e.to_string()),
+            Error::Numerical(_) | Error::Thread(_) => {
+                PyRuntimeError::new_err(e.to_string())
+            }
+            _ => PyValueError::new_err(e.to_string()),
+        }
+    }
+}
+
+/// The settings of a calculation that are passed from Python.
+struct Settings {
+    method: Method,
+    weight_tolerance: f64,
+    vacuum_tolerance: Option<f64>,
+    threads: usize,
+    volumes: bool,
+}
+
+impl Settings {
+    fn new(method: &str,
+           weight_tolerance: f64,
+           vacuum_tolerance: Option<f64>,
+           threads: usize,
+           volumes: bool)
+           -> Result<Self> {
+        // zero threads uses a thread for each cpu, up to 12, as bca does
+        let threads = match threads {
+            0 => num_cpus::get().min(12),
+            x => x,
+        };
+        Ok(Self { method: method.parse()?,
+                  weight_tolerance,
+                  vacuum_tolerance,
+                  threads,
+                  volumes })
+    }
+}
+
+/// The partitioned densities, ready to be turned into NumPy arrays once the
+/// interpreter is held again.
+struct Partitioned {
+    result: BaderResult,
+    /// The index of the atom, or Bader volume, with the largest weight at each
+    /// voxel, -1 for vacuum, in the order of the returned grid.
+    index: Vec<isize>,
+    /// The shape of the returned grid.
+    shape: [usize; 3],
+    /// The positions of the atoms in the order of the returned axes.
+    positions: Vec<[f64; 3]>,
+}
+
+/// Partitions the densities and labels each voxel with its partition, with the
+/// axes of the grid and positions reordered by axes, as the
+/// [`FileFormat`](crate::io::FileFormat) of a file does.
+fn run(densities: &[Vec<f64>],
+       reference: Option<&[f64]>,
+       atoms: &Atoms,
+       grid: [usize; 3],
+       voxel_origin: [f64; 3],
+       axes: [usize; 3],
+       settings: &Settings)
+       -> Result<Partitioned> {
+    let mut calculation =
+        BaderCalculation::new(densities, atoms, grid, voxel_origin)
+            .with_method(settings.method)
+            .with_weight_tolerance(settings.weight_tolerance)
+            .with_vacuum_tolerance(settings.vacuum_tolerance)
+            .with_threads(settings.threads)
+            .with_volumes(settings.volumes);
+    if let Some(reference) = reference {
+        calculation = calculation.with_reference(reference);
+    }
+    let result = calculation.run()?;
+    let dominant = result.voxel_map.dominant_map(None);
+    let shape = [grid[axes[0]], grid[axes[1]], grid[axes[2]]];
+    let mut index = vec![-1; dominant.len()];
+    for (p, d) in dominant.iter().enumerate() {
+        let voxel =
+            [p / (grid[1] * grid[2]), p / grid[2] % grid[1], p % grid[2]];
+        let q = (voxel[axes[0]] * shape[1] + voxel[axes[1]]) * shape[2]
+                + voxel[axes[2]];
+        index[q] = d.map_or(-1, |(i, _)| i as isize);
+    }
+    let positions = atoms.positions
+                         .iter()
+                         .map(|p| [p[axes[0]], p[axes[1]], p[axes[2]]])
+                         .collect();
+    Ok(Partitioned { result,
+                     index,
+                     shape,
+                     positions })
+}
+
+/// Converts the partitioned densities into a dictionary of NumPy arrays.
+fn to_dict(py: Python<'_>,
+           partitioned: Partitioned)
+           -> PyResult<Bound<'_, PyDict>> {
+    let Partitioned { result,
+                      index,
+                      shape,
+                      positions, } = partitioned;
+    let dict = PyDict::new(py);
+    let matrix = |v: &[Vec<f64>]| {
+        PyArray2::from_vec2(py, v).map_err(|e| {
+                                      PyValueError::new_err(e.to_string())
+                                  })
+    };
+    let positions = positions.iter().map(|p| p.to_vec()).collect::<Vec<_>>();
+    dict.set_item("positions", matrix(&positions)?)?;
+    dict.set_item("charges", matrix(&result.atoms.density)?)?;
+    dict.set_item("volumes", result.atoms.volume.into_pyarray(py))?;
+    dict.set_item("surface_distance",
+                  result.surface_distance.into_pyarray(py))?;
+    dict.set_item("atom_map", result.atom_map.into_pyarray(py))?;
+    dict.set_item("maxima_distance", result.minimum_distance.into_pyarray(py))?;
+    if let Some(volumes) = result.volumes {
+        dict.set_item("bader_charges", matrix(&volumes.density)?)?;
+        dict.set_item("bader_volumes", volumes.volume.into_pyarray(py))?;
+    }
+    dict.set_item("total", result.total_density.into_pyarray(py))?;
+    dict.set_item("basin_index", index.into_pyarray(py).reshape(shape)?)?;
+    Ok(dict)
+}
+
+/// Partition a density given as NumPy arrays.
+///
+/// The density is an array of shape (n0, n1, n2) on a grid spanning the
+/// lattice, whose rows are the lattice vectors along each axis of the array,
+/// or a stack of such densities of shape (m, n0, n1, n2) where the first is
+/// partitioned. The positions of the atoms are cartesian and in the same units
+/// as the lattice. The reference, if given, is partitioned instead.
+///
+/// Returns a dictionary with the positions of the atoms, the integrated
+/// densities ("charges") and volumes of the atoms, the atom each Bader maximum
+/// is assigned to ("atom_map") and the index of the partition with the largest
+/// weight at each voxel, -1 for vacuum ("basin_index"). The partitions are the
+/// atoms, or the Bader volumes if volumes is set, when the charges and volumes
+/// of the Bader volumes are also returned.
+#[pyfunction]
+#[pyo3(signature = (density, lattice, positions, *, reference=None,
+                    voxel_origin=[0.0; 3], method="weight",
+                    weight_tolerance=1E-8, vacuum_tolerance=None, threads=0,
+                    volumes=false))]
+#[allow(clippy::too_many_arguments)]
+fn partition<'py>(py: Python<'py>,
+                  density: PyArrayLikeDyn<'py, f64, AllowTypeChange>,
+                  lattice: PyArrayLike2<'py, f64, AllowTypeChange>,
+                  positions: PyArrayLike2<'py, f64, AllowTypeChange>,
+                  reference: Option<PyArrayLike3<'py, f64, AllowTypeChange>>,
+                  voxel_origin: [f64; 3],
+                  method: &str,
+                  weight_tolerance: f64,
+                  vacuum_tolerance: Option<f64>,
+                  threads: usize,
+                  volumes: bool)
+                  -> PyResult<Bound<'py, PyDict>> {
+    let settings = Settings::new(method,
+                                 weight_tolerance,
+                                 vacuum_tolerance,
+                                 threads,
+                                 volumes)?;
+    let density = density.as_array();
+    let shape = density.shape();
+    let grid = match shape.len() {
+        3 | 4 => {
+            let n = shape.len();
+            [shape[n - 3], shape[n - 2], shape[n - 1]]
+        }
+        n => {
+            return Err(PyValueError::new_err(format!("The density should \
+                                                      have 3 or 4 \
+                                                      dimensions, not {}.",
+                                                     n)))
+        }
+    };
+    // copy the arrays out in row-major order so the interpreter can be
+    // released whilst they are partitioned
+    let values = density.iter().copied().collect::<Vec<f64>>();
+    let densities = values.chunks(grid.iter().product::<usize>().max(1))
+                          .map(|d| d.to_vec())
+                          .collect::<Vec<Vec<f64>>>();
+    let reference = match reference {
+        Some(r) if r.as_array().shape() != grid => {
+            return Err(PyValueError::new_err(format!("The reference has a \
+                                                      shape of {:?} but the \
+                                                      density has {:?}.",
+                                                     r.as_array().shape(),
+                                                     grid)))
+        }
+        Some(r) => Some(r.as_array().iter().copied().collect::<Vec<f64>>()),
+        None => None,
+    };
+    let vectors = |a: &PyArrayLike2<'py, f64, AllowTypeChange>, name: &str| {
+        let a = a.as_array();
+        if a.ncols() != 3 {
+            return Err(PyValueError::new_err(format!("The {} should have 3 \
+                                                      columns.",
+                                                     name)));
+        }
+        Ok(a.rows()
+            .into_iter()
+            .map(|r| [r[0], r[1], r[2]])
+            .collect::<Vec<[f64; 3]>>())
+    };
+    let lattice = vectors(&lattice, "lattice")?;
+    if lattice.len() != 3 {
+        return Err(PyValueError::new_err("The lattice should have 3 rows."));
+    }
+    let atoms = Atoms::new(Lattice::new([lattice[0], lattice[1],
+                                         lattice[2]])?,
+                           vectors(&positions, "positions")?,
+                           String::new())?;
+    let partitioned = py.detach(|| {
+                            run(&densities,
+                                reference.as_deref(),
+                                &atoms,
+                                grid,
+                                voxel_origin,
+                                [0, 1, 2],
+                                &settings)
+                        })?;
+    to_dict(py, partitioned)
+}
+
+/// Partition the density of a file.
+///
+/// The file type is inferred from the filename if it isn't given, as one of
+/// vasp, cube, qe, xsf, castep, abinit or siesta. Any reference files are
+/// summed and partitioned instead of the density. The values of an XSF file
+/// are read per cubic Angstrom unless xsf_units is "bohr". The arrays
+/// returned, as for partition, follow the axes of the file.
+#[pyfunction]
+#[pyo3(signature = (filename, *, file_type=None, reference=Vec::new(),
+                    method="weight", weight_tolerance=1E-8,
+                    vacuum_tolerance=None, threads=0, volumes=false,
+                    xsf_units="angstrom"))]
+#[allow(clippy::too_many_arguments)]
+fn partition_file<'py>(py: Python<'py>,
+                       filename: String,
+                       file_type: Option<&str>,
+                       reference: Vec<String>,
+                       method: &str,
+                       weight_tolerance: f64,
+                       vacuum_tolerance: Option<f64>,
+                       threads: usize,
+                       volumes: bool,
+                       xsf_units: &str)
+                       -> PyResult<Bound<'py, PyDict>> {
+    let settings = Settings::new(method,
+                                 weight_tolerance,
+                                 vacuum_tolerance,
+                                 threads,
+                                 volumes)?;
+    let mut file_type = match file_type {
+        Some(t) => t.parse::<FileType>()?,
+        None => FileType::from_filename(&filename).ok_or_else(|| {
+                    PyValueError::new_err(format!("Cannot infer the file type \
+                                                   of {}.",
+                                                  filename))
+                })?,
+    };
+    if let FileType::Xsf(units) = &mut file_type {
+        *units = xsf_units.parse()?;
+    }
+    let partitioned =
+        py.detach(|| -> Result<Partitioned> {
+              let format = file_type.format();
+              let (voxel_origin, grid, atoms, densities) =
+                  format.read(filename, settings.threads)?;
+              let mut rho: Option<Vec<f64>> = None;
+              for f in reference.iter() {
+                  let (_, g, _, d) = format.read(f.clone(), settings.threads)?;
+                  if g != grid {
+                      return Err(Error::GridMismatch { source: f.clone(),
+                                                       expected: grid,
+                                                       found: g });
+                  }
+                  rho = Some(match rho {
+                                 Some(r) => r.iter()
+                                             .zip(&d[0])
+                                             .map(|(a, b)| a + b)
+                                             .collect(),
+                                 None => d[0].clone(),
+                             });
+              }
+              run(&densities,
+                  rho.as_deref(),
+                  &atoms,
+                  grid,
+                  voxel_origin,
+                  format.axes(),
+                  &settings)
+          })?;
+    to_dict(py, partitioned)
+}
+
+/// Multi-threaded Bader charge partitioning.
+#[pymodule]
+fn bader(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    m.add_function(wrap_pyfunction!(partition, m)?)?;
+    m.add_function(wrap_pyfunction!(partition_file, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_reversed_axes() {
+        // a 2x2x4 grid, with each half along the last axis an atom's basin
+        let lattice = [[2., 0., 0.], [0., 2., 0.], [0., 0., 4.]];
+        let atoms = Atoms::new(Lattice::new(lattice).unwrap(),
+                               vec![[0., 0., 0.], [0., 0., 2.]],
+                               String::new()).unwrap();
+        let density = (0..16).map(|p| [3., 2., 3., 2.][p % 4])
+                             .collect::<Vec<f64>>();
+        let settings = Settings::new("ongrid", 1E-8, None, 1, false).unwrap();
+        let partitioned = run(&[density],
+                              None,
+                              &atoms,
+                              [2, 2, 4],
+                              [0., 0., 0.],
+                              [2, 1, 0],
+                              &settings).unwrap();
+        assert_eq!(partitioned.shape, [4, 2, 2]);
+        assert_eq!(partitioned.positions[1], [2., 0., 0.]);
+        // the first axis of the reordered grid is the last of the density
+        let (first, third) =
+            (&partitioned.index[..4], &partitioned.index[8..12]);
+        assert!(first.iter().all(|i| *i == first[0]));
+        assert!(third.iter().all(|i| *i == third[0]));
+        assert_ne!(first[0], third[0]);
+    }
+}
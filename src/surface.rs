@@ -0,0 +1,529 @@
+use crate::critical_points::{voxel_index, voxel_to_cartesian};
+use crate::error::{Error, Result};
+use crate::grid::Grid;
+use crate::progress::Bar;
+use crate::utils::{cross, dot, norm};
+use crossbeam_utils::thread;
+use rustc_hash::FxHashMap;
+
+/// The value of the weight field at which the surface is drawn.
+const ISO_VALUE: f64 = 0.5;
+
+/// The position of each corner of a cube, corner i sits at
+/// (i & 1, (i >> 1) & 1, (i >> 2) & 1).
+const CORNERS: [[isize; 3]; 8] = [[0, 0, 0],
+                                  [1, 0, 0],
+                                  [0, 1, 0],
+                                  [1, 1, 0],
+                                  [0, 0, 1],
+                                  [1, 0, 1],
+                                  [0, 1, 1],
+                                  [1, 1, 1]];
+
+/// The corner each edge of the cube starts at and the axis it runs along.
+const EDGES: [(usize, usize); 12] = [(0, 0),
+                                     (2, 0),
+                                     (4, 0),
+                                     (6, 0),
+                                     (0, 1),
+                                     (1, 1),
+                                     (4, 1),
+                                     (5, 1),
+                                     (0, 2),
+                                     (1, 2),
+                                     (2, 2),
+                                     (3, 2)];
+
+/// The triangles, as triples of edges, for each of the 256 configurations of
+/// the cube corners. Configurations are indexed by setting bit i for each
+/// corner i inside the surface and each list is terminated by -1. Ambiguous
+/// faces always keep the inside corners separate so that neighbouring cubes
+/// agree, and triangles are wound to face away from the inside.
+const TRIANGLE_TABLE: [[i8; 16]; 256] =
+    [[-1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [8, 0, 4, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [5, 0, 9, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [8, 5, 4, 8, 9, 5, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [4, 1, 10, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [8, 1, 10, 8, 0, 1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [4, 1, 10, 5, 0, 9, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [8, 1, 10, 8, 5, 1, 8, 9, 5, -1, -1, -1, -1, -1, -1, -1],
+     [11, 1, 5, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [8, 0, 4, 11, 1, 5, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [11, 0, 9, 11, 1, 0, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [8, 1, 4, 8, 11, 1, 8, 9, 11, -1, -1, -1, -1, -1, -1, -1],
+     [4, 11, 10, 4, 5, 11, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [8, 11, 10, 8, 5, 11, 8, 0, 5, -1, -1, -1, -1, -1, -1, -1],
+     [4, 11, 10, 4, 9, 11, 4, 0, 9, -1, -1, -1, -1, -1, -1, -1],
+     [8, 11, 10, 8, 9, 11, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [6, 2, 8, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [6, 0, 4, 6, 2, 0, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [6, 2, 8, 5, 0, 9, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [6, 5, 4, 6, 9, 5, 6, 2, 9, -1, -1, -1, -1, -1, -1, -1],
+     [4, 1, 10, 6, 2, 8, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [6, 1, 10, 6, 0, 1, 6, 2, 0, -1, -1, -1, -1, -1, -1, -1],
+     [4, 1, 10, 6, 2, 8, 5, 0, 9, -1, -1, -1, -1, -1, -1, -1],
+     [6, 1, 10, 6, 5, 1, 6, 9, 5, 6, 2, 9, -1, -1, -1, -1],
+     [6, 2, 8, 11, 1, 5, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [6, 0, 4, 6, 2, 0, 11, 1, 5, -1, -1, -1, -1, -1, -1, -1],
+     [6, 2, 8, 11, 0, 9, 11, 1, 0, -1, -1, -1, -1, -1, -1, -1],
+     [6, 1, 4, 6, 11, 1, 6, 9, 11, 6, 2, 9, -1, -1, -1, -1],
+     [4, 11, 10, 4, 5, 11, 6, 2, 8, -1, -1, -1, -1, -1, -1, -1],
+     [6, 11, 10, 6, 5, 11, 6, 0, 5, 6, 2, 0, -1, -1, -1, -1],
+     [4, 11, 10, 4, 9, 11, 4, 0, 9, 6, 2, 8, -1, -1, -1, -1],
+     [6, 11, 10, 6, 9, 11, 6, 2, 9, -1, -1, -1, -1, -1, -1, -1],
+     [9, 2, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [8, 0, 4, 9, 2, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [5, 2, 7, 5, 0, 2, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [8, 5, 4, 8, 7, 5, 8, 2, 7, -1, -1, -1, -1, -1, -1, -1],
+     [4, 1, 10, 9, 2, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [8, 1, 10, 8, 0, 1, 9, 2, 7, -1, -1, -1, -1, -1, -1, -1],
+     [4, 1, 10, 5, 2, 7, 5, 0, 2, -1, -1, -1, -1, -1, -1, -1],
+     [8, 1, 10, 8, 5, 1, 8, 7, 5, 8, 2, 7, -1, -1, -1, -1],
+     [11, 1, 5, 9, 2, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [8, 0, 4, 11, 1, 5, 9, 2, 7, -1, -1, -1, -1, -1, -1, -1],
+     [11, 2, 7, 11, 0, 2, 11, 1, 0, -1, -1, -1, -1, -1, -1, -1],
+     [8, 1, 4, 8, 11, 1, 8, 7, 11, 8, 2, 7, -1, -1, -1, -1],
+     [4, 11, 10, 4, 5, 11, 9, 2, 7, -1, -1, -1, -1, -1, -1, -1],
+     [8, 11, 10, 8, 5, 11, 8, 0, 5, 9, 2, 7, -1, -1, -1, -1],
+     [4, 11, 10, 4, 7, 11, 4, 2, 7, 4, 0, 2, -1, -1, -1, -1],
+     [8, 11, 10, 8, 7, 11, 8, 2, 7, -1, -1, -1, -1, -1, -1, -1],
+     [6, 9, 8, 6, 7, 9, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [6, 0, 4, 6, 9, 0, 6, 7, 9, -1, -1, -1, -1, -1, -1, -1],
+     [6, 0, 8, 6, 5, 0, 6, 7, 5, -1, -1, -1, -1, -1, -1, -1],
+     [6, 5, 4, 6, 7, 5, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [4, 1, 10, 6, 9, 8, 6, 7, 9, -1, -1, -1, -1, -1, -1, -1],
+     [6, 1, 10, 6, 0, 1, 6, 9, 0, 6, 7, 9, -1, -1, -1, -1],
+     [4, 1, 10, 6, 0, 8, 6, 5, 0, 6, 7, 5, -1, -1, -1, -1],
+     [6, 1, 10, 6, 5, 1, 6, 7, 5, -1, -1, -1, -1, -1, -1, -1],
+     [6, 9, 8, 6, 7, 9, 11, 1, 5, -1, -1, -1, -1, -1, -1, -1],
+     [6, 0, 4, 6, 9, 0, 6, 7, 9, 11, 1, 5, -1, -1, -1, -1],
+     [6, 0, 8, 6, 1, 0, 6, 11, 1, 6, 7, 11, -1, -1, -1, -1],
+     [6, 1, 4, 6, 11, 1, 6, 7, 11, -1, -1, -1, -1, -1, -1, -1],
+     [4, 11, 10, 4, 5, 11, 6, 9, 8, 6, 7, 9, -1, -1, -1, -1],
+     [6, 11, 10, 6, 5, 11, 6, 0, 5, 6, 9, 0, 6, 7, 9, -1],
+     [4, 11, 10, 4, 7, 11, 4, 6, 7, 4, 8, 6, 4, 0, 8, -1],
+     [6, 11, 10, 6, 7, 11, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [10, 3, 6, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [10, 3, 6, 8, 0, 4, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [10, 3, 6, 5, 0, 9, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [10, 3, 6, 8, 5, 4, 8, 9, 5, -1, -1, -1, -1, -1, -1, -1],
+     [4, 3, 6, 4, 1, 3, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [8, 3, 6, 8, 1, 3, 8, 0, 1, -1, -1, -1, -1, -1, -1, -1],
+     [4, 3, 6, 4, 1, 3, 5, 0, 9, -1, -1, -1, -1, -1, -1, -1],
+     [8, 3, 6, 8, 1, 3, 8, 5, 1, 8, 9, 5, -1, -1, -1, -1],
+     [10, 3, 6, 11, 1, 5, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [10, 3, 6, 8, 0, 4, 11, 1, 5, -1, -1, -1, -1, -1, -1, -1],
+     [10, 3, 6, 11, 0, 9, 11, 1, 0, -1, -1, -1, -1, -1, -1, -1],
+     [10, 3, 6, 8, 1, 4, 8, 11, 1, 8, 9, 11, -1, -1, -1, -1],
+     [4, 3, 6, 4, 11, 3, 4, 5, 11, -1, -1, -1, -1, -1, -1, -1],
+     [8, 3, 6, 8, 11, 3, 8, 5, 11, 8, 0, 5, -1, -1, -1, -1],
+     [4, 3, 6, 4, 11, 3, 4, 9, 11, 4, 0, 9, -1, -1, -1, -1],
+     [8, 3, 6, 8, 11, 3, 8, 9, 11, -1, -1, -1, -1, -1, -1, -1],
+     [10, 2, 8, 10, 3, 2, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [10, 0, 4, 10, 2, 0, 10, 3, 2, -1, -1, -1, -1, -1, -1, -1],
+     [10, 2, 8, 10, 3, 2, 5, 0, 9, -1, -1, -1, -1, -1, -1, -1],
+     [10, 5, 4, 10, 9, 5, 10, 2, 9, 10, 3, 2, -1, -1, -1, -1],
+     [4, 2, 8, 4, 3, 2, 4, 1, 3, -1, -1, -1, -1, -1, -1, -1],
+     [0, 3, 2, 0, 1, 3, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [4, 2, 8, 4, 3, 2, 4, 1, 3, 5, 0, 9, -1, -1, -1, -1],
+     [5, 2, 9, 5, 3, 2, 5, 1, 3, -1, -1, -1, -1, -1, -1, -1],
+     [10, 2, 8, 10, 3, 2, 11, 1, 5, -1, -1, -1, -1, -1, -1, -1],
+     [10, 0, 4, 10, 2, 0, 10, 3, 2, 11, 1, 5, -1, -1, -1, -1],
+     [10, 2, 8, 10, 3, 2, 11, 0, 9, 11, 1, 0, -1, -1, -1, -1],
+     [10, 1, 4, 10, 11, 1, 10, 9, 11, 10, 2, 9, 10, 3, 2, -1],
+     [4, 2, 8, 4, 3, 2, 4, 11, 3, 4, 5, 11, -1, -1, -1, -1],
+     [11, 0, 5, 11, 2, 0, 11, 3, 2, -1, -1, -1, -1, -1, -1, -1],
+     [4, 2, 8, 4, 3, 2, 4, 11, 3, 4, 9, 11, 4, 0, 9, -1],
+     [11, 2, 9, 11, 3, 2, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [10, 3, 6, 9, 2, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [10, 3, 6, 8, 0, 4, 9, 2, 7, -1, -1, -1, -1, -1, -1, -1],
+     [10, 3, 6, 5, 2, 7, 5, 0, 2, -1, -1, -1, -1, -1, -1, -1],
+     [10, 3, 6, 8, 5, 4, 8, 7, 5, 8, 2, 7, -1, -1, -1, -1],
+     [4, 3, 6, 4, 1, 3, 9, 2, 7, -1, -1, -1, -1, -1, -1, -1],
+     [8, 3, 6, 8, 1, 3, 8, 0, 1, 9, 2, 7, -1, -1, -1, -1],
+     [4, 3, 6, 4, 1, 3, 5, 2, 7, 5, 0, 2, -1, -1, -1, -1],
+     [8, 3, 6, 8, 1, 3, 8, 5, 1, 8, 7, 5, 8, 2, 7, -1],
+     [10, 3, 6, 11, 1, 5, 9, 2, 7, -1, -1, -1, -1, -1, -1, -1],
+     [10, 3, 6, 8, 0, 4, 11, 1, 5, 9, 2, 7, -1, -1, -1, -1],
+     [10, 3, 6, 11, 2, 7, 11, 0, 2, 11, 1, 0, -1, -1, -1, -1],
+     [10, 3, 6, 8, 1, 4, 8, 11, 1, 8, 7, 11, 8, 2, 7, -1],
+     [4, 3, 6, 4, 11, 3, 4, 5, 11, 9, 2, 7, -1, -1, -1, -1],
+     [8, 3, 6, 8, 11, 3, 8, 5, 11, 8, 0, 5, 9, 2, 7, -1],
+     [4, 3, 6, 4, 11, 3, 4, 7, 11, 4, 2, 7, 4, 0, 2, -1],
+     [8, 3, 6, 8, 11, 3, 8, 7, 11, 8, 2, 7, -1, -1, -1, -1],
+     [10, 9, 8, 10, 7, 9, 10, 3, 7, -1, -1, -1, -1, -1, -1, -1],
+     [10, 0, 4, 10, 9, 0, 10, 7, 9, 10, 3, 7, -1, -1, -1, -1],
+     [10, 0, 8, 10, 5, 0, 10, 7, 5, 10, 3, 7, -1, -1, -1, -1],
+     [10, 5, 4, 10, 7, 5, 10, 3, 7, -1, -1, -1, -1, -1, -1, -1],
+     [4, 9, 8, 4, 7, 9, 4, 3, 7, 4, 1, 3, -1, -1, -1, -1],
+     [9, 3, 7, 9, 1, 3, 9, 0, 1, -1, -1, -1, -1, -1, -1, -1],
+     [4, 0, 8, 4, 5, 0, 4, 7, 5, 4, 3, 7, 4, 1, 3, -1],
+     [5, 3, 7, 5, 1, 3, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [10, 9, 8, 10, 7, 9, 10, 3, 7, 11, 1, 5, -1, -1, -1, -1],
+     [10, 0, 4, 10, 9, 0, 10, 7, 9, 10, 3, 7, 11, 1, 5, -1],
+     [10, 0, 8, 10, 1, 0, 10, 11, 1, 10, 7, 11, 10, 3, 7, -1],
+     [10, 1, 4, 10, 11, 1, 10, 7, 11, 10, 3, 7, -1, -1, -1, -1],
+     [4, 9, 8, 4, 7, 9, 4, 3, 7, 4, 11, 3, 4, 5, 11, -1],
+     [11, 0, 5, 11, 9, 0, 11, 7, 9, 11, 3, 7, -1, -1, -1, -1],
+     [4, 0, 8, 11, 3, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [11, 3, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [7, 3, 11, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [8, 0, 4, 7, 3, 11, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [7, 3, 11, 5, 0, 9, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [8, 5, 4, 8, 9, 5, 7, 3, 11, -1, -1, -1, -1, -1, -1, -1],
+     [4, 1, 10, 7, 3, 11, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [8, 1, 10, 8, 0, 1, 7, 3, 11, -1, -1, -1, -1, -1, -1, -1],
+     [4, 1, 10, 7, 3, 11, 5, 0, 9, -1, -1, -1, -1, -1, -1, -1],
+     [8, 1, 10, 8, 5, 1, 8, 9, 5, 7, 3, 11, -1, -1, -1, -1],
+     [7, 1, 5, 7, 3, 1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [8, 0, 4, 7, 1, 5, 7, 3, 1, -1, -1, -1, -1, -1, -1, -1],
+     [7, 0, 9, 7, 1, 0, 7, 3, 1, -1, -1, -1, -1, -1, -1, -1],
+     [8, 1, 4, 8, 3, 1, 8, 7, 3, 8, 9, 7, -1, -1, -1, -1],
+     [4, 3, 10, 4, 7, 3, 4, 5, 7, -1, -1, -1, -1, -1, -1, -1],
+     [8, 3, 10, 8, 7, 3, 8, 5, 7, 8, 0, 5, -1, -1, -1, -1],
+     [4, 3, 10, 4, 7, 3, 4, 9, 7, 4, 0, 9, -1, -1, -1, -1],
+     [8, 3, 10, 8, 7, 3, 8, 9, 7, -1, -1, -1, -1, -1, -1, -1],
+     [6, 2, 8, 7, 3, 11, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [6, 0, 4, 6, 2, 0, 7, 3, 11, -1, -1, -1, -1, -1, -1, -1],
+     [6, 2, 8, 7, 3, 11, 5, 0, 9, -1, -1, -1, -1, -1, -1, -1],
+     [6, 5, 4, 6, 9, 5, 6, 2, 9, 7, 3, 11, -1, -1, -1, -1],
+     [4, 1, 10, 6, 2, 8, 7, 3, 11, -1, -1, -1, -1, -1, -1, -1],
+     [6, 1, 10, 6, 0, 1, 6, 2, 0, 7, 3, 11, -1, -1, -1, -1],
+     [4, 1, 10, 6, 2, 8, 7, 3, 11, 5, 0, 9, -1, -1, -1, -1],
+     [6, 1, 10, 6, 5, 1, 6, 9, 5, 6, 2, 9, 7, 3, 11, -1],
+     [6, 2, 8, 7, 1, 5, 7, 3, 1, -1, -1, -1, -1, -1, -1, -1],
+     [6, 0, 4, 6, 2, 0, 7, 1, 5, 7, 3, 1, -1, -1, -1, -1],
+     [6, 2, 8, 7, 0, 9, 7, 1, 0, 7, 3, 1, -1, -1, -1, -1],
+     [6, 1, 4, 6, 3, 1, 6, 7, 3, 6, 9, 7, 6, 2, 9, -1],
+     [4, 3, 10, 4, 7, 3, 4, 5, 7, 6, 2, 8, -1, -1, -1, -1],
+     [6, 3, 10, 6, 7, 3, 6, 5, 7, 6, 0, 5, 6, 2, 0, -1],
+     [4, 3, 10, 4, 7, 3, 4, 9, 7, 4, 0, 9, 6, 2, 8, -1],
+     [6, 3, 10, 6, 7, 3, 6, 9, 7, 6, 2, 9, -1, -1, -1, -1],
+     [9, 3, 11, 9, 2, 3, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [8, 0, 4, 9, 3, 11, 9, 2, 3, -1, -1, -1, -1, -1, -1, -1],
+     [5, 3, 11, 5, 2, 3, 5, 0, 2, -1, -1, -1, -1, -1, -1, -1],
+     [8, 5, 4, 8, 11, 5, 8, 3, 11, 8, 2, 3, -1, -1, -1, -1],
+     [4, 1, 10, 9, 3, 11, 9, 2, 3, -1, -1, -1, -1, -1, -1, -1],
+     [8, 1, 10, 8, 0, 1, 9, 3, 11, 9, 2, 3, -1, -1, -1, -1],
+     [4, 1, 10, 5, 3, 11, 5, 2, 3, 5, 0, 2, -1, -1, -1, -1],
+     [8, 1, 10, 8, 5, 1, 8, 11, 5, 8, 3, 11, 8, 2, 3, -1],
+     [9, 1, 5, 9, 3, 1, 9, 2, 3, -1, -1, -1, -1, -1, -1, -1],
+     [8, 0, 4, 9, 1, 5, 9, 3, 1, 9, 2, 3, -1, -1, -1, -1],
+     [2, 1, 0, 2, 3, 1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [8, 1, 4, 8, 3, 1, 8, 2, 3, -1, -1, -1, -1, -1, -1, -1],
+     [4, 3, 10, 4, 2, 3, 4, 9, 2, 4, 5, 9, -1, -1, -1, -1],
+     [8, 3, 10, 8, 2, 3, 8, 9, 2, 8, 5, 9, 8, 0, 5, -1],
+     [4, 3, 10, 4, 2, 3, 4, 0, 2, -1, -1, -1, -1, -1, -1, -1],
+     [8, 3, 10, 8, 2, 3, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [6, 9, 8, 6, 11, 9, 6, 3, 11, -1, -1, -1, -1, -1, -1, -1],
+     [6, 0, 4, 6, 9, 0, 6, 11, 9, 6, 3, 11, -1, -1, -1, -1],
+     [6, 0, 8, 6, 5, 0, 6, 11, 5, 6, 3, 11, -1, -1, -1, -1],
+     [6, 5, 4, 6, 11, 5, 6, 3, 11, -1, -1, -1, -1, -1, -1, -1],
+     [4, 1, 10, 6, 9, 8, 6, 11, 9, 6, 3, 11, -1, -1, -1, -1],
+     [6, 1, 10, 6, 0, 1, 6, 9, 0, 6, 11, 9, 6, 3, 11, -1],
+     [4, 1, 10, 6, 0, 8, 6, 5, 0, 6, 11, 5, 6, 3, 11, -1],
+     [6, 1, 10, 6, 5, 1, 6, 11, 5, 6, 3, 11, -1, -1, -1, -1],
+     [6, 9, 8, 6, 5, 9, 6, 1, 5, 6, 3, 1, -1, -1, -1, -1],
+     [6, 0, 4, 6, 9, 0, 6, 5, 9, 6, 1, 5, 6, 3, 1, -1],
+     [6, 0, 8, 6, 1, 0, 6, 3, 1, -1, -1, -1, -1, -1, -1, -1],
+     [6, 1, 4, 6, 3, 1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [4, 3, 10, 4, 6, 3, 4, 8, 6, 4, 9, 8, 4, 5, 9, -1],
+     [6, 3, 10, 9, 0, 5, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [4, 3, 10, 4, 6, 3, 4, 8, 6, 4, 0, 8, -1, -1, -1, -1],
+     [6, 3, 10, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [10, 7, 6, 10, 11, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [10, 7, 6, 10, 11, 7, 8, 0, 4, -1, -1, -1, -1, -1, -1, -1],
+     [10, 7, 6, 10, 11, 7, 5, 0, 9, -1, -1, -1, -1, -1, -1, -1],
+     [10, 7, 6, 10, 11, 7, 8, 5, 4, 8, 9, 5, -1, -1, -1, -1],
+     [4, 7, 6, 4, 11, 7, 4, 1, 11, -1, -1, -1, -1, -1, -1, -1],
+     [8, 7, 6, 8, 11, 7, 8, 1, 11, 8, 0, 1, -1, -1, -1, -1],
+     [4, 7, 6, 4, 11, 7, 4, 1, 11, 5, 0, 9, -1, -1, -1, -1],
+     [8, 7, 6, 8, 11, 7, 8, 1, 11, 8, 5, 1, 8, 9, 5, -1],
+     [10, 7, 6, 10, 5, 7, 10, 1, 5, -1, -1, -1, -1, -1, -1, -1],
+     [10, 7, 6, 10, 5, 7, 10, 1, 5, 8, 0, 4, -1, -1, -1, -1],
+     [10, 7, 6, 10, 9, 7, 10, 0, 9, 10, 1, 0, -1, -1, -1, -1],
+     [10, 7, 6, 10, 9, 7, 10, 8, 9, 10, 4, 8, 10, 1, 4, -1],
+     [4, 7, 6, 4, 5, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [8, 7, 6, 8, 5, 7, 8, 0, 5, -1, -1, -1, -1, -1, -1, -1],
+     [4, 7, 6, 4, 9, 7, 4, 0, 9, -1, -1, -1, -1, -1, -1, -1],
+     [8, 7, 6, 8, 9, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [10, 2, 8, 10, 7, 2, 10, 11, 7, -1, -1, -1, -1, -1, -1, -1],
+     [10, 0, 4, 10, 2, 0, 10, 7, 2, 10, 11, 7, -1, -1, -1, -1],
+     [10, 2, 8, 10, 7, 2, 10, 11, 7, 5, 0, 9, -1, -1, -1, -1],
+     [10, 5, 4, 10, 9, 5, 10, 2, 9, 10, 7, 2, 10, 11, 7, -1],
+     [4, 2, 8, 4, 7, 2, 4, 11, 7, 4, 1, 11, -1, -1, -1, -1],
+     [7, 1, 11, 7, 0, 1, 7, 2, 0, -1, -1, -1, -1, -1, -1, -1],
+     [4, 2, 8, 4, 7, 2, 4, 11, 7, 4, 1, 11, 5, 0, 9, -1],
+     [7, 1, 11, 7, 5, 1, 7, 9, 5, 7, 2, 9, -1, -1, -1, -1],
+     [10, 2, 8, 10, 7, 2, 10, 5, 7, 10, 1, 5, -1, -1, -1, -1],
+     [10, 0, 4, 10, 2, 0, 10, 7, 2, 10, 5, 7, 10, 1, 5, -1],
+     [10, 2, 8, 10, 7, 2, 10, 9, 7, 10, 0, 9, 10, 1, 0, -1],
+     [10, 1, 4, 7, 2, 9, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [4, 2, 8, 4, 7, 2, 4, 5, 7, -1, -1, -1, -1, -1, -1, -1],
+     [7, 0, 5, 7, 2, 0, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [4, 2, 8, 4, 7, 2, 4, 9, 7, 4, 0, 9, -1, -1, -1, -1],
+     [7, 2, 9, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [10, 2, 6, 10, 9, 2, 10, 11, 9, -1, -1, -1, -1, -1, -1, -1],
+     [10, 2, 6, 10, 9, 2, 10, 11, 9, 8, 0, 4, -1, -1, -1, -1],
+     [10, 2, 6, 10, 0, 2, 10, 5, 0, 10, 11, 5, -1, -1, -1, -1],
+     [10, 2, 6, 10, 8, 2, 10, 4, 8, 10, 5, 4, 10, 11, 5, -1],
+     [4, 2, 6, 4, 9, 2, 4, 11, 9, 4, 1, 11, -1, -1, -1, -1],
+     [8, 2, 6, 8, 9, 2, 8, 11, 9, 8, 1, 11, 8, 0, 1, -1],
+     [4, 2, 6, 4, 0, 2, 4, 5, 0, 4, 11, 5, 4, 1, 11, -1],
+     [8, 2, 6, 5, 1, 11, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [10, 2, 6, 10, 9, 2, 10, 5, 9, 10, 1, 5, -1, -1, -1, -1],
+     [10, 2, 6, 10, 9, 2, 10, 5, 9, 10, 1, 5, 8, 0, 4, -1],
+     [10, 2, 6, 10, 0, 2, 10, 1, 0, -1, -1, -1, -1, -1, -1, -1],
+     [10, 2, 6, 10, 8, 2, 10, 4, 8, 10, 1, 4, -1, -1, -1, -1],
+     [4, 2, 6, 4, 9, 2, 4, 5, 9, -1, -1, -1, -1, -1, -1, -1],
+     [8, 2, 6, 8, 9, 2, 8, 5, 9, 8, 0, 5, -1, -1, -1, -1],
+     [4, 2, 6, 4, 0, 2, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [8, 2, 6, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [10, 9, 8, 10, 11, 9, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [10, 0, 4, 10, 9, 0, 10, 11, 9, -1, -1, -1, -1, -1, -1, -1],
+     [10, 0, 8, 10, 5, 0, 10, 11, 5, -1, -1, -1, -1, -1, -1, -1],
+     [10, 5, 4, 10, 11, 5, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [4, 9, 8, 4, 11, 9, 4, 1, 11, -1, -1, -1, -1, -1, -1, -1],
+     [9, 1, 11, 9, 0, 1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [4, 0, 8, 4, 5, 0, 4, 11, 5, 4, 1, 11, -1, -1, -1, -1],
+     [5, 1, 11, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [10, 9, 8, 10, 5, 9, 10, 1, 5, -1, -1, -1, -1, -1, -1, -1],
+     [10, 0, 4, 10, 9, 0, 10, 5, 9, 10, 1, 5, -1, -1, -1, -1],
+     [10, 0, 8, 10, 1, 0, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [10, 1, 4, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [4, 9, 8, 4, 5, 9, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [9, 0, 5, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [4, 0, 8, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+     [-1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1]];
+
+/// A vertex of the surface identified by the unwrapped voxel its edge starts
+/// at and the axis the edge runs along, along with its cartesian position.
+type EdgeVertex = (([isize; 3], usize), [f64; 3]);
+
+/// A triangulated surface.
+pub struct Mesh {
+    /// The cartesian positions of the vertices.
+    pub vertices: Vec<[f64; 3]>,
+    /// The triangles as indices into the vertices, wound anti-clockwise when
+    /// viewed from outside the surface.
+    pub triangles: Vec<[usize; 3]>,
+}
+
+impl Mesh {
+    /// The total area of the triangles.
+    pub fn area(&self) -> f64 {
+        self.triangles
+            .iter()
+            .map(|t| {
+                let a = self.vertices[t[0]];
+                let b = self.vertices[t[1]];
+                let c = self.vertices[t[2]];
+                0.5
+                * norm(cross([b[0] - a[0], b[1] - a[1], b[2] - a[2]],
+                             [c[0] - a[0], c[1] - a[1], c[2] - a[2]]))
+            })
+            .sum()
+    }
+
+    /// Reorders the cartesian axes of the vertices so that axis i becomes
+    /// axes\[i\]. If the reordering is a reflection the winding of the
+    /// triangles is reversed to keep them facing outwards.
+    pub fn permute_axes(&mut self, axes: [usize; 3]) {
+        self.vertices.iter_mut().for_each(|v| {
+                                    let old = *v;
+                                    for (i, axis) in axes.iter().enumerate() {
+                                        v[*axis] = old[i];
+                                    }
+                                });
+        // an odd number of swaps has a single fixed point
+        let fixed = axes.iter().enumerate().filter(|(i, a)| *i == **a).count();
+        if fixed == 1 {
+            self.triangles.iter_mut().for_each(|t| t.swap(1, 2));
+        }
+    }
+}
+
+/// Runs marching cubes over a range of voxels, each voxel being the first
+/// corner of a cube. Every cube is moved to the periodic image closest to the
+/// centre, given in the voxel basis, so that the surface is continuous.
+fn march_chunk(weights: &[Option<f64>],
+               grid: &Grid,
+               centre: [f64; 3],
+               start: usize,
+               end: usize,
+               progress_bar: &Bar)
+               -> Vec<[EdgeVertex; 3]> {
+    let size = [grid.size.x, grid.size.y, grid.size.z];
+    let mut triangles = Vec::new();
+    for p in start..end {
+        progress_bar.tick();
+        let p = p as isize;
+        let voxel = [p / (size[1] * size[2]),
+                     (p / size[2]).rem_euclid(size[1]),
+                     p.rem_euclid(size[2])];
+        let mut values = [0f64; 8];
+        let mut config = 0;
+        for (i, (value, corner)) in values.iter_mut().zip(&CORNERS).enumerate()
+        {
+            *value = weights[voxel_index(grid,
+                                         [voxel[0] + corner[0],
+                                          voxel[1] + corner[1],
+                                          voxel[2] + corner[2]])].unwrap_or(0.);
+            if *value >= ISO_VALUE {
+                config |= 1 << i;
+            }
+        }
+        if config == 0 || config == 255 {
+            continue;
+        }
+        let mut base = voxel;
+        for i in 0..3 {
+            let image =
+                ((centre[i] - voxel[i] as f64 - 0.5) / size[i] as f64).round()
+                as isize;
+            base[i] += image * size[i];
+        }
+        let edge_vertex = |edge: i8| {
+            let (c, axis) = EDGES[edge as usize];
+            let t =
+                (ISO_VALUE - values[c]) / (values[c | (1 << axis)] - values[c]);
+            let corner = [base[0] + CORNERS[c][0],
+                          base[1] + CORNERS[c][1],
+                          base[2] + CORNERS[c][2]];
+            let mut u = [corner[0] as f64, corner[1] as f64, corner[2] as f64];
+            u[axis] += t;
+            ((corner, axis), voxel_to_cartesian(grid, u))
+        };
+        for triangle in TRIANGLE_TABLE[config].chunks(3) {
+            if triangle[0] == -1 {
+                break;
+            }
+            triangles.push([edge_vertex(triangle[0]),
+                            edge_vertex(triangle[1]),
+                            edge_vertex(triangle[2])]);
+        }
+    }
+    triangles
+}
+
+/// Extracts the zero-flux surface of a Bader volume or atom with marching
+/// cubes.
+///
+/// The weights are the fraction of each voxel belonging to the volume, such as
+/// those returned by [`volume_map`](crate::voxel_map::NonBlockingVoxelMap::volume_map),
+/// and the surface is drawn where the interpolated weight is a half. This
+/// places the surface inside the boundary voxels in proportion to their
+/// weights. The surface is unwrapped around centre, the cartesian position of
+/// the atom or maxima, so that volumes crossing the cell boundary are returned
+/// as a single closed surface. A volume that touches its own periodic image
+/// has no boundary with it and so the surface is left open there.
+pub fn zero_flux_surface(weights: &[Option<f64>],
+                         grid: &Grid,
+                         centre: [f64; 3],
+                         threads: usize,
+                         progress_bar: Bar)
+                         -> Result<Mesh> {
+    let pbar = &progress_bar;
+    let centre = {
+        let u = dot(centre, grid.voxel_lattice.to_fractional);
+        [u[0] - grid.voxel_origin[0],
+         u[1] - grid.voxel_origin[1],
+         u[2] - grid.voxel_origin[2]]
+    };
+    let total = grid.size.total;
+    let chunk_size = (total / threads) + (total % threads).min(1);
+    let mut mesh = Mesh { vertices: Vec::new(),
+                          triangles: Vec::new() };
+    let mut vertex_map = FxHashMap::<([isize; 3], usize), usize>::default();
+    thread::scope(|s| -> Result<()> {
+        let th = (0..total).step_by(chunk_size)
+                           .map(|start| {
+                               let end = (start + chunk_size).min(total);
+                               s.spawn(move |_| {
+                                    march_chunk(weights, grid, centre, start,
+                                                end, pbar)
+                                })
+                           })
+                           .collect::<Vec<_>>();
+        for thread in th {
+            let triangles = thread.join().map_err(|_| Error::Thread(String::from("zero_flux_surface")))?;
+            // join the vertices shared between triangles
+            for triangle in triangles {
+                let mut t = [0usize; 3];
+                for (i, (key, position)) in triangle.iter().enumerate() {
+                    t[i] = *vertex_map.entry(*key).or_insert_with(|| {
+                                                      mesh.vertices
+                                                          .push(*position);
+                                                      mesh.vertices.len()
+                                                      - 1
+                                                  });
+                }
+                mesh.triangles.push(t);
+            }
+        }
+        Ok(())
+    }).map_err(|_| Error::Thread(String::from("zero_flux_surface")))??;
+    Ok(mesh)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn surface_sphere_across_boundary() {
+        // a sphere of radius 1.5 centred on the corner of a 4x4x4 cell
+        let n = 32;
+        let lattice = [[4., 0., 0.], [0., 4., 0.], [0., 0., 4.]];
+        let grid = Grid::new([n, n, n], lattice, [0., 0., 0.]).unwrap();
+        let mut weights = Vec::with_capacity(n * n * n);
+        for x in 0..n {
+            for y in 0..n {
+                for z in 0..n {
+                    let d = |i: usize| {
+                        let r = i as f64 * 4. / n as f64;
+                        r.min(4. - r)
+                    };
+                    let r = (d(x).powi(2) + d(y).powi(2) + d(z).powi(2)).sqrt();
+                    // a linear ramp across the boundary voxels
+                    let w = ((1.5 - r) * n as f64 / 4. + 0.5).clamp(0., 1.);
+                    weights.push(if w > 0. { Some(w) } else { None });
+                }
+            }
+        }
+        let mesh = zero_flux_surface(&weights,
+                                     &grid,
+                                     [0.1, 0.1, 0.1],
+                                     3,
+                                     Bar::new(0, 100, String::new())).unwrap();
+        let area = mesh.area();
+        assert!((area - 4. * PI * 1.5f64.powi(2)).abs() / area < 0.02);
+        // the surface is unwrapped around the centre and closed
+        let mut edges = FxHashMap::<(usize, usize), usize>::default();
+        for t in mesh.triangles.iter() {
+            for i in 0..3 {
+                *edges.entry((t[i], t[(i + 1) % 3])).or_insert(0) += 1;
+            }
+        }
+        for ((a, b), count) in edges.iter() {
+            assert_eq!(*count, 1);
+            assert_eq!(edges.get(&(*b, *a)), Some(&1));
+        }
+        for v in mesh.vertices.iter() {
+            assert!((norm(*v) - 1.5).abs() < 0.1);
+        }
+    }
+
+    #[test]
+    fn surface_permute_axes() {
+        let mut mesh =
+            Mesh { vertices: vec![[1., 0., 0.], [0., 2., 0.], [0., 0., 3.]],
+                   triangles: vec![[0, 1, 2]] };
+        mesh.permute_axes([2, 1, 0]);
+        assert_eq!(mesh.vertices,
+                   vec![[0., 0., 1.], [0., 2., 0.], [3., 0., 0.]]);
+        assert_eq!(mesh.triangles, vec![[0, 2, 1]]);
+    }
+}
@@ -1,4 +1,4 @@
-use anyhow::{bail, Result};
+use crate::error::{Error, Result};
 
 /// compute the dot product between a vector and a matrix
 pub fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
@@ -50,7 +50,7 @@ pub fn invert_lattice(lattice: &[[f64; 3]; 3]) -> Result<[[f64; 3]; 3]> {
     let determinant = lattice[0][0] * minor00 - lattice[0][1] * minor01
                       + lattice[0][2] * minor02;
     if determinant.abs() < 1e-16 {
-        bail!("Lattice doesn't span 3D space.")
+        Err(Error::Numerical(String::from("Lattice doesn't span 3D space.")))
     } else {
         Ok([[minor00 / determinant,
              (lattice[0][2] * lattice[2][1] - lattice[2][2] * lattice[0][1])
@@ -70,6 +70,58 @@ pub fn invert_lattice(lattice: &[[f64; 3]; 3]) -> Result<[[f64; 3]; 3]> {
     }
 }
 
+/// calculates the eigenvalues, in ascending order, and the eigenvectors of a
+/// symmetric 3x3 matrix using Jacobi rotations. eigenvectors\[i\] belongs to
+/// eigenvalues\[i\].
+pub fn symmetric_eigen(m: [[f64; 3]; 3]) -> ([f64; 3], [[f64; 3]; 3]) {
+    let mut a = m;
+    let mut v = [[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]];
+    for _ in 0..50 {
+        let off_diagonal = a[0][1].powi(2) + a[0][2].powi(2) + a[1][2].powi(2);
+        if off_diagonal < 1E-30 {
+            break;
+        }
+        for (p, q) in [(0, 1), (0, 2), (1, 2)].iter().copied() {
+            if a[p][q] == 0. {
+                continue;
+            }
+            // calculate the rotation that zeroes a[p][q]
+            let theta = (a[q][q] - a[p][p]) / (2. * a[p][q]);
+            let t =
+                theta.signum() / (theta.abs() + (theta * theta + 1.).sqrt());
+            let c = (t * t + 1.).sqrt().recip();
+            let s = t * c;
+            for row in a.iter_mut() {
+                let (rp, rq) = (row[p], row[q]);
+                row[p] = c * rp - s * rq;
+                row[q] = s * rp + c * rq;
+            }
+            let (row_p, row_q) = (a[p], a[q]);
+            a[p] = [c * row_p[0] - s * row_q[0],
+                    c * row_p[1] - s * row_q[1],
+                    c * row_p[2] - s * row_q[2]];
+            a[q] = [s * row_p[0] + c * row_q[0],
+                    s * row_p[1] + c * row_q[1],
+                    s * row_p[2] + c * row_q[2]];
+            for row in v.iter_mut() {
+                let vp = row[p];
+                let vq = row[q];
+                row[p] = c * vp - s * vq;
+                row[q] = s * vp + c * vq;
+            }
+        }
+    }
+    let mut order = [0usize, 1, 2];
+    order.sort_by(|i, j| a[*i][*i].total_cmp(&a[*j][*j]));
+    let mut values = [0f64; 3];
+    let mut vectors = [[0f64; 3]; 3];
+    for (i, o) in order.iter().enumerate() {
+        values[i] = a[*o][*o];
+        vectors[i] = [v[0][*o], v[1][*o], v[2][*o]];
+    }
+    (values, vectors)
+}
+
 /// returns the first index that is not vacuum from a sorted index list
 pub fn vacuum_index(density: &[f64],
                     index: &[usize],
@@ -82,11 +134,11 @@ pub fn vacuum_index(density: &[f64],
                     return Ok(index.len() - i);
                 }
             }
-            bail!(
+            Err(Error::InvalidArgument(format!(
                 "Vacuum tolerance ({}) is higher than maximum value of density ({}).",
                 tol,
                 density[index[0]]
-            )
+            )))
         }
         None => Ok(index.len()),
     }
@@ -120,6 +172,20 @@ mod tests {
         assert_eq!(transpose_square(matrix), t_squared)
     }
 
+    #[test]
+    fn utils_symmetric_eigen() {
+        let matrix = [[2., 1., 0.], [1., 2., 0.], [0., 0., -1.]];
+        let (values, vectors) = symmetric_eigen(matrix);
+        let expected = [-1., 1., 3.];
+        for i in 0..3 {
+            assert!((values[i] - expected[i]).abs() < 1E-12);
+            let mv = dot(vectors[i], matrix);
+            for j in 0..3 {
+                assert!((mv[j] - values[i] * vectors[i][j]).abs() < 1E-12);
+            }
+        }
+    }
+
     #[test]
     fn utils_vacuum_index_some_high() {
         let data = (0..60).map(|x| x as f64).collect::<Vec<f64>>();
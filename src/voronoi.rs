@@ -1,4 +1,5 @@
 use crate::atoms::{Lattice, ReducedLattice};
+use crate::error::Result;
 use crate::utils::{cross, invert_lattice, vdot};
 
 /// Holds information on the Voronoi vectors.
@@ -8,25 +9,32 @@ pub struct Voronoi {
     /// The alphas associated with each Voronoi vector.
     /// alphas are used to multiply the charge difference by to calculate flux.
     pub alphas: Vec<f64>,
+    /// The length of each Voronoi vector.
+    pub distances: Vec<f64>,
     /// The LLL-reduced lattice for the voxel basis.
     pub lll_lattice: ReducedLattice,
 }
 
 impl Voronoi {
     /// Generates a Voronoi struct from a [`Lattice`].
-    pub fn new(lattice: &Lattice) -> Self {
-        let lll_lattice = ReducedLattice::from_lattice(lattice);
-        let (vectors, alphas) = Voronoi::voronoi_vectors(&lll_lattice);
-        Self { vectors,
-               alphas,
-               lll_lattice }
+    pub fn new(lattice: &Lattice) -> Result<Self> {
+        let lll_lattice = ReducedLattice::from_lattice(lattice)?;
+        let (vectors, alphas, distances) =
+            Voronoi::voronoi_vectors(&lll_lattice);
+        Ok(Self { vectors,
+                  alphas,
+                  distances,
+                  lll_lattice })
     }
 
-    /// Calculates the Voronoi vectors and their alphas from a reduced basis.
-    fn voronoi_vectors(lll: &ReducedLattice) -> (Vec<Vec<usize>>, Vec<f64>) {
+    /// Calculates the Voronoi vectors, their alphas and their lengths from a
+    /// reduced basis.
+    fn voronoi_vectors(lll: &ReducedLattice)
+                       -> (Vec<Vec<usize>>, Vec<f64>, Vec<f64>) {
         // allocate the storage for voronoi vectors and flux coefficients
         let mut vectors = Vec::<Vec<usize>>::with_capacity(14);
         let mut alphas = Vec::<f64>::with_capacity(14);
+        let mut distances = Vec::<f64>::with_capacity(14);
         // allocate the vertex storage and vector/matrix for calculating them
         let mut vertices = Vec::<[f64; 3]>::with_capacity(28);
         let mut vector_basis = [[0f64; 3]; 3];
@@ -96,7 +104,7 @@ impl Voronoi {
                         |a, b| {
                             let c = vdot(*a, ry).atan2(vdot(*a, rx));
                             let d = vdot(*b, ry).atan2(vdot(*b, rx));
-                            c.partial_cmp(&d).unwrap()
+                            c.total_cmp(&d)
                         }
                     });
             let num_vertices = vertices.len();
@@ -117,11 +125,13 @@ impl Voronoi {
 
             vectors.push(lll.shift_matrix[vec_i].clone());
             alphas.push(alpha);
+            distances.push(vdot(c_shift, c_shift).sqrt());
             vertices.clear();
         }
         vectors.shrink_to_fit();
         alphas.shrink_to_fit();
-        (vectors, alphas)
+        distances.shrink_to_fit();
+        (vectors, alphas, distances)
     }
 }
 
@@ -130,9 +140,10 @@ mod test {
     use super::*;
     #[test]
     fn test_voronoi_vectors() {
-        let lattice =
-            Lattice::new([[1.0, 0., 0.], [0.707, 0.707, 0.], [0., 0., 1.]]);
-        let voronoi = Voronoi::new(&lattice);
+        let lattice = Lattice::new([[1.0, 0., 0.],
+                                    [0.707, 0.707, 0.],
+                                    [0., 0., 1.]]).unwrap();
+        let voronoi = Voronoi::new(&lattice).unwrap();
         let vecs = vec![vec![19],
                         vec![22],
                         vec![10],
@@ -1,3 +1,4 @@
+use crate::error::Result;
 use crate::grid::Grid;
 use rustc_hash::FxHashSet;
 use std::cell::UnsafeCell;
@@ -61,7 +62,7 @@ impl<'a> Drop for Lock<'a> {
 /// for p in 0..1isize {
 ///     let voxel_map = VoxelMap::new([2, 5, 2],
 ///                                   [[2.0, 0.0, 0.0], [0.0, 5.0, 0.0], [0.0, 0.0, 2.0]],
-///                                   [0.0, 0.0, 0.0]);
+///                                   [0.0, 0.0, 0.0]).unwrap();
 ///     let i = {
 ///         let mut weight = voxel_map.lock();
 ///         (*weight).push(Vec::with_capacity(0));
@@ -85,8 +86,8 @@ impl BlockingVoxelMap {
     pub fn new(grid: [usize; 3],
                lattice: [[f64; 3]; 3],
                voxel_origin: [f64; 3])
-               -> Self {
-        let grid = Grid::new(grid, lattice, voxel_origin);
+               -> Result<Self> {
+        let grid = Grid::new(grid, lattice, voxel_origin)?;
         let size = grid.size.total;
         // For mapping the the voxels
         let weight_map = UnsafeCell::new(Vec::<Vec<f64>>::with_capacity(size));
@@ -94,10 +95,10 @@ impl BlockingVoxelMap {
         voxel_map.resize_with(size, || AtomicIsize::new(-1));
         let lock = AtomicBool::new(false);
         // For post processing
-        Self { weight_map,
-               voxel_map,
-               grid,
-               lock }
+        Ok(Self { weight_map,
+                  voxel_map,
+                  grid,
+                  lock })
     }
 
     /// Retrieves the state of the voxel, p. This will lock until p has been stored
@@ -132,7 +133,7 @@ impl BlockingVoxelMap {
 
     /// Locks the structure for write access unlock occurs when the returned
     /// Lock is dropped.
-    pub fn lock(&self) -> Lock {
+    pub fn lock(&self) -> Lock<'_> {
         while self.lock.swap(true, Ordering::SeqCst) {}
         Lock { data: self }
     }
@@ -185,7 +186,7 @@ impl NonBlockingVoxelMap {
 
     /// A none locking retrieval of the state of voxel, p. This should only be
     /// used once the VoxelMap has been fully populated.
-    pub fn voxel_get(&self, p: isize) -> Voxel {
+    pub fn voxel_get(&self, p: isize) -> Voxel<'_> {
         let maxima = self.voxel_map[p as usize];
         match maxima.cmp(&-1) {
             std::cmp::Ordering::Equal => Voxel::Vacuum,
@@ -220,6 +221,51 @@ impl NonBlockingVoxelMap {
             .collect()
     }
 
+    /// The partition with the largest weight at each voxel, along with that
+    /// weight, or None for vacuum. The partitions are the maxima stored in the
+    /// map or, if an atoms_map is given, the atoms they are assigned to. Ties
+    /// go to the partition listed first in the weights of the voxel.
+    pub fn dominant_map(&self,
+                        atoms_map: Option<&[usize]>)
+                        -> Vec<Option<(usize, f64)>> {
+        let partition_of = |maxima: usize| match atoms_map {
+            Some(am) => am[maxima],
+            None => maxima,
+        };
+        self.voxel_map
+            .iter()
+            .map(|maxima| match maxima.cmp(&-1) {
+                std::cmp::Ordering::Greater => {
+                    Some((partition_of(*maxima as usize), 1.0))
+                }
+                std::cmp::Ordering::Less => {
+                    // sum the weights of any maxima that share a partition
+                    let mut weights: Vec<(usize, f64)> = Vec::new();
+                    for weight in self.weight_get(*maxima) {
+                        let m = *weight as usize;
+                        let partition = partition_of(m);
+                        match weights.iter_mut().find(|(p, _)| *p == partition)
+                        {
+                            Some((_, w)) => *w += weight - m as f64,
+                            None => {
+                                weights.push((partition, weight - m as f64))
+                            }
+                        }
+                    }
+                    weights.into_iter().fold(None, |dominant, (p, w)| {
+                                           match dominant {
+                                               Some((_, max)) if max >= w => {
+                                                   dominant
+                                               }
+                                               _ => Some((p, w)),
+                                           }
+                                       })
+                }
+                std::cmp::Ordering::Equal => None,
+            })
+            .collect()
+    }
+
     pub fn multi_volume_map(&self,
                             volume_numbers: &FxHashSet<isize>)
                             -> Vec<Option<f64>> {
@@ -244,3 +290,27 @@ impl NonBlockingVoxelMap {
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn voxel_map_dominant_map() {
+        let lattice = [[2., 0., 0.], [0., 2., 0.], [0., 0., 2.]];
+        let grid = Grid::new([2, 2, 2], lattice, [0., 0., 0.]).unwrap();
+        let voxel_map =
+            NonBlockingVoxelMap::new(vec![0, -2, -3, -1, 1, 1, 2, 2],
+                                     vec![vec![0.4, 1.3, 2.3], vec![1.5, 2.5]],
+                                     grid);
+        assert_eq!(voxel_map.dominant_map(None)[..4],
+                   [Some((0, 1.0)), Some((0, 0.4)), Some((1, 0.5)), None]);
+        // maxima 1 and 2 belong to the same atom so outweigh maxima 0
+        let dominant = voxel_map.dominant_map(Some(&[0, 1, 1]));
+        assert_eq!(dominant[0], Some((0, 1.0)));
+        assert_eq!(dominant[1].map(|(p, _)| p), Some(1));
+        assert!((dominant[1].unwrap().1 - 0.6).abs() < 1E-12);
+        assert_eq!(dominant[2], Some((1, 1.0)));
+        assert_eq!(dominant[3], None);
+    }
+}
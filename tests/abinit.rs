@@ -0,0 +1,35 @@
+#[cfg(test)]
+mod tests {
+    use bader::io::abinit::Abinit;
+    use bader::io::FileFormat;
+
+    const LENGTH_UNITS: f64 = 0.52917721067;
+    const VOLUME_UNITS: f64 = LENGTH_UNITS * LENGTH_UNITS * LENGTH_UNITS;
+
+    #[test]
+    fn abinit_read_spin() {
+        let filename = String::from("tests/abinit/Si_o_DEN");
+        let abinit = Abinit {};
+        let (voxel_origin, grid, atoms, densities) =
+            match abinit.read(filename, 1) {
+                Ok(r) => r,
+                Err(e) => panic!("{}", e),
+            };
+        assert_eq!(voxel_origin, [0.; 3]);
+        assert_eq!(grid, [6, 5, 4]);
+        assert_eq!(atoms.positions.len(), 2);
+        atoms.positions[1].iter()
+                          .for_each(|x| {
+                              assert!((x - 2.5 * LENGTH_UNITS).abs() < 1E-8)
+                          });
+        assert!((atoms.lattice.volume - 250. * VOLUME_UNITS).abs() < 1E-8);
+        assert_eq!(densities.len(), 2);
+        assert_eq!(densities[0][0], 1. / VOLUME_UNITS);
+        assert_eq!(densities[0][1], 2. / VOLUME_UNITS);
+        assert_eq!(densities[0][4], 11. / VOLUME_UNITS);
+        assert_eq!(densities[0][20], 101. / VOLUME_UNITS);
+        assert_eq!(densities[0][119], 544. / VOLUME_UNITS);
+        // the spin up density is converted to the magnetisation
+        assert!((densities[1][119] - 272. / VOLUME_UNITS).abs() < 1E-8);
+    }
+}
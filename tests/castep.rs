@@ -0,0 +1,59 @@
+#[cfg(test)]
+mod tests {
+    use bader::io::castep::Castep;
+    use bader::io::FileFormat;
+    use bader::progress::Bar;
+
+    #[test]
+    fn castep_read_spin() {
+        let filename = String::from("tests/castep/Si.den_fmt");
+        let castep = Castep {};
+        let (voxel_origin, grid, atoms, densities) =
+            match castep.read(filename, 1) {
+                Ok(r) => r,
+                Err(e) => panic!("{}", e),
+            };
+        let volume = atoms.lattice.volume;
+        assert_eq!(voxel_origin, [0.; 3]);
+        assert_eq!(grid, [3, 4, 5]);
+        assert_eq!(atoms.positions.len(), 2);
+        atoms.positions[1].iter()
+                          .for_each(|x| assert!((x - 1.3575).abs() < 1E-10));
+        assert_eq!(densities.len(), 2);
+        assert_eq!(densities[0][0], 1. / volume);
+        assert_eq!(densities[0][1], 101. / volume);
+        assert_eq!(densities[0][5], 11. / volume);
+        assert_eq!(densities[0][20], 2. / volume);
+        assert_eq!(densities[0][59], 433. / volume);
+        assert_eq!(densities[1][59], 0.5 / volume);
+    }
+
+    #[test]
+    fn castep_write_precision() {
+        let castep = Castep {};
+        let (_, grid, atoms, densities) =
+            castep.read(String::from("tests/castep/Si.den_fmt"), 1)
+                  .unwrap();
+        // low densities have to survive the round trip
+        let data = densities[0].iter()
+                               .map(|d| Some(d * 1.234567891E-9))
+                               .collect::<Vec<Option<f64>>>();
+        let dir = std::env::temp_dir().join(format!("bader-castep-{}",
+                                                    std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::copy("tests/castep/Si.cell", dir.join("Si.cell")).unwrap();
+        let filename = dir.join("Si").to_string_lossy().to_string();
+        castep.write(&atoms,
+                     data.clone(),
+                     filename.clone(),
+                     Bar::new(0, 100, String::new()))
+              .unwrap();
+        let (_, c_grid, _, c_densities) =
+            castep.read(format!("{}.den_fmt", filename), 1).unwrap();
+        assert_eq!(c_grid, grid);
+        for (c, d) in c_densities[0].iter().zip(data) {
+            let d = d.unwrap();
+            assert!((c - d).abs() <= d.abs() * 1E-10);
+        }
+    }
+}
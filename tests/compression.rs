@@ -0,0 +1,97 @@
+#[cfg(test)]
+mod tests {
+    use bader::io::qe::Qe;
+    use bader::io::siesta::Siesta;
+    use bader::io::vasp::Vasp;
+    use bader::io::FileFormat;
+    use std::fs;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    /// Compresses a fixture into a file in the temporary directory.
+    fn compress(fixture: &str, name: &str, method: &str) -> String {
+        let bytes = fs::read(fixture).unwrap();
+        let dir = std::env::temp_dir().join(format!("bader-compression-{}",
+                                                    std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path: PathBuf = dir.join(name);
+        let compressed = match method {
+            "gz" => {
+                let mut e =
+                    flate2::write::GzEncoder::new(Vec::new(),
+                                                  flate2::Compression::default());
+                e.write_all(&bytes).unwrap();
+                e.finish().unwrap()
+            }
+            "xz" => {
+                let mut e = xz2::write::XzEncoder::new(Vec::new(), 6);
+                e.write_all(&bytes).unwrap();
+                e.finish().unwrap()
+            }
+            "bz2" => {
+                let mut e =
+                    bzip2::write::BzEncoder::new(Vec::new(),
+                                                 bzip2::Compression::default());
+                e.write_all(&bytes).unwrap();
+                e.finish().unwrap()
+            }
+            _ => zstd::encode_all(&bytes[..], 3).unwrap(),
+        };
+        fs::write(&path, compressed).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn compression_read_vasp_spin() {
+        let vasp = Vasp {};
+        let (_, grid, atoms, densities) =
+            vasp.read(String::from("tests/vasp/CHGCAR_spin"), 1)
+                .unwrap();
+        for extension in ["gz", "xz", "bz2", "zst"].iter() {
+            let filename = compress("tests/vasp/CHGCAR_spin",
+                                    &format!("CHGCAR_spin.{}", extension),
+                                    extension);
+            let (voxel_origin, c_grid, c_atoms, c_densities) =
+                match vasp.read(filename, 4) {
+                    Ok(r) => r,
+                    Err(e) => panic!("{}", e),
+                };
+            assert_eq!(voxel_origin, [0.; 3]);
+            assert_eq!(c_grid, grid);
+            assert_eq!(c_atoms.positions, atoms.positions);
+            assert_eq!(c_atoms.text, atoms.text);
+            assert_eq!(c_densities, densities);
+        }
+    }
+
+    #[test]
+    fn compression_read_qe() {
+        let qe = Qe {};
+        let (_, grid, _, densities) =
+            qe.read(String::from("tests/qe/charge.pp"), 1).unwrap();
+        let filename = compress("tests/qe/charge.pp", "charge.pp.gz", "gz");
+        let (_, c_grid, _, c_densities) = match qe.read(filename, 1) {
+            Ok(r) => r,
+            Err(e) => panic!("{}", e),
+        };
+        assert_eq!(c_grid, grid);
+        assert_eq!(c_densities, densities);
+    }
+
+    #[test]
+    fn compression_read_siesta() {
+        let siesta = Siesta {};
+        let (_, grid, _, densities) =
+            siesta.read(String::from("tests/siesta/H2.RHO"), 1).unwrap();
+        // the atoms are read from H2.XV next to it, which can be compressed
+        // without changing its name
+        let filename = compress("tests/siesta/H2.RHO", "H2.RHO.xz", "xz");
+        let _ = compress("tests/siesta/H2.XV", "H2.XV", "zst");
+        let (_, c_grid, _, c_densities) = match siesta.read(filename, 1) {
+            Ok(r) => r,
+            Err(e) => panic!("{}", e),
+        };
+        assert_eq!(c_grid, grid);
+        assert_eq!(c_densities, densities);
+    }
+}
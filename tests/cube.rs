@@ -10,14 +10,34 @@ mod tests {
     fn cube_read() {
         let filename = String::from("tests/cube/anatase.cube");
         let cube = Cube {};
-        let (voxel_origin, grid, atoms, densities) = match cube.read(filename) {
-            Ok(r) => r,
-            Err(e) => panic!("{}", e),
-        };
+        let (voxel_origin, grid, atoms, densities) =
+            match cube.read(filename, 1) {
+                Ok(r) => r,
+                Err(e) => panic!("{}", e),
+            };
         assert_eq!(voxel_origin, [0.5; 3]);
         assert_eq!(grid, [96, 96, 180]);
         assert_eq!(atoms.positions.len(), 576);
         assert_eq!(densities[0][0], 0.13387E-02 / VOLUME_UNITS);
         assert_eq!(densities[0][1658879], 0.11782E+01 / VOLUME_UNITS);
     }
+
+    #[test]
+    fn cube_read_orbitals() {
+        let filename = String::from("tests/cube/orbitals.cube");
+        let cube = Cube {};
+        let (_, grid, atoms, densities) = match cube.read(filename, 2) {
+            Ok(r) => r,
+            Err(e) => panic!("{}", e),
+        };
+        assert_eq!(grid, [4, 3, 5]);
+        assert_eq!(atoms.positions.len(), 2);
+        assert_eq!(atoms.species, vec!["H", "H"]);
+        assert_eq!(densities.len(), 2);
+        assert_eq!(densities[0].len(), 60);
+        assert_eq!(densities[0][0], 1E-03 / VOLUME_UNITS);
+        assert_eq!(densities[1][0], -2E-03 / VOLUME_UNITS);
+        assert_eq!(densities[0][59], 6E-02 / VOLUME_UNITS);
+        assert_eq!(densities[1][59], -1.2E-01 / VOLUME_UNITS);
+    }
 }
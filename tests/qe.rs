@@ -0,0 +1,35 @@
+#[cfg(test)]
+mod tests {
+    use bader::io::qe::Qe;
+    use bader::io::FileFormat;
+    use bader::utils::dot;
+
+    const LENGTH_UNITS: f64 = 0.52917721067;
+    const VOLUME_UNITS: f64 = LENGTH_UNITS * LENGTH_UNITS * LENGTH_UNITS;
+
+    #[test]
+    fn qe_read() {
+        let filename = String::from("tests/qe/charge.pp");
+        let qe = Qe {};
+        let (voxel_origin, grid, atoms, densities) = match qe.read(filename, 1)
+        {
+            Ok(r) => r,
+            Err(e) => panic!("{}", e),
+        };
+        let alat = 10.2 * LENGTH_UNITS;
+        assert_eq!(voxel_origin, [0.; 3]);
+        assert_eq!(grid, [8, 8, 8]);
+        assert_eq!(atoms.positions.len(), 2);
+        assert!((atoms.lattice.volume - alat.powi(3) / 4.).abs() < 1E-10);
+        // (0.25, 0.25, 0.25) alat is (-0.25, 0.75, -0.25) in the fcc basis, which
+        // wraps to (0.75, 0.75, 0.75)
+        dot(atoms.positions[1], atoms.lattice.to_fractional)
+            .iter()
+            .for_each(|x| assert!((x - 0.75).abs() < 1E-10));
+        assert_eq!(densities[0].len(), 512);
+        assert_eq!(densities[0][0], 0.1E-02 / VOLUME_UNITS);
+        assert_eq!(densities[0][1], 0.2E-02 / VOLUME_UNITS);
+        assert_eq!(densities[0][8], 0.11E-01 / VOLUME_UNITS);
+        assert_eq!(densities[0][511], 0.778 / VOLUME_UNITS);
+    }
+}
@@ -0,0 +1,123 @@
+
+       9       8       8       8       8       8       2       1
+     2 10.20000000  0.00000000  0.00000000  0.00000000  0.00000000  0.00000000
+      120.0000000000        4.0000000000       30.0000000000     0
+   1   Si    4.00
+   1       0.000000000    0.000000000    0.000000000      1
+   2       0.250000000    0.250000000    0.250000000      1
+  1.000000000E-03  2.000000000E-03  3.000000000E-03  4.000000000E-03  5.000000000E-03
+  6.000000000E-03  7.000000000E-03  8.000000000E-03  0.000000000E+00  1.100000000E-02
+  1.200000000E-02  1.300000000E-02  1.400000000E-02  1.500000000E-02  1.600000000E-02
+  1.700000000E-02  1.800000000E-02  0.000000000E+00  2.100000000E-02  2.200000000E-02
+  2.300000000E-02  2.400000000E-02  2.500000000E-02  2.600000000E-02  2.700000000E-02
+  2.800000000E-02  0.000000000E+00  3.100000000E-02  3.200000000E-02  3.300000000E-02
+  3.400000000E-02  3.500000000E-02  3.600000000E-02  3.700000000E-02  3.800000000E-02
+  0.000000000E+00  4.100000000E-02  4.200000000E-02  4.300000000E-02  4.400000000E-02
+  4.500000000E-02  4.600000000E-02  4.700000000E-02  4.800000000E-02  0.000000000E+00
+  5.100000000E-02  5.200000000E-02  5.300000000E-02  5.400000000E-02  5.500000000E-02
+  5.600000000E-02  5.700000000E-02  5.800000000E-02  0.000000000E+00  6.100000000E-02
+  6.200000000E-02  6.300000000E-02  6.400000000E-02  6.500000000E-02  6.600000000E-02
+  6.700000000E-02  6.800000000E-02  0.000000000E+00  7.100000000E-02  7.200000000E-02
+  7.300000000E-02  7.400000000E-02  7.500000000E-02  7.600000000E-02  7.700000000E-02
+  7.800000000E-02  0.000000000E+00  1.010000000E-01  1.020000000E-01  1.030000000E-01
+  1.040000000E-01  1.050000000E-01  1.060000000E-01  1.070000000E-01  1.080000000E-01
+  0.000000000E+00  1.110000000E-01  1.120000000E-01  1.130000000E-01  1.140000000E-01
+  1.150000000E-01  1.160000000E-01  1.170000000E-01  1.180000000E-01  0.000000000E+00
+  1.210000000E-01  1.220000000E-01  1.230000000E-01  1.240000000E-01  1.250000000E-01
+  1.260000000E-01  1.270000000E-01  1.280000000E-01  0.000000000E+00  1.310000000E-01
+  1.320000000E-01  1.330000000E-01  1.340000000E-01  1.350000000E-01  1.360000000E-01
+  1.370000000E-01  1.380000000E-01  0.000000000E+00  1.410000000E-01  1.420000000E-01
+  1.430000000E-01  1.440000000E-01  1.450000000E-01  1.460000000E-01  1.470000000E-01
+  1.480000000E-01  0.000000000E+00  1.510000000E-01  1.520000000E-01  1.530000000E-01
+  1.540000000E-01  1.550000000E-01  1.560000000E-01  1.570000000E-01  1.580000000E-01
+  0.000000000E+00  1.610000000E-01  1.620000000E-01  1.630000000E-01  1.640000000E-01
+  1.650000000E-01  1.660000000E-01  1.670000000E-01  1.680000000E-01  0.000000000E+00
+  1.710000000E-01  1.720000000E-01  1.730000000E-01  1.740000000E-01  1.750000000E-01
+  1.760000000E-01  1.770000000E-01  1.780000000E-01  0.000000000E+00  2.010000000E-01
+  2.020000000E-01  2.030000000E-01  2.040000000E-01  2.050000000E-01  2.060000000E-01
+  2.070000000E-01  2.080000000E-01  0.000000000E+00  2.110000000E-01  2.120000000E-01
+  2.130000000E-01  2.140000000E-01  2.150000000E-01  2.160000000E-01  2.170000000E-01
+  2.180000000E-01  0.000000000E+00  2.210000000E-01  2.220000000E-01  2.230000000E-01
+  2.240000000E-01  2.250000000E-01  2.260000000E-01  2.270000000E-01  2.280000000E-01
+  0.000000000E+00  2.310000000E-01  2.320000000E-01  2.330000000E-01  2.340000000E-01
+  2.350000000E-01  2.360000000E-01  2.370000000E-01  2.380000000E-01  0.000000000E+00
+  2.410000000E-01  2.420000000E-01  2.430000000E-01  2.440000000E-01  2.450000000E-01
+  2.460000000E-01  2.470000000E-01  2.480000000E-01  0.000000000E+00  2.510000000E-01
+  2.520000000E-01  2.530000000E-01  2.540000000E-01  2.550000000E-01  2.560000000E-01
+  2.570000000E-01  2.580000000E-01  0.000000000E+00  2.610000000E-01  2.620000000E-01
+  2.630000000E-01  2.640000000E-01  2.650000000E-01  2.660000000E-01  2.670000000E-01
+  2.680000000E-01  0.000000000E+00  2.710000000E-01  2.720000000E-01  2.730000000E-01
+  2.740000000E-01  2.750000000E-01  2.760000000E-01  2.770000000E-01  2.780000000E-01
+  0.000000000E+00  3.010000000E-01  3.020000000E-01  3.030000000E-01  3.040000000E-01
+  3.050000000E-01  3.060000000E-01  3.070000000E-01  3.080000000E-01  0.000000000E+00
+  3.110000000E-01  3.120000000E-01  3.130000000E-01  3.140000000E-01  3.150000000E-01
+  3.160000000E-01  3.170000000E-01  3.180000000E-01  0.000000000E+00  3.210000000E-01
+  3.220000000E-01  3.230000000E-01  3.240000000E-01  3.250000000E-01  3.260000000E-01
+  3.270000000E-01  3.280000000E-01  0.000000000E+00  3.310000000E-01  3.320000000E-01
+  3.330000000E-01  3.340000000E-01  3.350000000E-01  3.360000000E-01  3.370000000E-01
+  3.380000000E-01  0.000000000E+00  3.410000000E-01  3.420000000E-01  3.430000000E-01
+  3.440000000E-01  3.450000000E-01  3.460000000E-01  3.470000000E-01  3.480000000E-01
+  0.000000000E+00  3.510000000E-01  3.520000000E-01  3.530000000E-01  3.540000000E-01
+  3.550000000E-01  3.560000000E-01  3.570000000E-01  3.580000000E-01  0.000000000E+00
+  3.610000000E-01  3.620000000E-01  3.630000000E-01  3.640000000E-01  3.650000000E-01
+  3.660000000E-01  3.670000000E-01  3.680000000E-01  0.000000000E+00  3.710000000E-01
+  3.720000000E-01  3.730000000E-01  3.740000000E-01  3.750000000E-01  3.760000000E-01
+  3.770000000E-01  3.780000000E-01  0.000000000E+00  4.010000000E-01  4.020000000E-01
+  4.030000000E-01  4.040000000E-01  4.050000000E-01  4.060000000E-01  4.070000000E-01
+  4.080000000E-01  0.000000000E+00  4.110000000E-01  4.120000000E-01  4.130000000E-01
+  4.140000000E-01  4.150000000E-01  4.160000000E-01  4.170000000E-01  4.180000000E-01
+  0.000000000E+00  4.210000000E-01  4.220000000E-01  4.230000000E-01  4.240000000E-01
+  4.250000000E-01  4.260000000E-01  4.270000000E-01  4.280000000E-01  0.000000000E+00
+  4.310000000E-01  4.320000000E-01  4.330000000E-01  4.340000000E-01  4.350000000E-01
+  4.360000000E-01  4.370000000E-01  4.380000000E-01  0.000000000E+00  4.410000000E-01
+  4.420000000E-01  4.430000000E-01  4.440000000E-01  4.450000000E-01  4.460000000E-01
+  4.470000000E-01  4.480000000E-01  0.000000000E+00  4.510000000E-01  4.520000000E-01
+  4.530000000E-01  4.540000000E-01  4.550000000E-01  4.560000000E-01  4.570000000E-01
+  4.580000000E-01  0.000000000E+00  4.610000000E-01  4.620000000E-01  4.630000000E-01
+  4.640000000E-01  4.650000000E-01  4.660000000E-01  4.670000000E-01  4.680000000E-01
+  0.000000000E+00  4.710000000E-01  4.720000000E-01  4.730000000E-01  4.740000000E-01
+  4.750000000E-01  4.760000000E-01  4.770000000E-01  4.780000000E-01  0.000000000E+00
+  5.010000000E-01  5.020000000E-01  5.030000000E-01  5.040000000E-01  5.050000000E-01
+  5.060000000E-01  5.070000000E-01  5.080000000E-01  0.000000000E+00  5.110000000E-01
+  5.120000000E-01  5.130000000E-01  5.140000000E-01  5.150000000E-01  5.160000000E-01
+  5.170000000E-01  5.180000000E-01  0.000000000E+00  5.210000000E-01  5.220000000E-01
+  5.230000000E-01  5.240000000E-01  5.250000000E-01  5.260000000E-01  5.270000000E-01
+  5.280000000E-01  0.000000000E+00  5.310000000E-01  5.320000000E-01  5.330000000E-01
+  5.340000000E-01  5.350000000E-01  5.360000000E-01  5.370000000E-01  5.380000000E-01
+  0.000000000E+00  5.410000000E-01  5.420000000E-01  5.430000000E-01  5.440000000E-01
+  5.450000000E-01  5.460000000E-01  5.470000000E-01  5.480000000E-01  0.000000000E+00
+  5.510000000E-01  5.520000000E-01  5.530000000E-01  5.540000000E-01  5.550000000E-01
+  5.560000000E-01  5.570000000E-01  5.580000000E-01  0.000000000E+00  5.610000000E-01
+  5.620000000E-01  5.630000000E-01  5.640000000E-01  5.650000000E-01  5.660000000E-01
+  5.670000000E-01  5.680000000E-01  0.000000000E+00  5.710000000E-01  5.720000000E-01
+  5.730000000E-01  5.740000000E-01  5.750000000E-01  5.760000000E-01  5.770000000E-01
+  5.780000000E-01  0.000000000E+00  6.010000000E-01  6.020000000E-01  6.030000000E-01
+  6.040000000E-01  6.050000000E-01  6.060000000E-01  6.070000000E-01  6.080000000E-01
+  0.000000000E+00  6.110000000E-01  6.120000000E-01  6.130000000E-01  6.140000000E-01
+  6.150000000E-01  6.160000000E-01  6.170000000E-01  6.180000000E-01  0.000000000E+00
+  6.210000000E-01  6.220000000E-01  6.230000000E-01  6.240000000E-01  6.250000000E-01
+  6.260000000E-01  6.270000000E-01  6.280000000E-01  0.000000000E+00  6.310000000E-01
+  6.320000000E-01  6.330000000E-01  6.340000000E-01  6.350000000E-01  6.360000000E-01
+  6.370000000E-01  6.380000000E-01  0.000000000E+00  6.410000000E-01  6.420000000E-01
+  6.430000000E-01  6.440000000E-01  6.450000000E-01  6.460000000E-01  6.470000000E-01
+  6.480000000E-01  0.000000000E+00  6.510000000E-01  6.520000000E-01  6.530000000E-01
+  6.540000000E-01  6.550000000E-01  6.560000000E-01  6.570000000E-01  6.580000000E-01
+  0.000000000E+00  6.610000000E-01  6.620000000E-01  6.630000000E-01  6.640000000E-01
+  6.650000000E-01  6.660000000E-01  6.670000000E-01  6.680000000E-01  0.000000000E+00
+  6.710000000E-01  6.720000000E-01  6.730000000E-01  6.740000000E-01  6.750000000E-01
+  6.760000000E-01  6.770000000E-01  6.780000000E-01  0.000000000E+00  7.010000000E-01
+  7.020000000E-01  7.030000000E-01  7.040000000E-01  7.050000000E-01  7.060000000E-01
+  7.070000000E-01  7.080000000E-01  0.000000000E+00  7.110000000E-01  7.120000000E-01
+  7.130000000E-01  7.140000000E-01  7.150000000E-01  7.160000000E-01  7.170000000E-01
+  7.180000000E-01  0.000000000E+00  7.210000000E-01  7.220000000E-01  7.230000000E-01
+  7.240000000E-01  7.250000000E-01  7.260000000E-01  7.270000000E-01  7.280000000E-01
+  0.000000000E+00  7.310000000E-01  7.320000000E-01  7.330000000E-01  7.340000000E-01
+  7.350000000E-01  7.360000000E-01  7.370000000E-01  7.380000000E-01  0.000000000E+00
+  7.410000000E-01  7.420000000E-01  7.430000000E-01  7.440000000E-01  7.450000000E-01
+  7.460000000E-01  7.470000000E-01  7.480000000E-01  0.000000000E+00  7.510000000E-01
+  7.520000000E-01  7.530000000E-01  7.540000000E-01  7.550000000E-01  7.560000000E-01
+  7.570000000E-01  7.580000000E-01  0.000000000E+00  7.610000000E-01  7.620000000E-01
+  7.630000000E-01  7.640000000E-01  7.650000000E-01  7.660000000E-01  7.670000000E-01
+  7.680000000E-01  0.000000000E+00  7.710000000E-01  7.720000000E-01  7.730000000E-01
+  7.740000000E-01  7.750000000E-01  7.760000000E-01  7.770000000E-01  7.780000000E-01
+  0.000000000E+00
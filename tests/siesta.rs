@@ -0,0 +1,33 @@
+#[cfg(test)]
+mod tests {
+    use bader::io::siesta::Siesta;
+    use bader::io::FileFormat;
+
+    const LENGTH_UNITS: f64 = 0.52917721067;
+    const VOLUME_UNITS: f64 = LENGTH_UNITS * LENGTH_UNITS * LENGTH_UNITS;
+
+    #[test]
+    fn siesta_read_spin() {
+        let filename = String::from("tests/siesta/H2.RHO");
+        let siesta = Siesta {};
+        let (voxel_origin, grid, atoms, densities) =
+            match siesta.read(filename, 1) {
+                Ok(r) => r,
+                Err(e) => panic!("{}", e),
+            };
+        assert_eq!(voxel_origin, [0.; 3]);
+        assert_eq!(grid, [6, 5, 4]);
+        assert!((atoms.lattice.volume - 336. * VOLUME_UNITS).abs() < 1E-8);
+        // the second atom is wrapped back into the cell, (3, 3.5, 2) Bohr
+        atoms.positions[1].iter()
+                          .zip([2., 3.5, 3.])
+                          .for_each(|(x, y)| {
+                              assert!((x - y * LENGTH_UNITS).abs() < 1E-8)
+                          });
+        assert_eq!(densities.len(), 2);
+        assert!((densities[0][0] - 1. / VOLUME_UNITS).abs() < 1E-6);
+        assert!((densities[0][4] - 11. / VOLUME_UNITS).abs() < 1E-6);
+        assert!((densities[0][119] - 544. / VOLUME_UNITS).abs() < 1E-6);
+        assert!((densities[1][119] - 272. / VOLUME_UNITS).abs() < 1E-6);
+    }
+}
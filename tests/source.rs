@@ -0,0 +1,65 @@
+#[cfg(test)]
+mod tests {
+    use bader::io::abinit::Abinit;
+    use bader::io::castep::Castep;
+    use bader::io::cube::Cube;
+    use bader::io::vasp::Vasp;
+    use bader::io::FileFormat;
+    use std::fs;
+    use std::io::Write;
+
+    /// Reads a fixture from a file and from its bytes in memory, checking that
+    /// both give the same density.
+    fn read_both(format: &dyn FileFormat, filename: &str) {
+        let (voxel_origin, grid, atoms, densities) =
+            format.read(String::from(filename), 2).unwrap();
+        let bytes = fs::read(filename).unwrap();
+        let (s_voxel_origin, s_grid, s_atoms, s_densities) =
+            match format.read_from(&mut &bytes[..], filename, 2) {
+                Ok(r) => r,
+                Err(e) => panic!("{}", e),
+            };
+        assert_eq!(s_voxel_origin, voxel_origin);
+        assert_eq!(s_grid, grid);
+        assert_eq!(s_atoms.positions, atoms.positions);
+        assert_eq!(s_densities, densities);
+    }
+
+    #[test]
+    fn source_read_vasp() {
+        read_both(&Vasp {}, "tests/vasp/CHGCAR_spin");
+    }
+
+    #[test]
+    fn source_read_cube() {
+        read_both(&Cube {}, "tests/cube/orbitals.cube");
+    }
+
+    #[test]
+    fn source_read_abinit() {
+        read_both(&Abinit {}, "tests/abinit/Si_o_DEN");
+    }
+
+    #[test]
+    fn source_read_castep() {
+        // the atoms are still read from the .cell file named after the source
+        read_both(&Castep {}, "tests/castep/Si.den_fmt");
+    }
+
+    #[test]
+    fn source_read_compressed() {
+        let vasp = Vasp {};
+        let filename = "tests/vasp/CHG_spin";
+        let (_, grid, _, densities) =
+            vasp.read(String::from(filename), 1).unwrap();
+        let mut e = flate2::write::GzEncoder::new(Vec::new(),
+                                                  flate2::Compression::default());
+        e.write_all(&fs::read(filename).unwrap()).unwrap();
+        let compressed = e.finish().unwrap();
+        let (_, c_grid, _, c_densities) =
+            vasp.read_from(&mut &compressed[..], "CHG_spin.gz", 1)
+                .unwrap();
+        assert_eq!(c_grid, grid);
+        assert_eq!(c_densities, densities);
+    }
+}
@@ -1,5 +1,6 @@
 #[cfg(test)]
 mod tests {
+    use bader::error::Error;
     use bader::io::vasp::Vasp;
     use bader::io::FileFormat;
 
@@ -7,13 +8,16 @@ mod tests {
     fn vasp_read_no_spin() {
         let filename = String::from("tests/vasp/CHGCAR_no_spin");
         let vasp = Vasp {};
-        let (voxel_origin, grid, atoms, densities) = match vasp.read(filename) {
-            Ok(r) => r,
-            Err(e) => panic!("{}", e),
-        };
+        let (voxel_origin, grid, atoms, densities) =
+            match vasp.read(filename, 1) {
+                Ok(r) => r,
+                Err(e) => panic!("{}", e),
+            };
         assert_eq!(voxel_origin, [0.; 3]);
         assert_eq!(grid, [32, 32, 32]);
         assert_eq!(atoms.positions, vec![[0., 0., 0.]]);
+        assert_eq!(atoms.species, vec!["Ni"]);
+        assert_eq!(atoms.atomic_numbers, vec![28]);
         assert_eq!(densities[0][0], 0.15246059033E+03 / atoms.lattice.volume);
         assert_eq!(densities[0][32767],
                    0.13036296982E+03 / atoms.lattice.volume);
@@ -23,10 +27,11 @@ mod tests {
     fn vasp_read_no_spin_chg() {
         let filename = String::from("tests/vasp/CHG_no_spin");
         let vasp = Vasp {};
-        let (voxel_origin, grid, atoms, densities) = match vasp.read(filename) {
-            Ok(r) => r,
-            Err(e) => panic!("{}", e),
-        };
+        let (voxel_origin, grid, atoms, densities) =
+            match vasp.read(filename, 1) {
+                Ok(r) => r,
+                Err(e) => panic!("{}", e),
+            };
         assert_eq!(voxel_origin, [0.; 3]);
         assert_eq!(grid, [32, 32, 32]);
         assert_eq!(atoms.positions, vec![[0., 0., 0.]]);
@@ -38,10 +43,11 @@ mod tests {
     fn vasp_read_spin() {
         let filename = String::from("tests/vasp/CHGCAR_spin");
         let vasp = Vasp {};
-        let (voxel_origin, grid, atoms, densities) = match vasp.read(filename) {
-            Ok(r) => r,
-            Err(e) => panic!("{}", e),
-        };
+        let (voxel_origin, grid, atoms, densities) =
+            match vasp.read(filename, 1) {
+                Ok(r) => r,
+                Err(e) => panic!("{}", e),
+            };
         assert_eq!(voxel_origin, [0.; 3]);
         assert_eq!(grid, [32, 32, 32]);
         assert_eq!(atoms.positions, vec![[0., 0., 0.]]);
@@ -57,10 +63,11 @@ mod tests {
     fn vasp_read_spin_chg() {
         let filename = String::from("tests/vasp/CHG_spin");
         let vasp = Vasp {};
-        let (voxel_origin, grid, atoms, densities) = match vasp.read(filename) {
-            Ok(r) => r,
-            Err(e) => panic!("{}", e),
-        };
+        let (voxel_origin, grid, atoms, densities) =
+            match vasp.read(filename, 1) {
+                Ok(r) => r,
+                Err(e) => panic!("{}", e),
+            };
         assert_eq!(voxel_origin, [0.; 3]);
         assert_eq!(grid, [32, 32, 32]);
         assert_eq!(atoms.positions, vec![[0., 0., 0.]]);
@@ -74,10 +81,11 @@ mod tests {
     fn vasp_read_ncl() {
         let filename = String::from("tests/vasp/CHGCAR_ncl");
         let vasp = Vasp {};
-        let (voxel_origin, grid, atoms, densities) = match vasp.read(filename) {
-            Ok(r) => r,
-            Err(e) => panic!("{}", e),
-        };
+        let (voxel_origin, grid, atoms, densities) =
+            match vasp.read(filename, 1) {
+                Ok(r) => r,
+                Err(e) => panic!("{}", e),
+            };
         assert_eq!(voxel_origin, [0.; 3]);
         assert_eq!(grid, [32, 32, 32]);
         assert_eq!(atoms.positions, vec![[0., 0., 0.]]);
@@ -99,10 +107,11 @@ mod tests {
     fn vasp_read_ncl_chg() {
         let filename = String::from("tests/vasp/CHG_ncl");
         let vasp = Vasp {};
-        let (voxel_origin, grid, atoms, densities) = match vasp.read(filename) {
-            Ok(r) => r,
-            Err(e) => panic!("{}", e),
-        };
+        let (voxel_origin, grid, atoms, densities) =
+            match vasp.read(filename, 1) {
+                Ok(r) => r,
+                Err(e) => panic!("{}", e),
+            };
         assert_eq!(voxel_origin, [0.; 3]);
         assert_eq!(grid, [32, 32, 32]);
         assert_eq!(atoms.positions, vec![[0., 0., 0.]]);
@@ -115,4 +124,20 @@ mod tests {
         assert_eq!(densities[3][0], 16.140 / atoms.lattice.volume);
         assert_eq!(densities[3][32767], 13.834 / atoms.lattice.volume);
     }
+
+    #[test]
+    fn vasp_read_huge_grid() {
+        let vasp = Vasp {};
+        let bytes = std::fs::read("tests/vasp/CHGCAR_no_spin").unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        for grid in ["   99999999999999999999   32   32",
+                     "   4294967296   4294967296   32"]
+        {
+            let text = text.replacen("   32   32   32", grid, 1);
+            assert!(matches!(vasp.read_from(&mut text.as_bytes(),
+                                            "CHGCAR",
+                                            1),
+                             Err(Error::Parse(_))));
+        }
+    }
 }
@@ -0,0 +1,66 @@
+#[cfg(test)]
+mod tests {
+    use bader::io::xsf::{write_grid, Units, Xsf};
+    use bader::io::FileFormat;
+    use bader::progress::Bar;
+
+    #[test]
+    fn xsf_read() {
+        let filename = String::from("tests/xsf/charge.xsf");
+        let xsf = Xsf { units: Units::Angstrom };
+        let (voxel_origin, grid, atoms, densities) = match xsf.read(filename, 1)
+        {
+            Ok(r) => r,
+            Err(e) => panic!("{}", e),
+        };
+        assert_eq!(voxel_origin, [0.; 3]);
+        assert_eq!(grid, [5, 4, 3]);
+        assert_eq!(atoms.lattice.to_cartesian,
+                   [[5., 0., 0.], [0., 4., 0.], [0., 0., 3.]]);
+        assert_eq!(atoms.positions, vec![[0., 0., 0.], [4., 2., 1.5]]);
+        assert_eq!(atoms.species, vec!["O", "H"]);
+        assert_eq!(densities[0].len(), 60);
+        // density[z, y, x] with the periodic duplicates removed
+        assert_eq!(densities[0][0], 1.);
+        assert_eq!(densities[0][1], 2.);
+        assert_eq!(densities[0][3], 11.);
+        assert_eq!(densities[0][12], 101.);
+        assert_eq!(densities[0][59], 433.);
+    }
+
+    #[test]
+    fn xsf_read_bohr() {
+        let filename = String::from("tests/xsf/charge.xsf");
+        let xsf = Xsf { units: Units::Bohr };
+        let (_, _, _, densities) = xsf.read(filename, 1).unwrap();
+        let volume = 0.52917721067f64.powi(3);
+        assert_eq!(densities[0][0], 1. / volume);
+        assert_eq!(densities[0][59], 433. / volume);
+    }
+
+    #[test]
+    fn xsf_write_grid_origin() {
+        let xsf = Xsf { units: Units::Angstrom };
+        let (_, grid, atoms, densities) =
+            xsf.read(String::from("tests/xsf/charge.xsf"), 1).unwrap();
+        let dir = std::env::temp_dir().join(format!("bader-xsf-{}",
+                                                    std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let filename = dir.join("origin").to_string_lossy().to_string();
+        let data = densities[0].iter().map(|d| Some(*d)).collect();
+        write_grid(&atoms,
+                   data,
+                   grid,
+                   [0.5; 3],
+                   xsf.axes(),
+                   filename.clone(),
+                   Bar::new(0, 100, String::new())).unwrap();
+        let (voxel_origin, c_grid, _, c_densities) =
+            xsf.read(format!("{}.xsf", filename), 1).unwrap();
+        for x in voxel_origin.iter() {
+            assert!((x - 0.5).abs() < 1E-9);
+        }
+        assert_eq!(c_grid, grid);
+        assert_eq!(c_densities, densities);
+    }
+}